@@ -3,58 +3,138 @@
 
 use crate::config::Config;
 use crate::connection::Connection;
+use crate::control::{ClientStatus, ConnectionState, ControlListener};
+use crate::metrics;
+use crate::ping::{self, PingSample};
 use crate::proxy::LocalProxy;
+use crate::range::RangeRequest;
 use crate::reconnect::ReconnectStrategy;
-use std::sync::Arc;
-use tokio::time::{interval, Duration};
-use tracing::{error, info};
-use tunnel_core::{quic, Message, PingMessage};
+use std::sync::{Arc, Mutex};
+use tokio::time::{interval, Duration, Instant};
+use tracing::{debug, error, info, warn};
+use tunnel_core::{quic, HttpResponseMessage, Message, StatusUpdateMessage};
+
+const PING_INTERVAL: Duration = Duration::from_secs(20);
 
 pub struct TunnelClient {
-    #[allow(dead_code)]
     config: Config,
     connection: Connection,
     proxy: Arc<LocalProxy>,
     reconnect: Option<ReconnectStrategy>,
+    last_ping: Mutex<Option<PingSample>>,
+    status: Arc<Mutex<ClientStatus>>,
 }
 
 impl TunnelClient {
     pub fn new(config: Config) -> Self {
         let connection = Connection::new(config.clone());
-        let proxy = Arc::new(LocalProxy::new(config.workstation.local_address.clone()));
+        let proxy = Arc::new(LocalProxy::from_config(&config));
         let reconnect = if config.reconnect.enabled {
-            Some(ReconnectStrategy::new(config.reconnect.max_delay))
+            Some(
+                ReconnectStrategy::new(config.reconnect.max_delay)
+                    .with_stable_after(Duration::from_secs(config.reconnect.stable_after)),
+            )
         } else {
             None
         };
 
+        let status = Arc::new(Mutex::new(ClientStatus::new(&config.workstation.id)));
+
         Self {
             config,
             connection,
             proxy,
             reconnect,
+            last_ping: Mutex::new(None),
+            status,
         }
     }
 
     pub async fn run(&mut self) -> anyhow::Result<()> {
+        let control =
+            match &self.config.workstation.control_socket {
+                Some(socket) => Some(ControlListener::bind(socket).await.map_err(|e| {
+                    anyhow::anyhow!("Failed to bind control socket {}: {}", socket, e)
+                })?),
+                None => None,
+            };
+        let metrics_listener = match &self.config.workstation.metrics_address {
+            Some(address) => {
+                let listener = tokio::net::TcpListener::bind(address).await.map_err(|e| {
+                    anyhow::anyhow!("Failed to bind metrics address {}: {}", address, e)
+                })?;
+                tunnel_core::codec::enable_metrics();
+                Some(listener)
+            }
+            None => None,
+        };
+        let status = self.status.clone();
+        let serve_control = async move {
+            match control {
+                Some(listener) => listener.serve(status).await,
+                None => std::future::pending().await,
+            }
+        };
+        let (metrics, status) = (self.proxy.metrics().clone(), self.status.clone());
+        let serve_metrics = async move {
+            match metrics_listener {
+                Some(listener) => metrics::serve(listener, metrics, status).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            _ = serve_control => Ok(()),
+            _ = serve_metrics => Ok(()),
+            result = self.connect_loop() => result,
+        }
+    }
+
+    /// Serves connections until reconnecting is off, or fails with the close
+    /// that no reconnect can get past.
+    async fn connect_loop(&mut self) -> anyhow::Result<()> {
         loop {
-            match self.connect_and_serve().await {
+            let result = match self.connect_and_serve().await {
+                Err(e)
+                    if matches!(
+                        e.downcast_ref::<tunnel_core::Error>(),
+                        Some(tunnel_core::Error::Closed(quic::CloseReason::LocallyClosed))
+                    ) =>
+                {
+                    Ok(())
+                }
+                other => other,
+            };
+            match result {
                 Ok(()) => {
                     info!("Connection closed gracefully");
                 }
-                Err(e) => {
-                    error!("Connection error: {}", e);
-                }
+                Err(e) => match e.downcast_ref::<tunnel_core::Error>() {
+                    Some(tunnel_core::Error::Closed(reason)) => {
+                        self.status.lock().unwrap().close_reason = Some(reason.to_string());
+                        if !reason.is_retryable() {
+                            error!("Connection closed: {}; not reconnecting", reason);
+                            self.status.lock().unwrap().state = ConnectionState::Disconnected;
+                            return Err(e);
+                        }
+                        warn!("Connection closed: {}", reason);
+                    }
+                    _ => error!("Connection error: {}", e),
+                },
             }
 
             if let Some(ref mut strategy) = self.reconnect {
+                {
+                    let mut status = self.status.lock().unwrap();
+                    status.state = ConnectionState::Reconnecting;
+                    status.reconnect_attempts = strategy.attempt + 1;
+                }
                 strategy.wait_before_retry().await;
             } else {
-                break;
+                self.status.lock().unwrap().state = ConnectionState::Disconnected;
+                return Ok(());
             }
         }
-
-        Ok(())
     }
 
     async fn connect_and_serve(&mut self) -> anyhow::Result<()> {
@@ -62,13 +142,20 @@ impl TunnelClient {
         let (conn, url) = self.connection.connect().await?;
 
         info!("Connected! Tunnel URL: {}", url);
-
-        if let Some(ref mut strategy) = self.reconnect {
-            strategy.reset();
+        self.proxy.metrics().record_connect();
+        {
+            let mut status = self.status.lock().unwrap();
+            status.state = ConnectionState::Connected;
+            status.url = Some(url);
         }
+        if let Some(version) = self.connection.server_version() {
+            info!("Server version: {}", version);
+        }
+        let connected_at = Instant::now();
 
         let ping_task = self.start_ping_task(conn.clone());
         let message_task = self.handle_messages(conn.clone());
+        let health_task = self.start_health_task(conn.clone());
 
         tokio::select! {
             _ = ping_task => {
@@ -77,72 +164,232 @@ impl TunnelClient {
             _ = message_task => {
                 info!("Message task ended");
             }
+            _ = health_task => {}
+        }
+
+        if let Some(ref mut strategy) = self.reconnect {
+            strategy.connection_ended(connected_at.elapsed());
+        }
+
+        match conn.close_reason() {
+            Some(e) => Err(tunnel_core::Error::Closed(quic::CloseReason::from(&e)).into()),
+            None => Ok(()),
         }
+    }
+
+    /// What the control API reports: connection state, tunnel URL and
+    /// reconnect attempts.
+    pub fn status(&self) -> ClientStatus {
+        self.status.lock().unwrap().clone()
+    }
 
-        Ok(())
+    /// Latest round-trip measurement from the keepalive pings.
+    pub fn last_ping(&self) -> Option<PingSample> {
+        *self.last_ping.lock().unwrap()
     }
 
     async fn start_ping_task(&self, connection: quinn::Connection) {
-        let mut ticker = interval(Duration::from_secs(20));
+        let mut ticker = interval(PING_INTERVAL);
         loop {
             ticker.tick().await;
 
-            let ping = Message::Ping(PingMessage {
-                timestamp: std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs(),
-            });
+            match tokio::time::timeout(PING_INTERVAL, ping::ping(&connection)).await {
+                Ok(Ok(sample)) => {
+                    sample.log();
+                    debug!(
+                        "Connection: {}",
+                        quic::ConnectionQuality::from_connection(&connection)
+                    );
+                    self.proxy.metrics().set_ping_rtt(sample.rtt);
+                    *self.last_ping.lock().unwrap() = Some(sample);
+                }
+                Ok(Err(e)) => {
+                    error!("Failed to send ping: {}", e);
+                    break;
+                }
+                Err(_) => {
+                    warn!("No pong within {:?}", PING_INTERVAL);
+                }
+            }
+        }
+    }
 
-            if quic::send_bidirectional_message(&connection, &ping)
-                .await
-                .is_err()
-            {
-                error!("Failed to send ping");
-                break;
+    /// Probes the local backend and tells the server whenever it turns
+    /// unhealthy or recovers. Runs until the connection is dropped.
+    async fn start_health_task(&self, connection: quinn::Connection) {
+        let Some(path) = self.config.workstation.health_check_path.clone() else {
+            return std::future::pending().await;
+        };
+        let period = Duration::from_secs(self.config.workstation.health_check_interval);
+        let mut ticker = interval(period);
+        let mut healthy = true;
+        loop {
+            ticker.tick().await;
+            let result = self.proxy.probe_backend(&path, period).await;
+            self.proxy.metrics().set_backend_healthy(result.is_ok());
+            if result.is_ok() == healthy {
+                continue;
+            }
+            healthy = result.is_ok();
+            match &result {
+                Ok(()) => info!("Local backend is healthy again"),
+                Err(detail) => warn!("Local backend is unhealthy: {}", detail),
+            }
+            let update = Message::StatusUpdate(StatusUpdateMessage {
+                healthy,
+                detail: result.err(),
+            });
+            if let Err(e) = send_status(&connection, &update).await {
+                warn!("Failed to report backend health: {}", e);
             }
         }
     }
 
     async fn handle_messages(&self, connection: quinn::Connection) {
-        loop {
-            match connection.accept_bi().await {
-                Ok((mut send, mut recv)) => {
-                    let proxy = self.proxy.clone();
-                    tokio::spawn(async move {
-                        match quic::recv_message(&mut recv).await {
-                            Ok(msg) => match msg {
-                                Message::HttpRequest(req) => {
-                                    if let Some(response) =
-                                        proxy.handle_message(Message::HttpRequest(req)).await
-                                    {
-                                        if let Err(e) =
-                                            quic::send_message(&mut send, &response).await
-                                        {
-                                            error!("Failed to send response: {}", e);
-                                        } else {
-                                            let _ = send.finish();
+        serve_connection(
+            connection,
+            self.proxy.clone(),
+            self.connection.max_frame_bytes(),
+        )
+        .await;
+    }
+}
+
+async fn send_status(connection: &quinn::Connection, update: &Message) -> tunnel_core::Result<()> {
+    let (mut send, _recv) = connection
+        .open_bi()
+        .await
+        .map_err(|e| tunnel_core::Error::Connection(e.to_string()))?;
+    quic::send_message(&mut send, update).await?;
+    send.finish()
+        .map_err(|e| tunnel_core::Error::Connection(e.to_string()))
+}
+
+/// Accepts streams opened by the server on `connection` and answers each one
+/// through `proxy` until the connection closes.
+pub(crate) async fn serve_connection(
+    connection: quinn::Connection,
+    proxy: Arc<LocalProxy>,
+    max_frame_bytes: usize,
+) {
+    loop {
+        match connection.accept_bi().await {
+            Ok((mut send, mut recv)) => {
+                let proxy = proxy.clone();
+                tokio::spawn(async move {
+                    match quic::recv_message_limited(&mut recv, max_frame_bytes).await {
+                        Ok(msg) => match msg {
+                            Message::HttpRequest(req) => {
+                                let stream_id = req.stream_id;
+                                let range = RangeRequest::of(&req);
+                                let mut response = if req.expect_continue {
+                                    proxy
+                                        .forward_continue_request(
+                                            req,
+                                            &mut send,
+                                            &mut recv,
+                                            max_frame_bytes,
+                                        )
+                                        .await
+                                        .map(Message::HttpResponse)
+                                        .map_err(|e| error!("Failed to forward request: {}", e))
+                                        .ok()
+                                } else if req.informational && proxy.forwards_informational() {
+                                    let stopped = send.stopped();
+                                    tokio::select! {
+                                        response = proxy.forward_informational_request(
+                                            req,
+                                            &mut send,
+                                            &mut recv,
+                                            max_frame_bytes,
+                                        ) => response
+                                            .map(Message::HttpResponse)
+                                            .map_err(|e| error!("Failed to forward request: {}", e))
+                                            .ok(),
+                                        _ = stopped => {
+                                            debug!("Stream {} cancelled by the server", stream_id);
+                                            None
                                         }
                                     }
+                                } else {
+                                    // The server stops the stream when its caller
+                                    // goes away. Dropping the backend request then
+                                    // tells the backend to stop as well.
+                                    tokio::select! {
+                                        response = proxy.handle_message(Message::HttpRequest(req)) => response,
+                                        _ = send.stopped() => {
+                                            debug!("Stream {} cancelled by the server", stream_id);
+                                            None
+                                        }
+                                    }
+                                };
+                                if let Some(Message::HttpResponse(resp)) = &mut response {
+                                    range.apply(resp, max_frame_bytes);
                                 }
-                                Message::WsOpen(open_msg) => {
-                                    proxy.handle_websocket_open(open_msg, send, recv).await;
-                                }
-                                Message::SseOpen(open_msg) => {
-                                    proxy.handle_sse_open(open_msg, send, recv).await;
+                                if let Some(response) = response {
+                                    let result = match quic::send_message_limited(
+                                        &mut send,
+                                        &response,
+                                        max_frame_bytes,
+                                    )
+                                    .await
+                                    {
+                                        Err(tunnel_core::Error::MessageTooLarge {
+                                            size,
+                                            limit,
+                                        }) => {
+                                            warn!(
+                                                "Response for stream {} is {} bytes, over the {} byte frame limit",
+                                                stream_id, size, limit
+                                            );
+                                            let fallback =
+                                                Message::HttpResponse(HttpResponseMessage {
+                                                    stream_id,
+                                                    status: 502,
+                                                    headers: Default::default(),
+                                                    body: None,
+                                                });
+                                            quic::send_message(&mut send, &fallback).await
+                                        }
+                                        other => other,
+                                    };
+                                    if let Err(e) = result {
+                                        error!("Failed to send response: {}", e);
+                                    } else {
+                                        let _ = send.finish();
+                                    }
                                 }
-                                _ => {}
-                            },
-                            Err(e) => {
-                                error!("Failed to receive message: {}", e);
                             }
+                            Message::WsOpen(open_msg) => {
+                                proxy.handle_websocket_open(open_msg, send, recv).await;
+                            }
+                            Message::SseOpen(open_msg) => {
+                                proxy.handle_sse_open(open_msg, send, recv).await;
+                            }
+                            Message::H2Open(open_msg) => {
+                                proxy.handle_h2_open(open_msg, send, recv).await;
+                            }
+                            Message::ConnectOpen(open_msg) => {
+                                proxy.handle_connect_open(open_msg, send, recv).await;
+                            }
+                            other => {
+                                warn!(
+                                    "{}",
+                                    tunnel_core::Error::InvalidMessageType(
+                                        other.message_type().to_string()
+                                    )
+                                );
+                            }
+                        },
+                        Err(e) => {
+                            error!("Failed to receive message: {}", e);
                         }
-                    });
-                }
-                Err(_) => {
-                    info!("Connection closed");
-                    break;
-                }
+                    }
+                });
+            }
+            Err(_) => {
+                info!("Connection closed");
+                break;
             }
         }
     }
@@ -2,8 +2,11 @@
 // Licensed under the FSL-1.1-NC.
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::env;
 use std::path::PathBuf;
+use tunnel_core::quic::QuicConfig;
+use tunnel_core::tls::TlsPolicy;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -12,22 +15,216 @@ pub struct Config {
     pub workstation: WorkstationConfig,
     pub reconnect: ReconnectConfig,
     pub session: SessionConfig,
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    #[serde(default)]
+    pub tls: TlsConfig,
+    #[serde(default)]
+    pub quic: QuicConfig,
+    /// Why `QUIC_CONGESTION_CONTROLLER` could not be applied, reported by
+    /// `validate`.
+    #[serde(skip)]
+    congestion_controller_error: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerConfig {
     pub address: String,
+    /// Try IPv6 addresses before IPv4 when the server name resolves to both.
+    #[serde(default)]
+    pub prefer_ipv6: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthConfig {
+    #[serde(default)]
     pub api_key: String,
+    /// File holding the API key, read at load time and preferred over `api_key`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_file: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WorkstationConfig {
     pub id: String,
     pub local_address: String,
+    /// Backends tried in order when `local_address` refuses the connection or
+    /// does not accept it within `local_connect_timeout`, e.g. a warm standby.
+    /// Applies to HTTP requests, the health check, and WebSocket, SSE and
+    /// HTTP/2 streams, which pick the first backend accepting connections.
+    #[serde(default)]
+    pub fallback_addresses: Vec<String>,
+    /// Path patterns that may be upgraded to WebSocket; `*` matches any run of
+    /// characters. Empty allows every path not denied.
+    #[serde(default)]
+    pub ws_allow_paths: Vec<String>,
+    /// Path patterns never upgraded to WebSocket. Checked before the allowlist.
+    #[serde(default)]
+    pub ws_deny_paths: Vec<String>,
+    /// Backends selected by the forwarded `Host`, keyed by full host name or by
+    /// its first label (`api` matches `api.ws.example.com`). Requests for other
+    /// hosts go to `local_address`.
+    #[serde(default)]
+    pub host_backends: BTreeMap<String, String>,
+    /// Ordered backend rules tried before `host_backends`; the first rule whose
+    /// conditions all match picks the backend.
+    #[serde(default)]
+    pub routes: Vec<RouteRule>,
+    /// `host:port` patterns the server may reach through HTTP `CONNECT`; `*`
+    /// matches any run of characters. Empty disables `CONNECT`.
+    #[serde(default)]
+    pub connect_allow: Vec<String>,
+    /// Seconds the local backend has to answer an HTTP request, or to accept an
+    /// SSE or WebSocket connection, before the client reports a timeout.
+    /// 0 waits as long as the server does.
+    #[serde(default = "default_local_request_timeout")]
+    pub local_request_timeout: u64,
+    /// Seconds a backend has to accept a connection before the next fallback
+    /// is tried. 0 leaves it to the operating system.
+    #[serde(default = "default_local_connect_timeout")]
+    pub local_connect_timeout: u64,
+    /// Idle connections kept open to each backend for reuse. 0 opens a new
+    /// connection for every request.
+    #[serde(default = "default_pool_max_idle_per_host")]
+    pub pool_max_idle_per_host: usize,
+    /// Seconds an idle backend connection is kept. 0 keeps it until the
+    /// backend closes it.
+    #[serde(default = "default_pool_idle_timeout")]
+    pub pool_idle_timeout: u64,
+    /// Speak HTTP/2 without upgrade (h2c) to backends, which multiplexes
+    /// requests over one connection. Every backend must support it.
+    #[serde(default)]
+    pub http2_prior_knowledge: bool,
+    /// Relay interim `1xx` responses such as `103 Early Hints` from plain
+    /// HTTP backends to HTTP/1.1 callers. Such requests then go through the
+    /// HTTP/1.1 client used for `Expect: 100-continue`, without fallbacks.
+    #[serde(default)]
+    pub forward_informational: bool,
+    /// Details sent with the registration (version, hostname, region) and
+    /// listed by the server's admin API.
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    /// Capabilities declared at registration, e.g. `env=staging` or `gpu=true`,
+    /// which the server can filter and select workstations by.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Path on `local_address` probed to report backend health to the server,
+    /// which refuses requests while the backend is down. Any answer below 500
+    /// counts as healthy. Unset disables the probe.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_check_path: Option<String>,
+    /// Seconds between backend probes.
+    #[serde(default = "default_health_check_interval")]
+    pub health_check_interval: u64,
+    /// Headers added to every HTTP, SSE, HTTP/2 and WebSocket request sent to
+    /// the backend, e.g. an internal `Authorization` public callers should
+    /// not need.
+    #[serde(default)]
+    pub inject_headers: BTreeMap<String, String>,
+    /// Files holding injected header values, read at load time and preferred
+    /// over `inject_headers`, for secrets kept out of the config.
+    #[serde(default)]
+    pub inject_header_files: BTreeMap<String, PathBuf>,
+    /// What happens to caller headers named in `inject_headers`.
+    #[serde(default)]
+    pub inject_headers_policy: InjectHeadersPolicy,
+    /// Rewrite of forwarded paths, for backends not mounted at the root.
+    /// Route and WebSocket path rules still see the original path.
+    #[serde(default)]
+    pub path_rewrite: PathRewrite,
+    /// Local control API answering `GET /status` with the tunnel URL,
+    /// connection state and reconnect attempts. A `host:port` listens on TCP,
+    /// anything else is a Unix socket path. Unset disables it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub control_socket: Option<String>,
+    /// `host:port` serving Prometheus metrics for this client at
+    /// `GET /metrics`. Unset disables it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics_address: Option<String>,
+}
+
+/// How injected headers combine with caller headers of the same name.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InjectHeadersPolicy {
+    /// Drop the caller's values, so callers cannot supply their own.
+    #[default]
+    Replace,
+    /// Keep the caller's values and add the injected one after them.
+    Append,
+}
+
+impl std::str::FromStr for InjectHeadersPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "replace" => Ok(Self::Replace),
+            "append" => Ok(Self::Append),
+            other => Err(format!(
+                "unknown inject headers policy '{}', expected replace or append",
+                other
+            )),
+        }
+    }
+}
+
+/// Prefix rewrite applied to a forwarded path before the backend URL is built.
+/// `strip_prefix` runs first, so both together replace one prefix with another.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PathRewrite {
+    /// Removed from paths that start with it as whole segments, e.g. `/app`
+    /// turns `/app/login` into `/login` but leaves `/apple` alone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strip_prefix: Option<String>,
+    /// Prepended to every path, e.g. `/internal` turns `/foo` into
+    /// `/internal/foo`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub add_prefix: Option<String>,
+}
+
+impl PathRewrite {
+    /// Rewrites `path`, which may carry a query string.
+    pub fn apply(&self, path: &str) -> String {
+        let mut path = path.to_string();
+        if let Some(prefix) = self
+            .strip_prefix
+            .as_deref()
+            .map(|p| p.trim_end_matches('/'))
+        {
+            if let Some(rest) = path.strip_prefix(prefix) {
+                if rest.is_empty() || rest.starts_with('?') {
+                    path = format!("/{}", rest);
+                } else if rest.starts_with('/') {
+                    path = rest.to_string();
+                }
+            }
+        }
+        if let Some(prefix) = self.add_prefix.as_deref().map(|p| p.trim_end_matches('/')) {
+            path = format!("{}{}", prefix, path);
+        }
+        path
+    }
+}
+
+/// One entry of `workstation.routes`. Unset conditions match any request, and
+/// patterns use `*` for any run of characters.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RouteRule {
+    /// Request method, compared case-insensitively.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    /// Pattern for the path without its query, e.g. `/api/*` or `*.css`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Pattern matched against each media type listed in `Accept`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accept: Option<String>,
+    /// Pattern matched against the media type of `Content-Type`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    /// Backend URL for matching requests.
+    pub backend: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -36,6 +233,21 @@ pub struct ReconnectConfig {
     pub enabled: bool,
     #[serde(default = "default_max_delay")]
     pub max_delay: u64,
+    /// Seconds a connection must stay up before the backoff starts over.
+    #[serde(default = "default_stable_after")]
+    pub stable_after: u64,
+    /// Quick retries of a failed handshake before the attempt counts as a
+    /// failure and the backoff applies. 0 disables them.
+    #[serde(default = "default_handshake_retries")]
+    pub handshake_retries: u32,
+    /// Milliseconds between handshake retries.
+    #[serde(default = "default_handshake_retry_delay")]
+    pub handshake_retry_delay: u64,
+    /// Seconds one connect may take, from resolving the server to its answer
+    /// to the registration, handshake retries included. 0 leaves it to the
+    /// QUIC idle timeout.
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,6 +256,56 @@ pub struct SessionConfig {
     pub ticket_path: PathBuf,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LimitsConfig {
+    #[serde(default = "default_max_frame_bytes")]
+    pub max_frame_bytes: usize,
+    /// Combined size of local backend response header names and values;
+    /// larger responses are answered with `502`. 0 disables the check.
+    #[serde(default = "default_max_header_bytes")]
+    pub max_header_bytes: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// ALPN protocol offered to the server; must match the server's `tls.alpn`.
+    #[serde(default = "default_alpn")]
+    pub alpn: String,
+    /// Oldest TLS version offered, `1.2` or `1.3`. The QUIC handshake itself is
+    /// always TLS 1.3.
+    #[serde(default = "default_tls_min_version")]
+    pub min_version: String,
+    /// Cipher suites offered to the server, e.g. `TLS13_AES_256_GCM_SHA384`.
+    /// Empty offers every suite rustls supports.
+    #[serde(default)]
+    pub cipher_suites: Vec<String>,
+}
+
+impl TlsConfig {
+    pub fn policy(&self) -> tunnel_core::Result<TlsPolicy> {
+        TlsPolicy::new(&self.min_version, &self.cipher_suites).map_err(tunnel_core::Error::Other)
+    }
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            alpn: default_alpn(),
+            min_version: default_tls_min_version(),
+            cipher_suites: Vec::new(),
+        }
+    }
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_frame_bytes: default_max_frame_bytes(),
+            max_header_bytes: default_max_header_bytes(),
+        }
+    }
+}
+
 fn default_reconnect_enabled() -> bool {
     true
 }
@@ -52,12 +314,89 @@ fn default_max_delay() -> u64 {
     30
 }
 
+fn default_local_request_timeout() -> u64 {
+    30
+}
+
+fn default_local_connect_timeout() -> u64 {
+    5
+}
+
+fn default_pool_max_idle_per_host() -> usize {
+    32
+}
+
+fn default_pool_idle_timeout() -> u64 {
+    90
+}
+
+fn default_health_check_interval() -> u64 {
+    10
+}
+
+fn default_stable_after() -> u64 {
+    10
+}
+
+fn default_handshake_retries() -> u32 {
+    3
+}
+
+fn default_handshake_retry_delay() -> u64 {
+    250
+}
+
+fn default_connect_timeout() -> u64 {
+    10
+}
+
 fn default_ticket_path() -> PathBuf {
     PathBuf::from("./session.ticket")
 }
 
+fn default_alpn() -> String {
+    tunnel_core::quic::DEFAULT_ALPN.to_string()
+}
+
+fn default_tls_min_version() -> String {
+    tunnel_core::tls::DEFAULT_MIN_VERSION.to_string()
+}
+
+fn default_max_frame_bytes() -> usize {
+    tunnel_core::quic::DEFAULT_MAX_FRAME_BYTES
+}
+
+fn default_max_header_bytes() -> usize {
+    64 * 1024
+}
+
+fn split_list(val: &str) -> Vec<String> {
+    val.split(',')
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+/// Settings given on the command line. Each one set replaces the value from
+/// the config file and the environment.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigOverrides {
+    pub server_address: Option<String>,
+    pub api_key: Option<String>,
+    pub workstation_id: Option<String>,
+    pub local_address: Option<String>,
+}
+
 impl Config {
     pub fn load(config_path: Option<PathBuf>) -> anyhow::Result<Self> {
+        Self::load_with_overrides(config_path, &ConfigOverrides::default())
+    }
+
+    /// Like [`Config::load`], then applies `overrides` before validating.
+    pub fn load_with_overrides(
+        config_path: Option<PathBuf>,
+        overrides: &ConfigOverrides,
+    ) -> anyhow::Result<Self> {
         let mut config = if let Some(path) = config_path {
             let content = std::fs::read_to_string(path)?;
             toml::from_str(&content)?
@@ -66,6 +405,9 @@ impl Config {
         };
 
         config.apply_env_overrides();
+        config.apply_overrides(overrides);
+        config.load_api_key_file()?;
+        config.load_inject_header_files()?;
         config.validate()?;
         Ok(config)
     }
@@ -74,15 +416,122 @@ impl Config {
         if let Ok(val) = env::var("SERVER_ADDRESS") {
             self.server.address = val;
         }
+        if let Ok(val) = env::var("SERVER_PREFER_IPV6") {
+            if let Ok(prefer) = val.parse() {
+                self.server.prefer_ipv6 = prefer;
+            }
+        }
         if let Ok(val) = env::var("AUTH_API_KEY") {
             self.auth.api_key = val;
         }
+        if let Ok(val) = env::var("AUTH_API_KEY_FILE") {
+            self.auth.api_key_file = Some(PathBuf::from(val));
+        }
         if let Ok(val) = env::var("WORKSTATION_ID") {
             self.workstation.id = val;
         }
         if let Ok(val) = env::var("WORKSTATION_LOCAL_ADDRESS") {
             self.workstation.local_address = val;
         }
+        if let Ok(val) = env::var("WORKSTATION_FALLBACK_ADDRESSES") {
+            self.workstation.fallback_addresses = split_list(&val);
+        }
+        if let Ok(val) = env::var("WORKSTATION_WS_ALLOW_PATHS") {
+            self.workstation.ws_allow_paths = split_list(&val);
+        }
+        if let Ok(val) = env::var("WORKSTATION_WS_DENY_PATHS") {
+            self.workstation.ws_deny_paths = split_list(&val);
+        }
+        if let Ok(val) = env::var("WORKSTATION_HOST_BACKENDS") {
+            self.workstation.host_backends = split_list(&val)
+                .iter()
+                .filter_map(|entry| entry.split_once('='))
+                .map(|(host, url)| (host.trim().to_string(), url.trim().to_string()))
+                .collect();
+        }
+        if let Ok(val) = env::var("WORKSTATION_METADATA") {
+            self.workstation.metadata = split_list(&val)
+                .iter()
+                .filter_map(|entry| entry.split_once('='))
+                .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+                .collect();
+        }
+        if let Ok(val) = env::var("WORKSTATION_TAGS") {
+            self.workstation.tags = split_list(&val);
+        }
+        if let Ok(val) = env::var("WORKSTATION_CONNECT_ALLOW") {
+            self.workstation.connect_allow = split_list(&val);
+        }
+        if let Ok(val) = env::var("WORKSTATION_LOCAL_REQUEST_TIMEOUT") {
+            if let Ok(timeout) = val.parse() {
+                self.workstation.local_request_timeout = timeout;
+            }
+        }
+        if let Ok(val) = env::var("WORKSTATION_LOCAL_CONNECT_TIMEOUT") {
+            if let Ok(timeout) = val.parse() {
+                self.workstation.local_connect_timeout = timeout;
+            }
+        }
+        if let Ok(val) = env::var("WORKSTATION_POOL_MAX_IDLE_PER_HOST") {
+            if let Ok(max) = val.parse() {
+                self.workstation.pool_max_idle_per_host = max;
+            }
+        }
+        if let Ok(val) = env::var("WORKSTATION_POOL_IDLE_TIMEOUT") {
+            if let Ok(secs) = val.parse() {
+                self.workstation.pool_idle_timeout = secs;
+            }
+        }
+        if let Ok(val) = env::var("WORKSTATION_HTTP2_PRIOR_KNOWLEDGE") {
+            if let Ok(enabled) = val.parse() {
+                self.workstation.http2_prior_knowledge = enabled;
+            }
+        }
+        if let Ok(val) = env::var("WORKSTATION_FORWARD_INFORMATIONAL") {
+            if let Ok(enabled) = val.parse() {
+                self.workstation.forward_informational = enabled;
+            }
+        }
+        if let Ok(val) = env::var("WORKSTATION_HEALTH_CHECK_PATH") {
+            self.workstation.health_check_path = (!val.is_empty()).then_some(val);
+        }
+        if let Ok(val) = env::var("WORKSTATION_CONTROL_SOCKET") {
+            self.workstation.control_socket = (!val.is_empty()).then_some(val);
+        }
+        if let Ok(val) = env::var("WORKSTATION_METRICS_ADDRESS") {
+            self.workstation.metrics_address = (!val.is_empty()).then_some(val);
+        }
+        if let Ok(val) = env::var("WORKSTATION_HEALTH_CHECK_INTERVAL") {
+            if let Ok(secs) = val.parse() {
+                self.workstation.health_check_interval = secs;
+            }
+        }
+        if let Ok(val) = env::var("WORKSTATION_INJECT_HEADERS") {
+            self.workstation.inject_headers = val
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+                .filter(|(name, _)| !name.is_empty())
+                .collect();
+        }
+        if let Ok(val) = env::var("WORKSTATION_INJECT_HEADER_FILES") {
+            self.workstation.inject_header_files = split_list(&val)
+                .iter()
+                .filter_map(|entry| entry.split_once('='))
+                .map(|(name, path)| (name.trim().to_string(), PathBuf::from(path.trim())))
+                .collect();
+        }
+        if let Ok(val) = env::var("WORKSTATION_INJECT_HEADERS_POLICY") {
+            if let Ok(policy) = val.parse() {
+                self.workstation.inject_headers_policy = policy;
+            }
+        }
+        if let Ok(val) = env::var("WORKSTATION_PATH_STRIP_PREFIX") {
+            self.workstation.path_rewrite.strip_prefix = (!val.is_empty()).then_some(val);
+        }
+        if let Ok(val) = env::var("WORKSTATION_PATH_ADD_PREFIX") {
+            self.workstation.path_rewrite.add_prefix = (!val.is_empty()).then_some(val);
+        }
         if let Ok(val) = env::var("RECONNECT_ENABLED") {
             if let Ok(enabled) = val.parse() {
                 self.reconnect.enabled = enabled;
@@ -93,12 +542,133 @@ impl Config {
                 self.reconnect.max_delay = delay;
             }
         }
+        if let Ok(val) = env::var("RECONNECT_STABLE_AFTER") {
+            if let Ok(secs) = val.parse() {
+                self.reconnect.stable_after = secs;
+            }
+        }
+        if let Ok(val) = env::var("RECONNECT_HANDSHAKE_RETRIES") {
+            if let Ok(retries) = val.parse() {
+                self.reconnect.handshake_retries = retries;
+            }
+        }
+        if let Ok(val) = env::var("RECONNECT_HANDSHAKE_RETRY_DELAY") {
+            if let Ok(delay) = val.parse() {
+                self.reconnect.handshake_retry_delay = delay;
+            }
+        }
+        if let Ok(val) = env::var("RECONNECT_CONNECT_TIMEOUT") {
+            if let Ok(secs) = val.parse() {
+                self.reconnect.connect_timeout = secs;
+            }
+        }
         if let Ok(val) = env::var("SESSION_TICKET_PATH") {
             self.session.ticket_path = PathBuf::from(val);
         }
+        if let Ok(val) = env::var("TLS_ALPN") {
+            self.tls.alpn = val;
+        }
+        if let Ok(val) = env::var("TLS_MIN_VERSION") {
+            self.tls.min_version = val;
+        }
+        if let Ok(val) = env::var("TLS_CIPHER_SUITES") {
+            self.tls.cipher_suites = split_list(&val);
+        }
+        if let Ok(val) = env::var("LIMITS_MAX_FRAME_BYTES") {
+            if let Ok(max) = val.parse() {
+                self.limits.max_frame_bytes = max;
+            }
+        }
+        if let Ok(val) = env::var("LIMITS_MAX_HEADER_BYTES") {
+            if let Ok(max) = val.parse() {
+                self.limits.max_header_bytes = max;
+            }
+        }
+        if let Ok(val) = env::var("QUIC_MAX_BIDI_STREAMS") {
+            if let Ok(max) = val.parse() {
+                self.quic.max_bidi_streams = max;
+            }
+        }
+        if let Ok(val) = env::var("QUIC_MAX_IDLE_TIMEOUT") {
+            if let Ok(timeout) = val.parse() {
+                self.quic.max_idle_timeout = timeout;
+            }
+        }
+        if let Ok(val) = env::var("QUIC_KEEP_ALIVE_INTERVAL") {
+            if let Ok(interval) = val.parse() {
+                self.quic.keep_alive_interval = interval;
+            }
+        }
+        if let Ok(val) = env::var("QUIC_STREAM_RECEIVE_WINDOW") {
+            if let Ok(window) = val.parse() {
+                self.quic.stream_receive_window = window;
+            }
+        }
+        if let Ok(val) = env::var("QUIC_RECEIVE_WINDOW") {
+            if let Ok(window) = val.parse() {
+                self.quic.receive_window = window;
+            }
+        }
+        if let Ok(val) = env::var("QUIC_CONGESTION_CONTROLLER") {
+            match val.parse() {
+                Ok(controller) => self.quic.congestion_controller = controller,
+                Err(e) => self.congestion_controller_error = Some(e),
+            }
+        }
+    }
+
+    fn apply_overrides(&mut self, overrides: &ConfigOverrides) {
+        if let Some(address) = &overrides.server_address {
+            self.server.address = address.clone();
+        }
+        if let Some(key) = &overrides.api_key {
+            self.auth.api_key = key.clone();
+            // A key given directly wins over a key file from the file or env.
+            self.auth.api_key_file = None;
+        }
+        if let Some(id) = &overrides.workstation_id {
+            self.workstation.id = id.clone();
+        }
+        if let Some(address) = &overrides.local_address {
+            self.workstation.local_address = address.clone();
+        }
+    }
+
+    /// Replaces `auth.api_key` with the contents of `auth.api_key_file`, if set,
+    /// without trailing whitespace.
+    pub fn load_api_key_file(&mut self) -> anyhow::Result<()> {
+        if let Some(path) = &self.auth.api_key_file {
+            let key = std::fs::read_to_string(path).map_err(|e| {
+                anyhow::anyhow!("failed to read AUTH_API_KEY_FILE {}: {}", path.display(), e)
+            })?;
+            self.auth.api_key = key.trim_end().to_string();
+        }
+        Ok(())
+    }
+
+    /// Fills `workstation.inject_headers` from `workstation.inject_header_files`,
+    /// without trailing whitespace.
+    pub fn load_inject_header_files(&mut self) -> anyhow::Result<()> {
+        for (name, path) in &self.workstation.inject_header_files {
+            let value = std::fs::read_to_string(path).map_err(|e| {
+                anyhow::anyhow!(
+                    "failed to read injected header '{}' from {}: {}",
+                    name,
+                    path.display(),
+                    e
+                )
+            })?;
+            self.workstation
+                .inject_headers
+                .insert(name.clone(), value.trim_end().to_string());
+        }
+        Ok(())
     }
 
     fn validate(&self) -> anyhow::Result<()> {
+        if let Some(e) = &self.congestion_controller_error {
+            anyhow::bail!("QUIC_CONGESTION_CONTROLLER: {}", e);
+        }
         if self.server.address.is_empty() {
             anyhow::bail!("SERVER_ADDRESS is required");
         }
@@ -111,6 +681,99 @@ impl Config {
         if self.workstation.local_address.is_empty() {
             anyhow::bail!("WORKSTATION_LOCAL_ADDRESS is required");
         }
+        if let Some(pattern) = self
+            .workstation
+            .ws_allow_paths
+            .iter()
+            .chain(&self.workstation.ws_deny_paths)
+            .find(|p| !p.starts_with('/'))
+        {
+            anyhow::bail!("WebSocket path pattern '{}' must start with '/'", pattern);
+        }
+        if let Some(url) = self
+            .workstation
+            .fallback_addresses
+            .iter()
+            .find(|url| !url.starts_with("http://") && !url.starts_with("https://"))
+        {
+            anyhow::bail!(
+                "WORKSTATION_FALLBACK_ADDRESSES entry '{}' must be an http(s) URL",
+                url
+            );
+        }
+        if let Some((host, _)) = self
+            .workstation
+            .host_backends
+            .iter()
+            .find(|(_, url)| !url.starts_with("http://") && !url.starts_with("https://"))
+        {
+            anyhow::bail!("Backend for host '{}' must be an http(s) URL", host);
+        }
+        for (idx, rule) in self.workstation.routes.iter().enumerate() {
+            if !rule.backend.starts_with("http://") && !rule.backend.starts_with("https://") {
+                anyhow::bail!("Backend of route {} must be an http(s) URL", idx + 1);
+            }
+            if let Some(path) = &rule.path {
+                if !path.starts_with('/') && !path.starts_with('*') {
+                    anyhow::bail!("Path pattern '{}' must start with '/' or '*'", path);
+                }
+            }
+        }
+        if let Some(path) = &self.workstation.health_check_path {
+            if !path.starts_with('/') {
+                anyhow::bail!("WORKSTATION_HEALTH_CHECK_PATH must start with '/'");
+            }
+            if self.workstation.health_check_interval == 0 {
+                anyhow::bail!("WORKSTATION_HEALTH_CHECK_INTERVAL must be greater than 0");
+            }
+        }
+        if let Some(socket) = &self.workstation.control_socket {
+            if !cfg!(unix) && socket.parse::<std::net::SocketAddr>().is_err() {
+                anyhow::bail!("WORKSTATION_CONTROL_SOCKET must be host:port on this platform");
+            }
+        }
+        if let Some(address) = &self.workstation.metrics_address {
+            if address.parse::<std::net::SocketAddr>().is_err() {
+                anyhow::bail!("WORKSTATION_METRICS_ADDRESS must be host:port");
+            }
+        }
+        for (name, value) in &self.workstation.inject_headers {
+            if hyper::header::HeaderName::from_bytes(name.as_bytes()).is_err() {
+                anyhow::bail!(
+                    "WORKSTATION_INJECT_HEADERS has an invalid header name '{}'",
+                    name
+                );
+            }
+            if hyper::header::HeaderValue::from_str(value).is_err() {
+                anyhow::bail!(
+                    "WORKSTATION_INJECT_HEADERS has an invalid value for '{}'",
+                    name
+                );
+            }
+        }
+        let rewrite = &self.workstation.path_rewrite;
+        if let Some(prefix) = rewrite
+            .strip_prefix
+            .iter()
+            .chain(&rewrite.add_prefix)
+            .find(|p| !p.starts_with('/'))
+        {
+            anyhow::bail!("Path rewrite prefix '{}' must start with '/'", prefix);
+        }
+        tunnel_core::validate_metadata(&self.workstation.metadata)
+            .map_err(|e| anyhow::anyhow!("WORKSTATION_METADATA: {}", e))?;
+        tunnel_core::validate_tags(&self.workstation.tags)
+            .map_err(|e| anyhow::anyhow!("WORKSTATION_TAGS: {}", e))?;
+        if self.tls.alpn.is_empty() {
+            anyhow::bail!("TLS_ALPN must not be empty");
+        }
+        self.tls
+            .policy()
+            .map_err(|e| anyhow::anyhow!("Invalid TLS settings: {}", e))?;
+        if self.limits.max_frame_bytes == 0 {
+            anyhow::bail!("LIMITS_MAX_FRAME_BYTES must be greater than 0");
+        }
+        self.quic.validate()?;
         Ok(())
     }
 }
@@ -120,21 +783,53 @@ impl Default for Config {
         Self {
             server: ServerConfig {
                 address: String::new(),
+                prefer_ipv6: false,
             },
             auth: AuthConfig {
                 api_key: String::new(),
+                api_key_file: None,
             },
             workstation: WorkstationConfig {
                 id: String::new(),
                 local_address: String::new(),
+                fallback_addresses: Vec::new(),
+                ws_allow_paths: Vec::new(),
+                ws_deny_paths: Vec::new(),
+                host_backends: BTreeMap::new(),
+                routes: Vec::new(),
+                connect_allow: Vec::new(),
+                local_request_timeout: default_local_request_timeout(),
+                local_connect_timeout: default_local_connect_timeout(),
+                pool_max_idle_per_host: default_pool_max_idle_per_host(),
+                pool_idle_timeout: default_pool_idle_timeout(),
+                http2_prior_knowledge: false,
+                forward_informational: false,
+                health_check_path: None,
+                health_check_interval: default_health_check_interval(),
+                control_socket: None,
+                metrics_address: None,
+                inject_headers: BTreeMap::new(),
+                inject_header_files: BTreeMap::new(),
+                inject_headers_policy: InjectHeadersPolicy::default(),
+                path_rewrite: PathRewrite::default(),
+                metadata: BTreeMap::new(),
+                tags: Vec::new(),
             },
             reconnect: ReconnectConfig {
                 enabled: default_reconnect_enabled(),
                 max_delay: default_max_delay(),
+                stable_after: default_stable_after(),
+                handshake_retries: default_handshake_retries(),
+                handshake_retry_delay: default_handshake_retry_delay(),
+                connect_timeout: default_connect_timeout(),
             },
             session: SessionConfig {
                 ticket_path: default_ticket_path(),
             },
+            limits: LimitsConfig::default(),
+            tls: TlsConfig::default(),
+            quic: QuicConfig::default(),
+            congestion_controller_error: None,
         }
     }
 }
@@ -2,12 +2,29 @@
 // Licensed under the FSL-1.1-NC.
 
 use crate::config::Config;
-use std::sync::Arc;
-use tunnel_core::{quic, ErrorMessage, Message, ReconnectMessage, RegisterMessage, Result};
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tunnel_core::{
+    quic, ErrorMessage, Message, ReconnectMessage, RegisterMessage, Result, GRACE_EXPIRED_ERROR,
+};
+
+/// Head start given to each connection attempt before the next address is tried.
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
 
 pub struct Connection {
     config: Config,
     session_ticket: Option<Vec<u8>>,
+    max_frame_bytes: usize,
+    server_version: Option<String>,
+    reconnect_token: Option<String>,
+    /// Built on first use and shared by every endpoint: rustls resumes a TLS
+    /// session, and so allows 0-RTT, only under the config that created it.
+    client_config: OnceLock<quinn::ClientConfig>,
+    /// Server address of the last successful connect, tried first for 0-RTT.
+    last_addr: Option<SocketAddr>,
+    resumed_early: bool,
 }
 
 impl Connection {
@@ -16,87 +33,383 @@ impl Connection {
         Self {
             config,
             session_ticket,
+            max_frame_bytes: quic::LEGACY_MAX_FRAME_BYTES,
+            server_version: None,
+            reconnect_token: None,
+            client_config: OnceLock::new(),
+            last_addr: None,
+            resumed_early: false,
         }
     }
 
+    /// Frame size limit negotiated with the server on the last successful connect.
+    pub fn max_frame_bytes(&self) -> usize {
+        self.max_frame_bytes
+    }
+
+    /// Build reported by the server on the last successful connect, if any.
+    pub fn server_version(&self) -> Option<&str> {
+        self.server_version.as_deref()
+    }
+
+    /// Secret the server expects on the next reconnect, issued on the last
+    /// successful connect.
+    pub fn reconnect_token(&self) -> Option<&str> {
+        self.reconnect_token.as_deref()
+    }
+
+    /// Whether the last connect sent its first message as 0-RTT early data
+    /// that the server accepted.
+    pub fn resumed_early(&self) -> bool {
+        self.resumed_early
+    }
+
+    /// Uses a token obtained by another `Connection`, e.g. to hand a
+    /// workstation over to a new process with [`migrate`](Self::migrate).
+    pub fn set_reconnect_token(&mut self, token: String) {
+        self.reconnect_token = Some(token);
+    }
+
+    /// Resolves the server and completes a QUIC handshake without registering,
+    /// for validating a deployment.
+    pub async fn check(&self) -> Result<()> {
+        let connection = with_timeout(self.connect_timeout(), self.handshake()).await?;
+        connection.close(0u32.into(), b"check");
+        Ok(())
+    }
+
+    /// Registers the workstation. After an earlier connect this resumes the
+    /// registration with a `Reconnect` instead, falling back to registering
+    /// again if the server no longer knows it, e.g. after a restart, or the
+    /// client stayed away past the grace period.
     pub async fn connect(&mut self) -> Result<(quinn::Connection, String)> {
-        let endpoint = self.create_endpoint()?;
+        let limit = self.connect_timeout();
+        with_timeout(limit, self.connect_inner()).await
+    }
 
-        let addr = tokio::net::lookup_host(&self.config.server.address)
-            .await
-            .map_err(|e| {
-                tunnel_core::Error::Other(format!("failed to resolve server address: {}", e))
-            })?
-            .find(|addr| addr.is_ipv4())
-            .ok_or_else(|| {
-                tunnel_core::Error::Other("no IPv4 addresses found for server".to_string())
-            })?;
+    async fn connect_inner(&mut self) -> Result<(quinn::Connection, String)> {
+        if self.session_ticket.is_some() || self.reconnect_token.is_some() {
+            match self.try_establish(true).await? {
+                Ok(registered) => return Ok(registered),
+                Err(refused) if refused.code == GRACE_EXPIRED_ERROR => {
+                    tracing::info!("Grace period expired while disconnected, registering again");
+                    self.clear_session();
+                }
+                Err(refused) => {
+                    tracing::info!(
+                        "Server refused the reconnect ({}), registering again",
+                        refused.message
+                    );
+                    self.clear_session();
+                }
+            }
+        }
+        self.establish(false).await
+    }
 
-        let connection = endpoint
-            .connect(addr, "tunnel")
-            .map_err(|e| tunnel_core::Error::Connection(format!("connection failed: {}", e)))?
-            .await
-            .map_err(|e| tunnel_core::Error::Connection(format!("connection failed: {}", e)))?;
+    /// Forgets the session held from an earlier connect, including the
+    /// ticket on disk, so the next connect registers from scratch.
+    fn clear_session(&mut self) {
+        self.session_ticket = None;
+        self.reconnect_token = None;
+        let _ = std::fs::remove_file(&self.config.session.ticket_path);
+    }
 
-        let is_reconnect = self.session_ticket.is_some();
+    /// Opens a new connection that takes over from one that is still up. The
+    /// server sends new streams to the returned connection and closes the old
+    /// one once the streams in flight on it have finished.
+    pub async fn migrate(&mut self) -> Result<(quinn::Connection, String)> {
+        let limit = self.connect_timeout();
+        with_timeout(limit, self.establish(true)).await
+    }
 
-        let (mut send, mut recv) = connection
-            .open_bi()
-            .await
-            .map_err(|e| tunnel_core::Error::Connection(format!("failed to open stream: {}", e)))?;
+    /// `reconnect.connect_timeout`, or `None` when disabled.
+    fn connect_timeout(&self) -> Option<Duration> {
+        match self.config.reconnect.connect_timeout {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        }
+    }
+
+    async fn establish(&mut self, is_reconnect: bool) -> Result<(quinn::Connection, String)> {
+        self.try_establish(is_reconnect)
+            .await?
+            .map_err(refusal_error)
+    }
 
-        let message = if is_reconnect {
+    /// Like [`establish`](Self::establish), but hands back the server's
+    /// refusal instead of turning it into an error.
+    async fn try_establish(
+        &mut self,
+        is_reconnect: bool,
+    ) -> Result<std::result::Result<(quinn::Connection, String), ErrorMessage>> {
+        let message = self.first_message(is_reconnect);
+
+        // Only a reconnect goes out as 0-RTT data, so the server can answer it
+        // while the handshake finishes. A register gains nothing from it.
+        let early = if is_reconnect {
+            self.exchange_early(&message).await
+        } else {
+            None
+        };
+        self.resumed_early = matches!(early, Some((_, _, true)));
+        let (connection, response) = match early {
+            Some((connection, response, _)) => (connection, response),
+            None => {
+                let connection = self.handshake_with_retries().await?;
+                let response = exchange(&connection, &message).await?;
+                (connection, response)
+            }
+        };
+
+        match response {
+            Message::Registered(reg) => {
+                self.max_frame_bytes = quic::negotiate_max_frame_bytes(
+                    self.config.limits.max_frame_bytes,
+                    reg.max_frame_bytes,
+                );
+                self.server_version = reg.server_version;
+                self.reconnect_token = reg.reconnect_token;
+                self.last_addr = Some(connection.remote_address());
+                self.save_session_ticket(&connection);
+                Ok(Ok((connection, reg.url)))
+            }
+            Message::Error(refused) => Ok(Err(refused)),
+            other => Err(tunnel_core::Error::InvalidMessageType(
+                other.message_type().to_string(),
+            )),
+        }
+    }
+
+    /// Sends `message` as 0-RTT data to the last server address when a TLS
+    /// session from an earlier connection can be resumed. If the server turns
+    /// the early data down, the message is sent again once the handshake is
+    /// done; the flag returned tells whether it was accepted. Returns `None`
+    /// when 0-RTT is not possible or the attempt failed, leaving the caller to
+    /// do a full handshake.
+    async fn exchange_early(
+        &self,
+        message: &Message,
+    ) -> Option<(quinn::Connection, Message, bool)> {
+        let addr = self.last_addr?;
+        let bind: SocketAddr = if addr.is_ipv6() {
+            (Ipv6Addr::UNSPECIFIED, 0).into()
+        } else {
+            (Ipv4Addr::UNSPECIFIED, 0).into()
+        };
+        let endpoint = self.create_endpoint(bind).ok()?;
+        let connecting = endpoint.connect(addr, "tunnel").ok()?;
+        let Ok((connection, accepted)) = connecting.into_0rtt() else {
+            tracing::debug!("No TLS session to resume, using a full handshake");
+            return None;
+        };
+
+        let result = exchange(&connection, message).await;
+        // Resolves once the handshake is done.
+        let accepted = accepted.await;
+        let result = match result {
+            Err(_) if !accepted && connection.close_reason().is_none() => {
+                tracing::debug!("Server rejected 0-RTT data, resending after the handshake");
+                exchange(&connection, message).await
+            }
+            other => other,
+        };
+        match result {
+            Ok(response) => Some((connection, response, accepted)),
+            Err(e) => {
+                tracing::debug!("0-RTT reconnect to {} failed: {}", addr, e);
+                connection.close(0u32.into(), b"0-rtt failed");
+                None
+            }
+        }
+    }
+
+    fn first_message(&self, is_reconnect: bool) -> Message {
+        if is_reconnect {
             Message::Reconnect(ReconnectMessage {
                 api_key: self.config.auth.api_key.clone(),
                 workstation_id: self.config.workstation.id.clone(),
                 session_ticket: None,
+                reconnect_token: self.reconnect_token.clone(),
+                max_frame_bytes: Some(self.config.limits.max_frame_bytes),
+                ws_open_ack: true,
+                expect_continue: true,
+                ws_fragments: true,
+                ordered_headers: true,
+                ws_passthrough: true,
             })
         } else {
             Message::Register(RegisterMessage {
                 api_key: self.config.auth.api_key.clone(),
                 workstation_id: self.config.workstation.id.clone(),
+                max_frame_bytes: Some(self.config.limits.max_frame_bytes),
+                ws_open_ack: true,
+                expect_continue: true,
+                ws_fragments: true,
+                ordered_headers: true,
+                ws_passthrough: true,
+                metadata: self
+                    .config
+                    .workstation
+                    .metadata
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect(),
+                tags: self.config.workstation.tags.clone(),
             })
-        };
+        }
+    }
 
-        quic::send_message(&mut send, &message).await?;
-        let response = quic::recv_message(&mut recv).await?;
+    /// Retries handshakes that failed because the server could not be reached,
+    /// so a brief outage does not cost a full reconnect backoff.
+    async fn handshake_with_retries(&self) -> Result<quinn::Connection> {
+        let delay = Duration::from_millis(self.config.reconnect.handshake_retry_delay);
+        let mut retries = self.config.reconnect.handshake_retries;
+        loop {
+            match self.handshake().await {
+                Err(e) if retries > 0 && is_retryable(&e) => {
+                    retries -= 1;
+                    tracing::debug!("Handshake failed, retrying in {:?}: {}", delay, e);
+                    tokio::time::sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
 
-        match response {
-            Message::Registered(reg) => {
-                self.save_session_ticket(&connection);
-                Ok((connection, reg.url))
+    async fn handshake(&self) -> Result<quinn::Connection> {
+        let resolved: Vec<SocketAddr> = tokio::net::lookup_host(&self.config.server.address)
+            .await
+            .map_err(|e| {
+                tunnel_core::Error::Connection(format!("failed to resolve server address: {}", e))
+            })?
+            .collect();
+        let addrs = order_addresses(resolved, self.config.server.prefer_ipv6);
+        if addrs.is_empty() {
+            return Err(tunnel_core::Error::Other(
+                "no addresses found for server".to_string(),
+            ));
+        }
+
+        // Happy eyeballs: start the next address if the current ones have not
+        // connected within the attempt delay, and take the first to succeed.
+        let mut pending = addrs.iter().copied();
+        let mut attempts = FuturesUnordered::new();
+        let mut failures: Vec<(SocketAddr, tunnel_core::Error)> = Vec::new();
+
+        if let Some(addr) = pending.next() {
+            attempts.push(self.attempt(addr));
+        }
+
+        loop {
+            let next = tokio::time::timeout(CONNECTION_ATTEMPT_DELAY, attempts.next()).await;
+            match next {
+                Ok(Some((_, Ok(connection)))) => return Ok(connection),
+                Ok(Some((addr, Err(e)))) => {
+                    tracing::debug!("Connection attempt to {} failed: {}", addr, e);
+                    failures.push((addr, e));
+                    match pending.next() {
+                        Some(addr) => attempts.push(self.attempt(addr)),
+                        None if attempts.is_empty() => break,
+                        None => {}
+                    }
+                }
+                Ok(None) => break,
+                Err(_) => {
+                    if let Some(addr) = pending.next() {
+                        attempts.push(self.attempt(addr));
+                    }
+                }
             }
-            Message::Error(ErrorMessage { message, .. }) => Err(tunnel_core::Error::Other(
-                format!("server error: {}", message),
-            )),
-            _ => Err(tunnel_core::Error::Other("unexpected response".to_string())),
         }
+
+        // Every address failing the same permanent way, e.g. a version
+        // mismatch, is reported as such so the caller does not retry.
+        if failures.len() == 1 || failures.iter().all(|(_, e)| !is_retryable(e)) {
+            return Err(failures.remove(0).1);
+        }
+        let details: Vec<String> = failures
+            .iter()
+            .map(|(addr, e)| format!("{}: {}", addr, e))
+            .collect();
+        Err(tunnel_core::Error::Connection(format!(
+            "failed to connect to {}: {}",
+            self.config.server.address,
+            details.join("; ")
+        )))
     }
 
-    fn create_endpoint(&self) -> Result<quinn::Endpoint> {
-        let mut client_crypto = rustls::ClientConfig::builder()
+    async fn attempt(&self, addr: SocketAddr) -> (SocketAddr, Result<quinn::Connection>) {
+        (addr, self.connect_to(addr).await)
+    }
+
+    async fn connect_to(&self, addr: SocketAddr) -> Result<quinn::Connection> {
+        let bind: SocketAddr = if addr.is_ipv6() {
+            (Ipv6Addr::UNSPECIFIED, 0).into()
+        } else {
+            (Ipv4Addr::UNSPECIFIED, 0).into()
+        };
+        let endpoint = self.create_endpoint(bind)?;
+
+        endpoint
+            .connect(addr, "tunnel")
+            .map_err(|e| tunnel_core::Error::Connection(format!("connection failed: {}", e)))?
+            .await
+            .map_err(|e| {
+                if is_alpn_mismatch(&e) {
+                    tunnel_core::Error::Other(format!(
+                        "server rejected ALPN \"{}\"; tls.alpn must match the server",
+                        self.config.tls.alpn
+                    ))
+                } else {
+                    tunnel_core::Error::Closed(quic::CloseReason::from(&e))
+                }
+            })
+    }
+
+    fn create_endpoint(&self, bind: SocketAddr) -> Result<quinn::Endpoint> {
+        let mut endpoint = quinn::Endpoint::client(bind)
+            .map_err(|e| tunnel_core::Error::Other(format!("failed to create endpoint: {}", e)))?;
+
+        endpoint.set_default_client_config(self.client_config()?);
+
+        Ok(endpoint)
+    }
+
+    fn client_config(&self) -> Result<quinn::ClientConfig> {
+        if let Some(client_config) = self.client_config.get() {
+            return Ok(client_config.clone());
+        }
+
+        let mut client_crypto = self
+            .config
+            .tls
+            .policy()?
+            .client_builder()
             .dangerous()
             .with_custom_certificate_verifier(Arc::new(SkipServerVerification::new()))
             .with_no_client_auth();
 
-        client_crypto.alpn_protocols = vec![b"tiflis-tunnel".to_vec()];
+        client_crypto.alpn_protocols = vec![self.config.tls.alpn.as_bytes().to_vec()];
+        client_crypto.enable_early_data = true;
 
         let mut client_config = quinn::ClientConfig::new(Arc::new(
-            quinn::crypto::rustls::QuicClientConfig::try_from(client_crypto).map_err(|e| {
+            quinn::crypto::rustls::QuicClientConfig::with_initial(
+                Arc::new(client_crypto),
+                tunnel_core::tls::quic_initial_suite(),
+            )
+            .map_err(|e| {
                 tunnel_core::Error::Other(format!("failed to create QUIC config: {}", e))
             })?,
         ));
 
-        let mut transport_config = quinn::TransportConfig::default();
-        transport_config.max_concurrent_bidi_streams(1000u32.into());
-        client_config.transport_config(Arc::new(transport_config));
-
-        let mut endpoint = quinn::Endpoint::client("0.0.0.0:0".parse().unwrap())
-            .map_err(|e| tunnel_core::Error::Other(format!("failed to create endpoint: {}", e)))?;
+        client_config.transport_config(Arc::new(self.config.quic.transport_config()));
+        tracing::info!(
+            "QUIC congestion control: {}",
+            self.config.quic.congestion_controller
+        );
 
-        endpoint.set_default_client_config(client_config);
-
-        Ok(endpoint)
+        Ok(self.client_config.get_or_init(|| client_config).clone())
     }
 
     fn load_session_ticket(config: &Config) -> Option<Vec<u8>> {
@@ -110,6 +423,76 @@ impl Connection {
     }
 }
 
+fn refusal_error(refused: ErrorMessage) -> tunnel_core::Error {
+    tunnel_core::Error::Other(format!("server error: {}", refused.message))
+}
+
+/// Fails with [`tunnel_core::Error::ConnectTimeout`] once `limit` has passed.
+async fn with_timeout<T>(
+    limit: Option<Duration>,
+    future: impl std::future::Future<Output = Result<T>>,
+) -> Result<T> {
+    match limit {
+        Some(limit) => tokio::time::timeout(limit, future)
+            .await
+            .unwrap_or(Err(tunnel_core::Error::ConnectTimeout(limit))),
+        None => future.await,
+    }
+}
+
+/// Sends the opening message on a new stream and reads the server's answer.
+async fn exchange(connection: &quinn::Connection, message: &Message) -> Result<Message> {
+    let (mut send, mut recv) = connection
+        .open_bi()
+        .await
+        .map_err(|e| tunnel_core::Error::Connection(format!("failed to open stream: {}", e)))?;
+    quic::send_message(&mut send, message).await?;
+    quic::recv_message(&mut recv).await
+}
+
+/// Interleaves address families, starting with the preferred one, so a broken
+/// family only delays the first attempt rather than all of them.
+fn order_addresses(addrs: Vec<SocketAddr>, prefer_ipv6: bool) -> Vec<SocketAddr> {
+    let (v6, v4): (Vec<_>, Vec<_>) = addrs.into_iter().partition(|a| a.is_ipv6());
+    let (first, second) = if prefer_ipv6 { (v6, v4) } else { (v4, v6) };
+
+    let mut ordered = Vec::with_capacity(first.len() + second.len());
+    let mut first = first.into_iter();
+    let mut second = second.into_iter();
+    loop {
+        match (first.next(), second.next()) {
+            (None, None) => break,
+            (a, b) => ordered.extend(a.into_iter().chain(b)),
+        }
+    }
+    ordered
+}
+
+/// TLS `no_application_protocol` alert, sent when no offered ALPN is accepted.
+const TLS_ALERT_NO_APPLICATION_PROTOCOL: u8 = 120;
+
+fn is_alpn_mismatch(e: &quinn::ConnectionError) -> bool {
+    matches!(
+        e,
+        quinn::ConnectionError::ConnectionClosed(close)
+            if close.error_code
+                == quinn::TransportErrorCode::crypto(TLS_ALERT_NO_APPLICATION_PROTOCOL)
+    )
+}
+
+/// Whether a handshake failure may clear up on its own: the server could not
+/// be resolved or reached in time. Configuration mistakes such as an ALPN
+/// mismatch, and QUIC version mismatches, fail the same way on every try.
+fn is_retryable(e: &tunnel_core::Error) -> bool {
+    match e {
+        tunnel_core::Error::Closed(reason) => reason.is_retryable(),
+        tunnel_core::Error::Connection(_)
+        | tunnel_core::Error::RequestTimeout
+        | tunnel_core::Error::Io(_) => true,
+        _ => false,
+    }
+}
+
 #[derive(Debug)]
 struct SkipServerVerification(Arc<rustls::crypto::CryptoProvider>);
 
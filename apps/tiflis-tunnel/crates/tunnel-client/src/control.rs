@@ -0,0 +1,186 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+//! Local control API (`workstation.control_socket`) so tooling can ask a
+//! running client for its tunnel URL without scraping logs.
+
+use serde::Serialize;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::time::{timeout, Duration};
+use tracing::{debug, info, warn};
+
+/// Largest request head the control API reads.
+const MAX_REQUEST_BYTES: usize = 8192;
+/// Time a caller has to send its request.
+pub(crate) const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionState {
+    /// First connection attempt in progress.
+    Connecting,
+    Connected,
+    /// Connection lost; backing off or retrying.
+    Reconnecting,
+    /// Connection lost with reconnects disabled.
+    Disconnected,
+}
+
+/// Answer to `GET /status`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientStatus {
+    pub workstation_id: String,
+    pub state: ConnectionState,
+    /// URL from the latest registration, kept while reconnecting.
+    pub url: Option<String>,
+    pub reconnect_attempts: u32,
+    /// Why the previous connection ended, if one has.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub close_reason: Option<String>,
+}
+
+impl ClientStatus {
+    pub fn new(workstation_id: impl Into<String>) -> Self {
+        Self {
+            workstation_id: workstation_id.into(),
+            state: ConnectionState::Connecting,
+            url: None,
+            reconnect_attempts: 0,
+            close_reason: None,
+        }
+    }
+}
+
+pub enum ControlListener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener, std::path::PathBuf),
+}
+
+impl ControlListener {
+    /// Listens on TCP when `socket` parses as `host:port`, otherwise on a Unix
+    /// socket at that path, replacing a stale socket file.
+    pub async fn bind(socket: &str) -> io::Result<Self> {
+        if let Ok(addr) = socket.parse::<SocketAddr>() {
+            return Ok(Self::Tcp(TcpListener::bind(addr).await?));
+        }
+        #[cfg(unix)]
+        {
+            let path = std::path::PathBuf::from(socket);
+            match std::fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+            let listener = tokio::net::UnixListener::bind(&path)?;
+            Ok(Self::Unix(listener, path))
+        }
+        #[cfg(not(unix))]
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("control socket '{}' is not host:port", socket),
+        ))
+    }
+
+    /// Answers requests with the current `status` until the task is dropped.
+    pub async fn serve(self, status: Arc<Mutex<ClientStatus>>) {
+        match &self {
+            Self::Tcp(listener) => {
+                if let Ok(addr) = listener.local_addr() {
+                    info!("Control API listening on {}", addr);
+                }
+            }
+            #[cfg(unix)]
+            Self::Unix(_, path) => info!("Control API listening on {}", path.display()),
+        }
+        loop {
+            let result = match &self {
+                Self::Tcp(listener) => listener.accept().await.map(|(stream, _)| {
+                    tokio::spawn(respond(stream, status.clone()));
+                }),
+                #[cfg(unix)]
+                Self::Unix(listener, _) => listener.accept().await.map(|(stream, _)| {
+                    tokio::spawn(respond(stream, status.clone()));
+                }),
+            };
+            if let Err(e) = result {
+                warn!("Control API accept failed: {}", e);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+    }
+}
+
+impl Drop for ControlListener {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Self::Unix(_, path) = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+async fn respond<S>(mut stream: S, status: Arc<Mutex<ClientStatus>>)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let head = match timeout(REQUEST_TIMEOUT, read_head(&mut stream)).await {
+        Ok(Ok(head)) => head,
+        Ok(Err(e)) => {
+            debug!("Control API read failed: {}", e);
+            return;
+        }
+        Err(_) => return,
+    };
+    let mut parts = head.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let (code, body) = match (method, path) {
+        ("GET", "/status") => {
+            let status = status.lock().unwrap().clone();
+            ("200 OK", serde_json::to_string(&status).unwrap_or_default())
+        }
+        (_, "/status") => ("405 Method Not Allowed", String::new()),
+        _ => ("404 Not Found", String::new()),
+    };
+    write_response(&mut stream, code, "application/json", &body).await;
+}
+
+/// Writes a complete response and closes the stream.
+pub(crate) async fn write_response<S>(stream: &mut S, code: &str, content_type: &str, body: &str)
+where
+    S: AsyncWrite + Unpin,
+{
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        code,
+        content_type,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Reads up to the end of the request head and returns its first line.
+pub(crate) async fn read_head<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<String> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if buf.len() > MAX_REQUEST_BYTES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "request head too large",
+            ));
+        }
+    }
+    let head = String::from_utf8_lossy(&buf);
+    Ok(head.lines().next().unwrap_or_default().to_string())
+}
@@ -0,0 +1,295 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+use crate::client::serve_connection;
+use crate::config::Config;
+use crate::connection::Connection;
+use crate::ping::{self, PingSample};
+use crate::proxy::{LocalProxy, LOCAL_WS_MAX_MESSAGE_BYTES};
+use futures::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::{CloseFrame, WebSocketConfig};
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tunnel_core::{quic, Error, Result};
+
+/// Typed access to a running tunnel. The workstation is served in the
+/// background, and `request`, `open_ws` and `open_sse` reach it through its
+/// public URL, the way any outside caller would, so embedders can exercise
+/// their tunnel without dealing with HTTP or WebSocket clients themselves.
+#[derive(Clone)]
+pub struct TunnelClientHandle {
+    connection: quinn::Connection,
+    /// Public tunnel URL returned at registration.
+    url: String,
+    http: reqwest::Client,
+    /// Presented by [`migrate`](Self::migrate).
+    reconnect_token: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TunnelRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TunnelResponse {
+    pub status: u16,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WsFrame {
+    Text(String),
+    Binary(Vec<u8>),
+    Close {
+        code: Option<u16>,
+        reason: Option<String>,
+    },
+}
+
+impl TunnelClientHandle {
+    /// Registers with the server and serves tunneled traffic to
+    /// `workstation.local_address` in the background. Returns the handle and
+    /// the public tunnel URL.
+    pub async fn connect(config: Config) -> Result<(Self, String)> {
+        let proxy = Arc::new(LocalProxy::from_config(&config));
+        let mut connection = Connection::new(config);
+        let (conn, url) = connection.connect().await?;
+        Self::serve(conn, url, &connection, proxy)
+    }
+
+    /// Like [`connect`](Self::connect), but takes over the workstation from
+    /// this handle while its connection is still up, e.g. before retiring a
+    /// degraded path. Streams in flight on the old connection keep being served
+    /// until they finish; the server then closes it.
+    pub async fn migrate(&self, config: Config) -> Result<(Self, String)> {
+        let proxy = Arc::new(LocalProxy::from_config(&config));
+        let mut connection = Connection::new(config);
+        if let Some(token) = &self.reconnect_token {
+            connection.set_reconnect_token(token.clone());
+        }
+        let (conn, url) = connection.migrate().await?;
+        Self::serve(conn, url, &connection, proxy)
+    }
+
+    fn serve(
+        conn: quinn::Connection,
+        url: String,
+        connection: &Connection,
+        proxy: Arc<LocalProxy>,
+    ) -> Result<(Self, String)> {
+        let http = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .map_err(|e| Error::Other(format!("failed to create HTTP client: {}", e)))?;
+        tokio::spawn(serve_connection(
+            conn.clone(),
+            proxy,
+            connection.max_frame_bytes(),
+        ));
+        let handle = Self {
+            connection: conn,
+            url: url.clone(),
+            http,
+            reconnect_token: connection.reconnect_token().map(str::to_string),
+        };
+        Ok((handle, url))
+    }
+
+    pub fn connection(&self) -> &quinn::Connection {
+        &self.connection
+    }
+
+    /// Public tunnel URL returned at registration.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Path statistics of the underlying QUIC connection.
+    pub fn quality(&self) -> quic::ConnectionQuality {
+        quic::ConnectionQuality::from_connection(&self.connection)
+    }
+
+    /// Measures the round trip to the peer with a ping.
+    pub async fn ping(&self) -> Result<PingSample> {
+        ping::ping(&self.connection).await
+    }
+
+    /// Sends `request` to the public URL and returns the workstation's
+    /// response. Redirects are returned, not followed.
+    pub async fn request(&self, request: TunnelRequest) -> Result<TunnelResponse> {
+        let method = reqwest::Method::from_bytes(request.method.as_bytes())
+            .map_err(|e| Error::Other(format!("invalid method: {}", e)))?;
+        let mut builder = self.http.request(method, self.public_url(&request.path));
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+        let response = builder
+            .body(request.body)
+            .send()
+            .await
+            .map_err(|e| Error::Connection(e.to_string()))?;
+
+        let status = response.status().as_u16();
+        let headers = response_headers(response.headers());
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| Error::Connection(e.to_string()))?;
+        Ok(TunnelResponse {
+            status,
+            headers,
+            body: body.to_vec(),
+        })
+    }
+
+    /// Opens a WebSocket to `path` through the public URL.
+    pub async fn open_ws(&self, path: &str, headers: HashMap<String, String>) -> Result<WsStream> {
+        let url = self
+            .public_url(path)
+            .replacen("http://", "ws://", 1)
+            .replacen("https://", "wss://", 1);
+        let mut request = url
+            .into_client_request()
+            .map_err(|e| Error::Other(format!("invalid WebSocket request: {}", e)))?;
+        for (name, value) in headers {
+            let name = reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| Error::Other(format!("invalid header name: {}", e)))?;
+            let value = reqwest::header::HeaderValue::from_str(&value)
+                .map_err(|e| Error::Other(format!("invalid header value: {}", e)))?;
+            request.headers_mut().insert(name, value);
+        }
+        let config = WebSocketConfig {
+            max_message_size: Some(LOCAL_WS_MAX_MESSAGE_BYTES),
+            ..Default::default()
+        };
+        let (socket, _) =
+            tokio_tungstenite::connect_async_with_config(request, Some(config), false)
+                .await
+                .map_err(|e| Error::Connection(e.to_string()))?;
+        Ok(WsStream { socket })
+    }
+
+    /// Opens an event stream at `path` through the public URL.
+    pub async fn open_sse(
+        &self,
+        method: &str,
+        path: &str,
+        headers: HashMap<String, String>,
+    ) -> Result<SseStream> {
+        let method = reqwest::Method::from_bytes(method.as_bytes())
+            .map_err(|e| Error::Other(format!("invalid method: {}", e)))?;
+        let mut builder = self
+            .http
+            .request(method, self.public_url(path))
+            .header(reqwest::header::ACCEPT, "text/event-stream");
+        for (name, value) in &headers {
+            builder = builder.header(name, value);
+        }
+        let response = builder
+            .send()
+            .await
+            .map_err(|e| Error::Connection(e.to_string()))?;
+        Ok(SseStream {
+            status: response.status().as_u16(),
+            headers: response_headers(response.headers()),
+            response,
+        })
+    }
+
+    fn public_url(&self, path: &str) -> String {
+        format!(
+            "{}/{}",
+            self.url.trim_end_matches('/'),
+            path.trim_start_matches('/')
+        )
+    }
+}
+
+/// Response headers keyed by name; repeated values are joined with `, `.
+fn response_headers(headers: &reqwest::header::HeaderMap) -> HashMap<String, String> {
+    let mut out: HashMap<String, String> = HashMap::new();
+    for (name, value) in headers {
+        let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+        out.entry(name.as_str().to_string())
+            .and_modify(|joined| {
+                joined.push_str(", ");
+                joined.push_str(&value);
+            })
+            .or_insert(value);
+    }
+    out
+}
+
+/// A WebSocket opened through the tunnel.
+pub struct WsStream {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl WsStream {
+    pub async fn send(&mut self, frame: WsFrame) -> Result<()> {
+        let msg = match frame {
+            WsFrame::Text(text) => WsMessage::Text(text),
+            WsFrame::Binary(data) => WsMessage::Binary(data),
+            WsFrame::Close { code, reason } => WsMessage::Close(code.map(|code| CloseFrame {
+                code: CloseCode::from(code),
+                reason: reason.unwrap_or_default().into(),
+            })),
+        };
+        self.socket
+            .send(msg)
+            .await
+            .map_err(|e| Error::Connection(e.to_string()))
+    }
+
+    /// Next text, binary or close message. Pings are answered as they arrive.
+    pub async fn recv(&mut self) -> Result<WsFrame> {
+        loop {
+            let msg = match self.socket.next().await {
+                Some(msg) => msg.map_err(|e| Error::Connection(e.to_string()))?,
+                None => {
+                    return Ok(WsFrame::Close {
+                        code: None,
+                        reason: None,
+                    })
+                }
+            };
+            return Ok(match msg {
+                WsMessage::Text(text) => WsFrame::Text(text),
+                WsMessage::Binary(data) => WsFrame::Binary(data),
+                WsMessage::Close(frame) => WsFrame::Close {
+                    code: frame.as_ref().map(|f| u16::from(f.code)),
+                    reason: frame.map(|f| f.reason.into_owned()),
+                },
+                WsMessage::Ping(_) | WsMessage::Pong(_) | WsMessage::Frame(_) => continue,
+            });
+        }
+    }
+}
+
+/// An event stream opened through the tunnel.
+pub struct SseStream {
+    pub status: u16,
+    pub headers: HashMap<String, String>,
+    response: reqwest::Response,
+}
+
+impl SseStream {
+    /// Next chunk of the event stream, or `None` once the origin closed it.
+    pub async fn next_chunk(&mut self) -> Result<Option<Vec<u8>>> {
+        self.response
+            .chunk()
+            .await
+            .map(|chunk| chunk.map(|chunk| chunk.to_vec()))
+            .map_err(|e| Error::Connection(e.to_string()))
+    }
+}
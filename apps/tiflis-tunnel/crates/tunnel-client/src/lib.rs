@@ -4,5 +4,13 @@
 pub mod client;
 pub mod config;
 pub mod connection;
+pub mod control;
+pub mod handle;
+pub mod metrics;
+pub mod ping;
 pub mod proxy;
+pub mod range;
 pub mod reconnect;
+pub mod sse;
+#[cfg(feature = "testing")]
+pub mod testing;
@@ -1,7 +1,7 @@
 // Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
 // Licensed under the FSL-1.1-NC.
 
-use tunnel_client::{client, config};
+use tunnel_client::{client, config, connection};
 
 use clap::Parser;
 use std::path::PathBuf;
@@ -13,6 +13,37 @@ use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 struct Args {
     #[arg(short, long, value_name = "FILE")]
     config: Option<PathBuf>,
+
+    /// Validate the configuration and complete a QUIC handshake with the server, then exit
+    #[arg(long)]
+    check: bool,
+
+    /// Server address, overriding the config file and SERVER_ADDRESS
+    #[arg(long, value_name = "HOST:PORT")]
+    server: Option<String>,
+
+    /// API key, overriding the config file, AUTH_API_KEY and AUTH_API_KEY_FILE
+    #[arg(long, value_name = "KEY")]
+    api_key: Option<String>,
+
+    /// Workstation id, overriding the config file and WORKSTATION_ID
+    #[arg(long, value_name = "ID")]
+    workstation_id: Option<String>,
+
+    /// Local backend URL, overriding the config file and WORKSTATION_LOCAL_ADDRESS
+    #[arg(long, value_name = "URL")]
+    local: Option<String>,
+}
+
+impl Args {
+    fn overrides(&self) -> config::ConfigOverrides {
+        config::ConfigOverrides {
+            server_address: self.server.clone(),
+            api_key: self.api_key.clone(),
+            workstation_id: self.workstation_id.clone(),
+            local_address: self.local.clone(),
+        }
+    }
 }
 
 #[tokio::main]
@@ -28,7 +59,14 @@ async fn main() -> anyhow::Result<()> {
         .init();
 
     let args = Args::parse();
-    let config = config::Config::load(args.config)?;
+    let overrides = args.overrides();
+    let config = config::Config::load_with_overrides(args.config, &overrides)?;
+
+    if args.check {
+        connection::Connection::new(config).check().await?;
+        tracing::info!("Configuration OK, server reachable");
+        return Ok(());
+    }
 
     tracing::info!("Starting Tiflis Tunnel Client");
     tracing::info!("Workstation ID: {}", config.workstation.id);
@@ -37,3 +75,69 @@ async fn main() -> anyhow::Result<()> {
     let mut client = client::TunnelClient::new(config);
     client.run().await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flags_override_file_and_env() {
+        let path =
+            std::env::temp_dir().join(format!("tunnel-client-args-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(
+            &path,
+            r#"
+[server]
+address = "file.example.com:443"
+
+[auth]
+api_key = "file-key"
+
+[workstation]
+id = "file-ws"
+local_address = "http://localhost:8080"
+
+[reconnect]
+
+[session]
+"#,
+        )
+        .unwrap();
+        std::env::set_var("WORKSTATION_ID", "env-ws");
+
+        let args = Args::try_parse_from([
+            "tunnel-client",
+            "--config",
+            path.to_str().unwrap(),
+            "--server",
+            "flag.example.com:443",
+            "--api-key",
+            "flag-key",
+            "--workstation-id",
+            "flag-ws",
+            "--local",
+            "http://localhost:3000",
+        ])
+        .unwrap();
+        let overrides = args.overrides();
+        let config = config::Config::load_with_overrides(args.config, &overrides);
+        std::env::remove_var("WORKSTATION_ID");
+        std::fs::remove_file(&path).unwrap();
+
+        let config = config.unwrap();
+        assert_eq!(config.server.address, "flag.example.com:443");
+        assert_eq!(config.auth.api_key, "flag-key");
+        assert_eq!(config.workstation.id, "flag-ws");
+        assert_eq!(config.workstation.local_address, "http://localhost:3000");
+    }
+
+    #[test]
+    fn test_unset_flags_keep_loaded_values() {
+        let args = Args::try_parse_from(["tunnel-client"]).unwrap();
+        let overrides = args.overrides();
+        assert!(overrides.server_address.is_none());
+        assert!(overrides.api_key.is_none());
+        assert!(overrides.workstation_id.is_none());
+        assert!(overrides.local_address.is_none());
+    }
+}
@@ -0,0 +1,342 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+//! Prometheus endpoint (`workstation.metrics_address`) with this client's
+//! own counters, so each workstation can be scraped without the server.
+
+use crate::control::{read_head, write_response, ClientStatus, ConnectionState, REQUEST_TIMEOUT};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{timeout, Duration};
+use tracing::{debug, info, warn};
+use tunnel_core::codec::CodecStats;
+use tunnel_core::HttpResponseMessage;
+
+/// Kinds of long-lived streams opened to the local backend.
+#[derive(Debug, Clone, Copy)]
+pub enum StreamKind {
+    WebSocket,
+    Sse,
+    H2,
+    Connect,
+}
+
+#[derive(Debug, Default)]
+pub struct ClientMetrics {
+    http_requests: AtomicU64,
+    /// HTTP requests the backend could not answer at all.
+    http_failures: AtomicU64,
+    http_request_bytes: AtomicU64,
+    http_response_bytes: AtomicU64,
+    /// Indexed by `StreamKind`.
+    streams: [AtomicU64; 4],
+    /// Successful registrations with the server, the first one included.
+    connects: AtomicU64,
+    /// Latest backend health check result; `None` until one has run.
+    backend_healthy: Mutex<Option<bool>>,
+    ping_rtt: Mutex<Option<Duration>>,
+}
+
+impl ClientMetrics {
+    pub fn record_http(&self, result: &Result<HttpResponseMessage, String>) {
+        self.http_requests.fetch_add(1, Ordering::Relaxed);
+        if result.is_err() {
+            self.http_failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Counts a request body sent to the backend and the response body it
+    /// returned.
+    pub fn record_http_bytes(&self, request: usize, response: usize) {
+        self.http_request_bytes
+            .fetch_add(request as u64, Ordering::Relaxed);
+        self.http_response_bytes
+            .fetch_add(response as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_stream(&self, kind: StreamKind) {
+        self.streams[kind as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_connect(&self) {
+        self.connects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_backend_healthy(&self, healthy: bool) {
+        *self.backend_healthy.lock().unwrap() = Some(healthy);
+    }
+
+    pub fn set_ping_rtt(&self, rtt: Duration) {
+        *self.ping_rtt.lock().unwrap() = Some(rtt);
+    }
+
+    /// Prometheus text exposition of the counters and `status`.
+    pub fn render(&self, status: &ClientStatus) -> String {
+        let mut out = String::new();
+        let counter = |out: &mut String, name: &str, help: &str, value: &AtomicU64| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+        };
+        let gauge = |out: &mut String, name: &str, help: &str, value: String| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} gauge", name);
+            let _ = writeln!(out, "{} {}", name, value);
+        };
+
+        counter(
+            &mut out,
+            "tunnel_client_http_requests_total",
+            "HTTP requests forwarded to the local backend.",
+            &self.http_requests,
+        );
+        counter(
+            &mut out,
+            "tunnel_client_http_failures_total",
+            "HTTP requests the local backend did not answer.",
+            &self.http_failures,
+        );
+        counter(
+            &mut out,
+            "tunnel_client_http_request_bytes_total",
+            "Request body bytes sent to the local backend.",
+            &self.http_request_bytes,
+        );
+        counter(
+            &mut out,
+            "tunnel_client_http_response_bytes_total",
+            "Response body bytes received from the local backend.",
+            &self.http_response_bytes,
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP tunnel_client_streams_total Long-lived streams opened to the local backend."
+        );
+        let _ = writeln!(out, "# TYPE tunnel_client_streams_total counter");
+        for (kind, count) in ["websocket", "sse", "h2", "connect"]
+            .iter()
+            .zip(&self.streams)
+        {
+            let _ = writeln!(
+                out,
+                "tunnel_client_streams_total{{kind=\"{}\"}} {}",
+                kind,
+                count.load(Ordering::Relaxed)
+            );
+        }
+
+        let connects = self.connects.load(Ordering::Relaxed);
+        let _ = writeln!(
+            out,
+            "# HELP tunnel_client_reconnects_total Registrations with the server after the first."
+        );
+        let _ = writeln!(out, "# TYPE tunnel_client_reconnects_total counter");
+        let _ = writeln!(
+            out,
+            "tunnel_client_reconnects_total {}",
+            connects.saturating_sub(1)
+        );
+
+        let connected = status.state == ConnectionState::Connected;
+        gauge(
+            &mut out,
+            "tunnel_client_connected",
+            "Whether the tunnel to the server is up.",
+            u8::from(connected).to_string(),
+        );
+        if let Some(healthy) = *self.backend_healthy.lock().unwrap() {
+            gauge(
+                &mut out,
+                "tunnel_client_backend_healthy",
+                "Result of the latest local backend health check.",
+                u8::from(healthy).to_string(),
+            );
+        }
+        if let Some(rtt) = *self.ping_rtt.lock().unwrap() {
+            gauge(
+                &mut out,
+                "tunnel_client_ping_rtt_seconds",
+                "Round-trip time of the latest keepalive ping.",
+                rtt.as_secs_f64().to_string(),
+            );
+        }
+        if let Some(codec) = tunnel_core::codec::metrics() {
+            render_codec(&mut out, &codec.snapshot());
+        }
+        out
+    }
+}
+
+/// Frame counters from the tunnel codec, split by direction.
+fn render_codec(out: &mut String, stats: &CodecStats) {
+    let by_direction = |out: &mut String, name: &str, kind: &str, help: &str, sent, received| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        let _ = writeln!(out, "{}{{direction=\"sent\"}} {}", name, sent);
+        let _ = writeln!(out, "{}{{direction=\"received\"}} {}", name, received);
+    };
+    by_direction(
+        out,
+        "tunnel_client_frames_total",
+        "counter",
+        "Tunnel frames exchanged with the server.",
+        stats.frames_encoded,
+        stats.frames_decoded,
+    );
+    by_direction(
+        out,
+        "tunnel_client_frame_bytes_total",
+        "counter",
+        "Tunnel frame bytes exchanged with the server, length prefix included.",
+        stats.bytes_encoded,
+        stats.bytes_decoded,
+    );
+    by_direction(
+        out,
+        "tunnel_client_largest_frame_bytes",
+        "gauge",
+        "Largest tunnel frame exchanged with the server.",
+        stats.largest_encoded,
+        stats.largest_decoded,
+    );
+    for (name, help, value) in [
+        (
+            "tunnel_client_ws_fragmented_messages_total",
+            "WebSocket messages split over several frames.",
+            stats.fragmented_messages,
+        ),
+        (
+            "tunnel_client_ws_fragments_total",
+            "Frames carrying pieces of fragmented WebSocket messages.",
+            stats.fragments,
+        ),
+    ] {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} counter", name);
+        let _ = writeln!(out, "{} {}", name, value);
+    }
+}
+
+/// Answers `GET /metrics` on `listener` until the task is dropped.
+pub async fn serve(
+    listener: TcpListener,
+    metrics: Arc<ClientMetrics>,
+    status: Arc<Mutex<ClientStatus>>,
+) {
+    if let Ok(addr) = listener.local_addr() {
+        info!("Metrics listening on {}", addr);
+    }
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(respond(stream, metrics.clone(), status.clone()));
+            }
+            Err(e) => {
+                warn!("Metrics accept failed: {}", e);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+    }
+}
+
+async fn respond(
+    mut stream: TcpStream,
+    metrics: Arc<ClientMetrics>,
+    status: Arc<Mutex<ClientStatus>>,
+) {
+    let head = match timeout(REQUEST_TIMEOUT, read_head(&mut stream)).await {
+        Ok(Ok(head)) => head,
+        Ok(Err(e)) => {
+            debug!("Metrics read failed: {}", e);
+            return;
+        }
+        Err(_) => return,
+    };
+    let mut parts = head.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let (code, body) = match (method, path) {
+        ("GET", "/metrics") => {
+            let status = status.lock().unwrap().clone();
+            ("200 OK", metrics.render(&status))
+        }
+        (_, "/metrics") => ("405 Method Not Allowed", String::new()),
+        _ => ("404 Not Found", String::new()),
+    };
+    write_response(&mut stream, code, "text/plain; version=0.0.4", &body).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_reports_counters_and_state() {
+        let metrics = ClientMetrics::default();
+        metrics.record_http(&Err("refused".to_string()));
+        metrics.record_http_bytes(10, 250);
+        metrics.record_stream(StreamKind::Sse);
+        metrics.record_connect();
+        metrics.record_connect();
+
+        let mut status = ClientStatus::new("ws");
+        status.state = ConnectionState::Connected;
+        let text = metrics.render(&status);
+        for line in [
+            "tunnel_client_http_requests_total 1",
+            "tunnel_client_http_failures_total 1",
+            "tunnel_client_http_request_bytes_total 10",
+            "tunnel_client_http_response_bytes_total 250",
+            "tunnel_client_streams_total{kind=\"sse\"} 1",
+            "tunnel_client_streams_total{kind=\"websocket\"} 0",
+            "tunnel_client_reconnects_total 1",
+            "tunnel_client_connected 1",
+        ] {
+            assert!(
+                text.lines().any(|l| l == line),
+                "missing {}:\n{}",
+                line,
+                text
+            );
+        }
+        assert!(!text.contains("tunnel_client_backend_healthy"));
+
+        metrics.set_backend_healthy(false);
+        let text = metrics.render(&status);
+        assert!(text.lines().any(|l| l == "tunnel_client_backend_healthy 0"));
+    }
+
+    #[test]
+    fn test_render_codec_splits_directions() {
+        let mut text = String::new();
+        render_codec(
+            &mut text,
+            &CodecStats {
+                frames_encoded: 3,
+                bytes_encoded: 120,
+                largest_encoded: 60,
+                frames_decoded: 2,
+                fragments: 4,
+                fragmented_messages: 1,
+                ..Default::default()
+            },
+        );
+        for line in [
+            "tunnel_client_frames_total{direction=\"sent\"} 3",
+            "tunnel_client_frames_total{direction=\"received\"} 2",
+            "tunnel_client_frame_bytes_total{direction=\"sent\"} 120",
+            "tunnel_client_largest_frame_bytes{direction=\"sent\"} 60",
+            "tunnel_client_ws_fragmented_messages_total 1",
+            "tunnel_client_ws_fragments_total 4",
+        ] {
+            assert!(
+                text.lines().any(|l| l == line),
+                "missing {}:\n{}",
+                line,
+                text
+            );
+        }
+    }
+}
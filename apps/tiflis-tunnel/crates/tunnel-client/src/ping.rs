@@ -0,0 +1,88 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+use std::time::Duration;
+use tunnel_core::{quic, unix_millis, Error, Message, PingMessage, PongMessage, Result};
+
+/// Round trips slower than this are logged as a connectivity warning.
+pub const RTT_WARN_THRESHOLD: Duration = Duration::from_secs(1);
+
+/// Clock differences beyond this are logged, since they usually mean one side
+/// has no working time sync.
+pub const CLOCK_SKEW_WARN_MS: i64 = 30_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PingSample {
+    pub rtt: Duration,
+    /// How far the server's clock is ahead of ours, if it reported its time.
+    pub clock_offset_ms: Option<i64>,
+}
+
+impl PingSample {
+    /// Derives RTT from the echoed timestamp and the clock offset from the
+    /// server's timestamp, assuming the pong was sent halfway through the trip.
+    pub fn from_pong(pong: &PongMessage, received_at_ms: u64) -> Self {
+        let rtt_ms = received_at_ms.saturating_sub(pong.timestamp);
+        let clock_offset_ms = pong
+            .server_timestamp
+            .map(|server| server as i64 - (pong.timestamp + rtt_ms / 2) as i64);
+        Self {
+            rtt: Duration::from_millis(rtt_ms),
+            clock_offset_ms,
+        }
+    }
+
+    pub fn log(&self) {
+        tracing::debug!(
+            "Ping RTT {:?}, server clock offset {:?} ms",
+            self.rtt,
+            self.clock_offset_ms
+        );
+        if self.rtt > RTT_WARN_THRESHOLD {
+            tracing::warn!("High tunnel latency: RTT {:?}", self.rtt);
+        }
+        if let Some(offset) = self.clock_offset_ms {
+            if offset.abs() > CLOCK_SKEW_WARN_MS {
+                tracing::warn!("Server clock differs from ours by {} ms", offset);
+            }
+        }
+    }
+}
+
+/// Sends a ping on its own stream and measures the reply.
+pub async fn ping(connection: &quinn::Connection) -> Result<PingSample> {
+    let ping = Message::Ping(PingMessage {
+        timestamp: unix_millis(),
+    });
+    match quic::send_and_receive(connection, &ping).await? {
+        Message::Pong(pong) => Ok(PingSample::from_pong(&pong, unix_millis())),
+        other => Err(Error::InvalidMessageType(other.message_type().to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_from_pong() {
+        let pong = PongMessage {
+            timestamp: 1_000,
+            server_timestamp: Some(61_050),
+        };
+        let sample = PingSample::from_pong(&pong, 1_100);
+        assert_eq!(sample.rtt, Duration::from_millis(100));
+        assert_eq!(sample.clock_offset_ms, Some(60_000));
+    }
+
+    #[test]
+    fn test_sample_never_goes_negative() {
+        let pong = PongMessage {
+            timestamp: 2_000,
+            server_timestamp: None,
+        };
+        let sample = PingSample::from_pong(&pong, 1_000);
+        assert_eq!(sample.rtt, Duration::ZERO);
+        assert_eq!(sample.clock_offset_ms, None);
+    }
+}
@@ -1,67 +1,396 @@
 // Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
 // Licensed under the FSL-1.1-NC.
 
+use crate::config::{Config, InjectHeadersPolicy, PathRewrite, RouteRule};
+use crate::metrics::{ClientMetrics, StreamKind};
+use crate::sse::SseFramer;
+use bytes::Bytes;
 use futures::StreamExt;
+use http_body_util::{BodyExt, StreamBody};
+use hyper::body::Frame;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::TokioExecutor;
 use reqwest::Client;
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
 use tunnel_core::{
-    codec, HttpRequestMessage, HttpResponseMessage, Message, SseCloseMessage, SseDataMessage,
-    SseHeadersMessage, SseOpenMessage, WsOpenMessage,
+    codec, ConnectOpenMessage, ConnectOpenedMessage, H2CloseMessage, H2DataMessage,
+    H2HeadersMessage, H2OpenMessage, HopByHop, HttpBodyMessage, HttpContinueMessage,
+    HttpInformationalMessage, HttpRequestMessage, HttpResponseMessage, Message, SseCloseMessage,
+    SseDataMessage, SseHeadersMessage, SseOpenMessage, WsOpenMessage, WsOpenedMessage,
+    WS_CLOSE_MESSAGE_TOO_BIG, WS_FRAGMENT_BYTES,
 };
 
+const WS_CHANNEL_CAPACITY: usize = 32;
+
+/// Largest WebSocket message reassembled from tunnel fragments, matching the
+/// local WebSocket client's own message limit.
+pub(crate) const LOCAL_WS_MAX_MESSAGE_BYTES: usize = 64 << 20;
+
+/// WebSocket close code sent when an upgrade is refused by the path rules.
+const WS_CLOSE_POLICY_VIOLATION: u16 = 1008;
+
+/// How long an `Expect: 100-continue` request waits for the backend's interim
+/// response before sending the body anyway, as curl does.
+const CONTINUE_TIMEOUT: Duration = Duration::from_secs(1);
+
+type ChannelBody =
+    StreamBody<futures::channel::mpsc::Receiver<Result<Frame<Bytes>, std::io::Error>>>;
+
 pub struct LocalProxy {
     client: Client,
+    /// Prior-knowledge HTTP/2 (h2c) client used for passthrough streams such as gRPC.
+    h2_client: hyper_util::client::legacy::Client<HttpConnector, ChannelBody>,
+    /// HTTP/1.1 client for `Expect: 100-continue` requests and relayed `1xx`
+    /// responses, which need the backend's interim responses that reqwest
+    /// does not expose.
+    h1_client: hyper_util::client::legacy::Client<HttpConnector, ChannelBody>,
+    /// HTTP/1.1 client for passthrough WebSocket handshakes, which are
+    /// upgraded to raw connections.
+    upgrade_client: Client,
+    forward_informational: bool,
     base_url: String,
+    /// Tried in order when `base_url` cannot be reached.
+    fallback_urls: Vec<String>,
+    /// Lowercased host or first host label to backend URL.
+    host_backends: HashMap<String, String>,
+    routes: Vec<RouteRule>,
+    ws_allow_paths: Vec<String>,
+    ws_deny_paths: Vec<String>,
+    connect_allow: Vec<String>,
+    /// Bound on a local HTTP request, or on SSE and WebSocket connects.
+    local_request_timeout: Option<Duration>,
+    /// Time a backend has to accept a connection before a fallback is tried.
+    local_connect_timeout: Option<Duration>,
+    /// Combined size of backend response header names and values. 0 disables.
+    max_header_bytes: usize,
+    path_rewrite: PathRewrite,
+    /// Added to every backend request.
+    inject_headers: Vec<(String, String)>,
+    inject_headers_policy: InjectHeadersPolicy,
+    metrics: Arc<ClientMetrics>,
 }
 
 impl LocalProxy {
     pub fn new(base_url: String) -> Self {
         Self {
-            client: Client::new(),
+            client: backend_client_builder().build().unwrap_or_default(),
+            h2_client: hyper_util::client::legacy::Client::builder(TokioExecutor::new())
+                .http2_only(true)
+                .build_http(),
+            h1_client: hyper_util::client::legacy::Client::builder(TokioExecutor::new())
+                .build_http(),
+            upgrade_client: backend_client_builder()
+                .http1_only()
+                .build()
+                .unwrap_or_default(),
+            forward_informational: false,
             base_url,
+            fallback_urls: Vec::new(),
+            host_backends: HashMap::new(),
+            routes: Vec::new(),
+            ws_allow_paths: Vec::new(),
+            ws_deny_paths: Vec::new(),
+            connect_allow: Vec::new(),
+            local_request_timeout: None,
+            local_connect_timeout: None,
+            max_header_bytes: 0,
+            path_rewrite: PathRewrite::default(),
+            inject_headers: Vec::new(),
+            inject_headers_policy: InjectHeadersPolicy::default(),
+            metrics: Arc::default(),
+        }
+    }
+
+    /// Counters served on `workstation.metrics_address`.
+    pub fn metrics(&self) -> &Arc<ClientMetrics> {
+        &self.metrics
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        let mut proxy = Self::new(config.workstation.local_address.clone());
+        proxy.fallback_urls = config.workstation.fallback_addresses.clone();
+        proxy.host_backends = config
+            .workstation
+            .host_backends
+            .iter()
+            .map(|(host, url)| (host.to_ascii_lowercase(), url.clone()))
+            .collect();
+        proxy.routes = config.workstation.routes.clone();
+        proxy.ws_allow_paths = config.workstation.ws_allow_paths.clone();
+        proxy.ws_deny_paths = config.workstation.ws_deny_paths.clone();
+        proxy.connect_allow = config.workstation.connect_allow.clone();
+        proxy.local_request_timeout = match config.workstation.local_request_timeout {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        };
+        proxy.local_connect_timeout = match config.workstation.local_connect_timeout {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        };
+        let mut upgrade_builder = backend_client_builder().http1_only();
+        if config.workstation.local_connect_timeout > 0 {
+            upgrade_builder = upgrade_builder.connect_timeout(Duration::from_secs(
+                config.workstation.local_connect_timeout,
+            ));
+        }
+        proxy.upgrade_client = upgrade_builder.build().unwrap_or_default();
+        let mut builder = backend_client_builder()
+            .pool_max_idle_per_host(config.workstation.pool_max_idle_per_host)
+            .pool_idle_timeout(match config.workstation.pool_idle_timeout {
+                0 => None,
+                secs => Some(Duration::from_secs(secs)),
+            });
+        if config.workstation.local_connect_timeout > 0 {
+            builder = builder.connect_timeout(Duration::from_secs(
+                config.workstation.local_connect_timeout,
+            ));
+        }
+        if config.workstation.http2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        }
+        proxy.client = builder.build().unwrap_or_default();
+        proxy.max_header_bytes = config.limits.max_header_bytes;
+        proxy.forward_informational = config.workstation.forward_informational;
+        proxy.path_rewrite = config.workstation.path_rewrite.clone();
+        proxy.inject_headers = config
+            .workstation
+            .inject_headers
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        proxy.inject_headers_policy = config.workstation.inject_headers_policy;
+        proxy
+    }
+
+    /// Whether requests whose caller takes interim responses should go through
+    /// [`forward_informational_request`](Self::forward_informational_request).
+    pub fn forwards_informational(&self) -> bool {
+        self.forward_informational
+    }
+
+    /// Whether a caller header is dropped because an injected header of the
+    /// same name replaces it.
+    fn replaced_by_injection(&self, name: &str) -> bool {
+        self.inject_headers_policy == InjectHeadersPolicy::Replace
+            && self
+                .inject_headers
+                .iter()
+                .any(|(injected, _)| name.eq_ignore_ascii_case(injected))
+    }
+
+    fn headers_too_large(&self, headers: &hyper::HeaderMap) -> bool {
+        self.max_header_bytes > 0
+            && headers
+                .iter()
+                .map(|(name, value)| name.as_str().len() + value.len())
+                .sum::<usize>()
+                > self.max_header_bytes
+    }
+
+    /// Requests `path` from the default backend, then from each fallback
+    /// until one is healthy. An answer below 500 within `timeout` is healthy;
+    /// anything else is described in the error.
+    pub async fn probe_backend(&self, path: &str, timeout: Duration) -> Result<(), String> {
+        let mut result = Ok(());
+        for base in self.backend_candidates(&self.base_url) {
+            result = self.probe(base, path, timeout).await;
+            if result.is_ok() {
+                break;
+            }
+        }
+        result
+    }
+
+    async fn probe(&self, base: &str, path: &str, timeout: Duration) -> Result<(), String> {
+        let url = format!("{}{}", base, path);
+        match self.client.get(&url).timeout(timeout).send().await {
+            Ok(response) if response.status().is_server_error() => {
+                Err(format!("{} answered {}", path, response.status()))
+            }
+            Ok(_) => Ok(()),
+            Err(e) if e.is_timeout() => {
+                Err(format!("{} did not answer within {:?}", path, timeout))
+            }
+            Err(e) => Err(format!("{} unreachable: {}", path, e)),
+        }
+    }
+
+    /// Backend for a request: the first matching route, else the one for its
+    /// forwarded `Host` header.
+    fn backend_url(&self, method: &str, path: &str, headers: &HashMap<String, String>) -> &str {
+        if let Some(rule) = self
+            .routes
+            .iter()
+            .find(|rule| route_matches(rule, method, path, headers))
+        {
+            return &rule.backend;
+        }
+        if self.host_backends.is_empty() {
+            return &self.base_url;
+        }
+        let host = headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("host"))
+            .map(|(_, value)| value.to_ascii_lowercase());
+        let Some(host) = host else {
+            return &self.base_url;
+        };
+        let host = host.split(':').next().unwrap_or_default();
+        let label = host.split('.').next().unwrap_or_default();
+        self.host_backends
+            .get(host)
+            .or_else(|| self.host_backends.get(label))
+            .unwrap_or(&self.base_url)
+    }
+
+    /// `backend`, followed by the fallbacks when it is the default backend.
+    fn backend_candidates<'a>(&'a self, backend: &'a str) -> impl Iterator<Item = &'a str> {
+        let fallbacks = if backend == self.base_url {
+            &self.fallback_urls[..]
+        } else {
+            &[]
+        };
+        std::iter::once(backend).chain(fallbacks.iter().map(String::as_str))
+    }
+
+    /// Backend for a stream, which cannot move to a fallback once it has
+    /// started: the first of the [candidates](Self::backend_candidates) that
+    /// accepts a connection, else `backend`, so the stream fails with its
+    /// error.
+    async fn reachable_backend<'a>(&'a self, backend: &'a str) -> &'a str {
+        let candidates: Vec<&str> = self.backend_candidates(backend).collect();
+        if candidates.len() == 1 {
+            return backend;
+        }
+        for candidate in candidates {
+            if self.accepts_connections(candidate).await {
+                if candidate != backend {
+                    tracing::warn!(
+                        "Local backend {} unreachable, streaming from {}",
+                        backend,
+                        candidate
+                    );
+                }
+                return candidate;
+            }
+        }
+        backend
+    }
+
+    /// Whether `base` accepts a TCP connection within the connect timeout.
+    async fn accepts_connections(&self, base: &str) -> bool {
+        let Ok(url) = reqwest::Url::parse(base) else {
+            return true;
+        };
+        let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+            return true;
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let connect = tokio::net::TcpStream::connect((host, port));
+        match self.local_connect_timeout {
+            Some(limit) => matches!(tokio::time::timeout(limit, connect).await, Ok(Ok(_))),
+            None => connect.await.is_ok(),
+        }
+    }
+
+    /// Whether a WebSocket upgrade of `path` may be forwarded to the backend.
+    fn ws_path_allowed(&self, path: &str) -> bool {
+        let path = path.split('?').next().unwrap_or(path);
+        if self.ws_deny_paths.iter().any(|p| glob_matches(p, path)) {
+            return false;
         }
+        self.ws_allow_paths.is_empty() || self.ws_allow_paths.iter().any(|p| glob_matches(p, path))
     }
 
     pub async fn forward_http_request(
         &self,
         request: HttpRequestMessage,
     ) -> Result<HttpResponseMessage, String> {
-        let url = format!("{}{}", self.base_url, request.path);
-        let method = request
+        let backend = self.backend_url(&request.method, &request.path, &request.header_map());
+        let path = self.path_rewrite.apply(&request.path);
+        let method: reqwest::Method = request
             .method
             .parse()
             .map_err(|e| format!("invalid method: {}", e))?;
+        let body = match &request.body {
+            Some(body_b64) => Some(Bytes::from(
+                codec::decode_body(body_b64)
+                    .map_err(|e| format!("failed to decode body: {}", e))?,
+            )),
+            None => None,
+        };
+        let request_bytes = body.as_ref().map_or(0, Bytes::len);
+        let forwarded = forwarded_headers(&request);
+        let hop = HopByHop::new(request.header_pairs());
 
-        let mut req_builder = self.client.request(method, &url);
+        let send = |url: &str| {
+            let mut req_builder = self.client.request(method.clone(), url);
+            if let Some(limit) = self.local_request_timeout {
+                req_builder = req_builder.timeout(limit);
+            }
+            for (name, value) in request.header_pairs() {
+                if !hop.contains(name)
+                    && !is_forwarded_header(&forwarded, name)
+                    && !self.replaced_by_injection(name)
+                {
+                    req_builder = req_builder.header(name, value);
+                }
+            }
+            for (name, value) in &forwarded {
+                req_builder = req_builder.header(*name, value);
+            }
+            for (name, value) in &self.inject_headers {
+                req_builder = req_builder.header(name, value);
+            }
+            if let Some(body) = &body {
+                req_builder = req_builder.body(body.clone());
+            }
+            req_builder.send()
+        };
 
-        for (name, value) in request.headers.iter() {
-            req_builder = req_builder.header(name, value);
-        }
+        // Only a backend that never accepted the connection is skipped; one
+        // that failed mid-request may already have acted on it.
+        let candidates: Vec<&str> = self.backend_candidates(backend).collect();
+        let mut attempt = 0;
+        let (url, response) = loop {
+            let url = format!("{}{}", candidates[attempt], path);
+            match send(&url).await {
+                Ok(response) => break (url, response),
+                Err(e) if e.is_connect() && attempt + 1 < candidates.len() => {
+                    tracing::warn!(
+                        "Local backend {} unreachable, trying {}: {}",
+                        candidates[attempt],
+                        candidates[attempt + 1],
+                        e
+                    );
+                    attempt += 1;
+                }
+                Err(e) if e.is_timeout() => return Ok(local_timeout_response(request.stream_id)),
+                Err(e) => return Err(format!("request failed: {}", e)),
+            }
+        };
 
-        if let Some(body_b64) = request.body {
-            let body_bytes = codec::decode_body(&body_b64)
-                .map_err(|e| format!("failed to decode body: {}", e))?;
-            req_builder = req_builder.body(body_bytes);
+        if self.headers_too_large(response.headers()) {
+            tracing::warn!(
+                "Local backend response headers for {} exceed the limit",
+                url
+            );
+            return Ok(oversized_headers_response(request.stream_id));
         }
 
-        let response = req_builder
-            .send()
-            .await
-            .map_err(|e| format!("request failed: {}", e))?;
-
         let status = response.status().as_u16();
-        let mut headers = HashMap::new();
+        let headers = header_map_to_hash(response.headers());
 
-        for (name, value) in response.headers().iter() {
-            if let Ok(val_str) = value.to_str() {
-                headers.insert(name.to_string(), val_str.to_string());
-            }
-        }
-
-        let body_bytes = response
-            .bytes()
-            .await
-            .map_err(|e| format!("failed to read response body: {}", e))?;
+        let body_bytes = match response.bytes().await {
+            Ok(bytes) => bytes,
+            Err(e) if e.is_timeout() => return Ok(local_timeout_response(request.stream_id)),
+            Err(e) => return Err(format!("failed to read response body: {}", e)),
+        };
+        self.metrics
+            .record_http_bytes(request_bytes, body_bytes.len());
 
         let body_base64 = if !body_bytes.is_empty() {
             Some(codec::encode_body(&body_bytes))
@@ -77,77 +406,372 @@ impl LocalProxy {
         })
     }
 
+    /// Forwards a request whose body the server withholds until the backend
+    /// asks for it: `HttpContinue` is sent once the backend replies
+    /// `100 Continue` (or stays silent for [`CONTINUE_TIMEOUT`]), and the body
+    /// then arrives on `quic_recv`.
+    pub async fn forward_continue_request(
+        &self,
+        request: HttpRequestMessage,
+        quic_send: &mut quinn::SendStream,
+        quic_recv: &mut quinn::RecvStream,
+        max_frame_bytes: usize,
+    ) -> Result<HttpResponseMessage, String> {
+        let result = self
+            .stream_request(request, quic_send, quic_recv, max_frame_bytes)
+            .await;
+        self.metrics.record_http(&result);
+        result
+    }
+
+    /// Forwards a request whose caller takes interim responses: each `1xx`
+    /// the backend sends other than `100 Continue`, such as `103 Early Hints`,
+    /// goes out on `quic_send` as `HttpInformational` ahead of the response.
+    /// Requests that also set `expect_continue` are handled as in
+    /// [`forward_continue_request`](Self::forward_continue_request).
+    pub async fn forward_informational_request(
+        &self,
+        request: HttpRequestMessage,
+        quic_send: &mut quinn::SendStream,
+        quic_recv: &mut quinn::RecvStream,
+        max_frame_bytes: usize,
+    ) -> Result<HttpResponseMessage, String> {
+        let result = self
+            .stream_request(request, quic_send, quic_recv, max_frame_bytes)
+            .await;
+        self.metrics.record_http(&result);
+        result
+    }
+
+    async fn stream_request(
+        &self,
+        request: HttpRequestMessage,
+        quic_send: &mut quinn::SendStream,
+        quic_recv: &mut quinn::RecvStream,
+        max_frame_bytes: usize,
+    ) -> Result<HttpResponseMessage, String> {
+        let stream_id = request.stream_id;
+        let backend = self.backend_url(&request.method, &request.path, &request.header_map());
+        if !backend.starts_with("http://") {
+            // Only plain HTTP backends go through the hyper client; others
+            // take the body up front and send no interim responses.
+            let mut request = request;
+            if request.expect_continue {
+                request.body = request_continue(stream_id, quic_send, quic_recv, max_frame_bytes)
+                    .await?
+                    .body;
+            }
+            return self.forward_http_request(request).await;
+        }
+        let backend = self.reachable_backend(backend).await;
+
+        let exchange = self.exchange_h1_request(
+            request,
+            backend.to_string(),
+            quic_send,
+            quic_recv,
+            max_frame_bytes,
+        );
+        match self.local_request_timeout {
+            Some(limit) => match tokio::time::timeout(limit, exchange).await {
+                Ok(result) => result,
+                Err(_) => Ok(local_timeout_response(stream_id)),
+            },
+            None => exchange.await,
+        }
+    }
+
+    async fn exchange_h1_request(
+        &self,
+        request: HttpRequestMessage,
+        backend: String,
+        quic_send: &mut quinn::SendStream,
+        quic_recv: &mut quinn::RecvStream,
+        max_frame_bytes: usize,
+    ) -> Result<HttpResponseMessage, String> {
+        let stream_id = request.stream_id;
+        let (body_tx, body_rx) =
+            futures::channel::mpsc::channel::<Result<Frame<Bytes>, std::io::Error>>(1);
+
+        let mut builder = hyper::Request::builder()
+            .method(request.method.as_str())
+            .uri(format!(
+                "{}{}",
+                backend,
+                self.path_rewrite.apply(&request.path)
+            ));
+        let forwarded = forwarded_headers(&request);
+        let hop = HopByHop::new(request.header_pairs());
+        for (name, value) in request.header_pairs() {
+            if name != "host"
+                && !hop.contains(name)
+                && !is_forwarded_header(&forwarded, name)
+                && !self.replaced_by_injection(name)
+            {
+                builder = builder.header(name, value);
+            }
+        }
+        for (name, value) in forwarded {
+            builder = builder.header(name, value);
+        }
+        for (name, value) in &self.inject_headers {
+            builder = builder.header(name, value);
+        }
+        let mut local_request = builder
+            .body(StreamBody::new(body_rx))
+            .map_err(|e| format!("invalid request: {}", e))?;
+
+        let continued = std::sync::Arc::new(tokio::sync::Notify::new());
+        let notify = continued.clone();
+        let (interim_tx, mut interim_rx) = mpsc::unbounded_channel();
+        let relay_interim = request.informational;
+        hyper::ext::on_informational(&mut local_request, move |res| {
+            if res.status() == hyper::StatusCode::CONTINUE {
+                notify.notify_one();
+            } else if relay_interim {
+                let _ = interim_tx.send(Message::HttpInformational(HttpInformationalMessage {
+                    stream_id,
+                    status: res.status().as_u16(),
+                    headers: joined_header_map(res.headers()),
+                }));
+            }
+        });
+
+        // Without `expect_continue` the body is already here.
+        let mut request_bytes = 0;
+        let mut body_tx = Some(body_tx);
+        if !request.expect_continue {
+            if let Some(body_tx) = body_tx.take() {
+                request_bytes = send_request_body(body_tx, request.body.as_deref()).await?;
+            }
+        }
+
+        let response = self.h1_client.request(local_request);
+        tokio::pin!(response);
+
+        let early = if !request.expect_continue {
+            None
+        } else {
+            let timer = tokio::time::sleep(CONTINUE_TIMEOUT);
+            tokio::pin!(timer);
+            loop {
+                tokio::select! {
+                    biased;
+                    Some(msg) = interim_rx.recv() => send_interim(quic_send, &msg).await,
+                    result = &mut response => break Some(result),
+                    _ = continued.notified() => break None,
+                    _ = &mut timer => break None,
+                }
+            }
+        };
+        let result = match early {
+            // Answered without the body, typically a rejection such as 417.
+            Some(result) => result,
+            None => {
+                if let Some(body_tx) = body_tx.take() {
+                    let body = request_continue(stream_id, quic_send, quic_recv, max_frame_bytes)
+                        .await?
+                        .body;
+                    request_bytes = send_request_body(body_tx, body.as_deref()).await?;
+                }
+                loop {
+                    tokio::select! {
+                        biased;
+                        Some(msg) = interim_rx.recv() => send_interim(quic_send, &msg).await,
+                        result = &mut response => break result,
+                    }
+                }
+            }
+        };
+        let response = result.map_err(|e| format!("request failed: {}", e))?;
+
+        let (parts, body) = response.into_parts();
+        if self.headers_too_large(&parts.headers) {
+            tracing::warn!(
+                "Local backend response headers for {} exceed the limit",
+                request.path
+            );
+            return Ok(oversized_headers_response(stream_id));
+        }
+        let body_bytes = body
+            .collect()
+            .await
+            .map_err(|e| format!("failed to read response body: {}", e))?
+            .to_bytes();
+        self.metrics
+            .record_http_bytes(request_bytes, body_bytes.len());
+
+        Ok(HttpResponseMessage {
+            stream_id,
+            status: parts.status.as_u16(),
+            headers: header_map_to_hash(&parts.headers),
+            body: (!body_bytes.is_empty()).then(|| codec::encode_body(&body_bytes)),
+        })
+    }
+
+    /// Upgrade request for `url` carrying the injected headers.
+    fn local_ws_request(
+        &self,
+        url: &str,
+    ) -> Result<tokio_tungstenite::tungstenite::handshake::client::Request, String> {
+        use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+        let mut request = url.into_client_request().map_err(|e| e.to_string())?;
+        for (name, value) in &self.inject_headers {
+            let (Ok(name), Ok(value)) = (
+                hyper::header::HeaderName::try_from(name.as_str()),
+                hyper::header::HeaderValue::try_from(value.as_str()),
+            ) else {
+                continue;
+            };
+            request.headers_mut().append(name, value);
+        }
+        Ok(request)
+    }
+
     pub async fn handle_websocket_open(
         &self,
         open_msg: WsOpenMessage,
         mut quic_send: quinn::SendStream,
         mut quic_recv: quinn::RecvStream,
     ) {
+        self.metrics.record_stream(StreamKind::WebSocket);
+        if !self.ws_path_allowed(&open_msg.path) {
+            tracing::warn!("Refusing WebSocket upgrade of {}", open_msg.path);
+            let close = Message::WsClose(tunnel_core::WsCloseMessage {
+                stream_id: open_msg.stream_id,
+                code: Some(WS_CLOSE_POLICY_VIOLATION),
+                reason: Some("WebSocket not allowed on this path".to_string()),
+            });
+            if tunnel_core::quic::send_message(&mut quic_send, &close)
+                .await
+                .is_ok()
+            {
+                let _ = quic_send.finish();
+            }
+            return;
+        }
+        if open_msg.passthrough {
+            self.handle_websocket_passthrough(open_msg, quic_send, quic_recv)
+                .await;
+            return;
+        }
+
         let ws_url = self
-            .base_url
+            .reachable_backend(self.backend_url("GET", &open_msg.path, &open_msg.headers))
+            .await
             .replace("http://", "ws://")
             .replace("https://", "wss://");
-        let url = format!("{}{}", ws_url, open_msg.path);
+        let url = format!("{}{}", ws_url, self.path_rewrite.apply(&open_msg.path));
 
-        match tokio_tungstenite::connect_async(&url).await {
+        let connected = match self.local_ws_request(&url) {
+            Err(e) => Err(e),
+            Ok(request) => match self.local_request_timeout {
+                Some(limit) => {
+                    tokio::time::timeout(limit, tokio_tungstenite::connect_async(request))
+                        .await
+                        .map_err(|_| "local WebSocket connect timed out".to_string())
+                        .and_then(|r| r.map_err(|e| e.to_string()))
+                }
+                None => tokio_tungstenite::connect_async(request)
+                    .await
+                    .map_err(|e| e.to_string()),
+            },
+        };
+        if open_msg.ack {
+            let opened = Message::WsOpened(WsOpenedMessage {
+                stream_id: open_msg.stream_id,
+                error: connected.as_ref().err().cloned(),
+                headers: HashMap::new(),
+            });
+            if tunnel_core::quic::send_message(&mut quic_send, &opened)
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+
+        match connected {
             Ok((ws_stream, _)) => {
                 use futures::{SinkExt, StreamExt};
+                use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+                use tokio_tungstenite::tungstenite::protocol::CloseFrame;
                 use tokio_tungstenite::tungstenite::Message as WsMessage;
 
                 let (mut ws_sender, mut ws_receiver) = ws_stream.split();
                 let stream_id = open_msg.stream_id;
+                // Older servers expect every message in a single frame.
+                let fragment_bytes = if open_msg.fragments {
+                    WS_FRAGMENT_BYTES
+                } else {
+                    usize::MAX
+                };
+
+                // Bounded channels between the halves let a slow consumer apply
+                // backpressure to the producer on the other side.
+                let (to_tunnel, mut to_tunnel_rx) = mpsc::channel::<Message>(WS_CHANNEL_CAPACITY);
+                let (to_ws, mut to_ws_rx) = mpsc::channel::<WsMessage>(WS_CHANNEL_CAPACITY);
 
+                let tunnel_writer = tokio::spawn(async move {
+                    while let Some(msg) = to_tunnel_rx.recv().await {
+                        let is_close = matches!(msg, Message::WsClose(_));
+                        if tunnel_core::quic::send_message(&mut quic_send, &msg)
+                            .await
+                            .is_err()
+                        {
+                            break;
+                        }
+                        if is_close {
+                            let _ = quic_send.finish();
+                            break;
+                        }
+                    }
+                });
+
+                let ws_writer = tokio::spawn(async move {
+                    while let Some(msg) = to_ws_rx.recv().await {
+                        let is_close = matches!(msg, WsMessage::Close(_));
+                        if ws_sender.send(msg).await.is_err() || is_close {
+                            break;
+                        }
+                    }
+                });
+
+                let close_tunnel = to_tunnel.clone();
                 let ws_to_tunnel_task = tokio::spawn(async move {
                     while let Some(result) = ws_receiver.next().await {
-                        match result {
-                            Ok(WsMessage::Text(text)) => {
-                                let data_msg = Message::WsData(tunnel_core::WsDataMessage {
-                                    stream_id,
-                                    data: codec::encode_body(text.as_bytes()),
-                                    is_binary: false,
-                                });
-                                if tunnel_core::quic::send_message(&mut quic_send, &data_msg)
-                                    .await
-                                    .is_err()
-                                {
-                                    break;
-                                }
-                            }
-                            Ok(WsMessage::Binary(data)) => {
-                                let data_msg = Message::WsData(tunnel_core::WsDataMessage {
-                                    stream_id,
-                                    data: codec::encode_body(&data),
-                                    is_binary: true,
-                                });
-                                if tunnel_core::quic::send_message(&mut quic_send, &data_msg)
-                                    .await
-                                    .is_err()
-                                {
-                                    break;
-                                }
-                            }
+                        let (data, is_binary) = match result {
+                            Ok(WsMessage::Text(text)) => (text.into_bytes(), false),
+                            Ok(WsMessage::Binary(data)) => (data, true),
                             Ok(WsMessage::Close(frame)) => {
                                 let close_msg = Message::WsClose(tunnel_core::WsCloseMessage {
                                     stream_id,
                                     code: frame.as_ref().map(|f| f.code.into()),
                                     reason: frame.as_ref().map(|f| f.reason.to_string()),
                                 });
-                                let _ = tunnel_core::quic::send_message(&mut quic_send, &close_msg)
-                                    .await;
-                                let _ = quic_send.finish();
+                                let _ = to_tunnel.send(close_msg).await;
                                 break;
                             }
                             Err(_) => break,
-                            _ => {}
+                            _ => continue,
+                        };
+                        for frame in
+                            codec::ws_data_frames(stream_id, &data, is_binary, fragment_bytes)
+                        {
+                            if to_tunnel.send(Message::WsData(frame)).await.is_err() {
+                                return;
+                            }
                         }
                     }
                 });
 
                 let tunnel_to_ws_task = tokio::spawn(async move {
+                    let mut reassembler = codec::WsReassembler::new(LOCAL_WS_MAX_MESSAGE_BYTES);
                     loop {
                         match tunnel_core::quic::recv_message(&mut quic_recv).await {
-                            Ok(Message::WsData(data)) => {
-                                if let Ok(decoded) = codec::decode_body(&data.data) {
+                            Ok(Message::WsData(data)) => match reassembler.push(&data) {
+                                Ok(Some(decoded)) => {
                                     let ws_msg = if data.is_binary {
                                         WsMessage::Binary(decoded)
                                     } else if let Ok(text) = String::from_utf8(decoded) {
@@ -155,39 +779,396 @@ impl LocalProxy {
                                     } else {
                                         continue;
                                     };
-                                    if ws_sender.send(ws_msg).await.is_err() {
+                                    if to_ws.send(ws_msg).await.is_err() {
                                         break;
                                     }
                                 }
-                            }
-                            Ok(Message::WsClose(_)) => {
-                                let _ = ws_sender.send(WsMessage::Close(None)).await;
+                                Ok(None) => {}
+                                Err(tunnel_core::Error::MessageTooLarge { size, limit }) => {
+                                    tracing::warn!(
+                                        "Closing WebSocket stream {}: message of {} bytes exceeds {} byte limit",
+                                        stream_id,
+                                        size,
+                                        limit
+                                    );
+                                    let _ = close_tunnel
+                                        .send(Message::WsClose(tunnel_core::WsCloseMessage {
+                                            stream_id,
+                                            code: Some(WS_CLOSE_MESSAGE_TOO_BIG),
+                                            reason: Some("message too big".to_string()),
+                                        }))
+                                        .await;
+                                    let _ = to_ws
+                                        .send(WsMessage::Close(Some(CloseFrame {
+                                            code: CloseCode::Size,
+                                            reason: "message too big".into(),
+                                        })))
+                                        .await;
+                                    break;
+                                }
+                                Err(_) => {}
+                            },
+                            Ok(Message::WsClose(close)) => {
+                                let frame = close.close_frame().map(|(code, reason)| CloseFrame {
+                                    code: CloseCode::from(code),
+                                    reason: reason.into(),
+                                });
+                                let _ = to_ws.send(WsMessage::Close(frame)).await;
                                 break;
                             }
                             Err(_) => break,
-                            _ => {}
+                            Ok(other) => {
+                                tracing::warn!(
+                                    "Unexpected {} on WebSocket stream {}",
+                                    other.message_type(),
+                                    stream_id
+                                );
+                            }
                         }
                     }
                 });
 
-                let _ = tokio::join!(ws_to_tunnel_task, tunnel_to_ws_task);
+                let _ = tokio::join!(
+                    ws_to_tunnel_task,
+                    tunnel_to_ws_task,
+                    ws_writer,
+                    tunnel_writer
+                );
             }
             Err(e) => {
                 tracing::error!("Failed to connect to local WebSocket: {}", e);
+                let _ = quic_send.finish();
+            }
+        }
+    }
+
+    /// Sends the caller's WebSocket handshake to the backend and, once it
+    /// answers `101`, relays raw bytes both ways. Extensions such as
+    /// `permessage-deflate` are left to the caller and the backend.
+    async fn handle_websocket_passthrough(
+        &self,
+        open_msg: WsOpenMessage,
+        mut quic_send: quinn::SendStream,
+        mut quic_recv: quinn::RecvStream,
+    ) {
+        use tokio::io::AsyncWriteExt;
+
+        let backend = self
+            .reachable_backend(self.backend_url("GET", &open_msg.path, &open_msg.headers))
+            .await;
+        let url = format!("{}{}", backend, self.path_rewrite.apply(&open_msg.path));
+        let hop = HopByHop::new(
+            open_msg
+                .headers
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+        );
+        let mut request = self
+            .upgrade_client
+            .get(&url)
+            .header(reqwest::header::CONNECTION, "upgrade")
+            .header(reqwest::header::UPGRADE, "websocket");
+        for (name, value) in &open_msg.headers {
+            if name != "host" && !hop.contains(name) && !self.replaced_by_injection(name) {
+                request = request.header(name, value);
             }
         }
+        for (name, value) in &self.inject_headers {
+            request = request.header(name, value);
+        }
+
+        let handshake = async {
+            let response = match self.local_request_timeout {
+                Some(limit) => tokio::time::timeout(limit, request.send())
+                    .await
+                    .map_err(|_| "local WebSocket connect timed out".to_string())?,
+                None => request.send().await,
+            }
+            .map_err(|e| e.to_string())?;
+            if response.status() != reqwest::StatusCode::SWITCHING_PROTOCOLS {
+                return Err(format!(
+                    "local WebSocket refused the upgrade with {}",
+                    response.status()
+                ));
+            }
+            let headers: HashMap<String, String> = response
+                .headers()
+                .iter()
+                .filter(|(name, _)| name.as_str().starts_with("sec-websocket-"))
+                .filter_map(|(name, value)| {
+                    Some((name.to_string(), value.to_str().ok()?.to_string()))
+                })
+                .collect();
+            let upgraded = response.upgrade().await.map_err(|e| e.to_string())?;
+            Ok((headers, upgraded))
+        }
+        .await;
+
+        let opened = Message::WsOpened(WsOpenedMessage {
+            stream_id: open_msg.stream_id,
+            error: handshake.as_ref().err().cloned(),
+            headers: handshake
+                .as_ref()
+                .map(|(headers, _)| headers.clone())
+                .unwrap_or_default(),
+        });
+        if tunnel_core::quic::send_message(&mut quic_send, &opened)
+            .await
+            .is_err()
+        {
+            return;
+        }
+        let upgraded = match handshake {
+            Ok((_, upgraded)) => upgraded,
+            Err(e) => {
+                tracing::error!("Failed to connect to local WebSocket: {}", e);
+                let _ = quic_send.finish();
+                return;
+            }
+        };
+
+        let (mut local_read, mut local_write) = tokio::io::split(upgraded);
+        let upstream = async {
+            let _ = tokio::io::copy(&mut quic_recv, &mut local_write).await;
+            let _ = local_write.shutdown().await;
+        };
+        let downstream = async {
+            let _ = tokio::io::copy(&mut local_read, &mut quic_send).await;
+            let _ = quic_send.finish();
+        };
+        tokio::join!(upstream, downstream);
+    }
+
+    /// Dials the target of an HTTP `CONNECT` if the allowlist permits it, reports
+    /// the outcome, then relays raw bytes until either side closes.
+    pub async fn handle_connect_open(
+        &self,
+        open_msg: ConnectOpenMessage,
+        mut quic_send: quinn::SendStream,
+        mut quic_recv: quinn::RecvStream,
+    ) {
+        use tokio::io::AsyncWriteExt;
+
+        self.metrics.record_stream(StreamKind::Connect);
+        let target = format!("{}:{}", open_msg.host, open_msg.port);
+        let allowed = self.connect_allow.iter().any(|p| glob_matches(p, &target));
+        let dialed = if allowed {
+            tokio::net::TcpStream::connect((open_msg.host.as_str(), open_msg.port))
+                .await
+                .map_err(|e| format!("failed to connect to {}: {}", target, e))
+        } else {
+            Err(format!("CONNECT to {} is not allowed", target))
+        };
+
+        let opened = Message::ConnectOpened(ConnectOpenedMessage {
+            stream_id: open_msg.stream_id,
+            error: dialed.as_ref().err().cloned(),
+            forbidden: !allowed,
+        });
+        if tunnel_core::quic::send_message(&mut quic_send, &opened)
+            .await
+            .is_err()
+        {
+            return;
+        }
+
+        let tcp = match dialed {
+            Ok(tcp) => tcp,
+            Err(e) => {
+                tracing::warn!("{}", e);
+                let _ = quic_send.finish();
+                return;
+            }
+        };
+
+        let (mut tcp_read, mut tcp_write) = tcp.into_split();
+        let upstream = async {
+            let _ = tokio::io::copy(&mut quic_recv, &mut tcp_write).await;
+            let _ = tcp_write.shutdown().await;
+        };
+        let downstream = async {
+            let _ = tokio::io::copy(&mut tcp_read, &mut quic_send).await;
+            let _ = quic_send.finish();
+        };
+        tokio::join!(upstream, downstream);
+    }
+
+    pub async fn handle_h2_open(
+        &self,
+        open_msg: H2OpenMessage,
+        mut quic_send: quinn::SendStream,
+        mut quic_recv: quinn::RecvStream,
+    ) {
+        use futures::SinkExt;
+
+        self.metrics.record_stream(StreamKind::H2);
+        let stream_id = open_msg.stream_id;
+        let backend = self
+            .reachable_backend(self.backend_url(
+                &open_msg.method,
+                &open_msg.path,
+                &open_msg.headers,
+            ))
+            .await;
+        let url = format!("{}{}", backend, self.path_rewrite.apply(&open_msg.path));
+
+        let (mut body_tx, body_rx) =
+            futures::channel::mpsc::channel::<Result<Frame<Bytes>, std::io::Error>>(16);
+
+        let mut builder = hyper::Request::builder()
+            .method(open_msg.method.as_str())
+            .uri(&url);
+        let hop = HopByHop::new(
+            open_msg
+                .headers
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+        );
+        for (name, value) in open_msg.headers.iter() {
+            if name != "host" && !hop.contains(name) && !self.replaced_by_injection(name) {
+                builder = builder.header(name, value);
+            }
+        }
+        for (name, value) in &self.inject_headers {
+            builder = builder.header(name, value);
+        }
+
+        let request = match builder.body(StreamBody::new(body_rx)) {
+            Ok(request) => request,
+            Err(e) => {
+                let close_msg = Message::H2Close(H2CloseMessage {
+                    stream_id,
+                    trailers: None,
+                    error: Some(format!("invalid request: {}", e)),
+                });
+                let _ = tunnel_core::quic::send_message(&mut quic_send, &close_msg).await;
+                let _ = quic_send.finish();
+                return;
+            }
+        };
+
+        let request_pump = tokio::spawn(async move {
+            loop {
+                let frame = match tunnel_core::quic::recv_message(&mut quic_recv).await {
+                    Ok(Message::H2Data(data)) => match codec::decode_body(&data.data) {
+                        Ok(decoded) => Ok(Frame::data(Bytes::from(decoded))),
+                        Err(_) => continue,
+                    },
+                    Ok(Message::H2Close(close)) => {
+                        if let Some(error) = close.error {
+                            let _ = body_tx.send(Err(std::io::Error::other(error))).await;
+                        } else if let Some(trailers) = close.trailers {
+                            let _ = body_tx
+                                .send(Ok(Frame::trailers(map_to_headers(&trailers))))
+                                .await;
+                        }
+                        break;
+                    }
+                    Ok(other) => {
+                        tracing::warn!(
+                            "Unexpected {} on H2 stream {}",
+                            other.message_type(),
+                            stream_id
+                        );
+                        continue;
+                    }
+                    Err(e) => Err(std::io::Error::other(e.to_string())),
+                };
+                let is_err = frame.is_err();
+                if body_tx.send(frame).await.is_err() || is_err {
+                    break;
+                }
+            }
+        });
+
+        let close_msg = match self.h2_client.request(request).await {
+            Ok(response) => {
+                let (parts, mut body) = response.into_parts();
+                let headers_msg = Message::H2Headers(H2HeadersMessage {
+                    stream_id,
+                    status: parts.status.as_u16(),
+                    headers: header_map_to_hash(&parts.headers),
+                });
+                if tunnel_core::quic::send_message(&mut quic_send, &headers_msg)
+                    .await
+                    .is_err()
+                {
+                    request_pump.abort();
+                    return;
+                }
+
+                let mut trailers = None;
+                let mut error = None;
+                while let Some(frame) = body.frame().await {
+                    match frame {
+                        Ok(frame) => match frame.into_data() {
+                            Ok(data) => {
+                                let data_msg = Message::H2Data(H2DataMessage {
+                                    stream_id,
+                                    data: codec::encode_body(&data),
+                                });
+                                if tunnel_core::quic::send_message(&mut quic_send, &data_msg)
+                                    .await
+                                    .is_err()
+                                {
+                                    request_pump.abort();
+                                    return;
+                                }
+                            }
+                            Err(frame) => {
+                                if let Ok(t) = frame.into_trailers() {
+                                    trailers = Some(header_map_to_hash(&t));
+                                }
+                            }
+                        },
+                        Err(e) => {
+                            error = Some(e.to_string());
+                            break;
+                        }
+                    }
+                }
+
+                Message::H2Close(H2CloseMessage {
+                    stream_id,
+                    trailers,
+                    error,
+                })
+            }
+            Err(e) => {
+                tracing::error!("Failed to reach local HTTP/2 backend: {}", e);
+                Message::H2Close(H2CloseMessage {
+                    stream_id,
+                    trailers: None,
+                    error: Some(e.to_string()),
+                })
+            }
+        };
+
+        let _ = tunnel_core::quic::send_message(&mut quic_send, &close_msg).await;
+        let _ = quic_send.finish();
+        request_pump.abort();
     }
 
     pub async fn handle_message(&self, msg: Message) -> Option<Message> {
         match msg {
-            Message::HttpRequest(req) => match self.forward_http_request(req).await {
-                Ok(resp) => Some(Message::HttpResponse(resp)),
-                Err(e) => {
-                    tracing::error!("Failed to forward request: {}", e);
-                    None
+            Message::HttpRequest(req) => {
+                let result = self.forward_http_request(req).await;
+                self.metrics.record_http(&result);
+                match result {
+                    Ok(resp) => Some(Message::HttpResponse(resp)),
+                    Err(e) => {
+                        tracing::error!("Failed to forward request: {}", e);
+                        None
+                    }
                 }
-            },
-            _ => None,
+            }
+            other => {
+                tracing::warn!(
+                    "{}",
+                    tunnel_core::Error::InvalidMessageType(other.message_type().to_string())
+                );
+                None
+            }
         }
     }
 
@@ -195,9 +1176,17 @@ impl LocalProxy {
         &self,
         open_msg: SseOpenMessage,
         mut quic_send: quinn::SendStream,
-        _quic_recv: quinn::RecvStream,
+        mut quic_recv: quinn::RecvStream,
     ) {
-        let url = format!("{}{}", self.base_url, open_msg.path);
+        self.metrics.record_stream(StreamKind::Sse);
+        let backend = self
+            .reachable_backend(self.backend_url(
+                &open_msg.method,
+                &open_msg.path,
+                &open_msg.headers,
+            ))
+            .await;
+        let url = format!("{}{}", backend, self.path_rewrite.apply(&open_msg.path));
         let method: reqwest::Method = open_msg.method.parse().unwrap_or(reqwest::Method::GET);
 
         let mut req_builder = self
@@ -205,20 +1194,50 @@ impl LocalProxy {
             .request(method, &url)
             .header("accept", "text/event-stream");
 
+        let hop = HopByHop::new(
+            open_msg
+                .headers
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+        );
         for (name, value) in open_msg.headers.iter() {
+            if !hop.contains(name) && !self.replaced_by_injection(name) {
+                req_builder = req_builder.header(name, value);
+            }
+        }
+        for (name, value) in &self.inject_headers {
             req_builder = req_builder.header(name, value);
         }
 
-        match req_builder.send().await {
+        // The server sends `SseClose`, or drops the stream, once its caller
+        // is gone or the stream hit `max_sse_duration`. Whatever arrives ends
+        // the relay, and dropping the backend response closes its connection.
+        let closed = tunnel_core::quic::recv_message(&mut quic_recv);
+        tokio::pin!(closed);
+
+        // Only the wait for response headers is bounded; the event stream
+        // itself may stay open indefinitely.
+        let send = async {
+            match self.local_request_timeout {
+                Some(limit) => tokio::time::timeout(limit, req_builder.send())
+                    .await
+                    .map_err(|_| "local SSE request timed out".to_string())
+                    .and_then(|r| r.map_err(|e| e.to_string())),
+                None => req_builder.send().await.map_err(|e| e.to_string()),
+            }
+        };
+        let sent = tokio::select! {
+            sent = send => sent,
+            _ = &mut closed => {
+                tracing::debug!("SSE stream {} closed by the server", open_msg.stream_id);
+                return;
+            }
+        };
+
+        match sent {
             Ok(response) => {
                 let status = response.status().as_u16();
-                let mut headers = HashMap::new();
-
-                for (name, value) in response.headers().iter() {
-                    if let Ok(val_str) = value.to_str() {
-                        headers.insert(name.to_string(), val_str.to_string());
-                    }
-                }
+                let headers = header_map_to_hash(response.headers());
 
                 let headers_msg = Message::SseHeaders(SseHeadersMessage {
                     stream_id: open_msg.stream_id,
@@ -235,22 +1254,37 @@ impl LocalProxy {
 
                 let stream_id = open_msg.stream_id;
                 let mut stream = response.bytes_stream();
+                // Network chunks can split an event; each `SseData` carries
+                // whole ones so the server relays intact events.
+                let mut framer = SseFramer::new();
 
-                while let Some(chunk_result) = stream.next().await {
-                    match chunk_result {
-                        Ok(chunk) => {
-                            let data_msg = Message::SseData(SseDataMessage {
-                                stream_id,
-                                data: codec::encode_body(&chunk),
-                            });
-                            if tunnel_core::quic::send_message(&mut quic_send, &data_msg)
-                                .await
-                                .is_err()
-                            {
-                                break;
-                            }
+                loop {
+                    let chunk = tokio::select! {
+                        chunk = stream.next() => chunk,
+                        _ = &mut closed => {
+                            tracing::debug!("SSE stream {} closed by the server", stream_id);
+                            return;
                         }
-                        Err(_) => break,
+                    };
+                    let Some(Ok(chunk)) = chunk else {
+                        break;
+                    };
+                    let Some(events) = framer.push(&chunk) else {
+                        continue;
+                    };
+                    if send_sse_data(&mut quic_send, stream_id, &events)
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+                if let Some(rest) = framer.finish() {
+                    if send_sse_data(&mut quic_send, stream_id, &rest)
+                        .await
+                        .is_err()
+                    {
+                        return;
                     }
                 }
 
@@ -264,7 +1298,7 @@ impl LocalProxy {
             Err(e) => {
                 let close_msg = Message::SseClose(SseCloseMessage {
                     stream_id: open_msg.stream_id,
-                    error: Some(e.to_string()),
+                    error: Some(e),
                 });
                 let _ = tunnel_core::quic::send_message(&mut quic_send, &close_msg).await;
                 let _ = quic_send.finish();
@@ -272,3 +1306,222 @@ impl LocalProxy {
         }
     }
 }
+
+async fn send_sse_data(
+    quic_send: &mut quinn::SendStream,
+    stream_id: uuid::Uuid,
+    data: &[u8],
+) -> tunnel_core::Result<()> {
+    let data_msg = Message::SseData(SseDataMessage {
+        stream_id,
+        data: codec::encode_body(data),
+    });
+    tunnel_core::quic::send_message(quic_send, &data_msg).await
+}
+
+/// Hands a base64 request body to the hyper client and ends the body. Returns
+/// its decoded size.
+async fn send_request_body(
+    mut body_tx: futures::channel::mpsc::Sender<Result<Frame<Bytes>, std::io::Error>>,
+    body: Option<&str>,
+) -> Result<usize, String> {
+    use futures::SinkExt;
+
+    let Some(body) = body else {
+        return Ok(0);
+    };
+    let bytes = codec::decode_body(body).map_err(|e| format!("failed to decode body: {}", e))?;
+    let len = bytes.len();
+    let _ = body_tx.send(Ok(Frame::data(Bytes::from(bytes)))).await;
+    Ok(len)
+}
+
+/// Relays an interim response to the server. A failure shows up again when
+/// the final response is sent.
+async fn send_interim(quic_send: &mut quinn::SendStream, msg: &Message) {
+    if let Err(e) = tunnel_core::quic::send_message(quic_send, msg).await {
+        tracing::debug!("Failed to relay {}: {}", msg.message_type(), e);
+    }
+}
+
+/// Tells the server the backend is ready for the body and waits for it.
+async fn request_continue(
+    stream_id: uuid::Uuid,
+    quic_send: &mut quinn::SendStream,
+    quic_recv: &mut quinn::RecvStream,
+    max_frame_bytes: usize,
+) -> Result<HttpBodyMessage, String> {
+    let continue_msg = Message::HttpContinue(HttpContinueMessage { stream_id });
+    tunnel_core::quic::send_message(quic_send, &continue_msg)
+        .await
+        .map_err(|e| format!("failed to send continue: {}", e))?;
+    match tunnel_core::quic::recv_message_limited(quic_recv, max_frame_bytes).await {
+        Ok(Message::HttpBody(body)) => Ok(body),
+        Ok(other) => Err(format!("expected http_body, got {}", other.message_type())),
+        Err(e) => Err(format!("failed to receive body: {}", e)),
+    }
+}
+
+/// Client settings shared by every backend client. Redirects are relayed to
+/// the caller, not followed here.
+fn backend_client_builder() -> reqwest::ClientBuilder {
+    Client::builder().redirect(reqwest::redirect::Policy::none())
+}
+
+/// Answer sent when the local backend does not respond within
+/// `workstation.local_request_timeout`.
+fn local_timeout_response(stream_id: uuid::Uuid) -> HttpResponseMessage {
+    HttpResponseMessage {
+        stream_id,
+        status: 504,
+        headers: HashMap::from([("content-type".to_string(), "text/plain".to_string())]),
+        body: Some(codec::encode_body(b"local backend timed out")),
+    }
+}
+
+fn oversized_headers_response(stream_id: uuid::Uuid) -> HttpResponseMessage {
+    HttpResponseMessage {
+        stream_id,
+        status: 502,
+        headers: HashMap::from([("content-type".to_string(), "text/plain".to_string())]),
+        body: Some(codec::encode_body(
+            b"local backend response headers too large",
+        )),
+    }
+}
+
+/// `X-Forwarded-*` and `Forwarded` headers describing the tunnel's public
+/// URL. Empty when the server did not send one.
+fn forwarded_headers(request: &HttpRequestMessage) -> Vec<(&'static str, String)> {
+    let (Some(scheme), Some(host)) = (&request.public_scheme, &request.public_host) else {
+        return Vec::new();
+    };
+    vec![
+        ("x-forwarded-proto", scheme.clone()),
+        ("x-forwarded-host", host.clone()),
+        ("forwarded", format!("proto={};host=\"{}\"", scheme, host)),
+    ]
+}
+
+/// Whether a caller header is replaced by one of `forwarded`, so a caller
+/// cannot pose as a different public URL.
+fn is_forwarded_header(forwarded: &[(&str, String)], name: &str) -> bool {
+    forwarded
+        .iter()
+        .any(|(forwarded, _)| name.eq_ignore_ascii_case(forwarded))
+}
+
+fn route_matches(
+    rule: &RouteRule,
+    method: &str,
+    path: &str,
+    headers: &HashMap<String, String>,
+) -> bool {
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.to_ascii_lowercase())
+    };
+    if rule
+        .method
+        .as_ref()
+        .is_some_and(|m| !m.eq_ignore_ascii_case(method))
+    {
+        return false;
+    }
+    let path = path.split('?').next().unwrap_or(path);
+    if rule.path.as_ref().is_some_and(|p| !glob_matches(p, path)) {
+        return false;
+    }
+    if let Some(pattern) = &rule.accept {
+        let pattern = pattern.to_ascii_lowercase();
+        let accept = header("accept").unwrap_or_default();
+        if !accept
+            .split(',')
+            .map(|range| range.split(';').next().unwrap_or_default().trim())
+            .any(|media| glob_matches(&pattern, media))
+        {
+            return false;
+        }
+    }
+    if let Some(pattern) = &rule.content_type {
+        let content_type = header("content-type").unwrap_or_default();
+        let media = content_type.split(';').next().unwrap_or_default().trim();
+        if !glob_matches(&pattern.to_ascii_lowercase(), media) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Matches `value` against a pattern where `*` stands for any run of characters.
+fn glob_matches(pattern: &str, value: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let Some(mut rest) = value.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// Backend response headers to relay, without connection-specific ones.
+fn header_map_to_hash(headers: &hyper::HeaderMap) -> HashMap<String, String> {
+    let hop = HopByHop::new(
+        headers
+            .iter()
+            .filter_map(|(name, value)| Some((name.as_str(), value.to_str().ok()?))),
+    );
+    headers
+        .iter()
+        .filter(|(name, _)| !hop.contains(name.as_str()))
+        .filter_map(|(name, value)| {
+            value
+                .to_str()
+                .ok()
+                .map(|v| (name.to_string(), v.to_string()))
+        })
+        .collect()
+}
+
+/// Like [`header_map_to_hash`], with repeated names such as `Link` joined by
+/// `, ` rather than the last one kept.
+fn joined_header_map(headers: &hyper::HeaderMap) -> HashMap<String, String> {
+    let mut joined: HashMap<String, String> = HashMap::new();
+    for (name, value) in headers {
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+        joined
+            .entry(name.to_string())
+            .and_modify(|v| {
+                v.push_str(", ");
+                v.push_str(value);
+            })
+            .or_insert_with(|| value.to_string());
+    }
+    joined
+}
+
+fn map_to_headers(map: &HashMap<String, String>) -> hyper::HeaderMap {
+    let mut headers = hyper::HeaderMap::new();
+    for (name, value) in map {
+        if let (Ok(name), Ok(value)) = (
+            hyper::header::HeaderName::try_from(name.as_str()),
+            hyper::header::HeaderValue::try_from(value.as_str()),
+        ) {
+            headers.append(name, value);
+        }
+    }
+    headers
+}
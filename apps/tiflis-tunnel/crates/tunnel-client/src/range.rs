@@ -0,0 +1,282 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+//! Byte ranges served within the frame limit. When a GET asks for one range
+//! and the backend ignores it, the full body is cut down to that range. A
+//! `206` too large for one frame is shortened, and its `Content-Range` then
+//! tells the caller to request the rest, as media players and resumable
+//! downloads already do.
+
+use tunnel_core::{codec, HttpRequestMessage, HttpResponseMessage, Message};
+
+/// A single range from a `Range: bytes=...` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// `bytes=first-last`, or `bytes=first-` to the end.
+    From { first: u64, last: Option<u64> },
+    /// `bytes=-len`: the final `len` bytes.
+    Suffix(u64),
+}
+
+impl ByteRange {
+    /// Parses a `Range` value. Other units and multiple ranges give `None`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let spec = value.trim().strip_prefix("bytes=")?.trim();
+        if spec.contains(',') {
+            return None;
+        }
+        let (first, last) = spec.split_once('-')?;
+        let (first, last) = (first.trim(), last.trim());
+        if first.is_empty() {
+            return Some(Self::Suffix(last.parse().ok()?));
+        }
+        let first = first.parse().ok()?;
+        let last = match last {
+            "" => None,
+            last => Some(last.parse().ok()?),
+        };
+        if last.is_some_and(|last| last < first) {
+            return None;
+        }
+        Some(Self::From { first, last })
+    }
+
+    /// First and last offset within a body of `len` bytes, or `None` when
+    /// no byte of it is in range.
+    fn resolve(self, len: u64) -> Option<(u64, u64)> {
+        match self {
+            Self::From { first, .. } if first >= len => None,
+            Self::From { first, last } => Some((first, last.unwrap_or(u64::MAX).min(len - 1))),
+            Self::Suffix(0) => None,
+            Self::Suffix(_) if len == 0 => None,
+            Self::Suffix(n) => Some((len.saturating_sub(n), len - 1)),
+        }
+    }
+}
+
+/// The range a request asked for, kept to apply to its response.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RangeRequest {
+    range: Option<ByteRange>,
+    /// `If-Range` was sent, so a `200` may mean the validator did not match.
+    conditional: bool,
+}
+
+impl RangeRequest {
+    pub fn of(request: &HttpRequestMessage) -> Self {
+        if !request.method.eq_ignore_ascii_case("GET") {
+            return Self::default();
+        }
+        let headers = request.header_pairs();
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| *value)
+        };
+        Self {
+            range: header("range").and_then(ByteRange::parse),
+            conditional: header("if-range").is_some(),
+        }
+    }
+
+    /// Serves the range from a full `200` the backend sent instead, as a `206`
+    /// or a `416`, then shortens a `206` whose frame would exceed
+    /// `max_frame_bytes`.
+    pub fn apply(&self, response: &mut HttpResponseMessage, max_frame_bytes: usize) {
+        if let Some(range) = self.range {
+            if response.status == 200
+                && !self.conditional
+                && !response.headers.contains_key("content-range")
+            {
+                cut_to_range(range, response);
+            }
+        }
+        if response.status == 206 {
+            fit_to_frame(response, max_frame_bytes);
+        }
+    }
+}
+
+fn cut_to_range(range: ByteRange, response: &mut HttpResponseMessage) {
+    let Some(body) = decoded_body(response) else {
+        return;
+    };
+    let len = body.len() as u64;
+    match range.resolve(len) {
+        Some((first, last)) => {
+            let part = &body[first as usize..=last as usize];
+            response.status = 206;
+            response.headers.insert(
+                "content-range".to_string(),
+                format!("bytes {}-{}/{}", first, last, len),
+            );
+            response
+                .headers
+                .insert("accept-ranges".to_string(), "bytes".to_string());
+            set_body(response, part);
+        }
+        None => {
+            response.status = 416;
+            response
+                .headers
+                .insert("content-range".to_string(), format!("bytes */{}", len));
+            set_body(response, &[]);
+        }
+    }
+}
+
+fn fit_to_frame(response: &mut HttpResponseMessage, max_frame_bytes: usize) {
+    let Some((first, total)) = response
+        .headers
+        .get("content-range")
+        .and_then(|value| parse_content_range(value))
+    else {
+        return;
+    };
+    let encoded_len = response.body.as_ref().map_or(0, String::len);
+    // The frame without its body, which adds its base64 length. Shortening
+    // the body never lengthens `Content-Range` or `Content-Length`.
+    let mut headers = response.headers.clone();
+    headers.insert("content-length".to_string(), encoded_len.to_string());
+    let Ok(empty) = codec::encode_message(&Message::HttpResponse(HttpResponseMessage {
+        stream_id: response.stream_id,
+        status: response.status,
+        headers,
+        body: Some(String::new()),
+    })) else {
+        return;
+    };
+    let overhead = empty.len() - 4;
+    if overhead + encoded_len <= max_frame_bytes {
+        return;
+    }
+    let budget = max_frame_bytes.saturating_sub(overhead) / 4 * 3;
+    let Some(body) = decoded_body(response) else {
+        return;
+    };
+    if budget == 0 || budget >= body.len() {
+        return;
+    }
+    response.headers.insert(
+        "content-range".to_string(),
+        format!("bytes {}-{}/{}", first, first + budget as u64 - 1, total),
+    );
+    set_body(response, &body[..budget]);
+}
+
+/// First offset and complete length (`*` when unknown) of a
+/// `Content-Range: bytes first-last/total` value.
+fn parse_content_range(value: &str) -> Option<(u64, &str)> {
+    let (range, total) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+    let (first, _) = range.split_once('-')?;
+    Some((first.trim().parse().ok()?, total.trim()))
+}
+
+fn decoded_body(response: &HttpResponseMessage) -> Option<Vec<u8>> {
+    match &response.body {
+        Some(body) => codec::decode_body(body).ok(),
+        None => Some(Vec::new()),
+    }
+}
+
+fn set_body(response: &mut HttpResponseMessage, body: &[u8]) {
+    response
+        .headers
+        .insert("content-length".to_string(), body.len().to_string());
+    response.body = (!body.is_empty()).then(|| codec::encode_body(body));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    fn response(status: u16, headers: &[(&str, &str)], body: &[u8]) -> HttpResponseMessage {
+        HttpResponseMessage {
+            stream_id: Uuid::nil(),
+            status,
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<HashMap<_, _>>(),
+            body: Some(codec::encode_body(body)),
+        }
+    }
+
+    fn request(range: &str) -> RangeRequest {
+        RangeRequest {
+            range: ByteRange::parse(range),
+            conditional: false,
+        }
+    }
+
+    #[test]
+    fn test_parses_single_byte_ranges() {
+        assert_eq!(
+            ByteRange::parse("bytes=10-19"),
+            Some(ByteRange::From {
+                first: 10,
+                last: Some(19)
+            })
+        );
+        assert_eq!(
+            ByteRange::parse("bytes=10-"),
+            Some(ByteRange::From {
+                first: 10,
+                last: None
+            })
+        );
+        assert_eq!(ByteRange::parse("bytes=-5"), Some(ByteRange::Suffix(5)));
+        assert_eq!(ByteRange::parse("bytes=0-1,5-6"), None);
+        assert_eq!(ByteRange::parse("bytes=9-3"), None);
+        assert_eq!(ByteRange::parse("items=0-1"), None);
+    }
+
+    #[test]
+    fn test_cuts_full_body_to_requested_range() {
+        let body: Vec<u8> = (0..100).collect();
+        let mut resp = response(200, &[("content-length", "100")], &body);
+        request("bytes=90-").apply(&mut resp, usize::MAX);
+        assert_eq!(resp.status, 206);
+        assert_eq!(resp.headers["content-range"], "bytes 90-99/100");
+        assert_eq!(resp.headers["content-length"], "10");
+        assert_eq!(decoded_body(&resp).unwrap(), &body[90..]);
+    }
+
+    #[test]
+    fn test_unsatisfiable_range_gives_416() {
+        let mut resp = response(200, &[], &[0; 100]);
+        request("bytes=100-").apply(&mut resp, usize::MAX);
+        assert_eq!(resp.status, 416);
+        assert_eq!(resp.headers["content-range"], "bytes */100");
+        assert!(resp.body.is_none());
+    }
+
+    #[test]
+    fn test_leaves_full_body_when_if_range_sent() {
+        let mut resp = response(200, &[], &[0; 100]);
+        let range = RangeRequest {
+            conditional: true,
+            ..request("bytes=0-9")
+        };
+        range.apply(&mut resp, usize::MAX);
+        assert_eq!(resp.status, 200);
+    }
+
+    #[test]
+    fn test_shortens_partial_content_to_fit_frame() {
+        let body = vec![7u8; 10_000];
+        let mut resp = response(206, &[("content-range", "bytes 500-10499/20000")], &body);
+        request("bytes=500-").apply(&mut resp, 4_000);
+        let kept = decoded_body(&resp).unwrap().len();
+        assert!(kept > 2_000 && kept < 3_000, "{}", kept);
+        assert_eq!(
+            resp.headers["content-range"],
+            format!("bytes 500-{}/20000", 500 + kept - 1)
+        );
+        let frame = codec::encode_message(&Message::HttpResponse(resp)).unwrap();
+        assert!(frame.len() - 4 <= 4_000);
+    }
+}
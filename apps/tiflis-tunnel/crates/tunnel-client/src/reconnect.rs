@@ -6,6 +6,7 @@ use tokio::time::sleep;
 
 pub struct ReconnectStrategy {
     max_delay: Duration,
+    stable_after: Duration,
     pub attempt: u32,
 }
 
@@ -13,14 +14,30 @@ impl ReconnectStrategy {
     pub fn new(max_delay_secs: u64) -> Self {
         Self {
             max_delay: Duration::from_secs(max_delay_secs),
+            stable_after: Duration::ZERO,
             attempt: 0,
         }
     }
 
+    /// Only connections that stay up for `stable_after` reset the backoff, so a
+    /// server that accepts and then drops clients is not hammered.
+    pub fn with_stable_after(mut self, stable_after: Duration) -> Self {
+        self.stable_after = stable_after;
+        self
+    }
+
     pub fn reset(&mut self) {
         self.attempt = 0;
     }
 
+    /// Records how long the last connection stayed up, resetting the backoff if
+    /// it was stable.
+    pub fn connection_ended(&mut self, uptime: Duration) {
+        if uptime >= self.stable_after {
+            self.reset();
+        }
+    }
+
     pub async fn wait_before_retry(&mut self) {
         self.attempt += 1;
         let delay = self.calculate_delay();
@@ -34,3 +51,42 @@ impl ReconnectStrategy {
         exponential_delay.min(self.max_delay)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_lived_connections_keep_backing_off() {
+        let mut strategy = ReconnectStrategy::new(30).with_stable_after(Duration::from_secs(10));
+
+        let mut delays = Vec::new();
+        for _ in 0..4 {
+            strategy.connection_ended(Duration::from_millis(200));
+            strategy.attempt += 1;
+            delays.push(strategy.calculate_delay());
+        }
+
+        assert_eq!(
+            delays,
+            vec![
+                Duration::from_millis(100),
+                Duration::from_millis(200),
+                Duration::from_millis(400),
+                Duration::from_millis(800),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stable_connection_resets_backoff() {
+        let mut strategy = ReconnectStrategy::new(30).with_stable_after(Duration::from_secs(10));
+        strategy.attempt = 5;
+
+        strategy.connection_ended(Duration::from_secs(9));
+        assert_eq!(strategy.attempt, 5);
+
+        strategy.connection_ended(Duration::from_secs(10));
+        assert_eq!(strategy.attempt, 0);
+    }
+}
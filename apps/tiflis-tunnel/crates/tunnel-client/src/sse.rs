@@ -0,0 +1,119 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+//! Re-framing of a backend event stream so every `SseData` message carries
+//! whole events, whatever network chunks the backend's bytes arrived in.
+
+/// Buffered bytes after which a partial event is sent anyway, so a backend
+/// that never ends an event cannot grow the buffer without bound.
+pub const MAX_SSE_EVENT_BYTES: usize = 1 << 20;
+
+#[derive(Debug, Default)]
+pub struct SseFramer {
+    buf: Vec<u8>,
+}
+
+impl SseFramer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a chunk and returns the complete events buffered so far, up to and
+    /// including the blank line ending the last of them.
+    pub fn push(&mut self, chunk: &[u8]) -> Option<Vec<u8>> {
+        self.buf.extend_from_slice(chunk);
+        let end = match last_event_end(&self.buf) {
+            Some(end) => end,
+            None if self.buf.len() > MAX_SSE_EVENT_BYTES => self.buf.len(),
+            None => return None,
+        };
+        let rest = self.buf.split_off(end);
+        Some(std::mem::replace(&mut self.buf, rest))
+    }
+
+    /// Whatever is left once the backend closes the stream.
+    pub fn finish(self) -> Option<Vec<u8>> {
+        (!self.buf.is_empty()).then_some(self.buf)
+    }
+}
+
+/// Offset just past the last blank line in `buf`. Lines may end in `\r\n`,
+/// `\n` or `\r`; a trailing `\r` is not trusted as an ending, as a `\n` may
+/// still follow in the next chunk.
+fn last_event_end(buf: &[u8]) -> Option<usize> {
+    let mut last = None;
+    let mut line_empty = true;
+    let mut i = 0;
+    while i < buf.len() {
+        let end = match buf[i] {
+            b'\n' => i + 1,
+            b'\r' if i + 1 == buf.len() => break,
+            b'\r' if buf[i + 1] == b'\n' => i + 2,
+            b'\r' => i + 1,
+            _ => {
+                line_empty = false;
+                i += 1;
+                continue;
+            }
+        };
+        if line_empty {
+            last = Some(end);
+        }
+        line_empty = true;
+        i = end;
+    }
+    last
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(chunks: &[&str]) -> Vec<String> {
+        let mut framer = SseFramer::new();
+        let mut out: Vec<String> = chunks
+            .iter()
+            .filter_map(|chunk| framer.push(chunk.as_bytes()))
+            .map(|frame| String::from_utf8(frame).unwrap())
+            .collect();
+        out.extend(framer.finish().map(|rest| String::from_utf8(rest).unwrap()));
+        out
+    }
+
+    #[test]
+    fn test_joins_split_events() {
+        assert_eq!(
+            frames(&["data: he", "llo\n", "\nda", "ta: world\n\n"]),
+            ["data: hello\n\n", "data: world\n\n"]
+        );
+    }
+
+    #[test]
+    fn test_keeps_several_events_in_one_frame() {
+        assert_eq!(
+            frames(&["data: a\n\ndata: b\n\nda", "ta: c\n\n"]),
+            ["data: a\n\ndata: b\n\n", "data: c\n\n"]
+        );
+    }
+
+    #[test]
+    fn test_handles_crlf_and_cr_endings() {
+        assert_eq!(
+            frames(&["data: a\r\n\r", "\ndata: b\r\r"]),
+            ["data: a\r\n\r\n", "data: b\r\r"]
+        );
+    }
+
+    #[test]
+    fn test_flushes_unterminated_tail_on_finish() {
+        assert_eq!(frames(&["data: a\n\ndata: b"]), ["data: a\n\n", "data: b"]);
+    }
+
+    #[test]
+    fn test_flushes_oversized_partial_event() {
+        let mut framer = SseFramer::new();
+        let big = "x".repeat(MAX_SSE_EVENT_BYTES + 1);
+        assert_eq!(framer.push(big.as_bytes()).unwrap().len(), big.len());
+        assert!(framer.finish().is_none());
+    }
+}
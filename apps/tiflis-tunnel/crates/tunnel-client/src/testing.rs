@@ -0,0 +1,83 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+//! In-process server and client for integration tests, enabled by the
+//! `testing` feature.
+
+use crate::config::Config;
+use crate::handle::TunnelClientHandle;
+use tunnel_server::config::Config as ServerConfig;
+pub use tunnel_server::testing::{TestServer, TEST_API_KEY};
+
+/// A [`TestServer`] with one workstation registered and forwarding to a
+/// local service.
+pub struct TestHarness {
+    pub server: TestServer,
+    pub client: TunnelClientHandle,
+    pub workstation_id: String,
+    /// Public URL the server assigned at registration.
+    pub tunnel_url: String,
+}
+
+impl TestHarness {
+    /// Starts a server and a client forwarding to `local_address`, e.g.
+    /// `http://127.0.0.1:3000`.
+    pub async fn start(local_address: &str) -> anyhow::Result<Self> {
+        Self::start_with(local_address, |_| {}, |_| {}).await
+    }
+
+    /// Like [`start`](Self::start), with hooks to adjust the server and
+    /// client configs. Returns once the workstation is registered.
+    pub async fn start_with(
+        local_address: &str,
+        configure_server: impl FnOnce(&mut ServerConfig),
+        configure_client: impl FnOnce(&mut Config),
+    ) -> anyhow::Result<Self> {
+        let server = TestServer::start_with(configure_server).await?;
+        let mut config = client_config(&server, local_address);
+        configure_client(&mut config);
+        let workstation_id = config.workstation.id.clone();
+
+        let (client, tunnel_url) = match TunnelClientHandle::connect(config).await {
+            Ok(connected) => connected,
+            Err(e) => {
+                server.shutdown().await;
+                return Err(e.into());
+            }
+        };
+
+        Ok(Self {
+            server,
+            client,
+            workstation_id,
+            tunnel_url,
+        })
+    }
+
+    /// URL that reaches `path` on the local service through the tunnel.
+    pub fn proxy_url(&self, path: &str) -> String {
+        self.server.proxy_url(&self.workstation_id, path)
+    }
+
+    /// Closes the client connection, then stops the server.
+    pub async fn shutdown(self) {
+        self.client
+            .connection()
+            .close(0u32.into(), b"test harness shutting down");
+        self.server.shutdown().await;
+    }
+}
+
+/// Client config for a workstation on `server` with a random id and its own
+/// session ticket file.
+pub fn client_config(server: &TestServer, local_address: &str) -> Config {
+    let suffix = uuid::Uuid::new_v4().simple().to_string();
+    let mut config = Config::default();
+    config.server.address = server.quic_addr.to_string();
+    config.auth.api_key = server.api_key.clone();
+    config.workstation.id = format!("test-ws-{}", &suffix[..8]);
+    config.workstation.local_address = local_address.to_string();
+    config.session.ticket_path =
+        std::env::temp_dir().join(format!("tunnel-test-{}.ticket", suffix));
+    config
+}
@@ -1,9 +1,82 @@
 // Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
 // Licensed under the FSL-1.1-NC.
 
+use crate::protocol::WsDataMessage;
 use crate::{Error, Message, Result};
 use base64::Engine;
 use bytes::{BufMut, BytesMut};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+/// Leading payload bytes quoted in a decode error.
+const DECODE_SNIPPET_BYTES: usize = 32;
+
+static METRICS: OnceLock<CodecMetrics> = OnceLock::new();
+
+/// Frame counters kept by the codec once [`enable_metrics`] has been called.
+/// Until then each encode and decode only checks that they are off.
+#[derive(Debug, Default)]
+pub struct CodecMetrics {
+    frames_encoded: AtomicU64,
+    bytes_encoded: AtomicU64,
+    largest_encoded: AtomicU64,
+    frames_decoded: AtomicU64,
+    bytes_decoded: AtomicU64,
+    largest_decoded: AtomicU64,
+    fragmented_messages: AtomicU64,
+    fragments: AtomicU64,
+}
+
+/// Point-in-time copy of [`CodecMetrics`]. Sizes are whole frames, length
+/// prefix included.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CodecStats {
+    pub frames_encoded: u64,
+    pub bytes_encoded: u64,
+    pub largest_encoded: u64,
+    pub frames_decoded: u64,
+    pub bytes_decoded: u64,
+    pub largest_decoded: u64,
+    /// WebSocket messages split over several `WsData` frames.
+    pub fragmented_messages: u64,
+    /// Frames those messages were split into.
+    pub fragments: u64,
+}
+
+impl CodecMetrics {
+    pub fn snapshot(&self) -> CodecStats {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        CodecStats {
+            frames_encoded: load(&self.frames_encoded),
+            bytes_encoded: load(&self.bytes_encoded),
+            largest_encoded: load(&self.largest_encoded),
+            frames_decoded: load(&self.frames_decoded),
+            bytes_decoded: load(&self.bytes_decoded),
+            largest_decoded: load(&self.largest_decoded),
+            fragmented_messages: load(&self.fragmented_messages),
+            fragments: load(&self.fragments),
+        }
+    }
+}
+
+/// Starts recording codec counters for this process and returns them.
+/// Later calls return the same counters.
+pub fn enable_metrics() -> &'static CodecMetrics {
+    METRICS.get_or_init(CodecMetrics::default)
+}
+
+/// The codec counters, if [`enable_metrics`] has been called.
+pub fn metrics() -> Option<&'static CodecMetrics> {
+    METRICS.get()
+}
+
+fn record_frame(frames: &AtomicU64, bytes: &AtomicU64, largest: &AtomicU64, size: usize) {
+    frames.fetch_add(1, Ordering::Relaxed);
+    bytes.fetch_add(size as u64, Ordering::Relaxed);
+    largest.fetch_max(size as u64, Ordering::Relaxed);
+}
 
 pub fn encode_message(msg: &Message) -> Result<Vec<u8>> {
     let json = serde_json::to_vec(msg)?;
@@ -13,6 +86,14 @@ pub fn encode_message(msg: &Message) -> Result<Vec<u8>> {
     buf.put_u32(len);
     buf.put_slice(&json);
 
+    if let Some(m) = metrics() {
+        record_frame(
+            &m.frames_encoded,
+            &m.bytes_encoded,
+            &m.largest_encoded,
+            buf.len(),
+        );
+    }
     Ok(buf.to_vec())
 }
 
@@ -33,10 +114,42 @@ pub fn decode_message(data: &[u8]) -> Result<(Message, usize)> {
         )));
     }
 
-    let msg = serde_json::from_slice(&data[4..4 + len])?;
+    let msg = decode_payload(&data[4..4 + len], "frame")?;
     Ok((msg, 4 + len))
 }
 
+/// Parses the JSON payload of one frame. A failure is reported as
+/// `Error::Decode` naming `context`, the declared length and the first bytes.
+pub fn decode_payload(payload: &[u8], context: &str) -> Result<Message> {
+    let msg = serde_json::from_slice(payload).map_err(|e| Error::Decode {
+        detail: format!(
+            "{}: {} (declared length {}, starts {})",
+            context,
+            e,
+            payload.len(),
+            hex_snippet(payload)
+        ),
+    })?;
+    if let Some(m) = metrics() {
+        record_frame(
+            &m.frames_decoded,
+            &m.bytes_decoded,
+            &m.largest_decoded,
+            4 + payload.len(),
+        );
+    }
+    Ok(msg)
+}
+
+fn hex_snippet(data: &[u8]) -> String {
+    let shown = &data[..data.len().min(DECODE_SNIPPET_BYTES)];
+    let mut hex: String = shown.iter().map(|b| format!("{:02x}", b)).collect();
+    if shown.len() < data.len() {
+        hex.push_str("...");
+    }
+    hex
+}
+
 pub fn encode_body(data: &[u8]) -> String {
     base64::engine::general_purpose::STANDARD.encode(data)
 }
@@ -45,6 +158,71 @@ pub fn decode_body(encoded: &str) -> Result<Vec<u8>> {
     Ok(base64::engine::general_purpose::STANDARD.decode(encoded)?)
 }
 
+/// Splits a WebSocket message into `WsData` frames carrying at most
+/// `fragment_bytes` of payload each. An empty message is a single frame.
+pub fn ws_data_frames(
+    stream_id: Uuid,
+    data: &[u8],
+    is_binary: bool,
+    fragment_bytes: usize,
+) -> impl Iterator<Item = WsDataMessage> + '_ {
+    let count = data.len().div_ceil(fragment_bytes).max(1);
+    if let Some(m) = metrics().filter(|_| count > 1) {
+        m.fragmented_messages.fetch_add(1, Ordering::Relaxed);
+        m.fragments.fetch_add(count as u64, Ordering::Relaxed);
+    }
+    (0..count).map(move |i| {
+        let start = i * fragment_bytes;
+        let end = start.saturating_add(fragment_bytes).min(data.len());
+        WsDataMessage {
+            stream_id,
+            data: encode_body(&data[start..end]),
+            is_binary,
+            is_final: i + 1 == count,
+        }
+    })
+}
+
+/// Rebuilds WebSocket messages from `WsData` frames, enforcing `limit` on the
+/// reassembled size.
+#[derive(Debug)]
+pub struct WsReassembler {
+    buf: Vec<u8>,
+    limit: usize,
+}
+
+impl WsReassembler {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            buf: Vec::new(),
+            limit,
+        }
+    }
+
+    /// Returns the complete payload once the final frame arrives. A message
+    /// over the limit is discarded with `MessageTooLarge`.
+    pub fn push(&mut self, frame: &WsDataMessage) -> Result<Option<Vec<u8>>> {
+        let data = decode_body(&frame.data)?;
+        let size = self.buf.len() + data.len();
+        if size > self.limit {
+            self.buf = Vec::new();
+            return Err(Error::MessageTooLarge {
+                size,
+                limit: self.limit,
+            });
+        }
+        if frame.is_final {
+            if self.buf.is_empty() {
+                return Ok(Some(data));
+            }
+            self.buf.extend_from_slice(&data);
+            return Ok(Some(std::mem::take(&mut self.buf)));
+        }
+        self.buf.extend_from_slice(&data);
+        Ok(None)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -76,4 +254,71 @@ mod tests {
         let result = decode_message(&[0, 0, 0]);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_decode_malformed_payload_has_context() {
+        let payload = br#"{"type":"ping","timestamp":"not-a-number","padding":"xxxxxxxx"}"#;
+        let mut frame = (payload.len() as u32).to_be_bytes().to_vec();
+        frame.extend_from_slice(payload);
+
+        let Err(Error::Decode { detail }) = decode_message(&frame) else {
+            panic!("expected a decode error");
+        };
+        assert!(detail.starts_with("frame: "), "{}", detail);
+        assert!(
+            detail.contains(&format!("declared length {}", payload.len())),
+            "{}",
+            detail
+        );
+        // `{"type":"ping"` in hex, cut off after 32 bytes.
+        assert!(
+            detail.contains("starts 7b2274797065223a2270696e6722"),
+            "{}",
+            detail
+        );
+        assert!(detail.ends_with("...)"), "{}", detail);
+    }
+
+    #[test]
+    fn test_ws_fragments_round_trip() {
+        let id = Uuid::new_v4();
+        let data: Vec<u8> = (0..2500u32).map(|i| i as u8).collect();
+        let frames: Vec<_> = ws_data_frames(id, &data, true, 1000).collect();
+        assert_eq!(frames.len(), 3);
+        assert!(!frames[0].is_final && !frames[1].is_final && frames[2].is_final);
+
+        let mut reassembler = WsReassembler::new(data.len());
+        assert!(reassembler.push(&frames[0]).unwrap().is_none());
+        assert!(reassembler.push(&frames[1]).unwrap().is_none());
+        assert_eq!(reassembler.push(&frames[2]).unwrap().unwrap(), data);
+    }
+
+    #[test]
+    fn test_ws_empty_message_is_one_frame() {
+        let frames: Vec<_> = ws_data_frames(Uuid::new_v4(), &[], false, 1000).collect();
+        assert_eq!(frames.len(), 1);
+        assert!(frames[0].is_final);
+    }
+
+    #[test]
+    fn test_ws_reassembly_enforces_limit() {
+        let data = vec![7u8; 300];
+        let mut reassembler = WsReassembler::new(250);
+        let mut frames = ws_data_frames(Uuid::new_v4(), &data, true, 100);
+        assert!(reassembler.push(&frames.next().unwrap()).unwrap().is_none());
+        assert!(reassembler.push(&frames.next().unwrap()).unwrap().is_none());
+        assert!(matches!(
+            reassembler.push(&frames.next().unwrap()),
+            Err(Error::MessageTooLarge {
+                size: 300,
+                limit: 250
+            })
+        ));
+
+        // The next message starts from scratch.
+        let small = ws_data_frames(Uuid::new_v4(), b"ok", false, 100)
+            .next()
+            .unwrap();
+        assert_eq!(reassembler.push(&small).unwrap().unwrap(), b"ok");
+    }
 }
@@ -10,6 +10,9 @@ pub enum Error {
     #[error("serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
 
+    #[error("decode error: {detail}")]
+    Decode { detail: String },
+
     #[error("base64 decode error: {0}")]
     Base64Decode(#[from] base64::DecodeError),
 
@@ -28,12 +31,21 @@ pub enum Error {
     #[error("workstation already registered: {0}")]
     WorkstationAlreadyRegistered(String),
 
+    #[error("message too large: {size} bytes exceeds limit of {limit} bytes")]
+    MessageTooLarge { size: usize, limit: usize },
+
     #[error("request timeout")]
     RequestTimeout,
 
+    #[error("connect timed out after {0:?}")]
+    ConnectTimeout(std::time::Duration),
+
     #[error("connection error: {0}")]
     Connection(String),
 
+    #[error("connection closed: {0}")]
+    Closed(crate::quic::CloseReason),
+
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
 
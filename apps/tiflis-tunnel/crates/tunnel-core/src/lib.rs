@@ -5,6 +5,7 @@ pub mod codec;
 pub mod error;
 pub mod protocol;
 pub mod quic;
+pub mod tls;
 
 pub use error::{Error, Result};
 pub use protocol::*;
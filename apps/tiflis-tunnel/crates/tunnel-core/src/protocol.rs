@@ -5,6 +5,32 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
 
+/// WebSocket close code for a message that exceeds the configured size limit.
+pub const WS_CLOSE_MESSAGE_TOO_BIG: u16 = 1009;
+
+/// Longest close reason that fits a close frame next to its code.
+pub const MAX_WS_CLOSE_REASON_BYTES: usize = 123;
+
+/// Most entries a workstation may report as registration metadata.
+pub const MAX_METADATA_ENTRIES: usize = 32;
+
+/// Upper bound on the combined length of all metadata keys and values.
+pub const MAX_METADATA_BYTES: usize = 4096;
+
+/// Most tags a workstation may declare at registration.
+pub const MAX_TAGS: usize = 32;
+
+/// Longest single tag, e.g. `env=staging`.
+pub const MAX_TAG_BYTES: usize = 128;
+
+/// Error code refusing a `reconnect` that arrives after the workstation's
+/// grace period ran out. The client starts over with a `register`.
+pub const GRACE_EXPIRED_ERROR: &str = "GRACE_EXPIRED";
+
+/// Largest raw payload carried by one `WsData` frame when the peer accepts
+/// fragments. Bigger WebSocket messages are split across several frames.
+pub const WS_FRAGMENT_BYTES: usize = 1024 * 1024;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum Message {
@@ -13,27 +39,74 @@ pub enum Message {
     Reconnect(ReconnectMessage),
     Ping(PingMessage),
     Pong(PongMessage),
+    StatusUpdate(StatusUpdateMessage),
     Error(ErrorMessage),
     HttpRequest(HttpRequestMessage),
     HttpResponse(HttpResponseMessage),
+    HttpInformational(HttpInformationalMessage),
+    HttpContinue(HttpContinueMessage),
+    HttpBody(HttpBodyMessage),
     WsOpen(WsOpenMessage),
+    WsOpened(WsOpenedMessage),
     WsData(WsDataMessage),
     WsClose(WsCloseMessage),
     SseOpen(SseOpenMessage),
     SseHeaders(SseHeadersMessage),
     SseData(SseDataMessage),
     SseClose(SseCloseMessage),
+    H2Open(H2OpenMessage),
+    H2Headers(H2HeadersMessage),
+    H2Data(H2DataMessage),
+    H2Close(H2CloseMessage),
+    ConnectOpen(ConnectOpenMessage),
+    ConnectOpened(ConnectOpenedMessage),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RegisterMessage {
     pub api_key: String,
     pub workstation_id: String,
+    /// Largest frame the client is willing to receive.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_frame_bytes: Option<usize>,
+    /// Client answers `WsOpen` requests that set `ack` with `WsOpened`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ws_open_ack: bool,
+    /// Client handles `HttpRequest`s that set `expect_continue`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub expect_continue: bool,
+    /// Client reassembles `WsData` frames sent with `final: false`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ws_fragments: bool,
+    /// Client takes HTTP request headers as an ordered `header_list`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ordered_headers: bool,
+    /// Client handles `WsOpen` requests that set `passthrough`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ws_passthrough: bool,
+    /// Free-form details about the workstation (version, hostname, region)
+    /// shown to operators. See [`validate_metadata`] for the limits.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+    /// Capabilities the workstation declares (`env=staging`, `gpu=true`) for
+    /// picking among workstations. See [`validate_tags`] for the limits.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RegisteredMessage {
     pub url: String,
+    /// Frame size limit agreed for this connection, applied in both directions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_frame_bytes: Option<usize>,
+    /// Server build, e.g. `0.1.11 (a1b2c3d)`. Informational only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_version: Option<String>,
+    /// Secret the client must present on its next `Reconnect`. A new one is
+    /// issued on every successful register or reconnect.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reconnect_token: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,16 +114,45 @@ pub struct ReconnectMessage {
     pub api_key: String,
     pub workstation_id: String,
     pub session_ticket: Option<String>,
+    /// The `reconnect_token` from the latest `Registered`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reconnect_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_frame_bytes: Option<usize>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ws_open_ack: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub expect_continue: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ws_fragments: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ordered_headers: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ws_passthrough: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PingMessage {
+    /// Sender's clock in milliseconds since the Unix epoch.
     pub timestamp: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PongMessage {
+    /// The ping's `timestamp`, echoed unchanged.
     pub timestamp: u64,
+    /// Responder's clock in milliseconds since the Unix epoch when replying.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_timestamp: Option<u64>,
+}
+
+/// Client's view of its local backend, sent on a stream of its own whenever
+/// the backend probe changes state. A new connection starts out healthy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusUpdateMessage {
+    pub healthy: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -65,8 +167,32 @@ pub struct HttpRequestMessage {
     pub method: String,
     pub path: String,
     pub headers: HashMap<String, String>,
+    /// Headers in the order the caller first sent each name, with repeated
+    /// names kept as separate entries, grouped after the first. Names are
+    /// lowercase. Sent instead of `headers` to clients that registered with
+    /// `ordered_headers`, since order-sensitive schemes such as AWS SigV4
+    /// break when headers are reshuffled or merged.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub header_list: Vec<(String, String)>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub body: Option<String>,
+    /// The caller sent `Expect: 100-continue`. The body is withheld until the
+    /// client answers `HttpContinue`, then follows in `HttpBody`; the client
+    /// may instead reply with an `HttpResponse` straight away.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub expect_continue: bool,
+    /// The caller can take interim `1xx` responses, so the client may send
+    /// `HttpInformational`s ahead of the `HttpResponse`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub informational: bool,
+    /// Scheme (`http` or `https`) the caller reached the tunnel with, passed
+    /// to the backend as `X-Forwarded-Proto` and `Forwarded`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_scheme: Option<String>,
+    /// Public host of the tunnel, passed to the backend as `X-Forwarded-Host`
+    /// and `Forwarded`, so URLs it builds point at the tunnel.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_host: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -78,11 +204,61 @@ pub struct HttpResponseMessage {
     pub body: Option<String>,
 }
 
+/// An interim `1xx` response from the local backend other than `100 Continue`,
+/// such as `103 Early Hints`, sent before the `HttpResponse` on the request's
+/// stream.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpInformationalMessage {
+    pub stream_id: Uuid,
+    pub status: u16,
+    pub headers: HashMap<String, String>,
+}
+
+/// The local backend is ready for the body of an `expect_continue` request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpContinueMessage {
+    pub stream_id: Uuid,
+}
+
+/// Body of an `expect_continue` request, sent after `HttpContinue`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpBodyMessage {
+    pub stream_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WsOpenMessage {
     pub stream_id: Uuid,
     pub path: String,
     pub headers: HashMap<String, String>,
+    /// Asks the client to report the local connect with `WsOpened` before
+    /// relaying any frames.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ack: bool,
+    /// Sender reassembles fragmented `WsData`, so the client may split large
+    /// messages for this stream.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub fragments: bool,
+    /// Relay the caller's handshake, `headers` included, to the local
+    /// WebSocket and then raw bytes both ways instead of `WsData`, so the
+    /// caller and the backend negotiate extensions such as
+    /// `permessage-deflate` between themselves. Set together with `ack`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub passthrough: bool,
+}
+
+/// Outcome of connecting to the local WebSocket, sent when `WsOpen.ack` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WsOpenedMessage {
+    pub stream_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// For a `passthrough` open, the `Sec-WebSocket-*` headers of the
+    /// backend's `101`, to be returned to the caller as they are.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -90,6 +266,22 @@ pub struct WsDataMessage {
     pub stream_id: Uuid,
     pub data: String,
     pub is_binary: bool,
+    /// Last frame of the message. Only frames of a fragmented message carry
+    /// `final: false`; the message is delivered once the final one arrives.
+    #[serde(
+        rename = "final",
+        default = "default_true",
+        skip_serializing_if = "is_true"
+    )]
+    pub is_final: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -126,6 +318,219 @@ pub struct SseCloseMessage {
     pub error: Option<String>,
 }
 
+/// Opens an HTTP/2 passthrough stream. Request body frames follow as `H2Data`
+/// and the request half ends with `H2Close`; the response mirrors this with
+/// `H2Headers`, `H2Data` and a closing `H2Close` carrying the trailers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct H2OpenMessage {
+    pub stream_id: Uuid,
+    pub method: String,
+    pub path: String,
+    pub headers: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct H2HeadersMessage {
+    pub stream_id: Uuid,
+    pub status: u16,
+    pub headers: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct H2DataMessage {
+    pub stream_id: Uuid,
+    pub data: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct H2CloseMessage {
+    pub stream_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trailers: Option<HashMap<String, String>>,
+    pub error: Option<String>,
+}
+
+/// Asks the client to dial `host:port` for an HTTP `CONNECT`. Once the client
+/// answers with `ConnectOpened`, the stream carries raw bytes both ways.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectOpenMessage {
+    pub stream_id: Uuid,
+    pub host: String,
+    pub port: u16,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectOpenedMessage {
+    pub stream_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Set when the target is outside the client's allowlist.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub forbidden: bool,
+}
+
+/// Current wall-clock time in milliseconds since the Unix epoch, as carried in
+/// ping and pong timestamps.
+pub fn unix_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Checks registration metadata against [`MAX_METADATA_ENTRIES`] and
+/// [`MAX_METADATA_BYTES`]. Keys must be non-empty.
+pub fn validate_metadata<'a>(
+    entries: impl IntoIterator<Item = (&'a String, &'a String)>,
+) -> Result<(), String> {
+    let mut count = 0;
+    let mut bytes = 0;
+    for (key, value) in entries {
+        if key.is_empty() {
+            return Err("metadata keys must not be empty".to_string());
+        }
+        count += 1;
+        bytes += key.len() + value.len();
+    }
+    if count > MAX_METADATA_ENTRIES {
+        return Err(format!(
+            "metadata has {} entries, limit is {}",
+            count, MAX_METADATA_ENTRIES
+        ));
+    }
+    if bytes > MAX_METADATA_BYTES {
+        return Err(format!(
+            "metadata is {} bytes, limit is {}",
+            bytes, MAX_METADATA_BYTES
+        ));
+    }
+    Ok(())
+}
+
+/// Checks registration tags against [`MAX_TAGS`] and [`MAX_TAG_BYTES`]. Tags
+/// must be non-empty and free of whitespace and commas.
+pub fn validate_tags(tags: &[String]) -> Result<(), String> {
+    if tags.len() > MAX_TAGS {
+        return Err(format!(
+            "{} tags declared, limit is {}",
+            tags.len(),
+            MAX_TAGS
+        ));
+    }
+    for tag in tags {
+        if tag.is_empty() || tag.len() > MAX_TAG_BYTES {
+            return Err(format!(
+                "tag '{}' must be between 1 and {} bytes",
+                tag, MAX_TAG_BYTES
+            ));
+        }
+        if tag.chars().any(|c| c.is_whitespace() || c == ',') {
+            return Err(format!(
+                "tag '{}' must not contain whitespace or commas",
+                tag
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Whether `name` always describes a single connection (RFC 7230 section
+/// 6.1) and so is never carried through the tunnel: `Connection`,
+/// `Keep-Alive`, `Transfer-Encoding`, `Upgrade` and any `Proxy-*` header.
+/// WebSocket upgrades are negotiated by each side on its own.
+pub fn is_hop_by_hop(name: &str) -> bool {
+    ["connection", "keep-alive", "transfer-encoding", "upgrade"]
+        .iter()
+        .any(|hop| name.eq_ignore_ascii_case(hop))
+        || name
+            .get(..6)
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case("proxy-"))
+}
+
+/// Hop-by-hop headers of one message: the [`is_hop_by_hop`] names plus any
+/// its `Connection` header lists, e.g. `X-Session` for
+/// `Connection: close, X-Session`.
+#[derive(Debug, Clone, Default)]
+pub struct HopByHop {
+    listed: Vec<String>,
+}
+
+impl HopByHop {
+    /// Collects the names listed by the `Connection` headers among `headers`.
+    pub fn new<'a>(headers: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        let listed = headers
+            .into_iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case("connection"))
+            .flat_map(|(_, value)| value.split(','))
+            .map(|option| option.trim().to_ascii_lowercase())
+            .filter(|option| !option.is_empty())
+            .collect();
+        Self { listed }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        is_hop_by_hop(name)
+            || self
+                .listed
+                .iter()
+                .any(|listed| name.eq_ignore_ascii_case(listed))
+    }
+}
+
+impl HttpRequestMessage {
+    /// Headers in forwarding order: `header_list` when set, else `headers`.
+    pub fn header_pairs(&self) -> Vec<(&str, &str)> {
+        if self.header_list.is_empty() {
+            self.headers
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str()))
+                .collect()
+        } else {
+            self.header_list
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str()))
+                .collect()
+        }
+    }
+
+    /// Headers by name for lookups, with repeated values joined by `, `.
+    pub fn header_map(&self) -> HashMap<String, String> {
+        if self.header_list.is_empty() {
+            return self.headers.clone();
+        }
+        let mut map: HashMap<String, String> = HashMap::new();
+        for (name, value) in &self.header_list {
+            map.entry(name.clone())
+                .and_modify(|joined| {
+                    joined.push_str(", ");
+                    joined.push_str(value);
+                })
+                .or_insert_with(|| value.clone());
+        }
+        map
+    }
+}
+
+impl WsCloseMessage {
+    /// Code and reason for the close frame that ends the other leg. Codes a
+    /// peer may not send, such as 1005 or 1006 which only describe a close
+    /// locally, give a frame without status; long reasons are cut to fit.
+    pub fn close_frame(self) -> Option<(u16, String)> {
+        let code = self
+            .code
+            .filter(|code| matches!(code, 1000..=1003 | 1007..=1014 | 3000..=4999))?;
+        let mut reason = self.reason.unwrap_or_default();
+        if reason.len() > MAX_WS_CLOSE_REASON_BYTES {
+            let mut end = MAX_WS_CLOSE_REASON_BYTES;
+            while !reason.is_char_boundary(end) {
+                end -= 1;
+            }
+            reason.truncate(end);
+        }
+        Some((code, reason))
+    }
+}
+
 impl Message {
     pub fn message_type(&self) -> &'static str {
         match self {
@@ -134,24 +539,131 @@ impl Message {
             Message::Reconnect(_) => "reconnect",
             Message::Ping(_) => "ping",
             Message::Pong(_) => "pong",
+            Message::StatusUpdate(_) => "status_update",
             Message::Error(_) => "error",
             Message::HttpRequest(_) => "http_request",
             Message::HttpResponse(_) => "http_response",
+            Message::HttpInformational(_) => "http_informational",
+            Message::HttpContinue(_) => "http_continue",
+            Message::HttpBody(_) => "http_body",
             Message::WsOpen(_) => "ws_open",
+            Message::WsOpened(_) => "ws_opened",
             Message::WsData(_) => "ws_data",
             Message::WsClose(_) => "ws_close",
             Message::SseOpen(_) => "sse_open",
             Message::SseHeaders(_) => "sse_headers",
             Message::SseData(_) => "sse_data",
             Message::SseClose(_) => "sse_close",
+            Message::H2Open(_) => "h2_open",
+            Message::H2Headers(_) => "h2_headers",
+            Message::H2Data(_) => "h2_data",
+            Message::H2Close(_) => "h2_close",
+            Message::ConnectOpen(_) => "connect_open",
+            Message::ConnectOpened(_) => "connect_opened",
+        }
+    }
+
+    /// Checks invariants serde cannot express: names and ids that must be
+    /// non-empty, status codes within 100-599 and a non-zero `CONNECT` port.
+    /// Every frame read by [`recv_message`](crate::quic::recv_message) passes
+    /// through this. Timestamps need no check; as `u64` they cannot be negative.
+    pub fn validate(&self) -> crate::Result<()> {
+        match self {
+            Message::Register(reg) => require("workstation_id", &reg.workstation_id),
+            Message::Reconnect(reconnect) => require("workstation_id", &reconnect.workstation_id),
+            Message::Registered(registered) => require("url", &registered.url),
+            Message::Error(error) => require("code", &error.code),
+            Message::HttpRequest(req) => require("method", &req.method),
+            Message::SseOpen(open) => require("method", &open.method),
+            Message::H2Open(open) => require("method", &open.method),
+            Message::HttpResponse(HttpResponseMessage { status, .. })
+            | Message::SseHeaders(SseHeadersMessage { status, .. })
+            | Message::H2Headers(H2HeadersMessage { status, .. }) => {
+                if (100..=599).contains(status) {
+                    Ok(())
+                } else {
+                    Err(crate::Error::InvalidMessageType(format!(
+                        "{} with status {}",
+                        self.message_type(),
+                        status
+                    )))
+                }
+            }
+            Message::ConnectOpen(open) => {
+                require("host", &open.host)?;
+                if open.port == 0 {
+                    return Err(crate::Error::InvalidMessageType(
+                        "connect_open with port 0".to_string(),
+                    ));
+                }
+                Ok(())
+            }
+            Message::HttpInformational(HttpInformationalMessage { status, .. }) => {
+                if (100..=199).contains(status) {
+                    Ok(())
+                } else {
+                    Err(crate::Error::InvalidMessageType(format!(
+                        "{} with status {}",
+                        self.message_type(),
+                        status
+                    )))
+                }
+            }
+            _ => Ok(()),
         }
     }
 }
 
+fn require(field: &str, value: &str) -> crate::Result<()> {
+    if value.is_empty() {
+        return Err(crate::Error::MissingField(field.to_string()));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn ws_close(code: Option<u16>, reason: &str) -> WsCloseMessage {
+        WsCloseMessage {
+            stream_id: Uuid::new_v4(),
+            code,
+            reason: Some(reason.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_ws_close_frame_keeps_sendable_codes() {
+        assert_eq!(
+            ws_close(Some(1011), "backend failed").close_frame(),
+            Some((1011, "backend failed".to_string()))
+        );
+        assert_eq!(
+            ws_close(Some(4001), "").close_frame(),
+            Some((4001, String::new()))
+        );
+        for code in [
+            None,
+            Some(999),
+            Some(1005),
+            Some(1006),
+            Some(1015),
+            Some(5000),
+        ] {
+            assert_eq!(ws_close(code, "x").close_frame(), None, "{:?}", code);
+        }
+    }
+
+    #[test]
+    fn test_ws_close_frame_truncates_long_reason() {
+        let (_, reason) = ws_close(Some(1000), &"é".repeat(100))
+            .close_frame()
+            .unwrap();
+        assert_eq!(reason.len(), 122);
+        assert!(reason.chars().all(|c| c == 'é'));
+    }
+
     #[test]
     fn test_sse_open_serialization() {
         let stream_id = Uuid::new_v4();
@@ -312,4 +824,423 @@ mod tests {
             "sse_close"
         );
     }
+
+    #[test]
+    fn test_h2_close_serialization() {
+        let stream_id = Uuid::new_v4();
+        let msg = Message::H2Close(H2CloseMessage {
+            stream_id,
+            trailers: Some(HashMap::from([(
+                "grpc-status".to_string(),
+                "0".to_string(),
+            )])),
+            error: None,
+        });
+        let encoded = serde_json::to_string(&msg).unwrap();
+        assert!(encoded.contains("\"type\":\"h2_close\""));
+
+        let decoded: Message = serde_json::from_str(&encoded).unwrap();
+        match decoded {
+            Message::H2Close(close) => {
+                assert_eq!(close.stream_id, stream_id);
+                assert_eq!(close.trailers.unwrap().get("grpc-status").unwrap(), "0");
+                assert!(close.error.is_none());
+            }
+            _ => panic!("Wrong message type"),
+        }
+    }
+
+    fn one_of_each() -> Vec<Message> {
+        let stream_id = Uuid::new_v4();
+        let headers = HashMap::from([("content-type".to_string(), "text/plain".to_string())]);
+        vec![
+            Message::Register(RegisterMessage {
+                api_key: "key".to_string(),
+                workstation_id: "ws".to_string(),
+                max_frame_bytes: Some(1024),
+                ws_open_ack: true,
+                expect_continue: true,
+                ws_fragments: true,
+                ordered_headers: true,
+                ws_passthrough: true,
+                metadata: HashMap::from([("region".to_string(), "eu".to_string())]),
+                tags: vec!["gpu".to_string()],
+            }),
+            Message::Registered(RegisteredMessage {
+                url: "https://tunnel.example.com/t/ws".to_string(),
+                max_frame_bytes: Some(1024),
+                server_version: Some("0.1.11".to_string()),
+                reconnect_token: Some("token".to_string()),
+            }),
+            Message::Reconnect(ReconnectMessage {
+                api_key: "key".to_string(),
+                workstation_id: "ws".to_string(),
+                session_ticket: None,
+                reconnect_token: Some("token".to_string()),
+                max_frame_bytes: None,
+                ws_open_ack: true,
+                expect_continue: false,
+                ws_fragments: true,
+                ordered_headers: false,
+                ws_passthrough: false,
+            }),
+            Message::Ping(PingMessage { timestamp: 1 }),
+            Message::Pong(PongMessage {
+                timestamp: 1,
+                server_timestamp: Some(2),
+            }),
+            Message::StatusUpdate(StatusUpdateMessage {
+                healthy: false,
+                detail: Some("connection refused".to_string()),
+            }),
+            Message::Error(ErrorMessage {
+                code: "AUTH_FAILED".to_string(),
+                message: "bad key".to_string(),
+            }),
+            Message::HttpRequest(HttpRequestMessage {
+                stream_id,
+                method: "POST".to_string(),
+                path: "/api".to_string(),
+                headers: headers.clone(),
+                header_list: vec![("accept".to_string(), "*/*".to_string())],
+                body: Some("aGk=".to_string()),
+                expect_continue: true,
+                informational: true,
+                public_scheme: Some("https".to_string()),
+                public_host: Some("tunnel.example.com".to_string()),
+            }),
+            Message::HttpResponse(HttpResponseMessage {
+                stream_id,
+                status: 201,
+                headers: headers.clone(),
+                body: Some("aGk=".to_string()),
+            }),
+            Message::HttpInformational(HttpInformationalMessage {
+                stream_id,
+                status: 103,
+                headers: headers.clone(),
+            }),
+            Message::HttpContinue(HttpContinueMessage { stream_id }),
+            Message::HttpBody(HttpBodyMessage {
+                stream_id,
+                body: Some("aGk=".to_string()),
+            }),
+            Message::WsOpen(WsOpenMessage {
+                stream_id,
+                path: "/ws".to_string(),
+                headers: headers.clone(),
+                ack: true,
+                fragments: true,
+                passthrough: true,
+            }),
+            Message::WsOpened(WsOpenedMessage {
+                stream_id,
+                error: Some("refused".to_string()),
+                headers: headers.clone(),
+            }),
+            Message::WsData(WsDataMessage {
+                stream_id,
+                data: "aGk=".to_string(),
+                is_binary: true,
+                is_final: false,
+            }),
+            Message::WsClose(WsCloseMessage {
+                stream_id,
+                code: Some(1000),
+                reason: Some("bye".to_string()),
+            }),
+            Message::SseOpen(SseOpenMessage {
+                stream_id,
+                method: "GET".to_string(),
+                path: "/events".to_string(),
+                headers: headers.clone(),
+            }),
+            Message::SseHeaders(SseHeadersMessage {
+                stream_id,
+                status: 200,
+                headers: headers.clone(),
+            }),
+            Message::SseData(SseDataMessage {
+                stream_id,
+                data: "aGk=".to_string(),
+            }),
+            Message::SseClose(SseCloseMessage {
+                stream_id,
+                error: None,
+            }),
+            Message::H2Open(H2OpenMessage {
+                stream_id,
+                method: "POST".to_string(),
+                path: "/pkg.Service/Call".to_string(),
+                headers: headers.clone(),
+            }),
+            Message::H2Headers(H2HeadersMessage {
+                stream_id,
+                status: 200,
+                headers,
+            }),
+            Message::H2Data(H2DataMessage {
+                stream_id,
+                data: "aGk=".to_string(),
+            }),
+            Message::H2Close(H2CloseMessage {
+                stream_id,
+                trailers: None,
+                error: Some("reset".to_string()),
+            }),
+            Message::ConnectOpen(ConnectOpenMessage {
+                stream_id,
+                host: "db.internal".to_string(),
+                port: 5432,
+            }),
+            Message::ConnectOpened(ConnectOpenedMessage {
+                stream_id,
+                error: None,
+                forbidden: true,
+            }),
+        ]
+    }
+
+    #[test]
+    fn test_every_variant_round_trips() {
+        let messages = one_of_each();
+        assert_eq!(messages.len(), 26, "add new variants to one_of_each");
+        for msg in messages {
+            let encoded = crate::codec::encode_message(&msg).unwrap();
+            let (decoded, size) = crate::codec::decode_message(&encoded).unwrap();
+            assert_eq!(size, encoded.len());
+            decoded.validate().unwrap();
+            assert_eq!(decoded.message_type(), msg.message_type());
+
+            let original = serde_json::to_value(&msg).unwrap();
+            assert_eq!(original["type"], msg.message_type());
+            assert_eq!(serde_json::to_value(&decoded).unwrap(), original);
+        }
+    }
+
+    #[test]
+    fn test_wire_field_names() {
+        let id = "00000000-0000-0000-0000-000000000001";
+        let decode = |json: String| -> Message { serde_json::from_str(&json).unwrap() };
+
+        let Message::Register(reg) = decode(
+            r#"{"type":"register","api_key":"k","workstation_id":"ws","ws_open_ack":true}"#
+                .to_string(),
+        ) else {
+            panic!("expected register");
+        };
+        assert_eq!(reg.workstation_id, "ws");
+        assert!(reg.ws_open_ack && !reg.ws_fragments);
+
+        let Message::Reconnect(reconnect) = decode(
+            r#"{"type":"reconnect","api_key":"k","workstation_id":"ws","session_ticket":null,"reconnect_token":"t"}"#
+                .to_string(),
+        ) else {
+            panic!("expected reconnect");
+        };
+        assert_eq!(reconnect.reconnect_token.as_deref(), Some("t"));
+
+        let Message::HttpResponse(response) = decode(format!(
+            r#"{{"type":"http_response","stream_id":"{}","status":404,"headers":{{}}}}"#,
+            id
+        )) else {
+            panic!("expected http_response");
+        };
+        assert_eq!(response.status, 404);
+        assert!(response.body.is_none());
+
+        let Message::WsOpen(open) = decode(format!(
+            r#"{{"type":"ws_open","stream_id":"{}","path":"/ws","headers":{{}}}}"#,
+            id
+        )) else {
+            panic!("expected ws_open");
+        };
+        assert!(!open.ack && !open.fragments);
+
+        let Message::WsData(data) = decode(format!(
+            r#"{{"type":"ws_data","stream_id":"{}","data":"","is_binary":false,"final":false}}"#,
+            id
+        )) else {
+            panic!("expected ws_data");
+        };
+        assert!(!data.is_final);
+
+        let Message::WsClose(close) = decode(format!(
+            r#"{{"type":"ws_close","stream_id":"{}","code":1001,"reason":null}}"#,
+            id
+        )) else {
+            panic!("expected ws_close");
+        };
+        assert_eq!(close.code, Some(1001));
+    }
+
+    #[test]
+    fn test_malformed_messages_are_rejected() {
+        let id = Uuid::nil();
+        let missing = |msg: Message, field: &str| match msg.validate() {
+            Err(crate::Error::MissingField(f)) => assert_eq!(f, field),
+            other => panic!("expected missing {}, got {:?}", field, other),
+        };
+        missing(
+            Message::Register(RegisterMessage {
+                api_key: "k".to_string(),
+                workstation_id: String::new(),
+                max_frame_bytes: None,
+                ws_open_ack: false,
+                expect_continue: false,
+                ws_fragments: false,
+                ordered_headers: false,
+                ws_passthrough: false,
+                metadata: HashMap::new(),
+                tags: Vec::new(),
+            }),
+            "workstation_id",
+        );
+        missing(
+            Message::Reconnect(ReconnectMessage {
+                api_key: "k".to_string(),
+                workstation_id: String::new(),
+                session_ticket: None,
+                reconnect_token: None,
+                max_frame_bytes: None,
+                ws_open_ack: false,
+                expect_continue: false,
+                ws_fragments: false,
+                ordered_headers: false,
+                ws_passthrough: false,
+            }),
+            "workstation_id",
+        );
+        missing(
+            Message::Registered(RegisteredMessage {
+                url: String::new(),
+                max_frame_bytes: None,
+                server_version: None,
+                reconnect_token: None,
+            }),
+            "url",
+        );
+        missing(
+            Message::Error(ErrorMessage {
+                code: String::new(),
+                message: "x".to_string(),
+            }),
+            "code",
+        );
+        missing(
+            Message::SseOpen(SseOpenMessage {
+                stream_id: id,
+                method: String::new(),
+                path: "/".to_string(),
+                headers: HashMap::new(),
+            }),
+            "method",
+        );
+        missing(
+            Message::ConnectOpen(ConnectOpenMessage {
+                stream_id: id,
+                host: String::new(),
+                port: 22,
+            }),
+            "host",
+        );
+
+        for status in [0, 99, 600, u16::MAX] {
+            let response = Message::HttpResponse(HttpResponseMessage {
+                stream_id: id,
+                status,
+                headers: HashMap::new(),
+                body: None,
+            });
+            assert!(
+                matches!(
+                    response.validate(),
+                    Err(crate::Error::InvalidMessageType(_))
+                ),
+                "{}",
+                status
+            );
+        }
+        assert!(matches!(
+            Message::HttpInformational(HttpInformationalMessage {
+                stream_id: id,
+                status: 200,
+                headers: HashMap::new(),
+            })
+            .validate(),
+            Err(crate::Error::InvalidMessageType(_))
+        ));
+        assert!(matches!(
+            Message::ConnectOpen(ConnectOpenMessage {
+                stream_id: id,
+                host: "db".to_string(),
+                port: 0,
+            })
+            .validate(),
+            Err(crate::Error::InvalidMessageType(_))
+        ));
+
+        // Types serde enforces on its own fail to decode at all.
+        for json in [
+            r#"{"type":"ping","timestamp":-1}"#,
+            r#"{"type":"register","api_key":"k"}"#,
+            r#"{"type":"http_response","stream_id":"not-a-uuid","status":200,"headers":{}}"#,
+            r#"{"type":"teleport"}"#,
+        ] {
+            assert!(serde_json::from_str::<Message>(json).is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn test_validate_metadata_limits() {
+        let ok = HashMap::from([("region".to_string(), "eu-west".to_string())]);
+        assert!(validate_metadata(&ok).is_ok());
+
+        let empty_key = HashMap::from([(String::new(), "x".to_string())]);
+        assert!(validate_metadata(&empty_key).is_err());
+
+        let too_many: HashMap<String, String> = (0..=MAX_METADATA_ENTRIES)
+            .map(|i| (format!("k{}", i), String::new()))
+            .collect();
+        assert!(validate_metadata(&too_many).is_err());
+
+        let too_big = HashMap::from([("notes".to_string(), "x".repeat(MAX_METADATA_BYTES))]);
+        assert!(validate_metadata(&too_big).is_err());
+    }
+
+    #[test]
+    fn test_validate_tags_limits() {
+        assert!(validate_tags(&["env=staging".to_string(), "gpu".to_string()]).is_ok());
+        assert!(validate_tags(&[String::new()]).is_err());
+        assert!(validate_tags(&["a b".to_string()]).is_err());
+        assert!(validate_tags(&["a,b".to_string()]).is_err());
+        assert!(validate_tags(&["x".repeat(MAX_TAG_BYTES + 1)]).is_err());
+
+        let too_many: Vec<String> = (0..=MAX_TAGS).map(|i| format!("t{}", i)).collect();
+        assert!(validate_tags(&too_many).is_err());
+    }
+
+    #[test]
+    fn test_hop_by_hop_includes_connection_listed_names() {
+        let hop = HopByHop::new([
+            ("Connection", "close, X-Session"),
+            ("connection", "x-trace"),
+            ("x-other", "1"),
+        ]);
+        for name in [
+            "connection",
+            "Keep-Alive",
+            "transfer-encoding",
+            "upgrade",
+            "proxy-authorization",
+            "Proxy-Connection",
+            "x-session",
+            "X-Trace",
+        ] {
+            assert!(hop.contains(name), "{}", name);
+        }
+        for name in ["x-other", "te", "proxy", "content-type"] {
+            assert!(!hop.contains(name), "{}", name);
+        }
+    }
 }
@@ -2,9 +2,312 @@
 // Licensed under the FSL-1.1-NC.
 
 use crate::{codec, Error, Message, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// ALPN protocol negotiated on tunnel connections unless configured otherwise.
+pub const DEFAULT_ALPN: &str = "tiflis-tunnel";
+
+/// Frame size assumed for peers that do not advertise one during the handshake.
+pub const LEGACY_MAX_FRAME_BYTES: usize = 10_000_000;
+
+/// Frame size limit used when none is configured.
+pub const DEFAULT_MAX_FRAME_BYTES: usize = 32 * 1024 * 1024;
+
+/// Picks the frame limit for a connection from the local limit and the peer's
+/// advertised limit, if any.
+pub fn negotiate_max_frame_bytes(local: usize, peer: Option<usize>) -> usize {
+    local.min(peer.unwrap_or(LEGACY_MAX_FRAME_BYTES))
+}
+
+/// QUIC transport parameters shared by the server and client `[quic]`
+/// sections. Durations are in seconds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuicConfig {
+    #[serde(default = "default_max_bidi_streams")]
+    pub max_bidi_streams: u32,
+    /// Seconds without traffic before the connection is closed. The smaller of
+    /// the two peers' values applies.
+    #[serde(default = "default_max_idle_timeout")]
+    pub max_idle_timeout: u64,
+    /// Seconds between transport keep-alive packets. 0 disables them.
+    #[serde(default)]
+    pub keep_alive_interval: u64,
+    /// Bytes a peer may send on one stream before it is acknowledged.
+    #[serde(default = "default_stream_receive_window")]
+    pub stream_receive_window: u32,
+    /// Bytes a peer may send across all streams. 0 leaves it unbounded.
+    #[serde(default)]
+    pub receive_window: u64,
+    #[serde(default)]
+    pub congestion_controller: CongestionController,
+}
+
+/// Congestion control algorithm for the connection's sending side. BBR tends
+/// to do better than Cubic on links with a high bandwidth-delay product.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CongestionController {
+    #[default]
+    Cubic,
+    Bbr,
+    NewReno,
+}
+
+impl CongestionController {
+    fn factory(self) -> Arc<dyn quinn::congestion::ControllerFactory + Send + Sync> {
+        match self {
+            Self::Cubic => Arc::new(quinn::congestion::CubicConfig::default()),
+            Self::Bbr => Arc::new(quinn::congestion::BbrConfig::default()),
+            Self::NewReno => Arc::new(quinn::congestion::NewRenoConfig::default()),
+        }
+    }
+}
+
+impl std::fmt::Display for CongestionController {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Cubic => "cubic",
+            Self::Bbr => "bbr",
+            Self::NewReno => "newreno",
+        })
+    }
+}
+
+impl std::str::FromStr for CongestionController {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "cubic" => Ok(Self::Cubic),
+            "bbr" => Ok(Self::Bbr),
+            "newreno" => Ok(Self::NewReno),
+            other => Err(format!(
+                "unknown congestion controller '{}', expected cubic, bbr or newreno",
+                other
+            )),
+        }
+    }
+}
+
+fn default_max_bidi_streams() -> u32 {
+    1000
+}
+
+fn default_max_idle_timeout() -> u64 {
+    30
+}
+
+fn default_stream_receive_window() -> u32 {
+    1_250_000
+}
+
+const MIN_STREAM_RECEIVE_WINDOW: u32 = 16 * 1024;
+const MAX_IDLE_TIMEOUT_SECS: u64 = 3600;
+
+impl Default for QuicConfig {
+    fn default() -> Self {
+        Self {
+            max_bidi_streams: default_max_bidi_streams(),
+            max_idle_timeout: default_max_idle_timeout(),
+            keep_alive_interval: 0,
+            stream_receive_window: default_stream_receive_window(),
+            receive_window: 0,
+            congestion_controller: CongestionController::default(),
+        }
+    }
+}
+
+impl QuicConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.max_bidi_streams == 0 {
+            return Err(Error::Other(
+                "QUIC_MAX_BIDI_STREAMS must be greater than 0".to_string(),
+            ));
+        }
+        if self.max_idle_timeout == 0 || self.max_idle_timeout > MAX_IDLE_TIMEOUT_SECS {
+            return Err(Error::Other(format!(
+                "QUIC_MAX_IDLE_TIMEOUT must be between 1 and {} seconds",
+                MAX_IDLE_TIMEOUT_SECS
+            )));
+        }
+        if self.keep_alive_interval >= self.max_idle_timeout {
+            return Err(Error::Other(
+                "QUIC_KEEP_ALIVE_INTERVAL must be shorter than QUIC_MAX_IDLE_TIMEOUT".to_string(),
+            ));
+        }
+        if self.stream_receive_window < MIN_STREAM_RECEIVE_WINDOW {
+            return Err(Error::Other(format!(
+                "QUIC_STREAM_RECEIVE_WINDOW must be at least {} bytes",
+                MIN_STREAM_RECEIVE_WINDOW
+            )));
+        }
+        if self.receive_window != 0 && self.receive_window < self.stream_receive_window as u64 {
+            return Err(Error::Other(
+                "QUIC_RECEIVE_WINDOW must be 0 or at least QUIC_STREAM_RECEIVE_WINDOW".to_string(),
+            ));
+        }
+        if self.receive_window > quinn::VarInt::MAX.into_inner() {
+            return Err(Error::Other("QUIC_RECEIVE_WINDOW is too large".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Builds the transport config for an endpoint. Expects a validated config.
+    pub fn transport_config(&self) -> quinn::TransportConfig {
+        let mut transport = quinn::TransportConfig::default();
+        transport.max_concurrent_bidi_streams(self.max_bidi_streams.into());
+        transport.max_idle_timeout(
+            quinn::IdleTimeout::try_from(Duration::from_secs(self.max_idle_timeout)).ok(),
+        );
+        if self.keep_alive_interval > 0 {
+            transport.keep_alive_interval(Some(Duration::from_secs(self.keep_alive_interval)));
+        }
+        transport.stream_receive_window(self.stream_receive_window.into());
+        if self.receive_window > 0 {
+            transport.receive_window(
+                quinn::VarInt::from_u64(self.receive_window).unwrap_or(quinn::VarInt::MAX),
+            );
+        }
+        transport.congestion_controller_factory(self.congestion_controller.factory());
+        transport
+    }
+}
+
+/// Snapshot of the path statistics quinn keeps for a connection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionQuality {
+    pub rtt_ms: u64,
+    pub cwnd: u64,
+    pub sent_packets: u64,
+    pub lost_packets: u64,
+    pub congestion_events: u64,
+}
+
+impl ConnectionQuality {
+    pub fn from_connection(connection: &quinn::Connection) -> Self {
+        let path = connection.stats().path;
+        Self {
+            rtt_ms: path.rtt.as_millis() as u64,
+            cwnd: path.cwnd,
+            sent_packets: path.sent_packets,
+            lost_packets: path.lost_packets,
+            congestion_events: path.congestion_events,
+        }
+    }
+
+    /// Fraction of sent packets declared lost.
+    pub fn loss_rate(&self) -> f64 {
+        if self.sent_packets == 0 {
+            0.0
+        } else {
+            self.lost_packets as f64 / self.sent_packets as f64
+        }
+    }
+}
+
+impl std::fmt::Display for ConnectionQuality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "rtt={}ms cwnd={} loss={:.2}% congestion_events={}",
+            self.rtt_ms,
+            self.cwnd,
+            self.loss_rate() * 100.0,
+            self.congestion_events
+        )
+    }
+}
+
+/// Why a connection ended, from the [`quinn::ConnectionError`] it closed with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CloseReason {
+    /// The peer closed the connection with an application error code.
+    ApplicationClosed { code: u64, reason: String },
+    /// The peer closed the connection with a transport error.
+    ConnectionClosed(String),
+    /// This end closed the connection.
+    LocallyClosed,
+    /// Nothing was heard from the peer within the idle timeout.
+    TimedOut,
+    /// The peer no longer recognizes the connection, e.g. after a restart.
+    Reset,
+    /// The peer speaks no QUIC version this end supports.
+    VersionMismatch,
+    /// This end detected a protocol violation.
+    TransportError(String),
+    /// No connection ids were left to migrate with.
+    CidsExhausted,
+}
+
+impl CloseReason {
+    /// Whether reconnecting may succeed. A version mismatch fails the same
+    /// way on every try.
+    pub fn is_retryable(&self) -> bool {
+        !matches!(self, Self::VersionMismatch)
+    }
+}
+
+impl From<&quinn::ConnectionError> for CloseReason {
+    fn from(e: &quinn::ConnectionError) -> Self {
+        match e {
+            quinn::ConnectionError::ApplicationClosed(close) => Self::ApplicationClosed {
+                code: close.error_code.into_inner(),
+                reason: String::from_utf8_lossy(&close.reason).into_owned(),
+            },
+            quinn::ConnectionError::ConnectionClosed(close) => {
+                Self::ConnectionClosed(close.to_string())
+            }
+            quinn::ConnectionError::LocallyClosed => Self::LocallyClosed,
+            quinn::ConnectionError::TimedOut => Self::TimedOut,
+            quinn::ConnectionError::Reset => Self::Reset,
+            quinn::ConnectionError::VersionMismatch => Self::VersionMismatch,
+            quinn::ConnectionError::TransportError(e) => Self::TransportError(e.to_string()),
+            quinn::ConnectionError::CidsExhausted => Self::CidsExhausted,
+        }
+    }
+}
+
+impl std::fmt::Display for CloseReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ApplicationClosed { code, reason } if reason.is_empty() => {
+                write!(f, "closed by peer with code {:#x}", code)
+            }
+            Self::ApplicationClosed { code, reason } => {
+                write!(f, "closed by peer with code {:#x}: {}", code, reason)
+            }
+            Self::ConnectionClosed(detail) => write!(f, "closed by peer: {}", detail),
+            Self::LocallyClosed => f.write_str("closed locally"),
+            Self::TimedOut => f.write_str("idle timeout"),
+            Self::Reset => f.write_str("reset by peer"),
+            Self::VersionMismatch => f.write_str("QUIC version negotiation failed"),
+            Self::TransportError(detail) => write!(f, "transport error: {}", detail),
+            Self::CidsExhausted => f.write_str("connection ids exhausted"),
+        }
+    }
+}
 
 pub async fn send_message(send_stream: &mut quinn::SendStream, msg: &Message) -> Result<()> {
+    send_message_limited(send_stream, msg, usize::MAX).await
+}
+
+/// Like [`send_message`], but refuses to write a frame the peer would reject.
+/// Nothing is written to the stream when the limit is exceeded.
+pub async fn send_message_limited(
+    send_stream: &mut quinn::SendStream,
+    msg: &Message,
+    max_frame_bytes: usize,
+) -> Result<()> {
     let data = codec::encode_message(msg)?;
+    let size = data.len() - 4;
+    if size > max_frame_bytes {
+        return Err(Error::MessageTooLarge {
+            size,
+            limit: max_frame_bytes,
+        });
+    }
     send_stream
         .write_all(&data)
         .await
@@ -13,6 +316,13 @@ pub async fn send_message(send_stream: &mut quinn::SendStream, msg: &Message) ->
 }
 
 pub async fn recv_message(recv_stream: &mut quinn::RecvStream) -> Result<Message> {
+    recv_message_limited(recv_stream, LEGACY_MAX_FRAME_BYTES).await
+}
+
+pub async fn recv_message_limited(
+    recv_stream: &mut quinn::RecvStream,
+    max_frame_bytes: usize,
+) -> Result<Message> {
     let mut len_buf = [0u8; 4];
     recv_stream
         .read_exact(&mut len_buf)
@@ -25,8 +335,11 @@ pub async fn recv_message(recv_stream: &mut quinn::RecvStream) -> Result<Message
         })?;
 
     let len = u32::from_be_bytes(len_buf) as usize;
-    if len > 10_000_000 {
-        return Err(Error::Other(format!("message too large: {} bytes", len)));
+    if len > max_frame_bytes {
+        return Err(Error::MessageTooLarge {
+            size: len,
+            limit: max_frame_bytes,
+        });
     }
 
     let mut data = vec![0u8; len];
@@ -40,7 +353,8 @@ pub async fn recv_message(recv_stream: &mut quinn::RecvStream) -> Result<Message
             quinn::ReadExactError::ReadError(e) => Error::Connection(e.to_string()),
         })?;
 
-    let msg = serde_json::from_slice(&data)?;
+    let msg = codec::decode_payload(&data, &format!("stream {}", recv_stream.id()))?;
+    msg.validate()?;
     Ok(msg)
 }
 
@@ -0,0 +1,147 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::version::{TLS12, TLS13};
+use rustls::{
+    ClientConfig, ConfigBuilder, ServerConfig, SupportedCipherSuite, SupportedProtocolVersion,
+    WantsVerifier,
+};
+use std::sync::Arc;
+
+pub const DEFAULT_MIN_VERSION: &str = "1.2";
+
+static TLS12_AND_UP: &[&SupportedProtocolVersion] = &[&TLS13, &TLS12];
+static TLS13_ONLY: &[&SupportedProtocolVersion] = &[&TLS13];
+
+/// Protocol versions and cipher suites a TLS endpoint may negotiate, built
+/// from `tls.min_version` and `tls.cipher_suites`.
+#[derive(Debug, Clone)]
+pub struct TlsPolicy {
+    provider: Arc<CryptoProvider>,
+    versions: &'static [&'static SupportedProtocolVersion],
+}
+
+impl TlsPolicy {
+    /// `min_version` is `1.2` or `1.3`. Suites are named as in rustls, e.g.
+    /// `TLS13_AES_256_GCM_SHA384`; an empty list allows all of them. Fails
+    /// unless a TLS 1.3 suite remains, since QUIC handshakes are TLS 1.3 only.
+    pub fn new(min_version: &str, cipher_suites: &[String]) -> Result<Self, String> {
+        let versions: &'static [&'static SupportedProtocolVersion] = match min_version {
+            "1.2" => TLS12_AND_UP,
+            "1.3" => TLS13_ONLY,
+            other => {
+                return Err(format!(
+                    "unsupported TLS version '{}', expected 1.2 or 1.3",
+                    other
+                ))
+            }
+        };
+
+        let mut provider = ring::default_provider();
+        if !cipher_suites.is_empty() {
+            let allowed = |suite: &SupportedCipherSuite| {
+                cipher_suites
+                    .iter()
+                    .any(|name| suite_name(suite).eq_ignore_ascii_case(name))
+            };
+            if let Some(unknown) = cipher_suites.iter().find(|name| {
+                !provider
+                    .cipher_suites
+                    .iter()
+                    .any(|suite| suite_name(suite).eq_ignore_ascii_case(name))
+            }) {
+                return Err(format!("unknown cipher suite '{}'", unknown));
+            }
+            provider.cipher_suites.retain(allowed);
+        }
+        provider.cipher_suites.retain(|suite| {
+            versions
+                .iter()
+                .any(|v| v.version == suite.version().version)
+        });
+        if !provider
+            .cipher_suites
+            .iter()
+            .any(|suite| suite.tls13().is_some())
+        {
+            return Err("no TLS 1.3 cipher suite is allowed, but QUIC requires one".to_string());
+        }
+
+        Ok(Self {
+            provider: Arc::new(provider),
+            versions,
+        })
+    }
+
+    pub fn server_builder(&self) -> ConfigBuilder<ServerConfig, WantsVerifier> {
+        ServerConfig::builder_with_provider(self.provider.clone())
+            .with_protocol_versions(self.versions)
+            .expect("suites are checked in TlsPolicy::new")
+    }
+
+    pub fn client_builder(&self) -> ConfigBuilder<ClientConfig, WantsVerifier> {
+        ClientConfig::builder_with_provider(self.provider.clone())
+            .with_protocol_versions(self.versions)
+            .expect("suites are checked in TlsPolicy::new")
+    }
+}
+
+impl Default for TlsPolicy {
+    fn default() -> Self {
+        Self::new(DEFAULT_MIN_VERSION, &[]).expect("defaults are valid")
+    }
+}
+
+/// QUIC protects Initial packets with `TLS13_AES_128_GCM_SHA256` whatever the
+/// negotiated suite, so endpoints need it even when the allowlist leaves it out.
+pub fn quic_initial_suite() -> rustls::quic::Suite {
+    ring::cipher_suite::TLS13_AES_128_GCM_SHA256
+        .tls13()
+        .and_then(|suite| suite.quic_suite())
+        .expect("ring provides TLS13_AES_128_GCM_SHA256")
+}
+
+fn suite_name(suite: &SupportedCipherSuite) -> String {
+    format!("{:?}", suite.suite())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suites(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_policy_validation() {
+        assert!(TlsPolicy::new("1.2", &[]).is_ok());
+        assert!(TlsPolicy::new("1.3", &suites(&["tls13_aes_256_gcm_sha384"])).is_ok());
+        assert!(TlsPolicy::new("1.1", &[]).is_err());
+        assert!(TlsPolicy::new("1.2", &suites(&["TLS13_NOT_A_SUITE"])).is_err());
+        assert!(
+            TlsPolicy::new("1.2", &suites(&["TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256"])).is_err()
+        );
+    }
+
+    #[test]
+    fn test_policy_limits_suites_and_versions() {
+        let policy = TlsPolicy::new(
+            "1.3",
+            &suites(&[
+                "TLS13_CHACHA20_POLY1305_SHA256",
+                "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256",
+            ]),
+        )
+        .unwrap();
+        let names: Vec<String> = policy
+            .provider
+            .cipher_suites
+            .iter()
+            .map(suite_name)
+            .collect();
+        assert_eq!(names, ["TLS13_CHACHA20_POLY1305_SHA256"]);
+        assert_eq!(policy.versions.len(), 1);
+    }
+}
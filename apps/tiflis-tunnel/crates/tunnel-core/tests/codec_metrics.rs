@@ -0,0 +1,55 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+// The codec counters are process-wide, so this test has a binary of its own
+// to keep other codec tests from moving them.
+
+use tunnel_core::codec::{self, CodecStats};
+use tunnel_core::{Message, PingMessage};
+use uuid::Uuid;
+
+#[test]
+fn test_counters_follow_known_sequence() {
+    let ping = Message::Ping(PingMessage { timestamp: 1 });
+    let frame = codec::encode_message(&ping).unwrap();
+
+    // Nothing is recorded until metrics are enabled.
+    assert!(codec::metrics().is_none());
+
+    let metrics = codec::enable_metrics();
+    assert_eq!(metrics.snapshot(), CodecStats::default());
+
+    let big = Message::Ping(PingMessage {
+        timestamp: u64::MAX,
+    });
+    let big_frame = codec::encode_message(&big).unwrap();
+    codec::encode_message(&ping).unwrap();
+    codec::decode_message(&frame).unwrap();
+    assert!(codec::decode_message(&[0, 0, 0, 2, b'{', b'{']).is_err());
+
+    let stream_id = Uuid::new_v4();
+    assert_eq!(
+        codec::ws_data_frames(stream_id, &[0; 10], true, 4).count(),
+        3
+    );
+    assert_eq!(
+        codec::ws_data_frames(stream_id, &[0; 4], true, 4).count(),
+        1
+    );
+
+    let (small, big) = (frame.len() as u64, big_frame.len() as u64);
+    assert_eq!(
+        metrics.snapshot(),
+        CodecStats {
+            frames_encoded: 2,
+            bytes_encoded: small + big,
+            largest_encoded: big,
+            frames_decoded: 1,
+            bytes_decoded: small,
+            largest_decoded: small,
+            fragmented_messages: 1,
+            fragments: 3,
+        }
+    );
+    assert!(std::ptr::eq(metrics, codec::enable_metrics()));
+}
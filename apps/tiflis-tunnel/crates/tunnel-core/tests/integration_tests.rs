@@ -9,6 +9,14 @@ async fn test_message_serialization() {
     let msg = Message::Register(RegisterMessage {
         api_key: "test-key".to_string(),
         workstation_id: "test-ws".to_string(),
+        max_frame_bytes: None,
+        ws_open_ack: false,
+        expect_continue: false,
+        ws_fragments: false,
+        ordered_headers: false,
+        ws_passthrough: false,
+        metadata: std::collections::HashMap::new(),
+        tags: vec!["env=staging".to_string()],
     });
 
     let encoded = codec::encode_message(&msg).unwrap();
@@ -19,6 +27,7 @@ async fn test_message_serialization() {
         Message::Register(reg) => {
             assert_eq!(reg.api_key, "test-key");
             assert_eq!(reg.workstation_id, "test-ws");
+            assert_eq!(reg.tags, ["env=staging"]);
         }
         _ => panic!("Expected Register message"),
     }
@@ -37,7 +46,12 @@ async fn test_http_request_message() {
         method: "POST".to_string(),
         path: "/api/test".to_string(),
         headers,
+        header_list: Vec::new(),
         body: Some(body_base64.clone()),
+        expect_continue: false,
+        informational: false,
+        public_scheme: None,
+        public_host: None,
     });
 
     let encoded = codec::encode_message(&msg).unwrap();
@@ -55,6 +69,53 @@ async fn test_http_request_message() {
     }
 }
 
+#[tokio::test]
+async fn test_http_request_header_list_keeps_order() {
+    let header_list: Vec<(String, String)> = [
+        ("x-amz-date", "20260101T000000Z"),
+        ("authorization", "AWS4-HMAC-SHA256 Credential=..."),
+        ("x-amz-content-sha256", "UNSIGNED-PAYLOAD"),
+        ("accept", "text/html"),
+        ("accept", "application/json"),
+    ]
+    .iter()
+    .map(|(name, value)| (name.to_string(), value.to_string()))
+    .collect();
+
+    let msg = Message::HttpRequest(HttpRequestMessage {
+        stream_id: uuid::Uuid::new_v4(),
+        method: "GET".to_string(),
+        path: "/bucket/key".to_string(),
+        headers: std::collections::HashMap::new(),
+        header_list: header_list.clone(),
+        body: None,
+        expect_continue: false,
+        informational: false,
+        public_scheme: None,
+        public_host: None,
+    });
+
+    let encoded = codec::encode_message(&msg).unwrap();
+    match codec::decode_message(&encoded).unwrap().0 {
+        Message::HttpRequest(req) => {
+            assert_eq!(req.header_list, header_list);
+            let names: Vec<&str> = req.header_pairs().iter().map(|(name, _)| *name).collect();
+            assert_eq!(
+                names,
+                [
+                    "x-amz-date",
+                    "authorization",
+                    "x-amz-content-sha256",
+                    "accept",
+                    "accept"
+                ]
+            );
+            assert_eq!(req.header_map()["accept"], "text/html, application/json");
+        }
+        _ => panic!("Expected HttpRequest message"),
+    }
+}
+
 #[tokio::test]
 async fn test_large_message() {
     let large_body = vec![0u8; 1_000_000];
@@ -65,7 +126,12 @@ async fn test_large_message() {
         method: "POST".to_string(),
         path: "/upload".to_string(),
         headers: std::collections::HashMap::new(),
+        header_list: Vec::new(),
         body: Some(body_base64),
+        expect_continue: false,
+        informational: false,
+        public_scheme: None,
+        public_host: None,
     });
 
     let encoded = codec::encode_message(&msg).unwrap();
@@ -98,3 +164,44 @@ async fn test_base64_encoding() {
     let decoded_binary = codec::decode_body(&encoded_binary).unwrap();
     assert_eq!(decoded_binary, binary_data);
 }
+
+#[test]
+fn test_negotiate_max_frame_bytes() {
+    use tunnel_core::quic::{negotiate_max_frame_bytes, LEGACY_MAX_FRAME_BYTES};
+
+    assert_eq!(
+        negotiate_max_frame_bytes(50_000_000, Some(20_000_000)),
+        20_000_000
+    );
+    assert_eq!(
+        negotiate_max_frame_bytes(5_000_000, Some(20_000_000)),
+        5_000_000
+    );
+    assert_eq!(
+        negotiate_max_frame_bytes(50_000_000, None),
+        LEGACY_MAX_FRAME_BYTES
+    );
+}
+
+#[test]
+fn test_close_reason_from_connection_error() {
+    use tunnel_core::quic::CloseReason;
+
+    let closed = quinn::ConnectionError::ApplicationClosed(quinn::ApplicationClose {
+        error_code: quinn::VarInt::from_u32(0x4001),
+        reason: "api key revoked".into(),
+    });
+    let reason = CloseReason::from(&closed);
+    assert_eq!(
+        reason.to_string(),
+        "closed by peer with code 0x4001: api key revoked"
+    );
+    assert!(reason.is_retryable());
+
+    let mismatch = CloseReason::from(&quinn::ConnectionError::VersionMismatch);
+    assert!(!mismatch.is_retryable());
+    assert_eq!(
+        CloseReason::from(&quinn::ConnectionError::TimedOut),
+        CloseReason::TimedOut
+    );
+}
@@ -0,0 +1,251 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+use crate::config::AccessLogConfig;
+use crate::proxy::proxy_auth_workstation;
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::Method,
+    middleware::Next,
+    response::Response,
+};
+use serde::Serialize;
+use std::ffi::OsString;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc;
+use tracing::{debug, error, warn};
+
+/// Entries buffered between request handlers and the writer task. Entries
+/// beyond this are dropped rather than slowing requests down.
+const ACCESS_LOG_CHANNEL_CAPACITY: usize = 4096;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AccessLogEntry {
+    /// Unix milliseconds when the request arrived.
+    pub ts: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote: Option<String>,
+    pub workstation: String,
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    /// Time until the response headers were ready.
+    pub duration_ms: u64,
+}
+
+/// Handle used by request handlers to queue access log entries.
+#[derive(Clone)]
+pub struct AccessLog {
+    tx: mpsc::Sender<AccessLogEntry>,
+    /// `server.base_path`, skipped when finding the workstation in a path.
+    base_path: String,
+}
+
+impl AccessLog {
+    /// Returns `None` when no access log path is configured. The writer must be
+    /// spawned for entries to reach the file.
+    pub fn from_config(config: &AccessLogConfig) -> Option<(Self, AccessLogWriter)> {
+        let path = config.path.clone()?;
+        let (tx, rx) = mpsc::channel(ACCESS_LOG_CHANNEL_CAPACITY);
+        let writer = AccessLogWriter {
+            rx,
+            path,
+            max_bytes: config.max_bytes,
+            rotate_interval: match config.rotate_interval {
+                0 => None,
+                secs => Some(Duration::from_secs(secs)),
+            },
+            max_files: config.max_files,
+        };
+        Some((
+            Self {
+                tx,
+                base_path: String::new(),
+            },
+            writer,
+        ))
+    }
+
+    /// Sets the prefix the `/t/` and `/ws/` routes are mounted under.
+    pub fn with_base_path(mut self, base_path: &str) -> Self {
+        self.base_path = base_path.to_string();
+        self
+    }
+
+    pub fn record(&self, entry: AccessLogEntry) {
+        if self.tx.try_send(entry).is_err() {
+            debug!("Access log writer is behind, dropping an entry");
+        }
+    }
+}
+
+/// Background task that appends queued entries to the log file.
+pub struct AccessLogWriter {
+    rx: mpsc::Receiver<AccessLogEntry>,
+    path: PathBuf,
+    max_bytes: u64,
+    rotate_interval: Option<Duration>,
+    max_files: usize,
+}
+
+impl AccessLogWriter {
+    /// Runs until every `AccessLog` handle is dropped. Entries that arrive
+    /// together are written as one batch and flushed once.
+    pub async fn run(mut self) {
+        let mut file = match RotatingFile::open(&self).await {
+            Ok(file) => file,
+            Err(e) => {
+                error!("Cannot open access log {}: {}", self.path.display(), e);
+                return;
+            }
+        };
+
+        while let Some(entry) = self.rx.recv().await {
+            let mut batch = vec![entry];
+            while let Ok(entry) = self.rx.try_recv() {
+                batch.push(entry);
+            }
+            for entry in batch {
+                let Ok(mut line) = serde_json::to_vec(&entry) else {
+                    continue;
+                };
+                line.push(b'\n');
+                if let Err(e) = file.write_line(&line).await {
+                    warn!("Failed to write access log: {}", e);
+                }
+            }
+            if let Err(e) = file.writer.flush().await {
+                warn!("Failed to flush access log: {}", e);
+            }
+        }
+    }
+}
+
+struct RotatingFile {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    size: u64,
+    opened_at: Instant,
+    max_bytes: u64,
+    rotate_interval: Option<Duration>,
+    max_files: usize,
+}
+
+impl RotatingFile {
+    async fn open(config: &AccessLogWriter) -> io::Result<Self> {
+        let (writer, size) = open_append(&config.path).await?;
+        Ok(Self {
+            path: config.path.clone(),
+            writer,
+            size,
+            opened_at: Instant::now(),
+            max_bytes: config.max_bytes,
+            rotate_interval: config.rotate_interval,
+            max_files: config.max_files,
+        })
+    }
+
+    async fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        let too_big = self.max_bytes > 0 && self.size + line.len() as u64 > self.max_bytes;
+        let too_old = self
+            .rotate_interval
+            .is_some_and(|interval| self.opened_at.elapsed() >= interval);
+        if self.size > 0 && (too_big || too_old) {
+            self.rotate().await?;
+        }
+        self.writer.write_all(line).await?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Shifts `<path>.N` to `<path>.N+1`, dropping the oldest, moves the
+    /// current file to `<path>.1` and starts a new one.
+    async fn rotate(&mut self) -> io::Result<()> {
+        self.writer.flush().await?;
+        if self.max_files == 0 {
+            fs::remove_file(&self.path).await?;
+        } else {
+            for n in (1..self.max_files).rev() {
+                match fs::rename(rotated_path(&self.path, n), rotated_path(&self.path, n + 1)).await
+                {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            fs::rename(&self.path, rotated_path(&self.path, 1)).await?;
+        }
+        let (writer, size) = open_append(&self.path).await?;
+        self.writer = writer;
+        self.size = size;
+        self.opened_at = Instant::now();
+        Ok(())
+    }
+}
+
+async fn open_append(path: &Path) -> io::Result<(BufWriter<File>, u64)> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    let size = file.metadata().await?.len();
+    Ok((BufWriter::new(file), size))
+}
+
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+/// The workstation a request is aimed at: the id in a `/t/` or `/ws/` path
+/// under `base_path`, or the proxy user name of a `CONNECT`.
+fn target_workstation(request: &Request, base_path: &str) -> Option<String> {
+    if request.method() == Method::CONNECT {
+        return proxy_auth_workstation(request.headers());
+    }
+    let path = request.uri().path().strip_prefix(base_path)?;
+    let mut segments = path.strip_prefix('/')?.split('/');
+    match (segments.next(), segments.next()) {
+        (Some("t" | "ws"), Some(id)) if !id.is_empty() => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+/// Records every request routed to a workstation. Other requests, such as
+/// health checks, are not logged.
+pub async fn access_log(State(log): State<AccessLog>, request: Request, next: Next) -> Response {
+    let Some(workstation) = target_workstation(&request, &log.base_path) else {
+        return next.run(request).await;
+    };
+    let method = request.method().to_string();
+    let path = match request.uri().authority() {
+        Some(authority) if request.method() == Method::CONNECT => authority.to_string(),
+        _ => request.uri().path().to_string(),
+    };
+    let remote = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.to_string());
+    let ts = tunnel_core::unix_millis();
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+
+    log.record(AccessLogEntry {
+        ts,
+        remote,
+        workstation,
+        method,
+        path,
+        status: response.status().as_u16(),
+        duration_ms: started.elapsed().as_millis() as u64,
+    });
+    response
+}
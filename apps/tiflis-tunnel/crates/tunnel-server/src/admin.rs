@@ -0,0 +1,296 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+use crate::auth::Authenticator;
+use crate::circuit_breaker::BreakerState;
+use crate::pending::PendingRequests;
+use crate::registry::{tokens_match, BackendHealth, WorkstationRegistry, WorkstationState};
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+use tunnel_core::codec::CodecStats;
+use tunnel_core::quic::ConnectionQuality;
+use uuid::Uuid;
+
+/// Operator endpoints under `/admin`, authenticated with the server API key
+/// as a bearer token.
+pub struct AdminState {
+    pub registry: Arc<WorkstationRegistry>,
+    pub pending: Arc<PendingRequests>,
+    /// Set once the QUIC listener is up.
+    pub quic_endpoint: Arc<Mutex<Option<quinn::Endpoint>>>,
+    /// Current `auth.api_key`, updated on reload.
+    pub api_key: watch::Receiver<String>,
+    /// Re-checks connected workstations for `/admin/revalidate`.
+    pub authenticator: Arc<dyn Authenticator>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WorkstationStatus {
+    pub id: String,
+    pub state: &'static str,
+    pub connected_secs: u64,
+    pub active_streams: usize,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub quality: ConnectionQuality,
+    pub circuit: BreakerState,
+    /// Backend health as last reported by the client.
+    pub backend: BackendHealth,
+    /// Clients waiting to take over if the active connection fails.
+    pub standbys: usize,
+    /// New requests are refused with `503` until the workstation is undrained.
+    pub draining: bool,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+/// Filters for `/admin/workstations`.
+#[derive(Debug, Default, Deserialize)]
+pub struct WorkstationQuery {
+    /// Comma-separated tags a workstation must all carry to be listed.
+    #[serde(default)]
+    pub tags: Option<String>,
+}
+
+/// Point-in-time view of the server internals for diagnosing a stuck server.
+#[derive(Debug, Serialize)]
+pub struct DebugSnapshot {
+    pub workstations: Vec<DebugWorkstation>,
+    pub pending: Vec<DebugPending>,
+    /// Open QUIC connections, including ones not yet registered.
+    pub quic_connections: Option<usize>,
+    pub runtime: RuntimeStats,
+    /// Frames encoded and decoded by this process.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub codec: Option<CodecStats>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DebugWorkstation {
+    pub id: String,
+    pub state: &'static str,
+    pub registered_secs: u64,
+    pub last_seen_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reconnecting_secs: Option<u64>,
+    pub active_streams: usize,
+    pub max_frame_bytes: usize,
+    pub circuit: BreakerState,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DebugPending {
+    pub stream_id: Uuid,
+    pub age_ms: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RuntimeStats {
+    pub workers: usize,
+    pub alive_tasks: usize,
+    pub global_queue_depth: usize,
+}
+
+pub fn router(state: Arc<AdminState>) -> Router {
+    Router::new()
+        .route("/admin/workstations", get(list_workstations))
+        .route(
+            "/admin/workstations/:id/drain",
+            post(drain_workstation).delete(undrain_workstation),
+        )
+        .route("/admin/workstations/:id/traffic/reset", post(reset_traffic))
+        .route("/admin/revalidate", post(revalidate))
+        .route("/admin/debug", get(debug_snapshot))
+        .with_state(state)
+}
+
+fn authorize(state: &AdminState, headers: &HeaderMap) -> Result<(), StatusCode> {
+    let token = headers
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    match token {
+        Some(token) if tokens_match(token, &state.api_key.borrow()) => Ok(()),
+        _ => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
+async fn list_workstations(
+    State(state): State<Arc<AdminState>>,
+    Query(query): Query<WorkstationQuery>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<WorkstationStatus>>, StatusCode> {
+    authorize(&state, &headers)?;
+
+    let tags: Vec<String> = query
+        .tags
+        .iter()
+        .flat_map(|tags| tags.split(','))
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(String::from)
+        .collect();
+    let mut workstations: Vec<WorkstationStatus> = state
+        .registry
+        .list()
+        .await
+        .into_iter()
+        .filter(|info| info.has_tags(&tags))
+        .map(|info| WorkstationStatus {
+            state: match info.state {
+                WorkstationState::Active => "active",
+                WorkstationState::Reconnecting { .. } => "reconnecting",
+            },
+            connected_secs: info.registered_at.elapsed().as_secs(),
+            active_streams: info.active_streams(),
+            bytes_in: info.traffic.bytes_in(),
+            bytes_out: info.traffic.bytes_out(),
+            quality: ConnectionQuality::from_connection(&info.connection),
+            circuit: info.breaker.state(),
+            backend: info.backend_health(),
+            standbys: info.standbys.len(),
+            draining: info.draining,
+            metadata: info.metadata,
+            tags: info.tags,
+            id: info.id,
+        })
+        .collect();
+    workstations.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(Json(workstations))
+}
+
+/// Stops sending new requests to the workstation; streams in flight finish.
+async fn drain_workstation(
+    State(state): State<Arc<AdminState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> StatusCode {
+    set_draining(&state, &id, &headers, true).await
+}
+
+async fn undrain_workstation(
+    State(state): State<Arc<AdminState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> StatusCode {
+    set_draining(&state, &id, &headers, false).await
+}
+
+async fn set_draining(
+    state: &AdminState,
+    id: &str,
+    headers: &HeaderMap,
+    draining: bool,
+) -> StatusCode {
+    if let Err(status) = authorize(state, headers) {
+        return status;
+    }
+    if state.registry.set_draining(id, draining).await {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+/// Zeroes the workstation's `bytes_in` and `bytes_out` counters.
+async fn reset_traffic(
+    State(state): State<Arc<AdminState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> StatusCode {
+    if let Err(status) = authorize(&state, &headers) {
+        return status;
+    }
+    if state.registry.reset_traffic(&id).await {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+/// Disconnects workstations whose credential is no longer accepted and
+/// lists their ids.
+async fn revalidate(
+    State(state): State<Arc<AdminState>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<String>>, StatusCode> {
+    authorize(&state, &headers)?;
+    Ok(Json(
+        state
+            .registry
+            .revalidate(state.authenticator.as_ref())
+            .await,
+    ))
+}
+
+async fn debug_snapshot(
+    State(state): State<Arc<AdminState>>,
+    headers: HeaderMap,
+) -> Result<Json<DebugSnapshot>, StatusCode> {
+    authorize(&state, &headers)?;
+
+    let mut workstations: Vec<DebugWorkstation> = state
+        .registry
+        .list()
+        .await
+        .into_iter()
+        .map(|info| {
+            let (state, reconnecting_secs) = match info.state {
+                WorkstationState::Active => ("active", None),
+                WorkstationState::Reconnecting { since } => {
+                    ("reconnecting", Some(since.elapsed().as_secs()))
+                }
+            };
+            DebugWorkstation {
+                state,
+                registered_secs: info.registered_at.elapsed().as_secs(),
+                last_seen_ms: info.last_seen().elapsed().as_millis() as u64,
+                reconnecting_secs,
+                active_streams: info.active_streams(),
+                max_frame_bytes: info.max_frame_bytes,
+                circuit: info.breaker.state(),
+                id: info.id,
+            }
+        })
+        .collect();
+    workstations.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let pending = state
+        .pending
+        .snapshot()
+        .await
+        .into_iter()
+        .map(|(stream_id, age)| DebugPending {
+            stream_id,
+            age_ms: age.as_millis() as u64,
+        })
+        .collect();
+
+    let metrics = tokio::runtime::Handle::current().metrics();
+    Ok(Json(DebugSnapshot {
+        workstations,
+        pending,
+        quic_connections: state
+            .quic_endpoint
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|e| e.open_connections()),
+        runtime: RuntimeStats {
+            workers: metrics.num_workers(),
+            alive_tasks: metrics.num_alive_tasks(),
+            global_queue_depth: metrics.global_queue_depth(),
+        },
+        codec: tunnel_core::codec::metrics().map(|m| m.snapshot()),
+    }))
+}
@@ -0,0 +1,55 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+use crate::config::AuthConfig;
+use async_trait::async_trait;
+use std::sync::RwLock;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AuthError {
+    #[error("Invalid API key")]
+    InvalidCredential,
+
+    #[error("{0}")]
+    Rejected(String),
+}
+
+/// Decides whether a workstation may register or reconnect with the credential
+/// it presented. Embedders can supply their own, e.g. to verify signed tokens.
+#[async_trait]
+pub trait Authenticator: Send + Sync {
+    async fn authenticate(&self, workstation_id: &str, credential: &str) -> Result<(), AuthError>;
+
+    /// Picks up a reloaded `[auth]` section. Does nothing by default.
+    fn reload(&self, _auth: &AuthConfig) {}
+}
+
+/// Accepts any workstation presenting the configured shared API key.
+pub struct StaticKeyAuthenticator {
+    /// Replaced when `auth.api_key` is reloaded.
+    api_key: RwLock<String>,
+}
+
+impl StaticKeyAuthenticator {
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            api_key: RwLock::new(api_key.into()),
+        }
+    }
+}
+
+#[async_trait]
+impl Authenticator for StaticKeyAuthenticator {
+    async fn authenticate(&self, _workstation_id: &str, credential: &str) -> Result<(), AuthError> {
+        if credential == *self.api_key.read().unwrap() {
+            Ok(())
+        } else {
+            Err(AuthError::InvalidCredential)
+        }
+    }
+
+    fn reload(&self, auth: &AuthConfig) {
+        *self.api_key.write().unwrap() = auth.api_key.clone();
+    }
+}
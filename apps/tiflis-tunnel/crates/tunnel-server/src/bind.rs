@@ -0,0 +1,48 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+//! Listener sockets for the configured bind addresses. The IPv6 wildcard
+//! `::` is made dual-stack where the OS allows it, so one listener also
+//! accepts IPv4 clients.
+
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
+use std::net::SocketAddr;
+use tracing::warn;
+
+/// Backlog of pending TCP connections, as tokio's own `bind` uses.
+const LISTEN_BACKLOG: i32 = 1024;
+
+pub fn tcp_listener(addr: SocketAddr) -> std::io::Result<tokio::net::TcpListener> {
+    let socket = socket(addr, Type::STREAM, Protocol::TCP)?;
+    #[cfg(not(windows))]
+    socket.set_reuse_address(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(LISTEN_BACKLOG)?;
+    tokio::net::TcpListener::from_std(socket.into())
+}
+
+pub fn udp_socket(addr: SocketAddr) -> std::io::Result<std::net::UdpSocket> {
+    let socket = socket(addr, Type::DGRAM, Protocol::UDP)?;
+    socket.bind(&addr.into())?;
+    Ok(socket.into())
+}
+
+fn socket(addr: SocketAddr, ty: Type, protocol: Protocol) -> std::io::Result<Socket> {
+    let socket = Socket::new(Domain::for_address(addr), ty, Some(protocol))?;
+    if addr.is_ipv6() && addr.ip().is_unspecified() {
+        if let Err(e) = socket.set_only_v6(false) {
+            warn!("{} stays IPv6-only: {}", addr, e);
+        }
+    }
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+}
+
+/// Address families a bound socket accepts, for the "listening on" logs.
+pub fn families(socket: SockRef<'_>) -> &'static str {
+    match socket.local_addr().ok().and_then(|addr| addr.as_socket()) {
+        Some(SocketAddr::V6(_)) if socket.only_v6().ok() == Some(false) => "IPv4 and IPv6",
+        Some(SocketAddr::V6(_)) => "IPv6",
+        _ => "IPv4",
+    }
+}
@@ -0,0 +1,329 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+use crate::config::CacheConfig;
+use axum::{
+    body::Bytes,
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Largest body kept in the cache; bigger responses are always forwarded.
+const MAX_ENTRY_BYTES: usize = 1 << 20;
+
+/// Edge cache for tunneled `GET` responses, built from `server.cache`.
+pub struct ResponseCache {
+    max_entries: usize,
+    max_ttl: Duration,
+    /// Request headers whose values are part of the key.
+    vary_headers: Vec<HeaderName>,
+    entries: Mutex<HashMap<String, CachedResponse>>,
+}
+
+/// A response served from the cache, with `Age` set.
+pub struct CacheHit {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+#[derive(Clone)]
+struct CachedResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+    stored_at: Instant,
+    expires_at: Instant,
+}
+
+impl ResponseCache {
+    /// Returns `None` when caching is disabled.
+    pub fn from_config(config: &CacheConfig) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+        Some(Self {
+            max_entries: config.max_entries,
+            max_ttl: Duration::from_secs(config.max_ttl),
+            vary_headers: config
+                .vary_headers
+                .iter()
+                .filter_map(|name| HeaderName::from_bytes(name.as_bytes()).ok())
+                .collect(),
+            entries: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Cache key for a request, or `None` when it must go to the workstation:
+    /// anything but `GET`, requests with credentials and requests asking for
+    /// a fresh answer with `no-cache` or `no-store`.
+    pub fn key(
+        &self,
+        workstation_id: &str,
+        method: &Method,
+        path: &str,
+        headers: &HeaderMap,
+    ) -> Option<String> {
+        if method != Method::GET || headers.contains_key(header::AUTHORIZATION) {
+            return None;
+        }
+        let directives = cache_control(headers);
+        if directives.contains_key("no-cache") || directives.contains_key("no-store") {
+            return None;
+        }
+        let mut key = format!("{} {} {}", workstation_id, method, path);
+        for name in &self.vary_headers {
+            let values: Vec<&str> = headers
+                .get_all(name)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .collect();
+            key.push_str(&format!("\n{}: {}", name, values.join(",")));
+        }
+        Some(key)
+    }
+
+    /// Serves a fresh entry. A request whose `If-None-Match` names the
+    /// entry's `ETag` gets `304 Not Modified` without a body.
+    pub fn get(&self, key: &str, request_headers: &HeaderMap) -> Option<CacheHit> {
+        let now = Instant::now();
+        let entry = {
+            let mut entries = self.entries.lock().unwrap();
+            match entries.get(key) {
+                Some(entry) if entry.expires_at > now => entry.clone(),
+                Some(_) => {
+                    entries.remove(key);
+                    return None;
+                }
+                None => return None,
+            }
+        };
+
+        let mut headers = entry.headers;
+        let age = now.duration_since(entry.stored_at).as_secs();
+        headers.insert(header::AGE, HeaderValue::from(age));
+        let not_modified = headers
+            .get(header::ETAG)
+            .is_some_and(|etag| etag_matches(request_headers, etag));
+        if not_modified {
+            headers.remove(header::CONTENT_LENGTH);
+            return Some(CacheHit {
+                status: StatusCode::NOT_MODIFIED,
+                headers,
+                body: Bytes::new(),
+            });
+        }
+        Some(CacheHit {
+            status: entry.status,
+            headers,
+            body: entry.body,
+        })
+    }
+
+    /// Stores a `200` response whose `Cache-Control` allows shared caching for
+    /// a positive lifetime, capped at `max_ttl`.
+    pub fn store(&self, key: String, status: StatusCode, headers: &HeaderMap, body: &[u8]) {
+        if status != StatusCode::OK || body.len() > MAX_ENTRY_BYTES {
+            return;
+        }
+        let Some(ttl) = self.ttl(headers) else {
+            return;
+        };
+
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.max_entries && !entries.contains_key(&key) {
+            entries.retain(|_, entry| entry.expires_at > now);
+            if entries.len() >= self.max_entries {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.expires_at)
+                    .map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    entries.remove(&oldest);
+                }
+            }
+        }
+        entries.insert(
+            key,
+            CachedResponse {
+                status,
+                headers: headers.clone(),
+                body: Bytes::copy_from_slice(body),
+                stored_at: now,
+                expires_at: now + ttl,
+            },
+        );
+    }
+
+    /// How long a response may be served from the cache, or `None` if it
+    /// must not be stored.
+    fn ttl(&self, headers: &HeaderMap) -> Option<Duration> {
+        if headers.contains_key(header::SET_COOKIE) {
+            return None;
+        }
+        let varies_on_unkeyed = headers
+            .get_all(header::VARY)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .any(|name| {
+                name == "*"
+                    || !self
+                        .vary_headers
+                        .iter()
+                        .any(|h| h.as_str().eq_ignore_ascii_case(name))
+            });
+        if varies_on_unkeyed {
+            return None;
+        }
+
+        let directives = cache_control(headers);
+        if ["no-store", "no-cache", "private"]
+            .iter()
+            .any(|d| directives.contains_key(*d))
+        {
+            return None;
+        }
+        let secs: u64 = directives
+            .get("s-maxage")
+            .or_else(|| directives.get("max-age"))
+            .and_then(|v| v.as_deref())
+            .and_then(|v| v.parse().ok())?;
+        (secs > 0).then(|| Duration::from_secs(secs).min(self.max_ttl))
+    }
+}
+
+/// Lowercased `Cache-Control` directives with their unquoted values.
+fn cache_control(headers: &HeaderMap) -> HashMap<String, Option<String>> {
+    headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|directive| {
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name, Some(value.trim().trim_matches('"').to_string())),
+                None => (directive, None),
+            };
+            let name = name.trim().to_ascii_lowercase();
+            (!name.is_empty()).then_some((name, value))
+        })
+        .collect()
+}
+
+fn etag_matches(request_headers: &HeaderMap, etag: &HeaderValue) -> bool {
+    let Ok(etag) = etag.to_str() else {
+        return false;
+    };
+    let strip_weak = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let etag = strip_weak(etag);
+    request_headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|tag| tag.trim() == "*" || strip_weak(tag) == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(vary_headers: &[&str]) -> ResponseCache {
+        ResponseCache::from_config(&CacheConfig {
+            enabled: true,
+            max_entries: 2,
+            max_ttl: 60,
+            vary_headers: vary_headers.iter().map(|h| h.to_string()).collect(),
+        })
+        .unwrap()
+    }
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| {
+                (
+                    HeaderName::from_static(name),
+                    HeaderValue::from_static(value),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_ttl_follows_cache_control() {
+        let cache = cache(&[]);
+        let ttl = |pairs| cache.ttl(&headers(pairs));
+
+        assert_eq!(
+            ttl(&[("cache-control", "public, max-age=30")]),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            ttl(&[("cache-control", "max-age=30, s-maxage=\"10\"")]),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            ttl(&[("cache-control", "max-age=3600")]),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(ttl(&[("cache-control", "no-store, max-age=30")]), None);
+        assert_eq!(ttl(&[("cache-control", "private, max-age=30")]), None);
+        assert_eq!(ttl(&[("cache-control", "max-age=0")]), None);
+        assert_eq!(ttl(&[]), None);
+    }
+
+    #[test]
+    fn test_vary_must_be_keyed() {
+        let keyed = cache(&["accept-language"]);
+        let vary = headers(&[("cache-control", "max-age=30"), ("vary", "Accept-Language")]);
+        assert!(keyed.ttl(&vary).is_some());
+        assert!(cache(&[]).ttl(&vary).is_none());
+
+        let english = headers(&[("accept-language", "en")]);
+        let german = headers(&[("accept-language", "de")]);
+        assert_ne!(
+            keyed.key("ws", &Method::GET, "/", &english),
+            keyed.key("ws", &Method::GET, "/", &german)
+        );
+    }
+
+    #[test]
+    fn test_full_cache_evicts_soonest_expiry() {
+        let cache = cache(&[]);
+        let short = headers(&[("cache-control", "max-age=5")]);
+        let long = headers(&[("cache-control", "max-age=50")]);
+        cache.store("a".into(), StatusCode::OK, &short, b"a");
+        cache.store("b".into(), StatusCode::OK, &long, b"b");
+        cache.store("c".into(), StatusCode::OK, &long, b"c");
+
+        let none = HeaderMap::new();
+        assert!(cache.get("a", &none).is_none());
+        assert!(cache.get("b", &none).is_some());
+        assert!(cache.get("c", &none).is_some());
+    }
+
+    #[test]
+    fn test_matching_etag_gets_not_modified() {
+        let cache = cache(&[]);
+        let response = headers(&[("cache-control", "max-age=30"), ("etag", "\"v1\"")]);
+        cache.store("k".into(), StatusCode::OK, &response, b"body");
+
+        let hit = cache
+            .get("k", &headers(&[("if-none-match", "W/\"v1\"")]))
+            .unwrap();
+        assert_eq!(hit.status, StatusCode::NOT_MODIFIED);
+        assert!(hit.body.is_empty());
+        let hit = cache
+            .get("k", &headers(&[("if-none-match", "\"v0\"")]))
+            .unwrap();
+        assert_eq!(hit.status, StatusCode::OK);
+        assert_eq!(hit.body, "body");
+    }
+}
@@ -0,0 +1,63 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+use async_trait::async_trait;
+use std::path::PathBuf;
+
+/// PEM-encoded certificate chain and private key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertificateBundle {
+    pub cert_pem: String,
+    pub key_pem: String,
+}
+
+/// Where the ACME task keeps the certificate and where the HTTPS and QUIC
+/// listeners load it from. Replicas that cannot share a volume can supply
+/// their own, e.g. backed by S3 or a KV store.
+#[async_trait]
+pub trait CertStore: Send + Sync {
+    /// Returns the stored certificate, or `None` if none has been stored yet.
+    async fn load(&self) -> anyhow::Result<Option<CertificateBundle>>;
+
+    async fn store(&self, bundle: &CertificateBundle) -> anyhow::Result<()>;
+}
+
+/// Keeps `cert.pem` and `key.pem` in a local directory (`tls.certs_dir`).
+pub struct FileCertStore {
+    dir: PathBuf,
+}
+
+impl FileCertStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn cert_path(&self) -> PathBuf {
+        self.dir.join("cert.pem")
+    }
+
+    fn key_path(&self) -> PathBuf {
+        self.dir.join("key.pem")
+    }
+}
+
+#[async_trait]
+impl CertStore for FileCertStore {
+    async fn load(&self) -> anyhow::Result<Option<CertificateBundle>> {
+        let (cert_path, key_path) = (self.cert_path(), self.key_path());
+        if !cert_path.exists() || !key_path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(CertificateBundle {
+            cert_pem: tokio::fs::read_to_string(&cert_path).await?,
+            key_pem: tokio::fs::read_to_string(&key_path).await?,
+        }))
+    }
+
+    async fn store(&self, bundle: &CertificateBundle) -> anyhow::Result<()> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        tokio::fs::write(self.key_path(), &bundle.key_pem).await?;
+        tokio::fs::write(self.cert_path(), &bundle.cert_pem).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,210 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// When a workstation's circuit trips and how long it stays open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BreakerPolicy {
+    /// Consecutive failures that open the circuit. 0 disables the breaker.
+    pub failures: u32,
+    /// Failures further apart than this start a new count.
+    pub window: Duration,
+    /// How long requests are refused before a probe is let through.
+    pub cooldown: Duration,
+}
+
+impl BreakerPolicy {
+    pub const DISABLED: Self = Self {
+        failures: 0,
+        window: Duration::ZERO,
+        cooldown: Duration::ZERO,
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    Closed,
+    Open,
+    /// The cooldown is over and a single probe request is in flight.
+    HalfOpen,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    failures: u32,
+    first_failure: Option<Instant>,
+    open_until: Option<Instant>,
+    probe_started: Option<Instant>,
+}
+
+/// Refuses requests to a workstation whose backend keeps failing, so they
+/// fail fast instead of each opening a stream that ends in a 502 or timeout.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    policy: BreakerPolicy,
+    inner: Mutex<Inner>,
+}
+
+impl CircuitBreaker {
+    pub fn new(policy: BreakerPolicy) -> Self {
+        Self {
+            policy,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    pub fn check(&self) -> Result<(), Duration> {
+        self.check_at(Instant::now())
+    }
+
+    /// Admits a request, or returns how long until the next probe. Once the
+    /// cooldown is over one request at a time goes through as a probe; a probe
+    /// that never reports back is replaced after another cooldown.
+    pub fn check_at(&self, now: Instant) -> Result<(), Duration> {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(open_until) = inner.open_until else {
+            return Ok(());
+        };
+        if now < open_until {
+            return Err(open_until - now);
+        }
+        if let Some(started) = inner.probe_started {
+            let retry_at = started + self.policy.cooldown;
+            if now < retry_at {
+                return Err(retry_at - now);
+            }
+        }
+        inner.probe_started = Some(now);
+        Ok(())
+    }
+
+    /// Closes the circuit: the backend answered.
+    pub fn record_success(&self) {
+        *self.inner.lock().unwrap() = Inner::default();
+    }
+
+    pub fn record_failure(&self) {
+        self.record_failure_at(Instant::now());
+    }
+
+    pub fn record_failure_at(&self, now: Instant) {
+        if self.policy.failures == 0 {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        if inner.open_until.is_some() {
+            // A failed probe, or a request admitted before the circuit opened.
+            inner.open_until = Some(now + self.policy.cooldown);
+            inner.probe_started = None;
+            return;
+        }
+        match inner.first_failure {
+            Some(first) if now.saturating_duration_since(first) <= self.policy.window => {
+                inner.failures += 1;
+            }
+            _ => {
+                inner.first_failure = Some(now);
+                inner.failures = 1;
+            }
+        }
+        if inner.failures >= self.policy.failures {
+            inner.open_until = Some(now + self.policy.cooldown);
+            inner.failures = 0;
+            inner.first_failure = None;
+        }
+    }
+
+    pub fn state(&self) -> BreakerState {
+        self.state_at(Instant::now())
+    }
+
+    pub fn state_at(&self, now: Instant) -> BreakerState {
+        let inner = self.inner.lock().unwrap();
+        match inner.open_until {
+            None => BreakerState::Closed,
+            Some(until) if now < until => BreakerState::Open,
+            Some(_) => BreakerState::HalfOpen,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> BreakerPolicy {
+        BreakerPolicy {
+            failures: 3,
+            window: Duration::from_secs(10),
+            cooldown: Duration::from_secs(5),
+        }
+    }
+
+    #[test]
+    fn test_trips_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new(policy());
+        let now = Instant::now();
+        breaker.record_failure_at(now);
+        breaker.record_failure_at(now);
+        assert!(breaker.check_at(now).is_ok());
+        breaker.record_failure_at(now);
+        assert_eq!(breaker.state_at(now), BreakerState::Open);
+        assert_eq!(breaker.check_at(now), Err(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn test_success_resets_the_count() {
+        let breaker = CircuitBreaker::new(policy());
+        let now = Instant::now();
+        breaker.record_failure_at(now);
+        breaker.record_failure_at(now);
+        breaker.record_success();
+        breaker.record_failure_at(now);
+        assert_eq!(breaker.state_at(now), BreakerState::Closed);
+    }
+
+    #[test]
+    fn test_failures_outside_window_start_over() {
+        let breaker = CircuitBreaker::new(policy());
+        let now = Instant::now();
+        breaker.record_failure_at(now);
+        breaker.record_failure_at(now);
+        breaker.record_failure_at(now + Duration::from_secs(11));
+        assert_eq!(breaker.state_at(now), BreakerState::Closed);
+    }
+
+    #[test]
+    fn test_half_open_admits_one_probe() {
+        let breaker = CircuitBreaker::new(policy());
+        let now = Instant::now();
+        for _ in 0..3 {
+            breaker.record_failure_at(now);
+        }
+        let later = now + Duration::from_secs(5);
+        assert_eq!(breaker.state_at(later), BreakerState::HalfOpen);
+        assert!(breaker.check_at(later).is_ok());
+        assert!(breaker.check_at(later).is_err());
+
+        breaker.record_failure_at(later);
+        assert_eq!(breaker.state_at(later), BreakerState::Open);
+
+        let probe_at = later + Duration::from_secs(5);
+        assert!(breaker.check_at(probe_at).is_ok());
+        breaker.record_success();
+        assert_eq!(breaker.state_at(probe_at), BreakerState::Closed);
+    }
+
+    #[test]
+    fn test_disabled_never_trips() {
+        let breaker = CircuitBreaker::new(BreakerPolicy::DISABLED);
+        let now = Instant::now();
+        for _ in 0..100 {
+            breaker.record_failure_at(now);
+        }
+        assert!(breaker.check_at(now).is_ok());
+    }
+}
@@ -2,8 +2,12 @@
 // Licensed under the FSL-1.1-NC.
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::env;
+use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
+use tunnel_core::quic::QuicConfig;
+use tunnel_core::tls::TlsPolicy;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -12,6 +16,14 @@ pub struct Config {
     pub auth: AuthConfig,
     pub reliability: ReliabilityConfig,
     pub limits: LimitsConfig,
+    #[serde(default)]
+    pub h2: H2Config,
+    #[serde(default)]
+    pub quic: QuicConfig,
+    /// Why `QUIC_CONGESTION_CONTROLLER` could not be applied, reported by
+    /// `validate`.
+    #[serde(skip)]
+    congestion_controller_error: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,6 +33,289 @@ pub struct ServerConfig {
     pub http_port: u16,
     #[serde(default = "default_https_port")]
     pub https_port: u16,
+    /// UDP port for the QUIC tunnel listener. Unset shares `https_port`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quic_port: Option<u16>,
+    /// IP address every listener binds, e.g. `0.0.0.0` for IPv4 only or `::`
+    /// for IPv6 and, where the OS allows, IPv4 as well.
+    #[serde(default = "default_bind_address")]
+    pub bind_address: String,
+    /// Per-listener overrides of `bind_address`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_bind_address: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub https_bind_address: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quic_bind_address: Option<String>,
+    /// Prefix every public route is mounted under, e.g. `/tunnel` for
+    /// `/tunnel/t/<id>/...`, when a reverse proxy serves the tunnel from a
+    /// subpath. Empty mounts at the root.
+    #[serde(default)]
+    pub base_path: String,
+    #[serde(default)]
+    pub response_headers: ResponseHeadersConfig,
+    /// URLs that receive a JSON POST for every workstation register,
+    /// reconnecting and disconnect event.
+    #[serde(default)]
+    pub event_webhook: Vec<String>,
+    #[serde(default)]
+    pub cors: CorsConfig,
+    /// Origins whose pages may open WebSockets through the tunnel, e.g.
+    /// `https://app.example.com` or `https://*.example.com`. Upgrades from
+    /// other origins get `403`. Empty or `*` allows any origin.
+    #[serde(default)]
+    pub ws_allowed_origins: Vec<String>,
+    #[serde(default)]
+    pub access_log: AccessLogConfig,
+    /// Inflate gzip responses for callers whose `Accept-Encoding` rules gzip
+    /// out, e.g. `identity`, instead of relaying the compressed bytes.
+    #[serde(default)]
+    pub decompress_responses: bool,
+    #[serde(default)]
+    pub path_normalization: PathNormalizationConfig,
+    /// Answer plain HTTP with 308 instead of 307 when TLS is enabled.
+    /// Permanent redirects are cached hard by browsers, so this is opt-in.
+    #[serde(default)]
+    pub https_redirect_permanent: bool,
+    /// Path prefixes served over plain HTTP instead of being redirected to
+    /// HTTPS. ACME challenges are always exempt.
+    #[serde(default)]
+    pub https_redirect_exclude: Vec<String>,
+    /// Methods the public proxy forwards, e.g. `["GET", "HEAD"]` for a
+    /// read-only tunnel. Others get `405` without reaching the workstation.
+    /// Empty allows every method.
+    #[serde(default)]
+    pub allowed_methods: Vec<String>,
+    /// `404` or `503` (with `Retry-After`) for requests to workstations that
+    /// are not connected. Workstations still in their grace period always get
+    /// `503 WORKSTATION_RECONNECTING`.
+    #[serde(default = "default_unknown_workstation_status")]
+    pub unknown_workstation_status: u16,
+    /// Directory for spooled bodies; unset uses the system temp directory.
+    #[serde(default)]
+    pub spool_dir: Option<PathBuf>,
+    /// Request and response bodies larger than this many bytes are buffered
+    /// in a temp file under `spool_dir` instead of memory. 0 disables.
+    #[serde(default)]
+    pub spool_threshold: usize,
+    /// Expect a PROXY protocol (v1 or v2) header on every HTTPS connection
+    /// and use the client address it carries. Only for listeners behind an
+    /// L4 load balancer, as anyone reaching the port could claim any address.
+    #[serde(default)]
+    pub proxy_protocol: bool,
+    /// Replacements applied to `text/html` and `text/css` response bodies,
+    /// e.g. to turn links to the local backend into tunnel links.
+    #[serde(default)]
+    pub url_rewrite: Vec<UrlRewriteRule>,
+    #[serde(default)]
+    pub cache: CacheConfig,
+}
+
+impl ServerConfig {
+    /// Port the QUIC listener binds.
+    pub fn quic_port(&self) -> u16 {
+        self.quic_port.unwrap_or(self.https_port)
+    }
+
+    /// `base_path` without its trailing slash; empty at the root.
+    pub fn base_path(&self) -> &str {
+        self.base_path.trim_end_matches('/')
+    }
+
+    /// Address the HTTP listener binds.
+    pub fn http_bind(&self) -> anyhow::Result<SocketAddr> {
+        Ok(SocketAddr::new(
+            self.bind_ip(&self.http_bind_address)?,
+            self.http_port,
+        ))
+    }
+
+    /// Address the HTTPS listener binds.
+    pub fn https_bind(&self) -> anyhow::Result<SocketAddr> {
+        Ok(SocketAddr::new(
+            self.bind_ip(&self.https_bind_address)?,
+            self.https_port,
+        ))
+    }
+
+    /// Address the QUIC listener binds.
+    pub fn quic_bind(&self) -> anyhow::Result<SocketAddr> {
+        Ok(SocketAddr::new(
+            self.bind_ip(&self.quic_bind_address)?,
+            self.quic_port(),
+        ))
+    }
+
+    /// The listener's own bind address, else `bind_address`. Fails for one
+    /// that is not an IP address rather than binding somewhere else.
+    fn bind_ip(&self, listener: &Option<String>) -> anyhow::Result<IpAddr> {
+        let address = listener.as_deref().unwrap_or(&self.bind_address);
+        address
+            .parse()
+            .map_err(|_| anyhow::anyhow!("bind address '{}' is not an IP address", address))
+    }
+}
+
+/// Header rewrites applied to every tunneled HTTP and SSE response.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResponseHeadersConfig {
+    /// Headers set on each response, replacing any value from the workstation.
+    #[serde(default)]
+    pub add: BTreeMap<String, String>,
+    /// Header names dropped from workstation responses.
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+/// Public URL of a workstation under `scheme://host` and `base_path`.
+pub fn workstation_url(scheme: &str, host: &str, base_path: &str, workstation_id: &str) -> String {
+    format!("{}://{}{}/t/{}", scheme, host, base_path, workstation_id)
+}
+
+/// One `server.url_rewrite` replacement.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UrlRewriteRule {
+    /// Text to replace, e.g. `http://localhost:3000`.
+    pub from: String,
+    /// Replacement. Unset uses the workstation's public URL, e.g.
+    /// `https://tunnel.example.com/t/my-ws`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+}
+
+/// Cleanup applied to the tunneled path before it is forwarded.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PathNormalizationConfig {
+    /// Collapse repeated slashes and resolve `.` and `..` segments. Paths that
+    /// climb above the root or contain NUL are refused with `400` either way.
+    #[serde(default = "default_path_normalization_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub trailing_slash: TrailingSlash,
+}
+
+impl Default for PathNormalizationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_path_normalization_enabled(),
+            trailing_slash: TrailingSlash::default(),
+        }
+    }
+}
+
+/// What normalization does with a trailing slash. Applied only while
+/// normalization is enabled; the root path always keeps its slash.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrailingSlash {
+    #[default]
+    Preserve,
+    Add,
+    Remove,
+}
+
+impl std::str::FromStr for TrailingSlash {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "preserve" => Ok(Self::Preserve),
+            "add" => Ok(Self::Add),
+            "remove" => Ok(Self::Remove),
+            other => Err(format!(
+                "unknown trailing slash policy '{}', expected preserve, add or remove",
+                other
+            )),
+        }
+    }
+}
+
+/// One JSON line per proxied request, written to a file that is rotated by
+/// size and/or age. Independent of the `tracing` output.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessLogConfig {
+    /// File to append to. Unset disables access logging.
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    /// Size at which the file is rotated. 0 disables size-based rotation.
+    #[serde(default = "default_access_log_max_bytes")]
+    pub max_bytes: u64,
+    /// Seconds after which the file is rotated. 0 disables time-based rotation.
+    #[serde(default)]
+    pub rotate_interval: u64,
+    /// Rotated files kept as `<path>.1` (newest) to `<path>.<max_files>`.
+    #[serde(default = "default_access_log_max_files")]
+    pub max_files: usize,
+}
+
+impl Default for AccessLogConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            max_bytes: default_access_log_max_bytes(),
+            rotate_interval: 0,
+            max_files: default_access_log_max_files(),
+        }
+    }
+}
+
+/// In-memory cache of tunneled `GET` responses. Only responses that allow it
+/// with `max-age` or `s-maxage` are stored, and hits skip the workstation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_cache_max_entries")]
+    pub max_entries: usize,
+    /// Longest time in seconds an entry is served, whatever the response allows.
+    #[serde(default = "default_cache_max_ttl")]
+    pub max_ttl: u64,
+    /// Request headers whose values are part of the cache key. A response
+    /// whose `Vary` names any other header is not cached.
+    #[serde(default)]
+    pub vary_headers: Vec<String>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_entries: default_cache_max_entries(),
+            max_ttl: default_cache_max_ttl(),
+            vary_headers: Vec::new(),
+        }
+    }
+}
+
+/// CORS answered at the tunnel edge: preflights are served without reaching
+/// the workstation and allow headers are added to tunneled responses.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorsConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Origins allowed to call tunneled endpoints; `*` allows any origin.
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    #[serde(default = "default_cors_allowed_methods")]
+    pub allowed_methods: Vec<String>,
+    /// Request headers allowed on preflight. Empty allows whatever the
+    /// browser asks for.
+    #[serde(default)]
+    pub allowed_headers: Vec<String>,
+    #[serde(default)]
+    pub allow_credentials: bool,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            allowed_origins: Vec::new(),
+            allowed_methods: default_cors_allowed_methods(),
+            allowed_headers: Vec::new(),
+            allow_credentials: false,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,11 +325,37 @@ pub struct TlsConfig {
     pub acme_email: Option<String>,
     #[serde(default = "default_certs_dir")]
     pub certs_dir: PathBuf,
+    /// ALPN protocol the QUIC endpoint accepts; clients must offer the same value.
+    #[serde(default = "default_alpn")]
+    pub alpn: String,
+    /// Oldest TLS version the HTTPS listener accepts, `1.2` or `1.3`. QUIC is
+    /// always TLS 1.3.
+    #[serde(default = "default_tls_min_version")]
+    pub min_version: String,
+    /// Cipher suites that may be negotiated, e.g. `TLS13_AES_256_GCM_SHA384`.
+    /// Empty allows every suite rustls supports.
+    #[serde(default)]
+    pub cipher_suites: Vec<String>,
+    /// Extra subject alternative names, DNS names or IP addresses, for the
+    /// self-signed certificate used while TLS is off or no certificate is
+    /// available. `server.domain` is always included.
+    #[serde(default)]
+    pub self_signed_sans: Vec<String>,
+}
+
+impl TlsConfig {
+    pub fn policy(&self) -> anyhow::Result<TlsPolicy> {
+        TlsPolicy::new(&self.min_version, &self.cipher_suites).map_err(|e| anyhow::anyhow!(e))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthConfig {
+    #[serde(default)]
     pub api_key: String,
+    /// File holding the API key, read at load time and preferred over `api_key`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_file: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -43,12 +364,106 @@ pub struct ReliabilityConfig {
     pub grace_period: u64,
     #[serde(default = "default_request_timeout")]
     pub request_timeout: u64,
+    #[serde(default = "default_sse_heartbeat_interval")]
+    pub sse_heartbeat_interval: u64,
+    /// Seconds after which an SSE stream is closed by the server. 0 means no limit.
+    #[serde(default)]
+    pub max_sse_duration: u64,
+    /// Seconds an HTTP or HTTP/2 request may take from arrival until its
+    /// response body ends; longer ones are aborted, with `504` if nothing was
+    /// sent yet. SSE streams follow `max_sse_duration`. 0 means no limit.
+    #[serde(default)]
+    pub max_request_duration: u64,
+    /// Milliseconds after which a tunneled HTTP request is logged as slow, at
+    /// warn level with its method, path, workstation and duration. 0 disables.
+    #[serde(default)]
+    pub slow_request_threshold: u64,
+    /// Seconds between sweeps that evict workstations past their grace period.
+    #[serde(default = "default_cleanup_interval")]
+    pub cleanup_interval: u64,
+    /// Seconds without any message from a workstation (clients ping every
+    /// 20s) before it is treated as disconnected. 0 disables the check.
+    #[serde(default = "default_liveness_timeout")]
+    pub liveness_timeout: u64,
+    /// Consecutive backend failures (502s and timeouts) that make the server
+    /// answer a workstation's HTTP requests with `503`. 0 disables the breaker.
+    #[serde(default = "default_circuit_breaker_failures")]
+    pub circuit_breaker_failures: u32,
+    /// Seconds within which those failures must occur.
+    #[serde(default = "default_circuit_breaker_window")]
+    pub circuit_breaker_window: u64,
+    /// Seconds requests are refused before one is let through as a probe.
+    #[serde(default = "default_circuit_breaker_cooldown")]
+    pub circuit_breaker_cooldown: u64,
+    /// Accept a second client registering under an active workstation id as
+    /// a standby that takes over when the active connection fails, instead of
+    /// rejecting it.
+    #[serde(default)]
+    pub allow_standby: bool,
+    /// Refuse new tunneled requests with `503` while those in flight finish,
+    /// e.g. ahead of a planned restart. Workstations stay connected.
+    #[serde(default)]
+    pub maintenance: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LimitsConfig {
     #[serde(default = "default_max_workstations")]
     pub max_workstations: usize,
+    #[serde(default = "default_max_streams_per_connection")]
+    pub max_streams_per_connection: usize,
+    /// HTTP requests per workstation that may wait for one of its
+    /// `max_streams_per_connection` slots; more are refused with `503`.
+    /// 0 lets every request wait.
+    #[serde(default)]
+    pub max_queued_requests: usize,
+    /// Milliseconds a queued HTTP request waits for a slot before `504`.
+    /// 0 waits until the request times out.
+    #[serde(default)]
+    pub queue_timeout: u64,
+    /// HTTP requests awaiting a workstation's answer across the whole server;
+    /// more are refused with `503`. 0 is unbounded.
+    #[serde(default)]
+    pub max_pending_requests: usize,
+    /// Sustained requests per second allowed per source IP. 0 disables limiting.
+    #[serde(default)]
+    pub requests_per_second: u32,
+    #[serde(default = "default_burst")]
+    pub burst: u32,
+    /// New QUIC connections accepted per second, with bursts up to one
+    /// second's worth; excess attempts are refused before the handshake.
+    /// 0 disables the limit.
+    #[serde(default)]
+    pub max_new_connections_per_sec: u32,
+    #[serde(default = "default_max_frame_bytes")]
+    pub max_frame_bytes: usize,
+    #[serde(default = "default_max_ws_message_bytes")]
+    pub max_ws_message_bytes: usize,
+    /// Combined size of request header names and values; larger requests are
+    /// refused with `431`. 0 disables the check.
+    #[serde(default = "default_max_header_bytes")]
+    pub max_header_bytes: usize,
+    /// Body bytes a workstation may proxy before requests are refused with
+    /// `429`, until its counters are reset. 0 disables the quota.
+    #[serde(default)]
+    pub max_bytes_per_workstation: u64,
+    /// Sustained body bytes per second proxied for one workstation, in both
+    /// directions combined. 0 disables throttling.
+    #[serde(default)]
+    pub max_bytes_per_sec_per_workstation: u64,
+}
+
+/// HTTP/2 passthrough, used for gRPC and other protocols that need trailers or
+/// bidirectional streaming.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct H2Config {
+    /// Tunneled path prefixes relayed as raw HTTP/2 streams, e.g. `/echo.Echo/`.
+    #[serde(default)]
+    pub path_prefixes: Vec<String>,
+}
+
+fn default_bind_address() -> String {
+    "0.0.0.0".to_string()
 }
 
 fn default_http_port() -> u16 {
@@ -59,6 +474,28 @@ fn default_https_port() -> u16 {
     443
 }
 
+fn default_unknown_workstation_status() -> u16 {
+    404
+}
+
+fn default_cors_allowed_methods() -> Vec<String> {
+    ["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE"]
+        .map(String::from)
+        .to_vec()
+}
+
+fn default_path_normalization_enabled() -> bool {
+    true
+}
+
+fn default_cache_max_entries() -> usize {
+    1000
+}
+
+fn default_cache_max_ttl() -> u64 {
+    300
+}
+
 fn default_tls_enabled() -> bool {
     true
 }
@@ -67,6 +504,14 @@ fn default_certs_dir() -> PathBuf {
     PathBuf::from("/var/lib/tunnel/certs")
 }
 
+fn default_alpn() -> String {
+    tunnel_core::quic::DEFAULT_ALPN.to_string()
+}
+
+fn default_tls_min_version() -> String {
+    tunnel_core::tls::DEFAULT_MIN_VERSION.to_string()
+}
+
 fn default_grace_period() -> u64 {
     30
 }
@@ -75,11 +520,111 @@ fn default_request_timeout() -> u64 {
     60
 }
 
+fn default_sse_heartbeat_interval() -> u64 {
+    15
+}
+
+fn default_cleanup_interval() -> u64 {
+    10
+}
+
+fn default_liveness_timeout() -> u64 {
+    60
+}
+
+fn default_circuit_breaker_failures() -> u32 {
+    5
+}
+
+fn default_circuit_breaker_window() -> u64 {
+    30
+}
+
+fn default_circuit_breaker_cooldown() -> u64 {
+    10
+}
+
+fn default_access_log_max_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_access_log_max_files() -> usize {
+    5
+}
+
 fn default_max_workstations() -> usize {
     100
 }
 
+fn default_max_streams_per_connection() -> usize {
+    1000
+}
+
+fn default_burst() -> u32 {
+    50
+}
+
+fn default_max_frame_bytes() -> usize {
+    tunnel_core::quic::DEFAULT_MAX_FRAME_BYTES
+}
+
+fn default_max_ws_message_bytes() -> usize {
+    16 * 1024 * 1024
+}
+
+fn default_max_header_bytes() -> usize {
+    64 * 1024
+}
+
+fn split_list(val: &str) -> Vec<String> {
+    val.split(',')
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
 impl Config {
+    /// `https` with TLS enabled, else `http`.
+    pub fn public_scheme(&self) -> &'static str {
+        if self.tls.enabled {
+            "https"
+        } else {
+            "http"
+        }
+    }
+
+    /// `server.domain`, with the port of the listener serving
+    /// [`public_scheme`](Self::public_scheme) unless it is that scheme's
+    /// default. A domain that names a port is used as is.
+    pub fn public_host(&self) -> String {
+        let (port, default_port) = if self.tls.enabled {
+            (self.server.https_port, 443)
+        } else {
+            (self.server.http_port, 80)
+        };
+        let names_port = self
+            .server
+            .domain
+            .rsplit(']')
+            .next()
+            .is_some_and(|host| host.contains(':'));
+        if port == default_port || names_port {
+            self.server.domain.clone()
+        } else {
+            format!("{}:{}", self.server.domain, port)
+        }
+    }
+
+    /// Public URL of a workstation, e.g. `https://tunnel.example.com/t/my-ws`.
+    pub fn workstation_url(&self, workstation_id: &str) -> String {
+        workstation_url(
+            self.public_scheme(),
+            &self.public_host(),
+            self.server.base_path(),
+            workstation_id,
+        )
+    }
+
     pub fn load(config_path: Option<PathBuf>) -> anyhow::Result<Self> {
         let mut config = if let Some(path) = config_path {
             let content = std::fs::read_to_string(path)?;
@@ -89,6 +634,7 @@ impl Config {
         };
 
         config.apply_env_overrides();
+        config.load_api_key_file()?;
         config.validate()?;
         Ok(config)
     }
@@ -107,6 +653,157 @@ impl Config {
                 self.server.https_port = port;
             }
         }
+        if let Ok(val) = env::var("SERVER_QUIC_PORT") {
+            if let Ok(port) = val.parse() {
+                self.server.quic_port = Some(port);
+            }
+        }
+        if let Ok(val) = env::var("SERVER_BIND_ADDRESS") {
+            self.server.bind_address = val;
+        }
+        if let Ok(val) = env::var("SERVER_HTTP_BIND_ADDRESS") {
+            self.server.http_bind_address = Some(val);
+        }
+        if let Ok(val) = env::var("SERVER_HTTPS_BIND_ADDRESS") {
+            self.server.https_bind_address = Some(val);
+        }
+        if let Ok(val) = env::var("SERVER_QUIC_BIND_ADDRESS") {
+            self.server.quic_bind_address = Some(val);
+        }
+        if let Ok(val) = env::var("SERVER_BASE_PATH") {
+            self.server.base_path = val;
+        }
+        if let Ok(val) = env::var("SERVER_RESPONSE_HEADERS_ADD") {
+            self.server.response_headers.add = val
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+                .filter(|(name, _)| !name.is_empty())
+                .collect();
+        }
+        if let Ok(val) = env::var("SERVER_RESPONSE_HEADERS_REMOVE") {
+            self.server.response_headers.remove = split_list(&val);
+        }
+        if let Ok(val) = env::var("SERVER_EVENT_WEBHOOK") {
+            self.server.event_webhook = split_list(&val);
+        }
+        if let Ok(val) = env::var("SERVER_WS_ALLOWED_ORIGINS") {
+            self.server.ws_allowed_origins = split_list(&val);
+        }
+        if let Ok(val) = env::var("SERVER_DECOMPRESS_RESPONSES") {
+            if let Ok(enabled) = val.parse() {
+                self.server.decompress_responses = enabled;
+            }
+        }
+        if let Ok(val) = env::var("SERVER_PATH_NORMALIZATION") {
+            if let Ok(enabled) = val.parse() {
+                self.server.path_normalization.enabled = enabled;
+            }
+        }
+        if let Ok(val) = env::var("SERVER_PATH_TRAILING_SLASH") {
+            if let Ok(policy) = val.parse() {
+                self.server.path_normalization.trailing_slash = policy;
+            }
+        }
+        if let Ok(val) = env::var("SERVER_HTTPS_REDIRECT_PERMANENT") {
+            if let Ok(permanent) = val.parse() {
+                self.server.https_redirect_permanent = permanent;
+            }
+        }
+        if let Ok(val) = env::var("SERVER_HTTPS_REDIRECT_EXCLUDE") {
+            self.server.https_redirect_exclude = split_list(&val);
+        }
+        if let Ok(val) = env::var("SERVER_ALLOWED_METHODS") {
+            self.server.allowed_methods = split_list(&val);
+        }
+        if let Ok(val) = env::var("SERVER_UNKNOWN_WORKSTATION_STATUS") {
+            if let Ok(status) = val.parse() {
+                self.server.unknown_workstation_status = status;
+            }
+        }
+        if let Ok(val) = env::var("SERVER_SPOOL_DIR") {
+            self.server.spool_dir = (!val.is_empty()).then(|| PathBuf::from(val));
+        }
+        if let Ok(val) = env::var("SERVER_SPOOL_THRESHOLD") {
+            if let Ok(threshold) = val.parse() {
+                self.server.spool_threshold = threshold;
+            }
+        }
+        if let Ok(val) = env::var("SERVER_PROXY_PROTOCOL") {
+            if let Ok(enabled) = val.parse() {
+                self.server.proxy_protocol = enabled;
+            }
+        }
+        if let Ok(val) = env::var("SERVER_URL_REWRITE") {
+            self.server.url_rewrite = split_list(&val)
+                .into_iter()
+                .map(|entry| match entry.split_once('=') {
+                    Some((from, to)) => UrlRewriteRule {
+                        from: from.trim().to_string(),
+                        to: Some(to.trim().to_string()),
+                    },
+                    None => UrlRewriteRule {
+                        from: entry,
+                        to: None,
+                    },
+                })
+                .collect();
+        }
+        if let Ok(val) = env::var("SERVER_CACHE_ENABLED") {
+            if let Ok(enabled) = val.parse() {
+                self.server.cache.enabled = enabled;
+            }
+        }
+        if let Ok(val) = env::var("SERVER_CACHE_MAX_ENTRIES") {
+            if let Ok(max) = val.parse() {
+                self.server.cache.max_entries = max;
+            }
+        }
+        if let Ok(val) = env::var("SERVER_CACHE_MAX_TTL") {
+            if let Ok(secs) = val.parse() {
+                self.server.cache.max_ttl = secs;
+            }
+        }
+        if let Ok(val) = env::var("SERVER_CACHE_VARY_HEADERS") {
+            self.server.cache.vary_headers = split_list(&val);
+        }
+        if let Ok(val) = env::var("SERVER_CORS_ENABLED") {
+            if let Ok(enabled) = val.parse() {
+                self.server.cors.enabled = enabled;
+            }
+        }
+        if let Ok(val) = env::var("SERVER_CORS_ALLOWED_ORIGINS") {
+            self.server.cors.allowed_origins = split_list(&val);
+        }
+        if let Ok(val) = env::var("SERVER_CORS_ALLOWED_METHODS") {
+            self.server.cors.allowed_methods = split_list(&val);
+        }
+        if let Ok(val) = env::var("SERVER_CORS_ALLOWED_HEADERS") {
+            self.server.cors.allowed_headers = split_list(&val);
+        }
+        if let Ok(val) = env::var("SERVER_CORS_ALLOW_CREDENTIALS") {
+            if let Ok(allow) = val.parse() {
+                self.server.cors.allow_credentials = allow;
+            }
+        }
+        if let Ok(val) = env::var("SERVER_ACCESS_LOG_PATH") {
+            self.server.access_log.path = (!val.is_empty()).then(|| PathBuf::from(val));
+        }
+        if let Ok(val) = env::var("SERVER_ACCESS_LOG_MAX_BYTES") {
+            if let Ok(max) = val.parse() {
+                self.server.access_log.max_bytes = max;
+            }
+        }
+        if let Ok(val) = env::var("SERVER_ACCESS_LOG_ROTATE_INTERVAL") {
+            if let Ok(interval) = val.parse() {
+                self.server.access_log.rotate_interval = interval;
+            }
+        }
+        if let Ok(val) = env::var("SERVER_ACCESS_LOG_MAX_FILES") {
+            if let Ok(max) = val.parse() {
+                self.server.access_log.max_files = max;
+            }
+        }
         if let Ok(val) = env::var("TLS_ENABLED") {
             if let Ok(enabled) = val.parse() {
                 self.tls.enabled = enabled;
@@ -118,9 +815,24 @@ impl Config {
         if let Ok(val) = env::var("TLS_CERTS_DIR") {
             self.tls.certs_dir = PathBuf::from(val);
         }
+        if let Ok(val) = env::var("TLS_ALPN") {
+            self.tls.alpn = val;
+        }
+        if let Ok(val) = env::var("TLS_MIN_VERSION") {
+            self.tls.min_version = val;
+        }
+        if let Ok(val) = env::var("TLS_CIPHER_SUITES") {
+            self.tls.cipher_suites = split_list(&val);
+        }
+        if let Ok(val) = env::var("TLS_SELF_SIGNED_SANS") {
+            self.tls.self_signed_sans = split_list(&val);
+        }
         if let Ok(val) = env::var("AUTH_API_KEY") {
             self.auth.api_key = val;
         }
+        if let Ok(val) = env::var("AUTH_API_KEY_FILE") {
+            self.auth.api_key_file = Some(PathBuf::from(val));
+        }
         if let Ok(val) = env::var("RELIABILITY_GRACE_PERIOD") {
             if let Ok(period) = val.parse() {
                 self.reliability.grace_period = period;
@@ -131,23 +843,357 @@ impl Config {
                 self.reliability.request_timeout = timeout;
             }
         }
+        if let Ok(val) = env::var("RELIABILITY_SSE_HEARTBEAT_INTERVAL") {
+            if let Ok(interval) = val.parse() {
+                self.reliability.sse_heartbeat_interval = interval;
+            }
+        }
+        if let Ok(val) = env::var("RELIABILITY_MAX_SSE_DURATION") {
+            if let Ok(duration) = val.parse() {
+                self.reliability.max_sse_duration = duration;
+            }
+        }
+        if let Ok(val) = env::var("RELIABILITY_MAX_REQUEST_DURATION") {
+            if let Ok(duration) = val.parse() {
+                self.reliability.max_request_duration = duration;
+            }
+        }
+        if let Ok(val) = env::var("RELIABILITY_SLOW_REQUEST_THRESHOLD") {
+            if let Ok(threshold) = val.parse() {
+                self.reliability.slow_request_threshold = threshold;
+            }
+        }
+        if let Ok(val) = env::var("RELIABILITY_CLEANUP_INTERVAL") {
+            if let Ok(interval) = val.parse() {
+                self.reliability.cleanup_interval = interval;
+            }
+        }
+        if let Ok(val) = env::var("RELIABILITY_LIVENESS_TIMEOUT") {
+            if let Ok(timeout) = val.parse() {
+                self.reliability.liveness_timeout = timeout;
+            }
+        }
+        if let Ok(val) = env::var("RELIABILITY_CIRCUIT_BREAKER_FAILURES") {
+            if let Ok(failures) = val.parse() {
+                self.reliability.circuit_breaker_failures = failures;
+            }
+        }
+        if let Ok(val) = env::var("RELIABILITY_CIRCUIT_BREAKER_WINDOW") {
+            if let Ok(window) = val.parse() {
+                self.reliability.circuit_breaker_window = window;
+            }
+        }
+        if let Ok(val) = env::var("RELIABILITY_CIRCUIT_BREAKER_COOLDOWN") {
+            if let Ok(cooldown) = val.parse() {
+                self.reliability.circuit_breaker_cooldown = cooldown;
+            }
+        }
+        if let Ok(val) = env::var("RELIABILITY_ALLOW_STANDBY") {
+            if let Ok(allow) = val.parse() {
+                self.reliability.allow_standby = allow;
+            }
+        }
+        if let Ok(val) = env::var("RELIABILITY_MAINTENANCE") {
+            if let Ok(maintenance) = val.parse() {
+                self.reliability.maintenance = maintenance;
+            }
+        }
         if let Ok(val) = env::var("LIMITS_MAX_WORKSTATIONS") {
             if let Ok(max) = val.parse() {
                 self.limits.max_workstations = max;
             }
         }
+        if let Ok(val) = env::var("LIMITS_MAX_STREAMS_PER_CONNECTION") {
+            if let Ok(max) = val.parse() {
+                self.limits.max_streams_per_connection = max;
+            }
+        }
+        if let Ok(val) = env::var("LIMITS_MAX_QUEUED_REQUESTS") {
+            if let Ok(max) = val.parse() {
+                self.limits.max_queued_requests = max;
+            }
+        }
+        if let Ok(val) = env::var("LIMITS_QUEUE_TIMEOUT") {
+            if let Ok(millis) = val.parse() {
+                self.limits.queue_timeout = millis;
+            }
+        }
+        if let Ok(val) = env::var("LIMITS_MAX_PENDING_REQUESTS") {
+            if let Ok(max) = val.parse() {
+                self.limits.max_pending_requests = max;
+            }
+        }
+        if let Ok(val) = env::var("LIMITS_REQUESTS_PER_SECOND") {
+            if let Ok(rps) = val.parse() {
+                self.limits.requests_per_second = rps;
+            }
+        }
+        if let Ok(val) = env::var("LIMITS_BURST") {
+            if let Ok(burst) = val.parse() {
+                self.limits.burst = burst;
+            }
+        }
+        if let Ok(val) = env::var("LIMITS_MAX_NEW_CONNECTIONS_PER_SEC") {
+            if let Ok(max) = val.parse() {
+                self.limits.max_new_connections_per_sec = max;
+            }
+        }
+        if let Ok(val) = env::var("LIMITS_MAX_FRAME_BYTES") {
+            if let Ok(max) = val.parse() {
+                self.limits.max_frame_bytes = max;
+            }
+        }
+        if let Ok(val) = env::var("LIMITS_MAX_WS_MESSAGE_BYTES") {
+            if let Ok(max) = val.parse() {
+                self.limits.max_ws_message_bytes = max;
+            }
+        }
+        if let Ok(val) = env::var("LIMITS_MAX_HEADER_BYTES") {
+            if let Ok(max) = val.parse() {
+                self.limits.max_header_bytes = max;
+            }
+        }
+        if let Ok(val) = env::var("LIMITS_MAX_BYTES_PER_WORKSTATION") {
+            if let Ok(max) = val.parse() {
+                self.limits.max_bytes_per_workstation = max;
+            }
+        }
+        if let Ok(val) = env::var("LIMITS_MAX_BYTES_PER_SEC_PER_WORKSTATION") {
+            if let Ok(max) = val.parse() {
+                self.limits.max_bytes_per_sec_per_workstation = max;
+            }
+        }
+        if let Ok(val) = env::var("QUIC_MAX_BIDI_STREAMS") {
+            if let Ok(max) = val.parse() {
+                self.quic.max_bidi_streams = max;
+            }
+        }
+        if let Ok(val) = env::var("QUIC_MAX_IDLE_TIMEOUT") {
+            if let Ok(timeout) = val.parse() {
+                self.quic.max_idle_timeout = timeout;
+            }
+        }
+        if let Ok(val) = env::var("QUIC_KEEP_ALIVE_INTERVAL") {
+            if let Ok(interval) = val.parse() {
+                self.quic.keep_alive_interval = interval;
+            }
+        }
+        if let Ok(val) = env::var("QUIC_STREAM_RECEIVE_WINDOW") {
+            if let Ok(window) = val.parse() {
+                self.quic.stream_receive_window = window;
+            }
+        }
+        if let Ok(val) = env::var("QUIC_RECEIVE_WINDOW") {
+            if let Ok(window) = val.parse() {
+                self.quic.receive_window = window;
+            }
+        }
+        if let Ok(val) = env::var("QUIC_CONGESTION_CONTROLLER") {
+            match val.parse() {
+                Ok(controller) => self.quic.congestion_controller = controller,
+                Err(e) => self.congestion_controller_error = Some(e),
+            }
+        }
+        if let Ok(val) = env::var("H2_PATH_PREFIXES") {
+            self.h2.path_prefixes = split_list(&val);
+        }
+    }
+
+    /// Replaces `auth.api_key` with the contents of `auth.api_key_file`, if set,
+    /// without trailing whitespace.
+    pub fn load_api_key_file(&mut self) -> anyhow::Result<()> {
+        if let Some(path) = &self.auth.api_key_file {
+            let key = std::fs::read_to_string(path).map_err(|e| {
+                anyhow::anyhow!("failed to read AUTH_API_KEY_FILE {}: {}", path.display(), e)
+            })?;
+            self.auth.api_key = key.trim_end().to_string();
+        }
+        Ok(())
     }
 
     fn validate(&self) -> anyhow::Result<()> {
+        if let Some(e) = &self.congestion_controller_error {
+            anyhow::bail!("QUIC_CONGESTION_CONTROLLER: {}", e);
+        }
         if self.server.domain.is_empty() {
             anyhow::bail!("SERVER_DOMAIN is required");
         }
+        for (name, address) in [
+            ("SERVER_BIND_ADDRESS", Some(&self.server.bind_address)),
+            (
+                "SERVER_HTTP_BIND_ADDRESS",
+                self.server.http_bind_address.as_ref(),
+            ),
+            (
+                "SERVER_HTTPS_BIND_ADDRESS",
+                self.server.https_bind_address.as_ref(),
+            ),
+            (
+                "SERVER_QUIC_BIND_ADDRESS",
+                self.server.quic_bind_address.as_ref(),
+            ),
+        ] {
+            if let Some(address) = address {
+                if address.parse::<IpAddr>().is_err() {
+                    anyhow::bail!("{} '{}' is not an IP address", name, address);
+                }
+            }
+        }
+        let base_path = &self.server.base_path;
+        if !base_path.is_empty()
+            && (!base_path.starts_with('/') || base_path.contains(['?', '#', ':', '*', '{', '}']))
+        {
+            anyhow::bail!(
+                "SERVER_BASE_PATH '{}' must be a path such as /tunnel",
+                base_path
+            );
+        }
         if self.auth.api_key.len() < 32 {
             anyhow::bail!("AUTH_API_KEY must be at least 32 characters");
         }
         if self.tls.enabled && self.tls.acme_email.is_none() {
             anyhow::bail!("TLS_ACME_EMAIL is required when TLS is enabled");
         }
+        if self.tls.alpn.is_empty() {
+            anyhow::bail!("TLS_ALPN must not be empty");
+        }
+        if let Err(e) = self.tls.policy() {
+            anyhow::bail!("Invalid TLS settings: {}", e);
+        }
+        if let Some(san) = self.tls.self_signed_sans.iter().find(|san| {
+            san.is_empty() || rcgen::CertificateParams::new(vec![san.to_string()]).is_err()
+        }) {
+            anyhow::bail!(
+                "TLS_SELF_SIGNED_SANS entry '{}' is not a DNS name or IP address",
+                san
+            );
+        }
+        for (name, value) in &self.server.response_headers.add {
+            if axum::http::HeaderName::from_bytes(name.as_bytes()).is_err() {
+                anyhow::bail!(
+                    "SERVER_RESPONSE_HEADERS_ADD has an invalid header name '{}'",
+                    name
+                );
+            }
+            if axum::http::HeaderValue::from_str(value).is_err() {
+                anyhow::bail!(
+                    "SERVER_RESPONSE_HEADERS_ADD has an invalid value for '{}'",
+                    name
+                );
+            }
+        }
+        if let Some(url) = self
+            .server
+            .event_webhook
+            .iter()
+            .find(|u| !u.starts_with("http://") && !u.starts_with("https://"))
+        {
+            anyhow::bail!(
+                "SERVER_EVENT_WEBHOOK entry '{}' must be an http(s) URL",
+                url
+            );
+        }
+        if self.server.cors.enabled {
+            if self.server.cors.allowed_origins.is_empty() {
+                anyhow::bail!("SERVER_CORS_ALLOWED_ORIGINS is required when CORS is enabled");
+            }
+            if self.server.cors.allow_credentials
+                && self.server.cors.allowed_origins.iter().any(|o| o == "*")
+            {
+                anyhow::bail!("SERVER_CORS_ALLOW_CREDENTIALS cannot be combined with a `*` origin");
+            }
+        }
+        if let Some(origin) = self.server.ws_allowed_origins.iter().find(|o| {
+            if *o == "*" {
+                return false;
+            }
+            let Some((scheme, host)) = o.split_once("://") else {
+                return true;
+            };
+            let host = host.strip_prefix("*.").unwrap_or(host);
+            scheme.is_empty() || host.is_empty() || host.contains(['/', '*'])
+        }) {
+            anyhow::bail!(
+                "SERVER_WS_ALLOWED_ORIGINS entry '{}' must be `*` or an origin such as https://app.example.com or https://*.example.com",
+                origin
+            );
+        }
+        if let Some(prefix) = self
+            .server
+            .https_redirect_exclude
+            .iter()
+            .find(|p| !p.starts_with('/'))
+        {
+            anyhow::bail!(
+                "SERVER_HTTPS_REDIRECT_EXCLUDE entry '{}' must start with '/'",
+                prefix
+            );
+        }
+        if let Some(method) = self
+            .server
+            .allowed_methods
+            .iter()
+            .find(|m| axum::http::Method::from_bytes(m.as_bytes()).is_err())
+        {
+            anyhow::bail!("SERVER_ALLOWED_METHODS entry '{}' is not a method", method);
+        }
+        if !matches!(self.server.unknown_workstation_status, 404 | 503) {
+            anyhow::bail!("SERVER_UNKNOWN_WORKSTATION_STATUS must be 404 or 503");
+        }
+        if self.server.spool_threshold > 0 {
+            if let Some(dir) = self.server.spool_dir.as_ref().filter(|dir| !dir.is_dir()) {
+                anyhow::bail!("SERVER_SPOOL_DIR '{}' is not a directory", dir.display());
+            }
+        }
+        if self
+            .server
+            .url_rewrite
+            .iter()
+            .any(|rule| rule.from.is_empty())
+        {
+            anyhow::bail!("SERVER_URL_REWRITE entries need a non-empty `from`");
+        }
+        if self.server.cache.enabled {
+            if self.server.cache.max_entries == 0 {
+                anyhow::bail!("SERVER_CACHE_MAX_ENTRIES must be greater than 0");
+            }
+            if self.server.cache.max_ttl == 0 {
+                anyhow::bail!("SERVER_CACHE_MAX_TTL must be greater than 0");
+            }
+            if let Some(name) = self
+                .server
+                .cache
+                .vary_headers
+                .iter()
+                .find(|n| axum::http::HeaderName::from_bytes(n.as_bytes()).is_err())
+            {
+                anyhow::bail!(
+                    "SERVER_CACHE_VARY_HEADERS has an invalid header name '{}'",
+                    name
+                );
+            }
+        }
+        if self.reliability.cleanup_interval == 0 {
+            anyhow::bail!("RELIABILITY_CLEANUP_INTERVAL must be greater than 0");
+        }
+        if self.reliability.circuit_breaker_failures > 0
+            && (self.reliability.circuit_breaker_window == 0
+                || self.reliability.circuit_breaker_cooldown == 0)
+        {
+            anyhow::bail!(
+                "RELIABILITY_CIRCUIT_BREAKER_WINDOW and RELIABILITY_CIRCUIT_BREAKER_COOLDOWN must be greater than 0"
+            );
+        }
+        if self.limits.max_streams_per_connection == 0 {
+            anyhow::bail!("LIMITS_MAX_STREAMS_PER_CONNECTION must be greater than 0");
+        }
+        if self.limits.max_ws_message_bytes == 0 {
+            anyhow::bail!("LIMITS_MAX_WS_MESSAGE_BYTES must be greater than 0");
+        }
+        if let Some(prefix) = self.h2.path_prefixes.iter().find(|p| !p.starts_with('/')) {
+            anyhow::bail!("H2_PATH_PREFIXES entry '{}' must start with '/'", prefix);
+        }
+        self.quic.validate()?;
         Ok(())
     }
 }
@@ -159,22 +1205,75 @@ impl Default for Config {
                 domain: String::new(),
                 http_port: default_http_port(),
                 https_port: default_https_port(),
+                quic_port: None,
+                bind_address: default_bind_address(),
+                http_bind_address: None,
+                https_bind_address: None,
+                quic_bind_address: None,
+                base_path: String::new(),
+                response_headers: ResponseHeadersConfig::default(),
+                event_webhook: Vec::new(),
+                cors: CorsConfig::default(),
+                ws_allowed_origins: Vec::new(),
+                access_log: AccessLogConfig::default(),
+                decompress_responses: false,
+                path_normalization: PathNormalizationConfig::default(),
+                https_redirect_permanent: false,
+                https_redirect_exclude: Vec::new(),
+                allowed_methods: Vec::new(),
+                unknown_workstation_status: default_unknown_workstation_status(),
+                spool_dir: None,
+                spool_threshold: 0,
+                proxy_protocol: false,
+                url_rewrite: Vec::new(),
+                cache: CacheConfig::default(),
             },
             tls: TlsConfig {
                 enabled: default_tls_enabled(),
                 acme_email: None,
                 certs_dir: default_certs_dir(),
+                alpn: default_alpn(),
+                min_version: default_tls_min_version(),
+                cipher_suites: Vec::new(),
+                self_signed_sans: Vec::new(),
             },
             auth: AuthConfig {
                 api_key: String::new(),
+                api_key_file: None,
             },
             reliability: ReliabilityConfig {
                 grace_period: default_grace_period(),
                 request_timeout: default_request_timeout(),
+                sse_heartbeat_interval: default_sse_heartbeat_interval(),
+                max_sse_duration: 0,
+                max_request_duration: 0,
+                slow_request_threshold: 0,
+                cleanup_interval: default_cleanup_interval(),
+                liveness_timeout: default_liveness_timeout(),
+                circuit_breaker_failures: default_circuit_breaker_failures(),
+                circuit_breaker_window: default_circuit_breaker_window(),
+                circuit_breaker_cooldown: default_circuit_breaker_cooldown(),
+                allow_standby: false,
+                maintenance: false,
             },
             limits: LimitsConfig {
                 max_workstations: default_max_workstations(),
+                max_streams_per_connection: default_max_streams_per_connection(),
+                max_queued_requests: 0,
+                queue_timeout: 0,
+                max_pending_requests: 0,
+                requests_per_second: 0,
+                max_new_connections_per_sec: 0,
+                burst: default_burst(),
+                max_frame_bytes: default_max_frame_bytes(),
+                max_ws_message_bytes: default_max_ws_message_bytes(),
+                max_header_bytes: default_max_header_bytes(),
+                max_bytes_per_workstation: 0,
+                max_bytes_per_sec_per_workstation: 0,
             },
+            h2: H2Config::default(),
+            quic: QuicConfig::default(),
+            congestion_controller_error: None,
         }
     }
 }
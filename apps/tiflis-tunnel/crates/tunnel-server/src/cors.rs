@@ -0,0 +1,179 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+use crate::config::CorsConfig;
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    response::Response,
+};
+
+/// CORS rules applied at the tunnel edge, built from `server.cors`.
+#[derive(Debug, Clone)]
+pub struct CorsPolicy {
+    allowed_origins: Vec<String>,
+    any_origin: bool,
+    allowed_methods: HeaderValue,
+    /// `None` echoes the headers the browser asks for.
+    allowed_headers: Option<HeaderValue>,
+    allow_credentials: bool,
+}
+
+impl CorsPolicy {
+    /// Returns `None` when CORS handling is disabled.
+    pub fn from_config(config: &CorsConfig) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+        let join = |values: &[String]| HeaderValue::from_str(&values.join(", ")).ok();
+        Some(Self {
+            allowed_origins: config.allowed_origins.clone(),
+            any_origin: config.allowed_origins.iter().any(|o| o == "*"),
+            allowed_methods: join(&config.allowed_methods)
+                .unwrap_or(HeaderValue::from_static("GET")),
+            allowed_headers: match config.allowed_headers.as_slice() {
+                [] => None,
+                headers => join(headers),
+            },
+            allow_credentials: config.allow_credentials,
+        })
+    }
+
+    fn allowed_origin(&self, origin: &HeaderValue) -> Option<HeaderValue> {
+        if self.any_origin {
+            return Some(HeaderValue::from_static("*"));
+        }
+        let origin_str = origin.to_str().ok()?;
+        self.allowed_origins
+            .iter()
+            .any(|o| o.eq_ignore_ascii_case(origin_str))
+            .then(|| origin.clone())
+    }
+
+    /// Answers a preflight request, or returns `None` for anything else. A
+    /// preflight from an origin that is not allowed gets no allow headers, so
+    /// the browser blocks the real request.
+    pub fn preflight(&self, method: &Method, headers: &HeaderMap) -> Option<Response> {
+        if method != Method::OPTIONS || !headers.contains_key(header::ACCESS_CONTROL_REQUEST_METHOD)
+        {
+            return None;
+        }
+        let origin = headers.get(header::ORIGIN)?;
+
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = StatusCode::NO_CONTENT;
+        let out = response.headers_mut();
+        out.insert(header::VARY, HeaderValue::from_static("origin"));
+        let Some(allow_origin) = self.allowed_origin(origin) else {
+            return Some(response);
+        };
+
+        out.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+        out.insert(
+            header::ACCESS_CONTROL_ALLOW_METHODS,
+            self.allowed_methods.clone(),
+        );
+        let allow_headers = self
+            .allowed_headers
+            .clone()
+            .or_else(|| headers.get(header::ACCESS_CONTROL_REQUEST_HEADERS).cloned());
+        if let Some(allow_headers) = allow_headers {
+            out.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, allow_headers);
+        }
+        if self.allow_credentials {
+            out.insert(
+                header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+        Some(response)
+    }
+
+    /// Adds allow headers to a tunneled response for a cross-origin request,
+    /// replacing any the workstation set.
+    pub fn apply(&self, origin: &HeaderValue, headers: &mut HeaderMap) {
+        let Some(allow_origin) = self.allowed_origin(origin) else {
+            return;
+        };
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+        headers.append(header::VARY, HeaderValue::from_static("origin"));
+        if self.allow_credentials {
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+    }
+}
+
+/// Origins allowed to open WebSockets through the tunnel, from
+/// `server.ws_allowed_origins`.
+#[derive(Debug, Clone)]
+pub struct OriginAllowlist {
+    /// Lowercased; `https://*.example.com` matches any subdomain.
+    patterns: Vec<String>,
+}
+
+impl OriginAllowlist {
+    /// Returns `None` when every origin is allowed: an empty list or `*`.
+    pub fn from_config(origins: &[String]) -> Option<Self> {
+        if origins.is_empty() || origins.iter().any(|o| o == "*") {
+            return None;
+        }
+        Some(Self {
+            patterns: origins.iter().map(|o| o.to_ascii_lowercase()).collect(),
+        })
+    }
+
+    pub fn allows(&self, origin: &str) -> bool {
+        let origin = origin.to_ascii_lowercase();
+        self.patterns
+            .iter()
+            .any(|pattern| origin_matches(pattern, &origin))
+    }
+}
+
+fn origin_matches(pattern: &str, origin: &str) -> bool {
+    let Some((scheme, domain)) = pattern.split_once("://*.") else {
+        return pattern == origin;
+    };
+    let Some((origin_scheme, host)) = origin.split_once("://") else {
+        return false;
+    };
+    origin_scheme == scheme
+        && host
+            .strip_suffix(domain)
+            .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_origin_allowlist_matches_exact_and_wildcard() {
+        let list = OriginAllowlist::from_config(&[
+            "https://app.example.com".to_string(),
+            "https://*.Example.org".to_string(),
+        ])
+        .unwrap();
+        assert!(list.allows("https://app.example.com"));
+        assert!(list.allows("HTTPS://APP.EXAMPLE.COM"));
+        assert!(list.allows("https://a.example.org"));
+        assert!(list.allows("https://a.b.example.org"));
+
+        assert!(!list.allows("http://app.example.com"));
+        assert!(!list.allows("https://app.example.com.evil.com"));
+        assert!(!list.allows("https://example.org"));
+        assert!(!list.allows("https://evilexample.org"));
+        assert!(!list.allows("http://a.example.org"));
+        assert!(!list.allows("https://a.example.org:8443"));
+        assert!(!list.allows("null"));
+    }
+
+    #[test]
+    fn test_origin_allowlist_defaults_to_any_origin() {
+        assert!(OriginAllowlist::from_config(&[]).is_none());
+        assert!(OriginAllowlist::from_config(&["*".to_string()]).is_none());
+    }
+}
@@ -0,0 +1,251 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+/// A failure answered by the tunnel itself rather than by the workstation.
+/// Rendered as a bare status, or as a JSON envelope for callers that accept
+/// `application/json` (see [`json_errors`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProxyError {
+    pub status: StatusCode,
+    pub code: &'static str,
+    /// Human-readable explanation sent in the JSON envelope.
+    pub message: &'static str,
+    /// Seconds sent as `Retry-After`.
+    pub retry_after: Option<u64>,
+}
+
+impl ProxyError {
+    pub const RATE_LIMITED: Self = Self {
+        status: StatusCode::TOO_MANY_REQUESTS,
+        code: "RATE_LIMITED",
+        message: "too many requests from this address",
+        retry_after: None,
+    };
+
+    pub const ROUTE_NOT_FOUND: Self = Self {
+        status: StatusCode::NOT_FOUND,
+        code: "ROUTE_NOT_FOUND",
+        message: "no tunnel route matches this path",
+        retry_after: None,
+    };
+
+    pub const CIRCUIT_OPEN: Self = Self {
+        status: StatusCode::SERVICE_UNAVAILABLE,
+        code: "CIRCUIT_OPEN",
+        message: "the workstation's local backend keeps failing; retry later",
+        retry_after: None,
+    };
+
+    pub const BACKEND_UNHEALTHY: Self = Self {
+        status: StatusCode::SERVICE_UNAVAILABLE,
+        code: "BACKEND_UNHEALTHY",
+        message: "the workstation reports its local backend as down",
+        retry_after: None,
+    };
+
+    pub const WORKSTATION_DRAINING: Self = Self {
+        status: StatusCode::SERVICE_UNAVAILABLE,
+        code: "WORKSTATION_DRAINING",
+        message: "the workstation is draining for maintenance",
+        retry_after: None,
+    };
+
+    pub const MAINTENANCE: Self = Self {
+        status: StatusCode::SERVICE_UNAVAILABLE,
+        code: "MAINTENANCE",
+        message: "the tunnel is down for maintenance; retry later",
+        retry_after: None,
+    };
+
+    pub const QUEUE_FULL: Self = Self {
+        status: StatusCode::SERVICE_UNAVAILABLE,
+        code: "QUEUE_FULL",
+        message: "too many requests are waiting for this workstation",
+        retry_after: None,
+    };
+
+    pub const TOO_MANY_PENDING: Self = Self {
+        status: StatusCode::SERVICE_UNAVAILABLE,
+        code: "TOO_MANY_PENDING",
+        message: "the server is waiting on too many requests; retry later",
+        retry_after: None,
+    };
+
+    pub const QUEUE_TIMEOUT: Self = Self {
+        status: StatusCode::GATEWAY_TIMEOUT,
+        code: "QUEUE_TIMEOUT",
+        message: "the request waited too long for a free workstation slot",
+        retry_after: None,
+    };
+
+    pub const ORIGIN_NOT_ALLOWED: Self = Self {
+        status: StatusCode::FORBIDDEN,
+        code: "ORIGIN_NOT_ALLOWED",
+        message: "this origin may not open WebSockets through the tunnel",
+        retry_after: None,
+    };
+
+    pub const METHOD_NOT_ALLOWED: Self = Self {
+        status: StatusCode::METHOD_NOT_ALLOWED,
+        code: "METHOD_NOT_ALLOWED",
+        message: "the tunnel does not forward this method",
+        retry_after: None,
+    };
+
+    pub const WORKSTATION_UNAVAILABLE: Self = Self {
+        status: StatusCode::SERVICE_UNAVAILABLE,
+        code: "WORKSTATION_UNAVAILABLE",
+        message: "no workstation is connected under this id; retry later",
+        retry_after: None,
+    };
+
+    pub const WORKSTATION_RECONNECTING: Self = Self {
+        status: StatusCode::SERVICE_UNAVAILABLE,
+        code: "WORKSTATION_RECONNECTING",
+        message: "the workstation is reconnecting; retry shortly",
+        retry_after: None,
+    };
+
+    pub const INVALID_PATH: Self = Self {
+        status: StatusCode::BAD_REQUEST,
+        code: "INVALID_PATH",
+        message: "the request path escapes the root or is malformed",
+        retry_after: None,
+    };
+
+    pub const HEADERS_TOO_LARGE: Self = Self {
+        status: StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+        code: "HEADERS_TOO_LARGE",
+        message: "the request headers exceed the tunnel limit",
+        retry_after: None,
+    };
+
+    pub const fn with_retry_after(self, secs: u64) -> Self {
+        Self {
+            retry_after: Some(secs),
+            ..self
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self.code {
+            "BAD_REQUEST" => "the request could not be forwarded",
+            "FORBIDDEN" => "the workstation refused the target",
+            "ORIGIN_NOT_ALLOWED" => "this origin may not open WebSockets through the tunnel",
+            "WORKSTATION_NOT_FOUND" => "no workstation is connected under this id",
+            "PAYLOAD_TOO_LARGE" => "the request exceeds the tunnel frame limit",
+            "INVALID_PATH" => "the request path escapes the root or is malformed",
+            "HEADERS_TOO_LARGE" => "the request headers exceed the tunnel limit",
+            "METHOD_NOT_ALLOWED" => "the tunnel does not forward this method",
+            "QUOTA_EXCEEDED" => "the workstation exceeded its traffic quota",
+            "RATE_LIMITED" => "too many requests from this address",
+            "ROUTE_NOT_FOUND" => "no tunnel route matches this path",
+            "CIRCUIT_OPEN" => "the workstation's local backend keeps failing; retry later",
+            "BACKEND_UNHEALTHY" => "the workstation reports its local backend as down",
+            "WORKSTATION_DRAINING" => "the workstation is draining for maintenance",
+            "QUEUE_FULL" => "too many requests are waiting for this workstation",
+            "TOO_MANY_PENDING" => "the server is waiting on too many requests; retry later",
+            "QUEUE_TIMEOUT" => "the request waited too long for a free workstation slot",
+            "WORKSTATION_UNAVAILABLE" => "no workstation is connected under this id; retry later",
+            "WORKSTATION_RECONNECTING" => "the workstation is reconnecting; retry shortly",
+            "PROTOCOL_ERROR" => "the workstation sent an unexpected reply",
+            "WORKSTATION_UNREACHABLE" => "the workstation or its local backend is unreachable",
+            "WORKSTATION_TIMEOUT" => "the workstation did not answer in time",
+            _ => "the request failed",
+        }
+    }
+}
+
+impl From<StatusCode> for ProxyError {
+    fn from(status: StatusCode) -> Self {
+        let (code, message) = match status {
+            StatusCode::BAD_REQUEST => ("BAD_REQUEST", "the request could not be forwarded"),
+            StatusCode::FORBIDDEN => ("FORBIDDEN", "the workstation refused the target"),
+            StatusCode::NOT_FOUND => (
+                "WORKSTATION_NOT_FOUND",
+                "no workstation is connected under this id",
+            ),
+            StatusCode::PAYLOAD_TOO_LARGE => (
+                "PAYLOAD_TOO_LARGE",
+                "the request exceeds the tunnel frame limit",
+            ),
+            StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE => (
+                "HEADERS_TOO_LARGE",
+                "the request headers exceed the tunnel limit",
+            ),
+            StatusCode::TOO_MANY_REQUESTS => (
+                "QUOTA_EXCEEDED",
+                "the workstation exceeded its traffic quota",
+            ),
+            StatusCode::INTERNAL_SERVER_ERROR => {
+                ("PROTOCOL_ERROR", "the workstation sent an unexpected reply")
+            }
+            StatusCode::BAD_GATEWAY => (
+                "WORKSTATION_UNREACHABLE",
+                "the workstation or its local backend is unreachable",
+            ),
+            StatusCode::GATEWAY_TIMEOUT => (
+                "WORKSTATION_TIMEOUT",
+                "the workstation did not answer in time",
+            ),
+            _ => ("PROXY_ERROR", "the request failed"),
+        };
+        Self {
+            status,
+            code,
+            message,
+            retry_after: None,
+        }
+    }
+}
+
+impl IntoResponse for ProxyError {
+    fn into_response(self) -> Response {
+        let mut response = self.status.into_response();
+        if let Some(secs) = self.retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, secs.into());
+        }
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+/// Rewrites tunnel-generated failures as
+/// `{"error": {"code": "...", "message": "..."}}` when the caller accepts JSON.
+/// Responses relayed from workstations are left untouched.
+pub async fn json_errors(request: Request, next: Next) -> Response {
+    let wants_json = request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/json"));
+
+    let response = next.run(request).await;
+    let Some(error) = response.extensions().get::<ProxyError>().copied() else {
+        return response;
+    };
+    if !wants_json {
+        return response;
+    }
+
+    let body = serde_json::json!({
+        "error": { "code": error.code, "message": error.message }
+    });
+    let (mut parts, _) = response.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    Response::from_parts(parts, Body::from(body.to_string()))
+}
@@ -0,0 +1,121 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+//! Gzip decoding for responses inflated at the edge.
+
+use flate2::read::MultiGzDecoder;
+use std::io::Read;
+
+/// Whether a request's `Accept-Encoding` allows a gzip response. A missing
+/// header accepts any encoding.
+pub fn accepts_gzip(accept_encoding: Option<&str>) -> bool {
+    let Some(value) = accept_encoding else {
+        return true;
+    };
+    let mut wildcard = None;
+    for item in value.split(',') {
+        let mut parts = item.split(';');
+        let coding = parts.next().unwrap_or_default().trim();
+        let allowed = parts
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .all(|q| q.trim().parse::<f32>().map_or(true, |q| q > 0.0));
+        if coding.eq_ignore_ascii_case("gzip") || coding.eq_ignore_ascii_case("x-gzip") {
+            return allowed;
+        }
+        if coding == "*" {
+            wildcard = Some(allowed);
+        }
+    }
+    wildcard.unwrap_or(false)
+}
+
+/// Decodes a gzip body of one or more members. Fails if the data is malformed,
+/// a checksum does not match, or the output would exceed `limit` bytes.
+pub fn decompress(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    MultiGzDecoder::new(data)
+        .take(limit as u64 + 1)
+        .read_to_end(&mut out)
+        .map_err(|e| e.to_string())?;
+    if out.len() > limit {
+        return Err(format!("decompressed size exceeds {} bytes", limit));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// "hello, tunnel" compressed with level 0, with a file name in the header.
+    const STORED: &[u8] = &[
+        0x1f, 0x8b, 0x08, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x78, 0x2e, 0x74, 0x78, 0x74,
+        0x00, 0x01, 0x0d, 0x00, 0xf2, 0xff, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x2c, 0x20, 0x74, 0x75,
+        0x6e, 0x6e, 0x65, 0x6c, 0xbb, 0x40, 0x61, 0xfc, 0x0d, 0x00, 0x00, 0x00,
+    ];
+
+    /// "hello, tunnel" in a fixed Huffman block.
+    const FIXED: &[u8] = &[
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xcb, 0x48, 0xcd, 0xc9, 0xc9,
+        0xd7, 0x51, 0x28, 0x29, 0xcd, 0xcb, 0x4b, 0xcd, 0x01, 0x00, 0xbb, 0x40, 0x61, 0xfc, 0x0d,
+        0x00, 0x00, 0x00,
+    ];
+
+    /// `lines()` in a dynamic Huffman block.
+    const DYNAMIC: &[u8] = &[
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x9d, 0xcb, 0xd7, 0x15, 0x40,
+        0x30, 0x00, 0x05, 0xd0, 0x7f, 0x53, 0xbc, 0x11, 0xf4, 0xb6, 0x8d, 0x12, 0x84, 0x48, 0xb4,
+        0x68, 0xd3, 0x3b, 0x6c, 0xe0, 0x7d, 0xdf, 0x73, 0x95, 0xd4, 0x02, 0x6e, 0x8e, 0xad, 0x13,
+        0x98, 0xad, 0xac, 0x06, 0x94, 0x8b, 0x39, 0x34, 0x1a, 0x73, 0xa2, 0xb7, 0xe3, 0xb4, 0xc2,
+        0xec, 0x62, 0xf9, 0x58, 0x15, 0xf7, 0x85, 0xda, 0xb4, 0x8e, 0x7a, 0x8f, 0x47, 0x1c, 0x9f,
+        0x38, 0x01, 0x71, 0x42, 0xe2, 0x44, 0xc4, 0x89, 0x89, 0x93, 0x10, 0x27, 0x25, 0x4e, 0xf6,
+        0xef, 0x3c, 0x1d, 0xe0, 0xe9, 0x84, 0x08, 0x02, 0x00, 0x00,
+    ];
+
+    fn lines() -> String {
+        (0..10)
+            .map(|i| format!("line {}: the quick brown fox jumps over the lazy dog\n", i))
+            .collect()
+    }
+
+    #[test]
+    fn test_decompress_block_types() {
+        assert_eq!(decompress(STORED, 1024).unwrap(), b"hello, tunnel");
+        assert_eq!(decompress(FIXED, 1024).unwrap(), b"hello, tunnel");
+        assert_eq!(decompress(DYNAMIC, 1024).unwrap(), lines().as_bytes());
+    }
+
+    #[test]
+    fn test_decompress_concatenated_members() {
+        let data = [FIXED, STORED].concat();
+        assert_eq!(
+            decompress(&data, 1024).unwrap(),
+            b"hello, tunnelhello, tunnel"
+        );
+    }
+
+    #[test]
+    fn test_decompress_rejects_bad_input() {
+        assert!(decompress(b"plain text, not gzip", 1024).is_err());
+        assert!(decompress(&FIXED[..FIXED.len() - 4], 1024).is_err());
+
+        let mut corrupt = FIXED.to_vec();
+        corrupt[FIXED.len() - 8] ^= 1;
+        assert!(decompress(&corrupt, 1024).is_err());
+
+        let err = decompress(DYNAMIC, 100).unwrap_err();
+        assert!(err.contains("exceeds"), "{}", err);
+    }
+
+    #[test]
+    fn test_accepts_gzip() {
+        assert!(accepts_gzip(None));
+        assert!(accepts_gzip(Some("gzip, deflate, br")));
+        assert!(accepts_gzip(Some("br;q=1.0, GZIP;q=0.5")));
+        assert!(accepts_gzip(Some("*")));
+        assert!(!accepts_gzip(Some("identity")));
+        assert!(!accepts_gzip(Some("")));
+        assert!(!accepts_gzip(Some("gzip;q=0, *")));
+        assert!(!accepts_gzip(Some("br, *;q=0")));
+    }
+}
@@ -0,0 +1,143 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+//! Interim `1xx` responses such as `103 Early Hints` on HTTP/1.1 connections.
+//! hyper's server sends no informational response besides `100 Continue`, so
+//! these are written to the connection directly while hyper waits for the
+//! handler's final response.
+
+use axum::http::{HeaderMap, StatusCode};
+use std::future::poll_fn;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// A connection whose reads and writes share a lock with its
+/// [`InterimResponses`].
+pub struct InterimIo<T> {
+    io: Arc<Mutex<T>>,
+}
+
+/// Writes interim responses on the HTTP/1.1 connection a request arrived on.
+/// Handlers find it among the request extensions.
+#[derive(Clone)]
+pub struct InterimResponses {
+    io: Arc<Mutex<dyn AsyncWrite + Send + Unpin>>,
+}
+
+/// Wraps `io` for hyper, along with the handle that writes interim responses
+/// on it.
+pub fn wrap<T>(io: T) -> (InterimIo<T>, InterimResponses)
+where
+    T: AsyncWrite + Send + Unpin + 'static,
+{
+    let io = Arc::new(Mutex::new(io));
+    (InterimIo { io: io.clone() }, InterimResponses { io })
+}
+
+impl InterimResponses {
+    /// Writes a `status` response head with `headers`. Only call this while
+    /// the request it belongs to has no final response yet.
+    pub async fn send(&self, status: StatusCode, headers: &HeaderMap) -> io::Result<()> {
+        let head = response_head(status, headers);
+        let mut written = 0;
+        while written < head.len() {
+            let n = poll_fn(|cx| {
+                Pin::new(&mut *self.io.lock().unwrap()).poll_write(cx, &head[written..])
+            })
+            .await?;
+            if n == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            written += n;
+        }
+        poll_fn(|cx| Pin::new(&mut *self.io.lock().unwrap()).poll_flush(cx)).await
+    }
+}
+
+fn response_head(status: StatusCode, headers: &HeaderMap) -> Vec<u8> {
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\n",
+        status.as_str(),
+        status.canonical_reason().unwrap_or("")
+    )
+    .into_bytes();
+    for (name, value) in headers {
+        head.extend_from_slice(name.as_str().as_bytes());
+        head.extend_from_slice(b": ");
+        head.extend_from_slice(value.as_bytes());
+        head.extend_from_slice(b"\r\n");
+    }
+    head.extend_from_slice(b"\r\n");
+    head
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for InterimIo<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.io.lock().unwrap()).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for InterimIo<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut *self.io.lock().unwrap()).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut *self.io.lock().unwrap()).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.io.lock().unwrap().is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.io.lock().unwrap()).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.io.lock().unwrap()).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_interim_response_precedes_later_writes() {
+        let (near, mut far) = tokio::io::duplex(1024);
+        let (mut io, interim) = wrap(near);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("link", "</app.css>; rel=preload".parse().unwrap());
+        interim
+            .send(StatusCode::from_u16(103).unwrap(), &headers)
+            .await
+            .unwrap();
+        io.write_all(b"HTTP/1.1 200 OK\r\n\r\n").await.unwrap();
+        drop((io, interim));
+
+        let mut received = String::new();
+        far.read_to_string(&mut received).await.unwrap();
+        assert_eq!(
+            received,
+            "HTTP/1.1 103 Early Hints\r\nlink: </app.css>; rel=preload\r\n\r\nHTTP/1.1 200 OK\r\n\r\n"
+        );
+    }
+}
@@ -1,8 +1,28 @@
 // Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
 // Licensed under the FSL-1.1-NC.
 
+pub mod access_log;
+pub mod admin;
+pub mod auth;
+pub mod bind;
+pub mod cache;
+pub mod cert_store;
+pub mod circuit_breaker;
 pub mod config;
+pub mod cors;
+pub mod error;
+pub mod gzip;
+pub mod interim;
+pub mod path;
 pub mod pending;
 pub mod proxy;
+pub mod proxy_protocol;
+pub mod rate_limit;
 pub mod registry;
 pub mod server;
+pub mod spool;
+pub mod stream_id;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod url_rewrite;
+pub mod workstation_counter;
@@ -13,6 +13,10 @@ use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 struct Args {
     #[arg(short, long, value_name = "FILE")]
     config: Option<PathBuf>,
+
+    /// Validate the configuration and check that the ports can be bound, then exit
+    #[arg(long)]
+    check: bool,
 }
 
 #[tokio::main]
@@ -28,12 +32,20 @@ async fn main() -> anyhow::Result<()> {
         .init();
 
     let args = Args::parse();
-    let config = config::Config::load(args.config)?;
+    let config = config::Config::load(args.config.clone())?;
+
+    if args.check {
+        server::TunnelServer::new(config).check().await?;
+        tracing::info!("Configuration OK");
+        return Ok(());
+    }
 
     tracing::info!("Starting Tiflis Tunnel Server");
     tracing::info!("Domain: {}", config.server.domain);
     tracing::info!("TLS enabled: {}", config.tls.enabled);
 
     let server = server::TunnelServer::init(config).await?;
+    #[cfg(unix)]
+    server.clone().reload_on_sighup(args.config)?;
     server.run().await
 }
@@ -0,0 +1,105 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+use crate::config::{PathNormalizationConfig, TrailingSlash};
+use crate::error::ProxyError;
+
+/// Builds the path forwarded to the workstation from the part of the public
+/// URL after `/t/<id>/`. With normalization enabled, empty and `.` segments
+/// are dropped, `..` removes the segment before it and the trailing slash
+/// follows the configured policy. A `..` above the root or a NUL byte is
+/// refused whether or not normalization is enabled.
+pub fn normalize_path(path: &str, config: &PathNormalizationConfig) -> Result<String, ProxyError> {
+    if path.contains('\0') {
+        return Err(ProxyError::INVALID_PATH);
+    }
+
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop().ok_or(ProxyError::INVALID_PATH)?;
+            }
+            segment => segments.push(segment),
+        }
+    }
+    if !config.enabled {
+        return Ok(format!("/{}", path));
+    }
+
+    let last = path.rsplit('/').next().unwrap_or_default();
+    let trailing = match config.trailing_slash {
+        TrailingSlash::Preserve => path.ends_with('/') || last == "." || last == "..",
+        TrailingSlash::Add => true,
+        TrailingSlash::Remove => false,
+    };
+    let mut normalized = format!("/{}", segments.join("/"));
+    if trailing && !segments.is_empty() {
+        normalized.push('/');
+    }
+    Ok(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(path: &str, trailing_slash: TrailingSlash) -> Result<String, ProxyError> {
+        normalize_path(
+            path,
+            &PathNormalizationConfig {
+                enabled: true,
+                trailing_slash,
+            },
+        )
+    }
+
+    #[test]
+    fn test_collapses_duplicate_slashes() {
+        assert_eq!(normalize("/a//b", TrailingSlash::Preserve).unwrap(), "/a/b");
+        assert_eq!(
+            normalize("a///b/", TrailingSlash::Preserve).unwrap(),
+            "/a/b/"
+        );
+    }
+
+    #[test]
+    fn test_resolves_dot_segments() {
+        assert_eq!(
+            normalize("a/./b/../c", TrailingSlash::Preserve).unwrap(),
+            "/a/c"
+        );
+        assert_eq!(normalize("a/b/..", TrailingSlash::Preserve).unwrap(), "/a/");
+        assert_eq!(normalize("a/..", TrailingSlash::Preserve).unwrap(), "/");
+    }
+
+    #[test]
+    fn test_rejects_escaping_the_root() {
+        for path in ["../etc/passwd", "a/../../b", "..", "a/\0b"] {
+            assert_eq!(
+                normalize(path, TrailingSlash::Preserve),
+                Err(ProxyError::INVALID_PATH),
+                "{:?}",
+                path
+            );
+        }
+        let disabled = PathNormalizationConfig {
+            enabled: false,
+            trailing_slash: TrailingSlash::Preserve,
+        };
+        assert_eq!(
+            normalize_path("../secret", &disabled),
+            Err(ProxyError::INVALID_PATH)
+        );
+        assert_eq!(normalize_path("a//b/", &disabled).unwrap(), "/a//b/");
+    }
+
+    #[test]
+    fn test_trailing_slash_policy() {
+        assert_eq!(normalize("a/b", TrailingSlash::Add).unwrap(), "/a/b/");
+        assert_eq!(normalize("a/b/", TrailingSlash::Remove).unwrap(), "/a/b");
+        assert_eq!(normalize("", TrailingSlash::Remove).unwrap(), "/");
+        assert_eq!(normalize("", TrailingSlash::Add).unwrap(), "/");
+    }
+}
@@ -3,32 +3,57 @@
 
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::{oneshot, RwLock};
 use tunnel_core::Message;
 use uuid::Uuid;
 
 pub type ResponseSender = oneshot::Sender<Message>;
 
+struct PendingEntry {
+    sender: ResponseSender,
+    registered_at: Instant,
+}
+
 pub struct PendingRequests {
-    requests: Arc<RwLock<HashMap<Uuid, ResponseSender>>>,
+    requests: Arc<RwLock<HashMap<Uuid, PendingEntry>>>,
+    /// Most entries waiting at once across all workstations. 0 is unbounded.
+    max_entries: usize,
 }
 
 impl PendingRequests {
     pub fn new() -> Self {
+        Self::with_limit(0)
+    }
+
+    /// Holds at most `max_entries` waiting requests; 0 is unbounded.
+    pub fn with_limit(max_entries: usize) -> Self {
         Self {
             requests: Arc::new(RwLock::new(HashMap::new())),
+            max_entries,
         }
     }
 
-    pub async fn register(&self, stream_id: Uuid, sender: ResponseSender) {
+    /// Returns `false`, dropping `sender`, when the limit is reached.
+    pub async fn register(&self, stream_id: Uuid, sender: ResponseSender) -> bool {
         let mut requests = self.requests.write().await;
-        requests.insert(stream_id, sender);
+        if self.max_entries > 0 && requests.len() >= self.max_entries {
+            return false;
+        }
+        requests.insert(
+            stream_id,
+            PendingEntry {
+                sender,
+                registered_at: Instant::now(),
+            },
+        );
+        true
     }
 
     pub async fn complete(&self, stream_id: Uuid, response: Message) -> bool {
         let mut requests = self.requests.write().await;
-        if let Some(sender) = requests.remove(&stream_id) {
-            sender.send(response).is_ok()
+        if let Some(entry) = requests.remove(&stream_id) {
+            entry.sender.send(response).is_ok()
         } else {
             false
         }
@@ -39,6 +64,27 @@ impl PendingRequests {
         requests.remove(&stream_id);
     }
 
+    /// Drops entries registered more than `max_age` ago, returning how many
+    /// were removed. A safety net for callers that never completed or cancelled.
+    pub async fn cleanup_expired(&self, max_age: Duration) -> usize {
+        let mut requests = self.requests.write().await;
+        let before = requests.len();
+        requests.retain(|_, entry| entry.registered_at.elapsed() <= max_age);
+        before - requests.len()
+    }
+
+    /// Stream ids of the waiting requests with how long each has waited,
+    /// oldest first.
+    pub async fn snapshot(&self) -> Vec<(Uuid, Duration)> {
+        let requests = self.requests.read().await;
+        let mut entries: Vec<_> = requests
+            .iter()
+            .map(|(id, entry)| (*id, entry.registered_at.elapsed()))
+            .collect();
+        entries.sort_by_key(|&(_, age)| std::cmp::Reverse(age));
+        entries
+    }
+
     pub async fn count(&self) -> usize {
         let requests = self.requests.read().await;
         requests.len()
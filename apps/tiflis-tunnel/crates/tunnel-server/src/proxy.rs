@@ -1,28 +1,186 @@
 // Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
 // Licensed under the FSL-1.1-NC.
 
+use crate::cache::ResponseCache;
+use crate::config::{self, Config, PathNormalizationConfig};
+use crate::cors::{CorsPolicy, OriginAllowlist};
+use crate::error::ProxyError;
+use crate::gzip;
+use crate::interim::InterimResponses;
+use crate::path::normalize_path;
 use crate::pending::PendingRequests;
-use crate::registry::WorkstationRegistry;
+use crate::rate_limit::BandwidthLimiter;
+use crate::registry::{
+    QueueRejection, StreamPermit, TrafficCounters, Unavailable, WorkstationInfo,
+    WorkstationRegistry,
+};
+use crate::spool::{CollectedBody, Spool, SpooledBody};
+use crate::stream_id::StreamIdGenerator;
+use crate::url_rewrite::UrlRewriter;
 use axum::body::Bytes;
 use axum::{
     body::Body,
-    extract::{Path, State, WebSocketUpgrade},
-    http::{HeaderMap, Method, StatusCode},
-    response::Response,
+    extract::{ws::CloseFrame, Path, Request, State, WebSocketUpgrade},
+    http::{header, HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Response},
 };
+use http_body_util::StreamBody;
+use hyper::body::Frame;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::time::timeout;
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::time::{timeout, timeout_at, Instant};
+use tracing::{debug, info, warn};
 use tunnel_core::{
-    codec, HttpRequestMessage, Message, SseOpenMessage, WsCloseMessage, WsDataMessage,
-    WsOpenMessage,
+    codec, ConnectOpenMessage, H2CloseMessage, H2DataMessage, H2OpenMessage, HopByHop,
+    HttpBodyMessage, HttpRequestMessage, HttpResponseMessage, Message, SseCloseMessage,
+    SseOpenMessage, WsCloseMessage, WsOpenMessage, WS_CLOSE_MESSAGE_TOO_BIG, WS_FRAGMENT_BYTES,
 };
 use uuid::Uuid;
 
+/// Per-request limits that `TunnelServer::reload` can change while
+/// connections stay up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProxyLimits {
+    pub request_timeout: Duration,
+    /// Idle interval after which an SSE comment is sent to keep intermediaries
+    /// from closing the stream. `None` disables heartbeats.
+    pub sse_heartbeat_interval: Option<Duration>,
+    /// Lifetime after which the server closes an SSE stream. `None` is unlimited.
+    pub max_sse_duration: Option<Duration>,
+    /// Total time an HTTP or HTTP/2 request may take, from arrival until its
+    /// response body ends. `None` is unlimited.
+    pub max_request_duration: Option<Duration>,
+    /// Round trip above which an HTTP request is logged as a warning. `None`
+    /// keeps every request at debug level.
+    pub slow_request_threshold: Option<Duration>,
+    pub max_ws_message_bytes: usize,
+    /// Combined size of request header names and values. 0 disables.
+    pub max_header_bytes: usize,
+    /// HTTP requests that may wait for a free stream slot per workstation
+    /// before more are refused with `503`. 0 is unbounded.
+    pub max_queued_requests: usize,
+    /// How long a queued HTTP request waits for a slot before `504`. `None`
+    /// waits until the request itself times out.
+    pub queue_timeout: Option<Duration>,
+    /// Body bytes a workstation may proxy before it is refused. 0 disables.
+    pub max_bytes_per_workstation: u64,
+    /// New requests are refused with `503` while set.
+    pub maintenance: bool,
+}
+
+impl ProxyLimits {
+    pub fn from_config(config: &Config) -> Self {
+        let secs = |secs| (secs > 0).then(|| Duration::from_secs(secs));
+        let millis = |millis| (millis > 0).then(|| Duration::from_millis(millis));
+        Self {
+            request_timeout: Duration::from_secs(config.reliability.request_timeout),
+            sse_heartbeat_interval: secs(config.reliability.sse_heartbeat_interval),
+            max_sse_duration: secs(config.reliability.max_sse_duration),
+            max_request_duration: secs(config.reliability.max_request_duration),
+            slow_request_threshold: millis(config.reliability.slow_request_threshold),
+            max_ws_message_bytes: config.limits.max_ws_message_bytes,
+            max_header_bytes: config.limits.max_header_bytes,
+            max_queued_requests: config.limits.max_queued_requests,
+            queue_timeout: millis(config.limits.queue_timeout),
+            max_bytes_per_workstation: config.limits.max_bytes_per_workstation,
+            maintenance: config.reliability.maintenance,
+        }
+    }
+}
+
 pub struct ProxyState {
     pub registry: Arc<WorkstationRegistry>,
     pub pending: Arc<PendingRequests>,
-    pub request_timeout: Duration,
+    /// Latest limits; replaced on reload.
+    pub limits: watch::Receiver<ProxyLimits>,
+    pub h2_path_prefixes: Vec<String>,
+    /// Set on every HTTP and SSE response, replacing workstation values.
+    pub add_response_headers: HeaderMap,
+    /// Lowercased header names dropped from workstation responses.
+    pub remove_response_headers: Vec<String>,
+    /// Edge CORS handling. `None` leaves CORS to the workstation.
+    pub cors: Option<CorsPolicy>,
+    /// Origins allowed to open WebSockets. `None` allows any origin.
+    pub ws_allowed_origins: Option<OriginAllowlist>,
+    /// Inflate gzip responses for callers that do not accept gzip.
+    pub decompress_responses: bool,
+    /// `404` or `503` for workstations that are not connected. Ones still in
+    /// their grace period always get `503`.
+    pub unknown_workstation_status: StatusCode,
+    /// Methods forwarded by `/t/` routes. Empty allows every method.
+    pub allowed_methods: Vec<Method>,
+    pub path_normalization: PathNormalizationConfig,
+    /// Edge cache for `GET` responses. `None` sends every request on.
+    pub cache: Option<Arc<ResponseCache>>,
+    /// Moves HTTP bodies over `server.spool_threshold` to disk. `None` keeps
+    /// them in memory.
+    pub spool: Option<Spool>,
+    /// `server.url_rewrite` rules for HTML and CSS bodies. `None` relays
+    /// bodies unchanged.
+    pub url_rewrite: Option<UrlRewriter>,
+    /// Source of the ids given to proxied requests and streams.
+    pub stream_ids: Arc<dyn StreamIdGenerator>,
+    /// `https` with TLS enabled, else `http`; forwarded to workstations.
+    pub public_scheme: &'static str,
+    /// `server.domain` with any non-default port, forwarded to workstations
+    /// as the public host.
+    pub public_host: String,
+    /// `server.base_path` without its trailing slash; empty at the root.
+    pub base_path: String,
+}
+
+impl ProxyState {
+    pub fn limits(&self) -> ProxyLimits {
+        *self.limits.borrow()
+    }
+}
+
+const WS_CHANNEL_CAPACITY: usize = 32;
+
+/// Largest body inflated for a caller that does not accept gzip; bigger ones
+/// are relayed compressed.
+const MAX_DECOMPRESSED_BYTES: usize = 64 << 20;
+
+/// `Retry-After` sent with `503` for workstations that are not connected.
+const UNKNOWN_WORKSTATION_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// Step size for throttled relays and bodies, so they advance in small steps.
+const THROTTLE_CHUNK: usize = 16 * 1024;
+
+fn header_bytes(headers: &HeaderMap) -> usize {
+    headers
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len())
+        .sum()
+}
+
+fn check_header_size(state: &ProxyState, headers: &HeaderMap) -> Result<(), ProxyError> {
+    let max = state.limits().max_header_bytes;
+    if max > 0 && header_bytes(headers) > max {
+        return Err(ProxyError::HEADERS_TOO_LARGE);
+    }
+    Ok(())
+}
+
+/// Runs `fut` until `deadline`, answering `504` once it passes.
+async fn until_deadline<T>(
+    deadline: Option<Instant>,
+    fut: impl std::future::Future<Output = Result<T, StatusCode>>,
+) -> Result<T, StatusCode> {
+    match deadline {
+        Some(deadline) => timeout_at(deadline, fut)
+            .await
+            .map_err(|_| StatusCode::GATEWAY_TIMEOUT)?,
+        None => fut.await,
+    }
+}
+
+fn expects_continue(headers: &HeaderMap) -> bool {
+    headers
+        .get("expect")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("100-continue"))
 }
 
 fn is_sse_request(headers: &HeaderMap) -> bool {
@@ -33,6 +191,168 @@ fn is_sse_request(headers: &HeaderMap) -> bool {
         .unwrap_or(false)
 }
 
+/// Looks up a workstation, holding the request while it is reconnecting so a
+/// quick reconnect does not fail requests issued in between.
+async fn active_workstation(
+    state: &ProxyState,
+    workstation_id: &str,
+) -> Result<WorkstationInfo, ProxyError> {
+    match state
+        .registry
+        .wait_until_active(workstation_id, state.limits().request_timeout)
+        .await
+    {
+        Ok(workstation) => Ok(workstation),
+        Err(Unavailable::Unknown) if state.unknown_workstation_status == StatusCode::NOT_FOUND => {
+            Err(StatusCode::NOT_FOUND.into())
+        }
+        Err(Unavailable::Unknown) => Err(ProxyError::WORKSTATION_UNAVAILABLE
+            .with_retry_after(UNKNOWN_WORKSTATION_RETRY_AFTER.as_secs())),
+        Err(Unavailable::Reconnecting { remaining }) => {
+            let secs = remaining.as_secs_f64().ceil().max(1.0) as u64;
+            Err(ProxyError::WORKSTATION_RECONNECTING.with_retry_after(secs))
+        }
+    }
+}
+
+fn check_traffic_quota(
+    state: &ProxyState,
+    workstation: &WorkstationInfo,
+) -> Result<(), StatusCode> {
+    let max = state.limits().max_bytes_per_workstation;
+    if max > 0 && workstation.traffic.total() >= max {
+        warn!(
+            "Workstation {} exceeded its {} byte traffic quota",
+            workstation.id, max
+        );
+        return Err(StatusCode::TOO_MANY_REQUESTS);
+    }
+    Ok(())
+}
+
+/// Refuses requests up front while the client reports its backend as down.
+fn check_backend_health(workstation: &WorkstationInfo) -> Result<(), ProxyError> {
+    if workstation.backend_health().healthy {
+        Ok(())
+    } else {
+        Err(ProxyError::BACKEND_UNHEALTHY)
+    }
+}
+
+/// Answers methods outside `server.allowed_methods` with `405` and an `Allow`
+/// header listing the permitted ones.
+fn method_not_allowed(state: &ProxyState, method: &Method) -> Option<Response> {
+    if state.allowed_methods.is_empty() || state.allowed_methods.contains(method) {
+        return None;
+    }
+    let allow: Vec<&str> = state.allowed_methods.iter().map(Method::as_str).collect();
+    Some(
+        (
+            [(header::ALLOW, allow.join(", "))],
+            ProxyError::METHOD_NOT_ALLOWED,
+        )
+            .into_response(),
+    )
+}
+
+/// Refuses WebSocket upgrades from pages outside `ws_allowed_origins`.
+/// Requests without `Origin` do not come from a browser and pass.
+fn check_ws_origin(
+    state: &ProxyState,
+    workstation_id: &str,
+    headers: &HeaderMap,
+) -> Result<(), ProxyError> {
+    let (Some(allowlist), Some(origin)) = (&state.ws_allowed_origins, headers.get(header::ORIGIN))
+    else {
+        return Ok(());
+    };
+    if origin.to_str().is_ok_and(|origin| allowlist.allows(origin)) {
+        return Ok(());
+    }
+    warn!(
+        "Refused WebSocket to {} from origin {:?}",
+        workstation_id, origin
+    );
+    Err(ProxyError::ORIGIN_NOT_ALLOWED)
+}
+
+/// Refuses new requests while the server is in maintenance or an operator
+/// drains the workstation.
+fn check_draining(state: &ProxyState, workstation: &WorkstationInfo) -> Result<(), ProxyError> {
+    if state.limits().maintenance {
+        Err(ProxyError::MAINTENANCE)
+    } else if workstation.draining {
+        Err(ProxyError::WORKSTATION_DRAINING)
+    } else {
+        Ok(())
+    }
+}
+
+fn is_h2_passthrough(state: &ProxyState, path: &str) -> bool {
+    state
+        .h2_path_prefixes
+        .iter()
+        .any(|prefix| path.starts_with(prefix.as_str()))
+}
+
+/// Connection-specific headers of a caller request, which must not be
+/// forwarded past this hop.
+fn hop_by_hop(headers: &HeaderMap) -> HopByHop {
+    HopByHop::new(
+        headers
+            .iter()
+            .filter_map(|(name, value)| Some((name.as_str(), value.to_str().ok()?))),
+    )
+}
+
+/// Connection-specific headers of a workstation response.
+fn response_hop_by_hop(headers: &std::collections::HashMap<String, String>) -> HopByHop {
+    HopByHop::new(
+        headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str())),
+    )
+}
+
+/// Builds the headers sent to the caller from a workstation response: drops
+/// connection-specific and configured names, then applies configured extras.
+fn response_headers(
+    state: &ProxyState,
+    headers: &std::collections::HashMap<String, String>,
+) -> HeaderMap {
+    let hop = response_hop_by_hop(headers);
+    let mut out = HeaderMap::new();
+    for (name, value) in headers {
+        let name = name.to_ascii_lowercase();
+        if hop.contains(&name) || state.remove_response_headers.contains(&name) {
+            continue;
+        }
+        if let (Ok(name), Ok(value)) = (
+            axum::http::HeaderName::try_from(name.as_str()),
+            axum::http::HeaderValue::try_from(value.as_str()),
+        ) {
+            out.append(name, value);
+        }
+    }
+    for (name, value) in &state.add_response_headers {
+        out.insert(name.clone(), value.clone());
+    }
+    out
+}
+
+fn map_to_headers(map: &std::collections::HashMap<String, String>) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in map {
+        if let (Ok(name), Ok(value)) = (
+            axum::http::HeaderName::try_from(name.as_str()),
+            axum::http::HeaderValue::try_from(value.as_str()),
+        ) {
+            headers.append(name, value);
+        }
+    }
+    headers
+}
+
 fn headers_to_map(headers: &HeaderMap) -> std::collections::HashMap<String, String> {
     let mut map = std::collections::HashMap::new();
     for (name, value) in headers.iter() {
@@ -46,257 +366,1225 @@ fn headers_to_map(headers: &HeaderMap) -> std::collections::HashMap<String, Stri
 pub async fn handle_http_proxy(
     Path(params): Path<(String, String)>,
     State(state): State<Arc<ProxyState>>,
-    ws: Option<WebSocketUpgrade>,
-    method: Method,
-    headers: HeaderMap,
     axum::extract::RawQuery(query): axum::extract::RawQuery,
-    body: Body,
-) -> Result<Response, StatusCode> {
+    request: Request,
+) -> Result<Response, ProxyError> {
+    check_header_size(&state, request.headers())?;
     let (workstation_id, path) = params;
+    let path = normalize_path(&path, &state.path_normalization)?;
     let full_path = match query {
-        Some(q) => format!("/{}?{}", path, q),
-        None => format!("/{}", path),
+        Some(q) => format!("{}?{}", path, q),
+        None => path,
+    };
+
+    let Some(cors) = state.cors.clone() else {
+        if let Some(response) = method_not_allowed(&state, request.method()) {
+            return Ok(response);
+        }
+        return forward_request(workstation_id, full_path, state, request).await;
     };
+    if let Some(preflight) = cors.preflight(request.method(), request.headers()) {
+        return Ok(preflight);
+    }
+    let origin = request.headers().get(axum::http::header::ORIGIN).cloned();
+    let result = match method_not_allowed(&state, request.method()) {
+        Some(response) => Ok(response),
+        None => forward_request(workstation_id, full_path, state, request).await,
+    };
+    let Some(origin) = origin else {
+        return result;
+    };
+    // Errors get allow headers too, so the browser can show their status.
+    let mut response = result.unwrap_or_else(IntoResponse::into_response);
+    cors.apply(&origin, response.headers_mut());
+    Ok(response)
+}
 
-    if let Some(ws_upgrade) = ws {
-        return handle_websocket_upgrade(workstation_id, full_path, state, ws_upgrade, headers)
-            .await;
+async fn forward_request(
+    workstation_id: String,
+    full_path: String,
+    state: Arc<ProxyState>,
+    request: Request,
+) -> Result<Response, ProxyError> {
+    if is_websocket_handshake(&request) {
+        return handle_websocket_upgrade(workstation_id, full_path, state, request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let axum::http::request::Parts {
+        method,
+        headers,
+        mut extensions,
+        ..
+    } = parts;
+    // Present only on HTTP/1.1 connections served by `TunnelServer`.
+    let interim = extensions.remove::<InterimResponses>();
+    let deadline = state
+        .limits()
+        .max_request_duration
+        .map(|max| Instant::now() + max);
+
+    if is_h2_passthrough(&state, &full_path) {
+        return handle_h2_proxy(
+            workstation_id,
+            full_path,
+            state,
+            method,
+            headers,
+            body,
+            deadline,
+        )
+        .await;
     }
 
     if is_sse_request(&headers) {
         return handle_sse_proxy(workstation_id, full_path, state, method, headers).await;
     }
 
-    let workstation = state
-        .registry
-        .get(&workstation_id)
-        .await
-        .ok_or(StatusCode::NOT_FOUND)?;
+    let accepts_gzip = gzip::accepts_gzip(
+        headers
+            .get(header::ACCEPT_ENCODING)
+            .and_then(|v| v.to_str().ok()),
+    );
 
-    let stream_id = Uuid::new_v4();
-    let body_bytes = match axum::body::to_bytes(body, usize::MAX).await {
-        Ok(bytes) => bytes,
-        Err(_) => return Err(StatusCode::BAD_REQUEST),
-    };
+    let cache_key = state
+        .cache
+        .as_ref()
+        .and_then(|cache| cache.key(&workstation_id, &method, &full_path, &headers));
+    if let (Some(cache), Some(key)) = (&state.cache, &cache_key) {
+        if let Some(hit) = cache.get(key, &headers) {
+            let mut out_headers = hit.headers;
+            let body_data = if state.decompress_responses && !accepts_gzip && !hit.body.is_empty() {
+                decompress_for_caller(&workstation_id, &mut out_headers, hit.body.to_vec())
+            } else {
+                hit.body.to_vec()
+            };
+            let body_data = rewrite_urls(&state, &workstation_id, &mut out_headers, body_data);
+            let mut builder = Response::builder().status(hit.status);
+            if let Some(headers) = builder.headers_mut() {
+                *headers = out_headers;
+            }
+            return Ok(builder.body(Body::from(body_data)).unwrap());
+        }
+    }
+
+    let workstation = active_workstation(&state, &workstation_id).await?;
+    check_draining(&state, &workstation)?;
+    check_traffic_quota(&state, &workstation)?;
+    check_backend_health(&workstation)?;
+    if let Err(retry_after) = workstation.breaker.check() {
+        let secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+        return Ok((
+            [(header::RETRY_AFTER, secs.to_string())],
+            ProxyError::CIRCUIT_OPEN,
+        )
+            .into_response());
+    }
 
-    let body_base64 = if !body_bytes.is_empty() {
-        Some(codec::encode_body(&body_bytes))
+    let stream_id = state.stream_ids.next_id();
+
+    // With `Expect: 100-continue` the body stays unread, and so no `100 Continue`
+    // goes out, until the workstation reports that its backend wants it.
+    let expect_continue = workstation.expect_continue && expects_continue(&headers);
+    let ((body_base64, spooled_body), deferred_body) = if expect_continue {
+        ((None, None), Some(body))
     } else {
-        None
+        (
+            until_deadline(deadline, read_request_body(&state, &workstation, body)).await?,
+            None,
+        )
     };
 
-    let mut headers_map = std::collections::HashMap::new();
-    for (name, value) in headers.iter() {
-        if let Ok(val_str) = value.to_str() {
-            headers_map.insert(name.to_string(), val_str.to_string());
-        }
-    }
+    let permit = queue_for_stream(&state, &workstation, deadline).await?;
+
+    // `HeaderMap` keeps names in the order they first arrived, each followed
+    // by all its values in arrival order; hyper keeps neither the original
+    // interleaving of repeated names nor the case of names.
+    let hop = hop_by_hop(&headers);
+    let header_pairs = headers
+        .iter()
+        .filter(|(name, _)| !hop.contains(name.as_str()))
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())));
+    let (headers_map, header_list) = if workstation.ordered_headers {
+        (std::collections::HashMap::new(), header_pairs.collect())
+    } else {
+        (header_pairs.collect(), Vec::new())
+    };
 
     let request_msg = Message::HttpRequest(HttpRequestMessage {
         stream_id,
         method: method.to_string(),
-        path: full_path,
+        path: full_path.clone(),
         headers: headers_map,
+        header_list,
         body: body_base64,
+        expect_continue,
+        informational: interim.is_some(),
+        public_scheme: Some(state.public_scheme.to_string()),
+        public_host: Some(state.public_host.clone()),
+    });
+
+    // The entry is removed on every exit so a dead workstation cannot leave a
+    // sender behind. That includes the caller disconnecting: hyper then drops
+    // this future, and dropping the QUIC stream stops it, which tells the
+    // workstation to abandon the request.
+    let (response_tx, response_rx) = oneshot::channel();
+    if !state.pending.register(stream_id, response_tx).await {
+        warn!(
+            "Too many pending requests, refusing one for {}",
+            workstation_id
+        );
+        return Err(ProxyError::TOO_MANY_PENDING);
+    }
+    let mut cancel_on_drop = CancelOnDrop {
+        pending: state.pending.clone(),
+        stream_id,
+        armed: true,
+    };
+    let started = Instant::now();
+    let result = until_deadline(
+        deadline,
+        exchange_http_request(
+            &state,
+            &workstation,
+            stream_id,
+            &request_msg,
+            spooled_body,
+            deferred_body,
+            interim.as_ref(),
+            response_rx,
+        ),
+    )
+    .await;
+    drop(permit);
+    cancel_on_drop.armed = false;
+    state.pending.cancel(stream_id).await;
+    log_round_trip(
+        &state,
+        &workstation_id,
+        &method,
+        &full_path,
+        started.elapsed(),
+    );
+    record_backend_outcome(&workstation, result.as_ref().map(|(resp, _)| resp));
+    let (response_msg, spooled) = result?;
+
+    let mut out_headers = response_headers(&state, &response_msg.headers);
+    let mut builder = Response::builder().status(response_msg.status);
+
+    // The response cache, `decompress_responses` and `url_rewrite` work on
+    // bodies in memory, so a spooled body goes out as it is.
+    if let Some(spooled) = spooled {
+        workstation.traffic.add_out(spooled.len() as usize);
+        out_headers.insert(header::CONTENT_LENGTH, spooled.len().into());
+        let body = spooled.into_body().await.map_err(|e| {
+            warn!("Failed to read spooled response: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        if let Some(headers) = builder.headers_mut() {
+            *headers = out_headers;
+        }
+        return Ok(builder
+            .body(paced_body(body, workstation.bandwidth.clone()))
+            .unwrap());
+    }
+
+    let body_data = if let Some(body_b64) = response_msg.body {
+        match codec::decode_body(&body_b64) {
+            Ok(data) => data,
+            Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR.into()),
+        }
+    } else {
+        vec![]
+    };
+    workstation.traffic.add_out(body_data.len());
+
+    if let (Some(cache), Some(key)) = (&state.cache, cache_key) {
+        if let Ok(status) = StatusCode::from_u16(response_msg.status) {
+            cache.store(key, status, &out_headers, &body_data);
+        }
+    }
+
+    let body_data = if state.decompress_responses && !accepts_gzip {
+        decompress_for_caller(&workstation_id, &mut out_headers, body_data)
+    } else {
+        body_data
+    };
+    let body_data = rewrite_urls(&state, &workstation_id, &mut out_headers, body_data);
+
+    let body = if workstation.bandwidth.is_limited() && !body_data.is_empty() {
+        // A streamed body has no size of its own.
+        if !out_headers.contains_key(header::CONTENT_LENGTH) {
+            out_headers.insert(header::CONTENT_LENGTH, body_data.len().into());
+        }
+        paced_body(Body::from(body_data), workstation.bandwidth.clone())
+    } else {
+        Body::from(body_data)
+    };
+    if let Some(headers) = builder.headers_mut() {
+        *headers = out_headers;
+    }
+
+    Ok(builder.body(body).unwrap())
+}
+
+/// Logs how long a request took through the tunnel, as a warning once it
+/// crosses `slow_request_threshold`.
+fn log_round_trip(
+    state: &ProxyState,
+    workstation_id: &str,
+    method: &Method,
+    path: &str,
+    elapsed: Duration,
+) {
+    let millis = elapsed.as_millis();
+    match state.limits().slow_request_threshold {
+        Some(threshold) if elapsed >= threshold => warn!(
+            "Slow request: {} {} on {} took {}ms",
+            method, path, workstation_id, millis
+        ),
+        _ => debug!(
+            "{} {} on {} took {}ms",
+            method, path, workstation_id, millis
+        ),
+    }
+}
+
+/// Removes the pending entry of a request whose handler is dropped before the
+/// response arrives.
+struct CancelOnDrop {
+    pending: Arc<PendingRequests>,
+    stream_id: Uuid,
+    armed: bool,
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        info!("Caller of stream {} went away, cancelling", self.stream_id);
+        let (pending, stream_id) = (self.pending.clone(), self.stream_id);
+        tokio::spawn(async move { pending.cancel(stream_id).await });
+    }
+}
+
+/// Inflates a gzip-encoded body and drops the headers describing the encoded
+/// form. Anything else, or a body that fails to inflate, is returned as is.
+fn decompress_for_caller(workstation_id: &str, headers: &mut HeaderMap, body: Vec<u8>) -> Vec<u8> {
+    let is_gzip = headers
+        .get(header::CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| {
+            let v = v.trim();
+            v.eq_ignore_ascii_case("gzip") || v.eq_ignore_ascii_case("x-gzip")
+        });
+    if !is_gzip {
+        return body;
+    }
+    match gzip::decompress(&body, MAX_DECOMPRESSED_BYTES) {
+        Ok(inflated) => {
+            headers.remove(header::CONTENT_ENCODING);
+            headers.remove(header::CONTENT_LENGTH);
+            inflated
+        }
+        Err(e) => {
+            warn!(
+                "Relaying gzip response from {} compressed: {}",
+                workstation_id, e
+            );
+            body
+        }
+    }
+}
+
+/// Applies `server.url_rewrite`, with the workstation's public URL as the
+/// default replacement.
+fn rewrite_urls(
+    state: &ProxyState,
+    workstation_id: &str,
+    headers: &mut HeaderMap,
+    body: Vec<u8>,
+) -> Vec<u8> {
+    let Some(rewriter) = &state.url_rewrite else {
+        return body;
+    };
+    let tunnel_url = config::workstation_url(
+        state.public_scheme,
+        &state.public_host,
+        &state.base_path,
+        workstation_id,
+    );
+    rewriter.apply(&tunnel_url, headers, body)
+}
+
+/// Feeds the circuit breaker. 502s and timeouts, whether from the tunnel or
+/// relayed from the workstation, count as failures; errors that say nothing
+/// about the backend, such as an oversized request, are ignored.
+fn record_backend_outcome(
+    workstation: &WorkstationInfo,
+    result: Result<&HttpResponseMessage, &StatusCode>,
+) {
+    match result {
+        Ok(resp) if resp.status == 502 || resp.status == 504 => {
+            workstation.breaker.record_failure()
+        }
+        Ok(_) => workstation.breaker.record_success(),
+        Err(&StatusCode::BAD_GATEWAY | &StatusCode::GATEWAY_TIMEOUT) => {
+            workstation.breaker.record_failure()
+        }
+        Err(_) => {}
+    }
+}
+
+/// Buffers a request body for the tunnel, returning it base64-encoded. A
+/// body spooled to disk comes back as an empty string and the spool file, to
+/// be streamed into the frame by [`send_request_frame`].
+async fn read_request_body(
+    state: &ProxyState,
+    workstation: &WorkstationInfo,
+    body: Body,
+) -> Result<(Option<String>, Option<SpooledBody>), StatusCode> {
+    let collected = match &state.spool {
+        Some(spool) => spool.collect(body).await?,
+        None => CollectedBody::Memory(
+            axum::body::to_bytes(body, usize::MAX)
+                .await
+                .map_err(|_| StatusCode::BAD_REQUEST)?
+                .to_vec(),
+        ),
+    };
+    let body_bytes = match collected {
+        CollectedBody::Memory(body_bytes) => body_bytes,
+        CollectedBody::Spooled(spooled) => {
+            workstation.traffic.add_in(spooled.len() as usize);
+            return Ok((Some(String::new()), Some(spooled)));
+        }
+    };
+    workstation.traffic.add_in(body_bytes.len());
+
+    Ok((
+        (!body_bytes.is_empty()).then(|| codec::encode_body(&body_bytes)),
+        None,
+    ))
+}
+
+/// Sends `msg` on `send`, streaming its body from `spooled` if it has one.
+/// The body is paced by `bandwidth` as the frame goes out.
+async fn send_request_frame(
+    send: &mut quinn::SendStream,
+    msg: &Message,
+    spooled: Option<&SpooledBody>,
+    max_frame_bytes: usize,
+    bandwidth: &BandwidthLimiter,
+) -> Result<(), StatusCode> {
+    let sent = match spooled {
+        Some(spooled) => spooled.send_in(send, msg, max_frame_bytes, bandwidth).await,
+        None => send_paced(send, msg, max_frame_bytes, bandwidth).await,
+    };
+    match sent {
+        Ok(()) => Ok(()),
+        Err(tunnel_core::Error::MessageTooLarge { .. }) => {
+            let _ = send.reset(0u32.into());
+            Err(StatusCode::PAYLOAD_TOO_LARGE)
+        }
+        Err(_) => Err(StatusCode::BAD_GATEWAY),
+    }
+}
+
+/// Like `send_message_limited`, but writes in [`THROTTLE_CHUNK`] pieces, each
+/// waiting on `bandwidth` for its share of the body. The body is counted as
+/// it was before base64 encoding, like the rest of the throttled traffic.
+async fn send_paced(
+    send: &mut quinn::SendStream,
+    msg: &Message,
+    max_frame_bytes: usize,
+    bandwidth: &BandwidthLimiter,
+) -> tunnel_core::Result<()> {
+    if !bandwidth.is_limited() {
+        return tunnel_core::quic::send_message_limited(send, msg, max_frame_bytes).await;
+    }
+    let data = codec::encode_message(msg)?;
+    let size = data.len() - 4;
+    if size > max_frame_bytes {
+        return Err(tunnel_core::Error::MessageTooLarge {
+            size,
+            limit: max_frame_bytes,
+        });
+    }
+    let body = match msg {
+        Message::HttpRequest(HttpRequestMessage { body, .. })
+        | Message::HttpBody(HttpBodyMessage { body, .. }) => body.as_ref().map_or(0, String::len),
+        _ => 0,
+    } / 4
+        * 3;
+    let mut paced = 0;
+    let mut written = 0;
+    for piece in data.chunks(THROTTLE_CHUNK) {
+        written += piece.len();
+        let due = body * written / data.len();
+        bandwidth.acquire(due - paced).await;
+        paced = due;
+        send.write_all(piece)
+            .await
+            .map_err(|e| tunnel_core::Error::Connection(e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Streams `body` in [`THROTTLE_CHUNK`] pieces, each waiting on `bandwidth`,
+/// so a large body is paced rather than released after one long wait.
+fn paced_body(body: Body, bandwidth: Arc<BandwidthLimiter>) -> Body {
+    use futures::StreamExt;
+
+    let pieces = body.into_data_stream().flat_map(|chunk| {
+        let pieces: Vec<Result<Bytes, axum::Error>> = match chunk {
+            Ok(mut chunk) => {
+                let mut pieces = Vec::with_capacity(chunk.len() / THROTTLE_CHUNK + 1);
+                while chunk.len() > THROTTLE_CHUNK {
+                    pieces.push(Ok(chunk.split_to(THROTTLE_CHUNK)));
+                }
+                pieces.push(Ok(chunk));
+                pieces
+            }
+            Err(e) => vec![Err(e)],
+        };
+        futures::stream::iter(pieces)
     });
+    Body::from_stream(pieces.then(move |piece| {
+        let bandwidth = bandwidth.clone();
+        async move {
+            if let Ok(piece) = &piece {
+                bandwidth.acquire(piece.len()).await;
+            }
+            piece
+        }
+    }))
+}
+
+/// Waits for one of the workstation's stream slots within the
+/// `max_queued_requests` and `queue_timeout` bounds.
+async fn queue_for_stream(
+    state: &ProxyState,
+    workstation: &WorkstationInfo,
+    deadline: Option<Instant>,
+) -> Result<StreamPermit, ProxyError> {
+    let limits = state.limits();
+    let queued = workstation.queue_for_stream(limits.max_queued_requests, limits.queue_timeout);
+    let result = until_deadline(deadline, async { Ok(queued.await) }).await?;
+    match result {
+        Ok(permit) => Ok(permit),
+        Err(QueueRejection::Full) => {
+            warn!("Request queue for {} is full", workstation.id);
+            Err(ProxyError::QUEUE_FULL)
+        }
+        Err(QueueRejection::TimedOut) => Err(ProxyError::QUEUE_TIMEOUT),
+        Err(QueueRejection::Closed) => Err(StatusCode::BAD_GATEWAY.into()),
+    }
+}
 
+/// Sends `request_msg` on a new stream and waits for the response, which the
+/// client writes back on the same stream or on one it opens itself, in which
+/// case it arrives through `PendingRequests`. A `spooled_body` is streamed
+/// into the request frame, and a `deferred_body` is sent only once the client
+/// answers `HttpContinue`. `HttpInformational`s arriving first are written to
+/// `interim`. A response on the stream that is large enough to spool comes
+/// back with its body in a spool file. The caller holds the stream slot.
+#[allow(clippy::too_many_arguments)]
+async fn exchange_http_request(
+    state: &ProxyState,
+    workstation: &WorkstationInfo,
+    stream_id: Uuid,
+    request_msg: &Message,
+    spooled_body: Option<SpooledBody>,
+    deferred_body: Option<Body>,
+    interim: Option<&InterimResponses>,
+    routed: oneshot::Receiver<Message>,
+) -> Result<(HttpResponseMessage, Option<SpooledBody>), StatusCode> {
     let (mut send, mut recv) = match workstation.connection.open_bi().await {
         Ok(streams) => streams,
         Err(_) => return Err(StatusCode::BAD_GATEWAY),
     };
+    let spool = state.spool.as_ref();
 
-    if tunnel_core::quic::send_message(&mut send, &request_msg)
+    send_request_frame(
+        &mut send,
+        request_msg,
+        spooled_body.as_ref(),
+        workstation.max_frame_bytes,
+        &workstation.bandwidth,
+    )
+    .await?;
+    drop(spooled_body);
+
+    if let Some(body) = deferred_body {
+        let reply = timeout(
+            state.limits().request_timeout,
+            recv_relaying_interim(&mut recv, workstation.max_frame_bytes, interim, spool),
+        )
         .await
-        .is_err()
-    {
-        return Err(StatusCode::BAD_GATEWAY);
+        .map_err(|_| StatusCode::GATEWAY_TIMEOUT)?
+        .map_err(|_| StatusCode::BAD_GATEWAY)?;
+        match reply {
+            (Message::HttpContinue(_), _) => {}
+            // The backend answered without wanting the body, e.g. with 417.
+            (Message::HttpResponse(resp), spooled) => {
+                let _ = send.finish();
+                return Ok((resp, spooled));
+            }
+            (other, _) => {
+                warn!(
+                    "Expected http_continue for stream {}, got {}",
+                    stream_id,
+                    other.message_type()
+                );
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        }
+
+        let (body, spooled) = read_request_body(state, workstation, body).await?;
+        let body_msg = Message::HttpBody(HttpBodyMessage { stream_id, body });
+        send_request_frame(
+            &mut send,
+            &body_msg,
+            spooled.as_ref(),
+            workstation.max_frame_bytes,
+            &workstation.bandwidth,
+        )
+        .await?;
     }
 
     if send.finish().is_err() {
         return Err(StatusCode::BAD_GATEWAY);
     }
 
-    let response_msg = match timeout(
-        state.request_timeout,
-        tunnel_core::quic::recv_message(&mut recv),
-    )
+    let response = timeout(state.limits().request_timeout, async {
+        tokio::select! {
+            msg = recv_relaying_interim(&mut recv, workstation.max_frame_bytes, interim, spool) => {
+                msg.map_err(|_| StatusCode::BAD_GATEWAY)
+            }
+            msg = routed => msg.map(|msg| (msg, None)).map_err(|_| StatusCode::BAD_GATEWAY),
+        }
+    })
     .await
-    {
-        Ok(Ok(Message::HttpResponse(resp))) => resp,
-        Ok(Ok(_)) => {
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    .map_err(|_| StatusCode::GATEWAY_TIMEOUT)??;
+
+    match response {
+        (Message::HttpResponse(resp), spooled) => Ok((resp, spooled)),
+        (other, _) => {
+            warn!(
+                "Expected http_response for stream {}, got {}",
+                stream_id,
+                other.message_type()
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
-        Ok(Err(_)) => {
-            return Err(StatusCode::BAD_GATEWAY);
+    }
+}
+
+/// Receives the next message on `recv`, first writing any `HttpInformational`s
+/// to `interim`. Without `interim` they are dropped, as are those whose
+/// status hyper reserves for itself. With a `spool`, a large message comes
+/// back with its body in a spool file.
+async fn recv_relaying_interim(
+    recv: &mut quinn::RecvStream,
+    max_frame_bytes: usize,
+    interim: Option<&InterimResponses>,
+    spool: Option<&Spool>,
+) -> tunnel_core::Result<(Message, Option<SpooledBody>)> {
+    loop {
+        let (msg, spooled) = match spool {
+            Some(spool) => spool.recv_message(recv, max_frame_bytes).await?,
+            None => (
+                tunnel_core::quic::recv_message_limited(recv, max_frame_bytes).await?,
+                None,
+            ),
+        };
+        let Message::HttpInformational(info) = msg else {
+            return Ok((msg, spooled));
+        };
+        let Some(interim) = interim else {
+            continue;
+        };
+        let status = match StatusCode::from_u16(info.status) {
+            Ok(status)
+                if status != StatusCode::CONTINUE && status != StatusCode::SWITCHING_PROTOCOLS =>
+            {
+                status
+            }
+            _ => continue,
+        };
+        let mut headers = HeaderMap::new();
+        for (name, value) in &info.headers {
+            if let (Ok(name), Ok(value)) = (
+                header::HeaderName::try_from(name.as_str()),
+                header::HeaderValue::try_from(value.as_str()),
+            ) {
+                headers.append(name, value);
+            }
         }
-        Err(_) => {
-            return Err(StatusCode::GATEWAY_TIMEOUT);
+        if let Err(e) = interim.send(status, &headers).await {
+            debug!(
+                "Failed to relay {} for stream {}: {}",
+                status, info.stream_id, e
+            );
         }
+    }
+}
+
+/// Whether `request` is a WebSocket handshake axum's `WebSocketUpgrade`
+/// would accept.
+fn is_websocket_handshake(request: &Request) -> bool {
+    let headers = request.headers();
+    let has_token = |name: header::HeaderName, token: &str| {
+        headers.get_all(name).iter().any(|value| {
+            value.to_str().is_ok_and(|value| {
+                value
+                    .split(',')
+                    .any(|item| item.trim().eq_ignore_ascii_case(token))
+            })
+        })
     };
+    request.method() == Method::GET
+        && request.version() == axum::http::Version::HTTP_11
+        && has_token(header::CONNECTION, "upgrade")
+        && has_token(header::UPGRADE, "websocket")
+        && headers
+            .get(header::SEC_WEBSOCKET_VERSION)
+            .is_some_and(|v| v == "13")
+        && headers.contains_key(header::SEC_WEBSOCKET_KEY)
+}
 
-    let mut builder = Response::builder().status(response_msg.status);
+async fn websocket_upgrade(
+    parts: &mut axum::http::request::Parts,
+) -> Result<WebSocketUpgrade, Response> {
+    use axum::extract::FromRequestParts;
+
+    WebSocketUpgrade::from_request_parts(parts, &())
+        .await
+        .map_err(IntoResponse::into_response)
+}
+
+/// Opens a tunnel stream for a WebSocket handshake. A caller offering
+/// `Sec-WebSocket-Extensions` to a client that supports it is relayed in
+/// passthrough mode, so the extensions are negotiated with the backend;
+/// otherwise the server completes the handshake and relays messages.
+async fn handle_websocket_upgrade(
+    workstation_id: String,
+    full_path: String,
+    state: Arc<ProxyState>,
+    request: Request,
+) -> Result<Response, ProxyError> {
+    let (mut parts, _body) = request.into_parts();
+    let offers_extensions = parts.headers.contains_key(header::SEC_WEBSOCKET_EXTENSIONS);
+    let mut ws = None;
+    if !offers_extensions {
+        match websocket_upgrade(&mut parts).await {
+            Ok(upgrade) => ws = Some(upgrade),
+            Err(rejection) => return Ok(rejection),
+        }
+    }
+    let headers = &parts.headers;
+
+    check_ws_origin(&state, &workstation_id, headers)?;
+    let workstation = active_workstation(&state, &workstation_id).await?;
+    check_draining(&state, &workstation)?;
+    check_traffic_quota(&state, &workstation)?;
+    check_backend_health(&workstation)?;
 
-    for (name, value) in response_msg.headers.iter() {
-        builder = builder.header(name, value);
+    let passthrough = ws.is_none() && workstation.ws_passthrough;
+    if ws.is_none() && !passthrough {
+        match websocket_upgrade(&mut parts).await {
+            Ok(upgrade) => ws = Some(upgrade),
+            Err(rejection) => return Ok(rejection),
+        }
     }
+    let headers = &parts.headers;
 
-    let body_data = if let Some(body_b64) = response_msg.body {
-        match codec::decode_body(&body_b64) {
-            Ok(data) => data,
-            Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+    let mut headers_map = std::collections::HashMap::new();
+    for (name, value) in headers.iter() {
+        if let Ok(val_str) = value.to_str() {
+            headers_map.insert(name.to_string(), val_str.to_string());
+        }
+    }
+
+    let stream_id = state.stream_ids.next_id();
+    let permit = workstation
+        .acquire_stream()
+        .await
+        .map_err(|_| StatusCode::BAD_GATEWAY)?;
+
+    let (mut quic_send, mut quic_recv) = match workstation.connection.open_bi().await {
+        Ok(streams) => streams,
+        Err(_) => return Err(StatusCode::BAD_GATEWAY.into()),
+    };
+
+    let open_msg = Message::WsOpen(WsOpenMessage {
+        stream_id,
+        path: full_path,
+        headers: headers_map,
+        ack: workstation.ws_open_ack || passthrough,
+        fragments: true,
+        passthrough,
+    });
+
+    if tunnel_core::quic::send_message(&mut quic_send, &open_msg)
+        .await
+        .is_err()
+    {
+        return Err(StatusCode::BAD_GATEWAY.into());
+    }
+
+    // Clients that acknowledge opens report the local connect before the browser
+    // gets its 101, so a dead backend surfaces as a 502 instead of a bare close.
+    let mut backend_headers = std::collections::HashMap::new();
+    let first_msg = if workstation.ws_open_ack || passthrough {
+        match timeout(
+            state.limits().request_timeout,
+            tunnel_core::quic::recv_message(&mut quic_recv),
+        )
+        .await
+        {
+            Ok(Ok(Message::WsOpened(opened))) => match opened.error {
+                Some(error) => {
+                    warn!("WebSocket stream {} failed to open: {}", stream_id, error);
+                    return Err(StatusCode::BAD_GATEWAY.into());
+                }
+                None => {
+                    backend_headers = opened.headers;
+                    None
+                }
+            },
+            Ok(Ok(other)) => Some(other),
+            Ok(Err(_)) => return Err(StatusCode::BAD_GATEWAY.into()),
+            Err(_) => return Err(StatusCode::GATEWAY_TIMEOUT.into()),
         }
     } else {
-        vec![]
+        None
     };
 
-    Ok(builder.body(Body::from(body_data)).unwrap())
-}
-
-async fn handle_websocket_upgrade(
-    workstation_id: String,
-    full_path: String,
-    state: Arc<ProxyState>,
-    ws: WebSocketUpgrade,
-    headers: HeaderMap,
-) -> Result<Response, StatusCode> {
-    let workstation = state
-        .registry
-        .get(&workstation_id)
-        .await
-        .ok_or(StatusCode::NOT_FOUND)?;
-
-    let mut headers_map = std::collections::HashMap::new();
-    for (name, value) in headers.iter() {
-        if let Ok(val_str) = value.to_str() {
-            headers_map.insert(name.to_string(), val_str.to_string());
+    let Some(ws) = ws else {
+        let Some(on_upgrade) = parts.extensions.remove::<hyper::upgrade::OnUpgrade>() else {
+            return Err(StatusCode::UPGRADE_REQUIRED.into());
+        };
+        if let Some(msg) = first_msg {
+            warn!(
+                "Expected ws_opened for passthrough stream {}, got {}",
+                stream_id,
+                msg.message_type()
+            );
+            return Err(StatusCode::BAD_GATEWAY.into());
         }
-    }
+        tokio::spawn(relay_upgraded(
+            stream_id,
+            on_upgrade,
+            quic_send,
+            quic_recv,
+            permit,
+            workstation,
+        ));
+        let mut response = Response::builder()
+            .status(StatusCode::SWITCHING_PROTOCOLS)
+            .header(header::CONNECTION, "upgrade")
+            .header(header::UPGRADE, "websocket");
+        for (name, value) in &backend_headers {
+            if name.to_ascii_lowercase().starts_with("sec-websocket-") {
+                response = response.header(name, value);
+            }
+        }
+        return Ok(response
+            .body(Body::empty())
+            .unwrap_or_else(|_| StatusCode::BAD_GATEWAY.into_response()));
+    };
 
-    let stream_id = Uuid::new_v4();
-    let connection = workstation.connection.clone();
+    let max_message_bytes = state.limits().max_ws_message_bytes;
 
     Ok(ws.on_upgrade(move |socket| async move {
-        handle_websocket_connection(socket, connection, stream_id, full_path, headers_map).await
+        let _permit = permit;
+        handle_websocket_connection(
+            socket,
+            quic_send,
+            quic_recv,
+            first_msg,
+            stream_id,
+            max_message_bytes,
+            workstation,
+        )
+        .await
     }))
 }
 
 pub async fn handle_websocket_proxy(
     Path(params): Path<(String, String)>,
     State(state): State<Arc<ProxyState>>,
-    ws: WebSocketUpgrade,
-    headers: HeaderMap,
     axum::extract::RawQuery(query): axum::extract::RawQuery,
-) -> Result<Response, StatusCode> {
+    request: Request,
+) -> Result<Response, ProxyError> {
+    check_header_size(&state, request.headers())?;
     let (workstation_id, path) = params;
+    let path = normalize_path(&path, &state.path_normalization)?;
     let full_path = match query {
-        Some(q) => format!("/{}?{}", path, q),
-        None => format!("/{}", path),
+        Some(q) => format!("{}?{}", path, q),
+        None => path,
     };
-    handle_websocket_upgrade(workstation_id, full_path, state, ws, headers).await
+    handle_websocket_upgrade(workstation_id, full_path, state, request).await
 }
 
+/// Relays an upgraded WebSocket over a stream that already carried `WsOpen`.
+/// `first_msg` is a frame the client sent before the upgrade completed.
 async fn handle_websocket_connection(
     socket: axum::extract::ws::WebSocket,
-    connection: quinn::Connection,
+    mut quic_send: quinn::SendStream,
+    mut quic_recv: quinn::RecvStream,
+    mut first_msg: Option<Message>,
     stream_id: Uuid,
-    path: String,
-    headers: std::collections::HashMap<String, String>,
+    max_message_bytes: usize,
+    workstation: WorkstationInfo,
 ) {
     use axum::extract::ws::Message as WsMessage;
     use futures::{SinkExt, StreamExt};
 
     let (mut client_sender, mut client_receiver) = socket.split();
-
-    let (mut quic_send, mut quic_recv) = match connection.open_bi().await {
-        Ok(streams) => streams,
-        Err(_) => return,
+    let traffic = workstation.traffic;
+    let bandwidth = workstation.bandwidth;
+    // Clients that predate fragmentation expect each message in one frame.
+    let fragment_bytes = if workstation.ws_fragments {
+        WS_FRAGMENT_BYTES
+    } else {
+        usize::MAX
     };
 
-    let open_msg = Message::WsOpen(WsOpenMessage {
-        stream_id,
-        path,
-        headers,
+    // Each side is written by a single task fed through a bounded channel, so a
+    // slow consumer stalls the reader on the opposite side instead of buffering.
+    let (to_tunnel, mut to_tunnel_rx) = mpsc::channel::<Message>(WS_CHANNEL_CAPACITY);
+    let (to_client, mut to_client_rx) = mpsc::channel::<WsMessage>(WS_CHANNEL_CAPACITY);
+
+    let tunnel_writer = tokio::spawn(async move {
+        while let Some(msg) = to_tunnel_rx.recv().await {
+            let is_close = matches!(msg, Message::WsClose(_));
+            if tunnel_core::quic::send_message(&mut quic_send, &msg)
+                .await
+                .is_err()
+            {
+                break;
+            }
+            if is_close {
+                let _ = quic_send.finish();
+                break;
+            }
+        }
     });
 
-    if tunnel_core::quic::send_message(&mut quic_send, &open_msg)
-        .await
-        .is_err()
-    {
-        return;
-    }
+    let client_writer = tokio::spawn(async move {
+        while let Some(msg) = to_client_rx.recv().await {
+            let is_close = matches!(msg, WsMessage::Close(_));
+            if client_sender.send(msg).await.is_err() || is_close {
+                break;
+            }
+        }
+    });
 
-    let client_to_tunnel_task = tokio::spawn(async move {
-        while let Some(msg) = client_receiver.next().await {
-            match msg {
-                Ok(WsMessage::Text(text)) => {
-                    let data_msg = Message::WsData(WsDataMessage {
-                        stream_id,
-                        data: codec::encode_body(text.as_bytes()),
-                        is_binary: false,
-                    });
-                    if tunnel_core::quic::send_message(&mut quic_send, &data_msg)
-                        .await
-                        .is_err()
-                    {
-                        break;
-                    }
-                }
-                Ok(WsMessage::Binary(data)) => {
-                    let data_msg = Message::WsData(WsDataMessage {
-                        stream_id,
-                        data: codec::encode_body(&data),
-                        is_binary: true,
-                    });
-                    if tunnel_core::quic::send_message(&mut quic_send, &data_msg)
-                        .await
-                        .is_err()
-                    {
+    let client_to_tunnel_task = {
+        let to_tunnel = to_tunnel.clone();
+        let to_client = to_client.clone();
+        let traffic = traffic.clone();
+        let bandwidth = bandwidth.clone();
+        tokio::spawn(async move {
+            while let Some(msg) = client_receiver.next().await {
+                let (data, is_binary) = match msg {
+                    Ok(WsMessage::Text(text)) => (text.into_bytes(), false),
+                    Ok(WsMessage::Binary(data)) => (data, true),
+                    Ok(WsMessage::Close(frame)) => {
+                        let close_msg = Message::WsClose(WsCloseMessage {
+                            stream_id,
+                            code: frame.as_ref().map(|f| f.code),
+                            reason: frame.as_ref().map(|f| f.reason.to_string()),
+                        });
+                        let _ = to_tunnel.send(close_msg).await;
                         break;
                     }
-                }
-                Ok(WsMessage::Close(frame)) => {
-                    let close_msg = Message::WsClose(WsCloseMessage {
+                    Ok(_) => continue,
+                    Err(_) => break,
+                };
+                if data.len() > max_message_bytes {
+                    warn!(
+                        "WebSocket stream {} sent a {} byte message, over the {} byte limit",
                         stream_id,
-                        code: frame.as_ref().map(|f| f.code),
-                        reason: frame.as_ref().map(|f| f.reason.to_string()),
-                    });
-                    let _ = tunnel_core::quic::send_message(&mut quic_send, &close_msg).await;
-                    let _ = quic_send.finish();
+                        data.len(),
+                        max_message_bytes
+                    );
+                    close_message_too_big(&to_client, &to_tunnel, stream_id).await;
                     break;
                 }
-                _ => {}
+                traffic.add_in(data.len());
+                bandwidth.acquire(data.len()).await;
+                for frame in codec::ws_data_frames(stream_id, &data, is_binary, fragment_bytes) {
+                    if to_tunnel.send(Message::WsData(frame)).await.is_err() {
+                        return;
+                    }
+                }
             }
-        }
-    });
+        })
+    };
 
     let tunnel_to_client_task = tokio::spawn(async move {
+        let mut reassembler = codec::WsReassembler::new(max_message_bytes);
         loop {
-            match tunnel_core::quic::recv_message(&mut quic_recv).await {
+            let next = match first_msg.take() {
+                Some(msg) => Ok(msg),
+                None => tunnel_core::quic::recv_message(&mut quic_recv).await,
+            };
+            match next {
                 Ok(Message::WsData(data)) => {
-                    if let Ok(decoded) = codec::decode_body(&data.data) {
-                        let ws_msg = if data.is_binary {
-                            WsMessage::Binary(decoded)
-                        } else if let Ok(text) = String::from_utf8(decoded) {
-                            WsMessage::Text(text)
-                        } else {
-                            continue;
-                        };
-                        if client_sender.send(ws_msg).await.is_err() {
+                    let decoded = match reassembler.push(&data) {
+                        Ok(Some(decoded)) => decoded,
+                        Ok(None) => continue,
+                        Err(tunnel_core::Error::MessageTooLarge { size, limit }) => {
+                            warn!(
+                                "WebSocket stream {} received a {} byte message, over the {} byte limit",
+                                stream_id, size, limit
+                            );
+                            close_message_too_big(&to_client, &to_tunnel, stream_id).await;
                             break;
                         }
+                        Err(_) => continue,
+                    };
+                    traffic.add_out(decoded.len());
+                    bandwidth.acquire(decoded.len()).await;
+                    let ws_msg = if data.is_binary {
+                        WsMessage::Binary(decoded)
+                    } else if let Ok(text) = String::from_utf8(decoded) {
+                        WsMessage::Text(text)
+                    } else {
+                        continue;
+                    };
+                    if to_client.send(ws_msg).await.is_err() {
+                        break;
                     }
                 }
-                Ok(Message::WsClose(_)) => {
-                    let _ = client_sender.send(WsMessage::Close(None)).await;
+                Ok(Message::WsClose(close)) => {
+                    let frame = close.close_frame().map(|(code, reason)| CloseFrame {
+                        code,
+                        reason: reason.into(),
+                    });
+                    let _ = to_client.send(WsMessage::Close(frame)).await;
                     break;
                 }
                 Err(_) => break,
-                _ => {}
+                Ok(other) => {
+                    warn!(
+                        "Unexpected {} on WebSocket stream {}",
+                        other.message_type(),
+                        stream_id
+                    );
+                }
             }
         }
     });
 
-    let _ = tokio::join!(client_to_tunnel_task, tunnel_to_client_task);
+    let _ = tokio::join!(
+        client_to_tunnel_task,
+        tunnel_to_client_task,
+        client_writer,
+        tunnel_writer
+    );
+}
+
+/// Closes both legs of a WebSocket with 1009 after a message exceeded the limit.
+async fn close_message_too_big(
+    to_client: &mpsc::Sender<axum::extract::ws::Message>,
+    to_tunnel: &mpsc::Sender<Message>,
+    stream_id: Uuid,
+) {
+    let _ = to_client
+        .send(axum::extract::ws::Message::Close(Some(CloseFrame {
+            code: WS_CLOSE_MESSAGE_TOO_BIG,
+            reason: "message too big".into(),
+        })))
+        .await;
+    let _ = to_tunnel
+        .send(Message::WsClose(WsCloseMessage {
+            stream_id,
+            code: Some(WS_CLOSE_MESSAGE_TOO_BIG),
+            reason: Some("message too big".to_string()),
+        }))
+        .await;
+}
+
+/// Workstation id from the `Proxy-Authorization: Basic` user name, which is
+/// how `CONNECT` clients pick the workstation to tunnel through.
+pub(crate) fn proxy_auth_workstation(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get("proxy-authorization")?.to_str().ok()?;
+    let encoded = value.strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(codec::decode_body(encoded.trim()).ok()?).ok()?;
+    let (user, _) = decoded.split_once(':').unwrap_or((&decoded, ""));
+    (!user.is_empty()).then(|| user.to_string())
+}
+
+/// Router fallback that serves HTTP `CONNECT`. Authority-form targets have no
+/// path, so they never match the `/t/` routes.
+pub async fn handle_connect_proxy(
+    State(state): State<Arc<ProxyState>>,
+    req: Request,
+) -> Result<Response, ProxyError> {
+    if req.method() != Method::CONNECT {
+        return Err(ProxyError::ROUTE_NOT_FOUND);
+    }
+    let authority = req.uri().authority().ok_or(StatusCode::BAD_REQUEST)?;
+    let port = authority.port_u16().ok_or(StatusCode::BAD_REQUEST)?;
+    let host = authority
+        .host()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+
+    let Some(workstation_id) = proxy_auth_workstation(req.headers()) else {
+        return Ok(Response::builder()
+            .status(StatusCode::PROXY_AUTHENTICATION_REQUIRED)
+            .header("proxy-authenticate", "Basic realm=\"tiflis-tunnel\"")
+            .body(Body::empty())
+            .unwrap());
+    };
+
+    let workstation = active_workstation(&state, &workstation_id).await?;
+    check_draining(&state, &workstation)?;
+    check_traffic_quota(&state, &workstation)?;
+
+    let stream_id = state.stream_ids.next_id();
+    let permit = workstation
+        .acquire_stream()
+        .await
+        .map_err(|_| StatusCode::BAD_GATEWAY)?;
+
+    let (mut quic_send, mut quic_recv) = match workstation.connection.open_bi().await {
+        Ok(streams) => streams,
+        Err(_) => return Err(StatusCode::BAD_GATEWAY.into()),
+    };
+
+    let open_msg = Message::ConnectOpen(ConnectOpenMessage {
+        stream_id,
+        host,
+        port,
+    });
+    if tunnel_core::quic::send_message(&mut quic_send, &open_msg)
+        .await
+        .is_err()
+    {
+        return Err(StatusCode::BAD_GATEWAY.into());
+    }
+
+    match timeout(
+        state.limits().request_timeout,
+        tunnel_core::quic::recv_message(&mut quic_recv),
+    )
+    .await
+    {
+        Ok(Ok(Message::ConnectOpened(opened))) => {
+            if let Some(error) = opened.error {
+                warn!("CONNECT stream {} failed: {}", stream_id, error);
+                return Err(if opened.forbidden {
+                    StatusCode::FORBIDDEN.into()
+                } else {
+                    StatusCode::BAD_GATEWAY.into()
+                });
+            }
+        }
+        Ok(Ok(other)) => {
+            warn!(
+                "Expected connect_opened for stream {}, got {}",
+                stream_id,
+                other.message_type()
+            );
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into());
+        }
+        Ok(Err(_)) => return Err(StatusCode::BAD_GATEWAY.into()),
+        Err(_) => return Err(StatusCode::GATEWAY_TIMEOUT.into()),
+    }
+
+    tokio::spawn(relay_upgraded(
+        stream_id,
+        hyper::upgrade::on(req),
+        quic_send,
+        quic_recv,
+        permit,
+        workstation,
+    ));
+
+    // An unsized body keeps axum from adding `content-length: 0`, which hyper
+    // refuses to send on a successful CONNECT response.
+    let body = Body::from_stream(futures::stream::empty::<Result<Bytes, std::io::Error>>());
+    Ok(Response::new(body))
+}
+
+/// Relays raw bytes between an upgraded caller connection, once the upgrade
+/// completes, and a tunnel stream, as for `CONNECT` and passthrough
+/// WebSockets.
+async fn relay_upgraded(
+    stream_id: Uuid,
+    on_upgrade: hyper::upgrade::OnUpgrade,
+    mut quic_send: quinn::SendStream,
+    mut quic_recv: quinn::RecvStream,
+    permit: StreamPermit,
+    workstation: WorkstationInfo,
+) {
+    use tokio::io::AsyncWriteExt;
+
+    let _permit = permit;
+    let upgraded = match on_upgrade.await {
+        Ok(upgraded) => upgraded,
+        Err(e) => {
+            warn!("Stream {} upgrade failed: {}", stream_id, e);
+            let _ = quic_send.reset(0u32.into());
+            return;
+        }
+    };
+    let (mut client_read, mut client_write) =
+        tokio::io::split(hyper_util::rt::TokioIo::new(upgraded));
+    let bandwidth = &workstation.bandwidth;
+
+    let upstream = async {
+        let sent = copy_throttled(&mut client_read, &mut quic_send, bandwidth)
+            .await
+            .unwrap_or(0);
+        let _ = quic_send.finish();
+        sent
+    };
+    let downstream = async {
+        let received = copy_throttled(&mut quic_recv, &mut client_write, bandwidth)
+            .await
+            .unwrap_or(0);
+        let _ = client_write.shutdown().await;
+        received
+    };
+    let (sent, received) = tokio::join!(upstream, downstream);
+    workstation.traffic.add_in(sent as usize);
+    workstation.traffic.add_out(received as usize);
+}
+
+/// `tokio::io::copy` that waits on the workstation's bandwidth budget before
+/// each write.
+async fn copy_throttled<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    bandwidth: &BandwidthLimiter,
+) -> std::io::Result<u64>
+where
+    R: tokio::io::AsyncRead + Unpin,
+    W: tokio::io::AsyncWrite + Unpin,
+{
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut buf = vec![0u8; THROTTLE_CHUNK];
+    let mut total = 0u64;
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(total);
+        }
+        bandwidth.acquire(n).await;
+        writer.write_all(&buf[..n]).await?;
+        total += n as u64;
+    }
 }
 
 async fn handle_sse_proxy(
@@ -305,19 +1593,25 @@ async fn handle_sse_proxy(
     state: Arc<ProxyState>,
     method: Method,
     headers: HeaderMap,
-) -> Result<Response, StatusCode> {
-    let workstation = state
-        .registry
-        .get(&workstation_id)
-        .await
-        .ok_or(StatusCode::NOT_FOUND)?;
+) -> Result<Response, ProxyError> {
+    let workstation = active_workstation(&state, &workstation_id).await?;
+    check_draining(&state, &workstation)?;
+    check_traffic_quota(&state, &workstation)?;
+    check_backend_health(&workstation)?;
+
+    let stream_id = state.stream_ids.next_id();
+    let hop = hop_by_hop(&headers);
+    let mut headers_map = headers_to_map(&headers);
+    headers_map.retain(|name, _| !hop.contains(name));
 
-    let stream_id = Uuid::new_v4();
-    let headers_map = headers_to_map(&headers);
+    let permit = workstation
+        .acquire_stream()
+        .await
+        .map_err(|_| StatusCode::BAD_GATEWAY)?;
 
     let (mut quic_send, mut quic_recv) = match workstation.connection.open_bi().await {
         Ok(streams) => streams,
-        Err(_) => return Err(StatusCode::BAD_GATEWAY),
+        Err(_) => return Err(StatusCode::BAD_GATEWAY.into()),
     };
 
     let open_msg = Message::SseOpen(SseOpenMessage {
@@ -331,41 +1625,68 @@ async fn handle_sse_proxy(
         .await
         .is_err()
     {
-        return Err(StatusCode::BAD_GATEWAY);
+        return Err(StatusCode::BAD_GATEWAY.into());
     }
 
     let headers_msg = match timeout(
-        state.request_timeout,
+        state.limits().request_timeout,
         tunnel_core::quic::recv_message(&mut quic_recv),
     )
     .await
     {
         Ok(Ok(Message::SseHeaders(h))) => h,
         Ok(Ok(Message::SseClose(c))) => {
-            let status = if c.error.is_some() {
-                StatusCode::BAD_GATEWAY
-            } else {
-                StatusCode::NO_CONTENT
-            };
-            return Err(status);
+            if c.error.is_some() {
+                return Err(StatusCode::BAD_GATEWAY.into());
+            }
+            return Ok(StatusCode::NO_CONTENT.into_response());
         }
-        Ok(Ok(_)) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
-        Ok(Err(_)) => return Err(StatusCode::BAD_GATEWAY),
-        Err(_) => return Err(StatusCode::GATEWAY_TIMEOUT),
+        Ok(Ok(other)) => {
+            warn!(
+                "Expected sse_headers for stream {}, got {}",
+                stream_id,
+                other.message_type()
+            );
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into());
+        }
+        Ok(Err(_)) => return Err(StatusCode::BAD_GATEWAY.into()),
+        Err(_) => return Err(StatusCode::GATEWAY_TIMEOUT.into()),
     };
 
     let (mut tx, rx) = futures::channel::mpsc::channel::<Result<Bytes, std::io::Error>>(16);
 
+    let limits = state.limits();
+    let heartbeat_interval = limits.sse_heartbeat_interval;
+    let max_duration = limits.max_sse_duration;
+    let traffic = workstation.traffic.clone();
+    let bandwidth = workstation.bandwidth.clone();
     tokio::spawn(async move {
-        relay_sse_to_client(quic_recv, &mut tx).await;
+        let _permit = permit;
+        let relay =
+            relay_sse_to_client(quic_recv, &mut tx, heartbeat_interval, &traffic, &bandwidth);
+        let Some(max_duration) = max_duration else {
+            relay.await;
+            return;
+        };
+        if timeout(max_duration, relay).await.is_err() {
+            info!(
+                "SSE stream {} reached the {:?} duration limit",
+                stream_id, max_duration
+            );
+            let close_msg = Message::SseClose(SseCloseMessage {
+                stream_id,
+                error: Some("maximum SSE duration reached".to_string()),
+            });
+            let _ = tunnel_core::quic::send_message(&mut quic_send, &close_msg).await;
+            let _ = quic_send.finish();
+        }
     });
 
     let body = Body::from_stream(rx);
 
     let mut builder = Response::builder().status(headers_msg.status);
-
-    for (name, value) in headers_msg.headers.iter() {
-        builder = builder.header(name, value);
+    if let Some(headers) = builder.headers_mut() {
+        *headers = response_headers(&state, &headers_msg.headers);
     }
 
     builder = builder
@@ -376,23 +1697,287 @@ async fn handle_sse_proxy(
     Ok(builder.body(body).unwrap())
 }
 
+async fn handle_h2_proxy(
+    workstation_id: String,
+    path: String,
+    state: Arc<ProxyState>,
+    method: Method,
+    headers: HeaderMap,
+    body: Body,
+    deadline: Option<Instant>,
+) -> Result<Response, ProxyError> {
+    use futures::SinkExt;
+
+    let workstation = active_workstation(&state, &workstation_id).await?;
+    check_draining(&state, &workstation)?;
+    check_traffic_quota(&state, &workstation)?;
+    check_backend_health(&workstation)?;
+
+    let stream_id = state.stream_ids.next_id();
+    let hop = hop_by_hop(&headers);
+    let mut headers_map = headers_to_map(&headers);
+    headers_map.retain(|name, _| !hop.contains(name));
+
+    let permit = workstation
+        .acquire_stream()
+        .await
+        .map_err(|_| StatusCode::BAD_GATEWAY)?;
+
+    let (mut quic_send, mut quic_recv) = match workstation.connection.open_bi().await {
+        Ok(streams) => streams,
+        Err(_) => return Err(StatusCode::BAD_GATEWAY.into()),
+    };
+
+    let open_msg = Message::H2Open(H2OpenMessage {
+        stream_id,
+        method: method.to_string(),
+        path,
+        headers: headers_map,
+    });
+
+    if tunnel_core::quic::send_message(&mut quic_send, &open_msg)
+        .await
+        .is_err()
+    {
+        return Err(StatusCode::BAD_GATEWAY.into());
+    }
+
+    // The request body is pumped concurrently with the response so streaming
+    // RPCs can interleave in both directions.
+    let relay = relay_h2_request(
+        body,
+        quic_send,
+        stream_id,
+        workstation.traffic.clone(),
+        workstation.bandwidth.clone(),
+    );
+    tokio::spawn(async move {
+        match deadline {
+            Some(deadline) => {
+                let _ = timeout_at(deadline, relay).await;
+            }
+            None => relay.await,
+        }
+    });
+
+    let request_timeout = state.limits().request_timeout;
+    let headers_timeout = match deadline {
+        Some(deadline) => request_timeout.min(deadline.saturating_duration_since(Instant::now())),
+        None => request_timeout,
+    };
+    let headers_msg = match timeout(
+        headers_timeout,
+        tunnel_core::quic::recv_message(&mut quic_recv),
+    )
+    .await
+    {
+        Ok(Ok(Message::H2Headers(h))) => h,
+        Ok(Ok(Message::H2Close(c))) => {
+            warn!(
+                "H2 stream {} closed before headers: {}",
+                stream_id,
+                c.error.unwrap_or_default()
+            );
+            return Err(StatusCode::BAD_GATEWAY.into());
+        }
+        Ok(Ok(other)) => {
+            warn!(
+                "Expected h2_headers for stream {}, got {}",
+                stream_id,
+                other.message_type()
+            );
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into());
+        }
+        Ok(Err(_)) => return Err(StatusCode::BAD_GATEWAY.into()),
+        Err(_) => return Err(StatusCode::GATEWAY_TIMEOUT.into()),
+    };
+
+    let (mut tx, rx) = futures::channel::mpsc::channel::<Result<Frame<Bytes>, std::io::Error>>(16);
+
+    let traffic = workstation.traffic.clone();
+    let bandwidth = workstation.bandwidth.clone();
+    tokio::spawn(async move {
+        let _permit = permit;
+        let relay = relay_h2_response(quic_recv, &mut tx, stream_id, &traffic, &bandwidth);
+        let Some(deadline) = deadline else {
+            relay.await;
+            return;
+        };
+        if timeout_at(deadline, relay).await.is_err() {
+            info!("H2 stream {} reached the request duration limit", stream_id);
+            let _ = tx
+                .send(Err(std::io::Error::other(
+                    "maximum request duration reached",
+                )))
+                .await;
+        }
+    });
+
+    let body = Body::new(StreamBody::new(rx));
+
+    let hop = response_hop_by_hop(&headers_msg.headers);
+    let mut builder = Response::builder().status(headers_msg.status);
+    for (name, value) in headers_msg.headers.iter() {
+        if !hop.contains(name) {
+            builder = builder.header(name, value);
+        }
+    }
+
+    Ok(builder.body(body).unwrap())
+}
+
+async fn relay_h2_request(
+    body: Body,
+    mut quic_send: quinn::SendStream,
+    stream_id: Uuid,
+    traffic: Arc<TrafficCounters>,
+    bandwidth: Arc<BandwidthLimiter>,
+) {
+    use http_body_util::BodyExt;
+
+    let mut body = body;
+    let mut trailers = None;
+    let mut error = None;
+
+    while let Some(frame) = body.frame().await {
+        match frame {
+            Ok(frame) => match frame.into_data() {
+                Ok(data) => {
+                    traffic.add_in(data.len());
+                    bandwidth.acquire(data.len()).await;
+                    let data_msg = Message::H2Data(H2DataMessage {
+                        stream_id,
+                        data: codec::encode_body(&data),
+                    });
+                    if tunnel_core::quic::send_message(&mut quic_send, &data_msg)
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+                Err(frame) => {
+                    if let Ok(t) = frame.into_trailers() {
+                        trailers = Some(headers_to_map(&t));
+                    }
+                }
+            },
+            Err(e) => {
+                error = Some(e.to_string());
+                break;
+            }
+        }
+    }
+
+    let close_msg = Message::H2Close(H2CloseMessage {
+        stream_id,
+        trailers,
+        error,
+    });
+    let _ = tunnel_core::quic::send_message(&mut quic_send, &close_msg).await;
+    let _ = quic_send.finish();
+}
+
+async fn relay_h2_response(
+    mut quic_recv: quinn::RecvStream,
+    tx: &mut futures::channel::mpsc::Sender<Result<Frame<Bytes>, std::io::Error>>,
+    stream_id: Uuid,
+    traffic: &TrafficCounters,
+    bandwidth: &BandwidthLimiter,
+) {
+    use futures::SinkExt;
+
+    loop {
+        match tunnel_core::quic::recv_message(&mut quic_recv).await {
+            Ok(Message::H2Data(data)) => {
+                if let Ok(decoded) = codec::decode_body(&data.data) {
+                    traffic.add_out(decoded.len());
+                    bandwidth.acquire(decoded.len()).await;
+                    if tx
+                        .send(Ok(Frame::data(Bytes::from(decoded))))
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            }
+            Ok(Message::H2Close(close)) => {
+                if let Some(error) = close.error {
+                    let _ = tx.send(Err(std::io::Error::other(error))).await;
+                } else if let Some(trailers) = close.trailers {
+                    let _ = tx
+                        .send(Ok(Frame::trailers(map_to_headers(&trailers))))
+                        .await;
+                }
+                break;
+            }
+            Err(_) => {
+                let _ = tx
+                    .send(Err(std::io::Error::other("tunnel stream closed")))
+                    .await;
+                break;
+            }
+            Ok(other) => {
+                warn!(
+                    "Unexpected {} on H2 stream {}",
+                    other.message_type(),
+                    stream_id
+                );
+            }
+        }
+    }
+}
+
+const SSE_HEARTBEAT: &[u8] = b":\n\n";
+
 async fn relay_sse_to_client(
     mut quic_recv: quinn::RecvStream,
     tx: &mut futures::channel::mpsc::Sender<Result<Bytes, std::io::Error>>,
+    heartbeat_interval: Option<Duration>,
+    traffic: &TrafficCounters,
+    bandwidth: &BandwidthLimiter,
 ) {
     use futures::SinkExt;
 
     loop {
-        match tunnel_core::quic::recv_message(&mut quic_recv).await {
+        // Keep the receive future alive across heartbeats so a partially read
+        // frame is never dropped.
+        let recv = tunnel_core::quic::recv_message(&mut quic_recv);
+        tokio::pin!(recv);
+
+        let result = loop {
+            let heartbeat = async {
+                match heartbeat_interval {
+                    Some(interval) => tokio::time::sleep(interval).await,
+                    None => std::future::pending().await,
+                }
+            };
+
+            tokio::select! {
+                result = &mut recv => break result,
+                _ = heartbeat => {
+                    if tx.send(Ok(Bytes::from_static(SSE_HEARTBEAT))).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        };
+
+        match result {
             Ok(Message::SseData(data)) => {
                 if let Ok(decoded) = codec::decode_body(&data.data) {
+                    traffic.add_out(decoded.len());
+                    bandwidth.acquire(decoded.len()).await;
                     if tx.send(Ok(Bytes::from(decoded))).await.is_err() {
                         break;
                     }
                 }
             }
             Ok(Message::SseClose(_)) | Err(_) => break,
-            _ => {}
+            Ok(other) => {
+                warn!("Unexpected {} on SSE stream", other.message_type());
+            }
         }
     }
 }
@@ -0,0 +1,174 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+//! PROXY protocol (v1 and v2) headers, sent by an L4 load balancer ahead of
+//! the TLS handshake to pass on the address of the client it accepted.
+
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// Longest v1 header, including the trailing CRLF.
+const V1_MAX_LEN: usize = 107;
+
+/// Reads the PROXY header at the start of `stream` and nothing past it.
+/// Returns the client address, or `None` for headers that carry none (v1
+/// `UNKNOWN`, v2 `LOCAL` health checks, non-IP families); the caller then
+/// keeps the socket's peer address.
+pub async fn read_header<S>(stream: &mut S) -> io::Result<Option<SocketAddr>>
+where
+    S: AsyncRead + Unpin,
+{
+    // Both versions are at least this long: the v2 signature, or
+    // "PROXY UNKNOWN\r\n".
+    let mut start = [0u8; 12];
+    stream.read_exact(&mut start).await?;
+    if start == V2_SIGNATURE {
+        read_v2(stream).await
+    } else if start.starts_with(b"PROXY ") {
+        read_v1(stream, &start).await
+    } else {
+        Err(invalid("missing PROXY protocol header"))
+    }
+}
+
+async fn read_v1<S>(stream: &mut S, start: &[u8]) -> io::Result<Option<SocketAddr>>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut line = start.to_vec();
+    while !line.ends_with(b"\r\n") {
+        if line.len() == V1_MAX_LEN {
+            return Err(invalid("PROXY v1 header too long"));
+        }
+        line.push(stream.read_u8().await?);
+    }
+    let line = std::str::from_utf8(&line[..line.len() - 2])
+        .map_err(|_| invalid("PROXY v1 header is not ASCII"))?;
+
+    let fields: Vec<&str> = line.split(' ').collect();
+    match fields.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => Ok(None),
+        ["PROXY", "TCP4" | "TCP6", src, _dst, src_port, _dst_port] => {
+            let ip: IpAddr = src.parse().map_err(|_| invalid("bad PROXY v1 address"))?;
+            let port: u16 = src_port.parse().map_err(|_| invalid("bad PROXY v1 port"))?;
+            Ok(Some(SocketAddr::new(ip, port)))
+        }
+        _ => Err(invalid("malformed PROXY v1 header")),
+    }
+}
+
+async fn read_v2<S>(stream: &mut S) -> io::Result<Option<SocketAddr>>
+where
+    S: AsyncRead + Unpin,
+{
+    let version_command = stream.read_u8().await?;
+    let family = stream.read_u8().await?;
+    let len = stream.read_u16().await? as usize;
+    let mut body = vec![0u8; len];
+    stream.read_exact(&mut body).await?;
+
+    if version_command >> 4 != 2 {
+        return Err(invalid("unsupported PROXY protocol version"));
+    }
+    match version_command & 0x0f {
+        0 => return Ok(None),
+        1 => {}
+        _ => return Err(invalid("unsupported PROXY v2 command")),
+    }
+    // The high nibble is the address family, the low one TCP or UDP; the
+    // addresses are laid out the same for both.
+    match family >> 4 {
+        1 if len >= 12 => {
+            let ip = Ipv4Addr::new(body[0], body[1], body[2], body[3]);
+            let port = u16::from_be_bytes([body[8], body[9]]);
+            Ok(Some(SocketAddr::new(ip.into(), port)))
+        }
+        2 if len >= 36 => {
+            let octets: [u8; 16] = body[..16].try_into().unwrap();
+            let port = u16::from_be_bytes([body[32], body[33]]);
+            Ok(Some(SocketAddr::new(Ipv6Addr::from(octets).into(), port)))
+        }
+        1 | 2 => Err(invalid("truncated PROXY v2 addresses")),
+        _ => Ok(None),
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v2_header(command: u8, family: u8, addresses: &[u8]) -> Vec<u8> {
+        let mut header = V2_SIGNATURE.to_vec();
+        header.push(0x20 | command);
+        header.push(family);
+        header.extend_from_slice(&(addresses.len() as u16).to_be_bytes());
+        header.extend_from_slice(addresses);
+        header
+    }
+
+    #[tokio::test]
+    async fn test_reads_v1_tcp4_and_leaves_payload() {
+        let mut input: &[u8] = b"PROXY TCP4 203.0.113.7 10.0.0.1 4242 443\r\nGET /";
+        let addr = read_header(&mut input).await.unwrap();
+        assert_eq!(addr, Some("203.0.113.7:4242".parse().unwrap()));
+        assert_eq!(input, b"GET /");
+    }
+
+    #[tokio::test]
+    async fn test_reads_v1_tcp6_and_unknown() {
+        let mut input: &[u8] = b"PROXY TCP6 2001:db8::1 2001:db8::2 5000 443\r\n";
+        let addr = read_header(&mut input).await.unwrap();
+        assert_eq!(addr, Some("[2001:db8::1]:5000".parse().unwrap()));
+
+        let mut input: &[u8] = b"PROXY UNKNOWN\r\n";
+        assert_eq!(read_header(&mut input).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_reads_v2_tcp4_with_tlvs() {
+        let mut addresses = vec![198, 51, 100, 9, 10, 0, 0, 1];
+        addresses.extend_from_slice(&8080u16.to_be_bytes());
+        addresses.extend_from_slice(&443u16.to_be_bytes());
+        // A TLV after the addresses is skipped.
+        addresses.extend_from_slice(&[0x04, 0x00, 0x01, 0x00]);
+        let mut input = v2_header(1, 0x11, &addresses);
+        input.extend_from_slice(b"payload");
+
+        let mut reader = input.as_slice();
+        let addr = read_header(&mut reader).await.unwrap();
+        assert_eq!(addr, Some("198.51.100.9:8080".parse().unwrap()));
+        assert_eq!(reader, b"payload");
+    }
+
+    #[tokio::test]
+    async fn test_reads_v2_tcp6_and_local() {
+        let mut addresses = Ipv6Addr::LOCALHOST.octets().to_vec();
+        addresses.extend_from_slice(&[0u8; 16]);
+        addresses.extend_from_slice(&[0x13, 0x88, 0x01, 0xbb]);
+        let input = v2_header(1, 0x21, &addresses);
+        let addr = read_header(&mut input.as_slice()).await.unwrap();
+        assert_eq!(addr, Some("[::1]:5000".parse().unwrap()));
+
+        let input = v2_header(0, 0x00, &[]);
+        assert_eq!(read_header(&mut input.as_slice()).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_missing_or_malformed_header() {
+        let mut input: &[u8] = b"GET / HTTP/1.1\r\n\r\n";
+        assert!(read_header(&mut input).await.is_err());
+
+        let mut input: &[u8] = b"PROXY TCP4 not-an-ip 10.0.0.1 1 2\r\n";
+        assert!(read_header(&mut input).await.is_err());
+
+        let long = format!("PROXY TCP4 {}\r\n", "1".repeat(200));
+        assert!(read_header(&mut long.as_bytes()).await.is_err());
+    }
+}
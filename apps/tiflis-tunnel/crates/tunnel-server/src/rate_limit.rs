@@ -0,0 +1,241 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+use crate::error::ProxyError;
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(refill_per_sec: f64, capacity: f64) -> Self {
+        Self {
+            capacity,
+            tokens: capacity,
+            refill_per_sec,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+    }
+
+    /// Takes `amount` tokens, or returns how long to wait until they are available.
+    pub fn try_take(&mut self, amount: f64) -> Result<(), Duration> {
+        self.refill();
+        if self.tokens >= amount {
+            self.tokens -= amount;
+            Ok(())
+        } else {
+            let missing = amount - self.tokens;
+            Err(Duration::from_secs_f64(missing / self.refill_per_sec))
+        }
+    }
+
+    pub fn is_full(&mut self) -> bool {
+        self.refill();
+        self.tokens >= self.capacity
+    }
+}
+
+pub struct IpRateLimiter {
+    buckets: Mutex<IpBuckets>,
+}
+
+struct IpBuckets {
+    by_ip: HashMap<IpAddr, TokenBucket>,
+    /// 0 lets every request through.
+    requests_per_second: f64,
+    burst: f64,
+}
+
+impl IpRateLimiter {
+    /// A `requests_per_second` of 0 disables limiting.
+    pub fn new(requests_per_second: u32, burst: u32) -> Self {
+        Self {
+            buckets: Mutex::new(IpBuckets {
+                by_ip: HashMap::new(),
+                requests_per_second: requests_per_second as f64,
+                burst: burst.max(1) as f64,
+            }),
+        }
+    }
+
+    /// Switches to a new rate. Every address starts over with a full bucket.
+    pub fn set_rate(&self, requests_per_second: u32, burst: u32) {
+        let mut buckets = self.buckets.lock().unwrap();
+        buckets.by_ip.clear();
+        buckets.requests_per_second = requests_per_second as f64;
+        buckets.burst = burst.max(1) as f64;
+    }
+
+    pub fn check(&self, ip: IpAddr) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let (rate, burst) = (buckets.requests_per_second, buckets.burst);
+        if rate == 0.0 {
+            return Ok(());
+        }
+        buckets
+            .by_ip
+            .entry(ip)
+            .or_insert_with(|| TokenBucket::new(rate, burst))
+            .try_take(1.0)
+    }
+
+    /// Drops buckets that have fully refilled, since they carry no state.
+    pub fn prune(&self) {
+        let mut buckets = self.buckets.lock().unwrap();
+        buckets.by_ip.retain(|_ip, bucket| !bucket.is_full());
+    }
+
+    pub fn tracked_ips(&self) -> usize {
+        self.buckets.lock().unwrap().by_ip.len()
+    }
+}
+
+/// Paces a workstation's proxied bytes to a sustained rate, allowing bursts of
+/// up to one second's worth. Shared by every stream of the workstation.
+#[derive(Debug)]
+pub struct BandwidthLimiter {
+    bucket: Option<Mutex<TokenBucket>>,
+    burst: usize,
+}
+
+impl BandwidthLimiter {
+    /// `bytes_per_sec` of 0 disables throttling.
+    pub fn new(bytes_per_sec: u64) -> Self {
+        let bucket = (bytes_per_sec > 0)
+            .then(|| Mutex::new(TokenBucket::new(bytes_per_sec as f64, bytes_per_sec as f64)));
+        Self {
+            bucket,
+            burst: bytes_per_sec.try_into().unwrap_or(usize::MAX),
+        }
+    }
+
+    /// Whether sending is throttled at all.
+    pub fn is_limited(&self) -> bool {
+        self.bucket.is_some()
+    }
+
+    /// Waits until `bytes` may be sent. Amounts larger than the burst are taken
+    /// in burst-sized pieces so they still pass, just spread over time.
+    pub async fn acquire(&self, bytes: usize) {
+        let Some(bucket) = &self.bucket else {
+            return;
+        };
+        let mut remaining = bytes;
+        while remaining > 0 {
+            let chunk = remaining.min(self.burst);
+            let taken = bucket.lock().unwrap().try_take(chunk as f64);
+            match taken {
+                Ok(()) => remaining -= chunk,
+                Err(wait) => tokio::time::sleep(wait).await,
+            }
+        }
+    }
+}
+
+pub async fn rate_limit(
+    State(limiter): State<Arc<IpRateLimiter>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match limiter.check(addr.ip()) {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => {
+            let secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+            (
+                [(header::RETRY_AFTER, secs.to_string())],
+                ProxyError::RATE_LIMITED,
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_allows_burst_then_limits() {
+        let mut bucket = TokenBucket::new(1.0, 3.0);
+        assert!(bucket.try_take(1.0).is_ok());
+        assert!(bucket.try_take(1.0).is_ok());
+        assert!(bucket.try_take(1.0).is_ok());
+        let wait = bucket.try_take(1.0).unwrap_err();
+        assert!(wait > Duration::ZERO && wait <= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_limiter_is_per_ip() {
+        let limiter = IpRateLimiter::new(1, 1);
+        let a: IpAddr = "10.0.0.1".parse().unwrap();
+        let b: IpAddr = "10.0.0.2".parse().unwrap();
+        assert!(limiter.check(a).is_ok());
+        assert!(limiter.check(a).is_err());
+        assert!(limiter.check(b).is_ok());
+        assert_eq!(limiter.tracked_ips(), 2);
+    }
+
+    #[test]
+    fn test_limiter_rate_can_change() {
+        let limiter = IpRateLimiter::new(0, 1);
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        for _ in 0..10 {
+            assert!(limiter.check(ip).is_ok());
+        }
+        assert_eq!(limiter.tracked_ips(), 0);
+
+        limiter.set_rate(1, 1);
+        assert!(limiter.check(ip).is_ok());
+        assert!(limiter.check(ip).is_err());
+
+        limiter.set_rate(0, 1);
+        assert!(limiter.check(ip).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_bandwidth_limiter_paces_past_burst() {
+        let limiter = BandwidthLimiter::new(10_000);
+        let start = Instant::now();
+        limiter.acquire(10_000).await;
+        assert!(start.elapsed() < Duration::from_millis(100));
+        limiter.acquire(5_000).await;
+        assert!(start.elapsed() >= Duration::from_millis(450));
+    }
+
+    #[tokio::test]
+    async fn test_bandwidth_limiter_zero_is_unlimited() {
+        let limiter = BandwidthLimiter::new(0);
+        limiter.acquire(usize::MAX).await;
+    }
+
+    #[test]
+    fn test_prune_keeps_depleted_buckets() {
+        let limiter = IpRateLimiter::new(1, 2);
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        limiter.check(ip).unwrap();
+        limiter.prune();
+        assert_eq!(limiter.tracked_ips(), 1);
+    }
+}
@@ -1,10 +1,272 @@
 // Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
 // Licensed under the FSL-1.1-NC.
 
-use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::{Duration, Instant};
-use tokio::sync::RwLock;
+use crate::auth::Authenticator;
+use crate::circuit_breaker::{BreakerPolicy, CircuitBreaker};
+use crate::rate_limit::BandwidthLimiter;
+use crate::workstation_counter::{InMemoryWorkstationCounter, WorkstationCounter};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{
+    broadcast, AcquireError, Notify, OwnedSemaphorePermit, RwLock, Semaphore, TryAcquireError,
+};
+
+pub const MAX_WORKSTATION_ID_LEN: usize = 63;
+
+/// How often a request waiting on a reconnecting workstation re-checks it.
+const RECONNECT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// QUIC application close code sent to workstations evicted by `revalidate`.
+pub const API_KEY_REVOKED_CODE: u32 = 0x4001;
+
+/// QUIC application close code sent to workstations evicted after their grace period.
+pub const GRACE_EXPIRED_CODE: u32 = 0x4002;
+
+/// QUIC application close code sent to workstations that went silent for
+/// longer than the liveness timeout.
+pub const LIVENESS_TIMEOUT_CODE: u32 = 0x4003;
+
+/// QUIC application close code sent on a connection replaced by a reconnect,
+/// once the streams still running on it have finished.
+pub const MIGRATED_CODE: u32 = 0x4004;
+
+/// QUIC application close code sent when a 0-RTT reconnect, already
+/// answered, is refused once its handshake completes.
+pub const RECONNECT_FAILED_CODE: u32 = 0x4005;
+
+/// Events buffered per subscriber before the slowest one starts lagging.
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// Ids that would collide with server routes or read as official endpoints.
+const RESERVED_WORKSTATION_IDS: &[&str] = &[
+    "admin", "api", "health", "metrics", "status", "t", "ws", "www", "tunnel",
+];
+
+/// Checks that `id` is safe to use as a URL path segment: 1-63 characters of
+/// `[a-z0-9-]`, not starting or ending with `-`, and not reserved.
+pub fn validate_workstation_id(id: &str) -> Result<(), String> {
+    if id.is_empty() || id.len() > MAX_WORKSTATION_ID_LEN {
+        return Err(format!(
+            "workstation id must be 1-{} characters",
+            MAX_WORKSTATION_ID_LEN
+        ));
+    }
+    if !id
+        .bytes()
+        .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+    {
+        return Err(format!(
+            "workstation id '{}' may only contain a-z, 0-9 and '-'",
+            id
+        ));
+    }
+    if id.starts_with('-') || id.ends_with('-') {
+        return Err(format!(
+            "workstation id '{}' must not start or end with '-'",
+            id
+        ));
+    }
+    if RESERVED_WORKSTATION_IDS.contains(&id) {
+        return Err(format!("workstation id '{}' is reserved", id));
+    }
+    Ok(())
+}
+
+/// Random per-session secret handed to the client for its next reconnect.
+pub fn new_reconnect_token() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+/// Compares secret tokens, such as reconnect tokens and the admin key,
+/// without stopping at the first differing byte.
+pub(crate) fn tokens_match(presented: &str, expected: &str) -> bool {
+    presented.len() == expected.len()
+        && presented
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Body bytes proxied for a workstation. `bytes_in` counts data sent from public
+/// clients into the tunnel, `bytes_out` counts data relayed back to them.
+#[derive(Debug, Default)]
+pub struct TrafficCounters {
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+}
+
+impl TrafficCounters {
+    pub fn add_in(&self, bytes: usize) {
+        self.bytes_in.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn add_out(&self, bytes: usize) {
+        self.bytes_out.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn bytes_in(&self) -> u64 {
+        self.bytes_in.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_out(&self) -> u64 {
+        self.bytes_out.load(Ordering::Relaxed)
+    }
+
+    pub fn total(&self) -> u64 {
+        self.bytes_in() + self.bytes_out()
+    }
+
+    pub fn reset(&self) {
+        self.bytes_in.store(0, Ordering::Relaxed);
+        self.bytes_out.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Proxied streams in flight on one connection, so a connection replaced by a
+/// reconnect can be closed once they have finished.
+#[derive(Debug, Default)]
+pub struct ConnectionStreams {
+    active: AtomicUsize,
+    idle: Notify,
+}
+
+impl ConnectionStreams {
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Acquire)
+    }
+
+    /// Resolves once no streams are in flight.
+    pub async fn wait_idle(&self) {
+        loop {
+            let notified = self.idle.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if self.active() == 0 {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Held for the lifetime of a proxied stream: a slot of the workstation's
+/// stream limit, counted against the connection the stream was opened on.
+#[derive(Debug)]
+pub struct StreamPermit {
+    _permit: OwnedSemaphorePermit,
+    streams: Arc<ConnectionStreams>,
+}
+
+impl Drop for StreamPermit {
+    fn drop(&mut self) {
+        if self.streams.active.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.streams.idle.notify_waiters();
+        }
+    }
+}
+
+/// Why a queued request got no stream slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueRejection {
+    /// As many requests as allowed were already waiting.
+    Full,
+    /// No slot freed up within the queue timeout.
+    TimedOut,
+    /// The workstation's stream limit was closed.
+    Closed,
+}
+
+/// Counts a request as waiting in the queue until dropped.
+struct Queued<'a>(&'a AtomicUsize);
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Local backend state last reported by the workstation's client.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BackendHealth {
+    pub healthy: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl Default for BackendHealth {
+    fn default() -> Self {
+        Self {
+            healthy: true,
+            detail: None,
+        }
+    }
+}
+
+/// A connection taken over by a reconnect while it was still open.
+#[derive(Debug)]
+pub struct ReplacedConnection {
+    pub connection: quinn::Connection,
+    pub streams: Arc<ConnectionStreams>,
+}
+
+/// Outcome of a successful reconnect.
+#[derive(Debug)]
+pub struct Reconnected {
+    /// Token the client must present on its next reconnect.
+    pub reconnect_token: String,
+    /// The previous connection, if it was still open.
+    pub replaced: Option<ReplacedConnection>,
+}
+
+/// Why a reconnect was refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReconnectRejection {
+    /// The token does not match the one issued last.
+    InvalidToken,
+    /// The workstation was disconnected for longer than the grace period.
+    GraceExpired,
+    NotFound(String),
+}
+
+impl std::fmt::Display for ReconnectRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidToken => f.write_str("invalid reconnect token"),
+            Self::GraceExpired => f.write_str("grace period expired"),
+            Self::NotFound(id) => write!(f, "workstation {} not found", id),
+        }
+    }
+}
+
+/// What a client announced it supports during the handshake.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientCapabilities {
+    /// Frame size limit negotiated with the client.
+    pub max_frame_bytes: usize,
+    pub ws_open_ack: bool,
+    pub expect_continue: bool,
+    pub ws_fragments: bool,
+    pub ordered_headers: bool,
+    pub ws_passthrough: bool,
+}
+
+/// A client registered under an id that is already served, kept connected so
+/// it can take over as soon as the active connection fails.
+#[derive(Debug, Clone)]
+pub struct Standby {
+    pub connection: quinn::Connection,
+    capabilities: ClientCapabilities,
+    metadata: BTreeMap<String, String>,
+    tags: Vec<String>,
+    api_key: String,
+    reconnect_token: String,
+    /// Woken once this standby becomes the active connection.
+    promoted: Arc<Notify>,
+}
 
 #[derive(Debug, Clone)]
 pub struct WorkstationInfo {
@@ -12,6 +274,170 @@ pub struct WorkstationInfo {
     pub connection: quinn::Connection,
     pub registered_at: Instant,
     pub state: WorkstationState,
+    /// Bounds the number of concurrently handled QUIC streams for this
+    /// workstation, shared by inbound stream handlers and proxy-opened streams.
+    pub stream_permits: Arc<Semaphore>,
+    /// Requests waiting in `queue_for_stream` for a slot.
+    queued: Arc<AtomicUsize>,
+    /// Streams in flight on `connection`; replaced along with it.
+    pub streams: Arc<ConnectionStreams>,
+    /// Frame size limit negotiated with the client during the handshake.
+    pub max_frame_bytes: usize,
+    /// Whether the client reports WebSocket connects with `WsOpened`.
+    pub ws_open_ack: bool,
+    /// Whether the client handles `expect_continue` HTTP requests.
+    pub expect_continue: bool,
+    /// Whether the client reassembles fragmented `WsData` messages.
+    pub ws_fragments: bool,
+    /// Whether the client takes request headers as an ordered list.
+    pub ordered_headers: bool,
+    /// Whether the client relays WebSocket handshakes and raw frames.
+    pub ws_passthrough: bool,
+    /// Details the client reported at registration, for operators.
+    pub metadata: BTreeMap<String, String>,
+    /// Tags the client declared at registration, sorted and de-duplicated.
+    pub tags: Vec<String>,
+    /// Standby clients in registration order; the first one still connected
+    /// takes over when `connection` fails.
+    pub standbys: Vec<Standby>,
+    /// Set by an operator: new requests are refused while in-flight streams
+    /// finish. Kept across reconnects.
+    pub draining: bool,
+    /// API key presented on the latest register or reconnect.
+    api_key: String,
+    /// Secret a `Reconnect` must carry; rotated on every register and reconnect.
+    reconnect_token: String,
+    /// Shared with in-flight relays so usage survives reconnects.
+    pub traffic: Arc<TrafficCounters>,
+    /// Paces proxied bytes in both directions across all of the workstation's streams.
+    pub bandwidth: Arc<BandwidthLimiter>,
+    /// Trips when the workstation's backend keeps failing.
+    pub breaker: Arc<CircuitBreaker>,
+    /// Reported by the client; reset when it connects again.
+    backend_health: Arc<Mutex<BackendHealth>>,
+    /// When a message was last received from the workstation.
+    last_seen: Arc<Mutex<Instant>>,
+    max_streams: usize,
+}
+
+impl WorkstationInfo {
+    pub fn active_streams(&self) -> usize {
+        self.max_streams - self.stream_permits.available_permits()
+    }
+
+    /// Records that a message just arrived from the workstation.
+    pub fn touch(&self) {
+        *self.last_seen.lock().unwrap() = Instant::now();
+    }
+
+    pub fn last_seen(&self) -> Instant {
+        *self.last_seen.lock().unwrap()
+    }
+
+    /// Whether every tag in `tags` was declared by the workstation.
+    pub fn has_tags(&self, tags: &[String]) -> bool {
+        tags.iter().all(|tag| self.tags.binary_search(tag).is_ok())
+    }
+
+    pub fn backend_health(&self) -> BackendHealth {
+        self.backend_health.lock().unwrap().clone()
+    }
+
+    /// Stores the client's latest report, returning whether it changed.
+    pub fn set_backend_health(&self, health: BackendHealth) -> bool {
+        let mut current = self.backend_health.lock().unwrap();
+        let changed = *current != health;
+        *current = health;
+        changed
+    }
+
+    /// Waits for a free stream slot on the current connection.
+    pub async fn acquire_stream(&self) -> Result<StreamPermit, AcquireError> {
+        let permit = self.stream_permits.clone().acquire_owned().await?;
+        self.streams.active.fetch_add(1, Ordering::AcqRel);
+        Ok(StreamPermit {
+            _permit: permit,
+            streams: self.streams.clone(),
+        })
+    }
+
+    /// Like `acquire_stream`, but waits behind at most `max_waiting` other
+    /// requests (0 is unbounded) and for at most `timeout`.
+    pub async fn queue_for_stream(
+        &self,
+        max_waiting: usize,
+        timeout: Option<Duration>,
+    ) -> Result<StreamPermit, QueueRejection> {
+        let permit = match self.stream_permits.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(TryAcquireError::Closed) => return Err(QueueRejection::Closed),
+            Err(TryAcquireError::NoPermits) => {
+                let waiting = self.queued.fetch_add(1, Ordering::AcqRel);
+                let _queued = Queued(&self.queued);
+                if max_waiting > 0 && waiting >= max_waiting {
+                    return Err(QueueRejection::Full);
+                }
+                let acquire = self.stream_permits.clone().acquire_owned();
+                let result = match timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, acquire)
+                        .await
+                        .map_err(|_| QueueRejection::TimedOut)?,
+                    None => acquire.await,
+                };
+                result.map_err(|_| QueueRejection::Closed)?
+            }
+        };
+        self.streams.active.fetch_add(1, Ordering::AcqRel);
+        Ok(StreamPermit {
+            _permit: permit,
+            streams: self.streams.clone(),
+        })
+    }
+
+    /// Switches to `connection`, returning the previous one if it is still
+    /// open and different.
+    fn replace_connection(&mut self, connection: quinn::Connection) -> Option<ReplacedConnection> {
+        let old = std::mem::replace(&mut self.connection, connection);
+        let streams = std::mem::take(&mut self.streams);
+        self.set_backend_health(BackendHealth::default());
+        (old.stable_id() != self.connection.stable_id() && old.close_reason().is_none()).then_some(
+            ReplacedConnection {
+                connection: old,
+                streams,
+            },
+        )
+    }
+
+    /// Makes the first standby that is still connected the active connection.
+    /// Returns `false` if none is left.
+    fn promote_standby(&mut self) -> bool {
+        while !self.standbys.is_empty() {
+            let standby = self.standbys.remove(0);
+            if standby.connection.close_reason().is_some() {
+                continue;
+            }
+            self.replace_connection(standby.connection);
+            self.apply(standby.capabilities);
+            self.metadata = standby.metadata;
+            self.tags = standby.tags;
+            self.api_key = standby.api_key;
+            self.reconnect_token = standby.reconnect_token;
+            self.state = WorkstationState::Active;
+            self.touch();
+            standby.promoted.notify_one();
+            return true;
+        }
+        false
+    }
+
+    fn apply(&mut self, capabilities: ClientCapabilities) {
+        self.max_frame_bytes = capabilities.max_frame_bytes;
+        self.ws_open_ack = capabilities.ws_open_ack;
+        self.expect_continue = capabilities.expect_continue;
+        self.ws_fragments = capabilities.ws_fragments;
+        self.ordered_headers = capabilities.ordered_headers;
+        self.ws_passthrough = capabilities.ws_passthrough;
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -20,37 +446,209 @@ pub enum WorkstationState {
     Reconnecting { since: Instant },
 }
 
+/// Why [`WorkstationRegistry::wait_until_active`] gave up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unavailable {
+    /// Nothing is registered under the id, or its grace period ran out.
+    Unknown,
+    /// Still reconnecting; the grace period ends after `remaining`.
+    Reconnecting { remaining: Duration },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistryEventKind {
+    Registered,
+    Reconnecting,
+    Disconnected,
+}
+
+/// A workstation lifecycle change, published to `WorkstationRegistry::subscribe`.
+#[derive(Debug, Clone, Serialize)]
+pub struct RegistryEvent {
+    pub workstation_id: String,
+    pub state: RegistryEventKind,
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+impl RegistryEvent {
+    fn new(workstation_id: &str, state: RegistryEventKind) -> Self {
+        Self {
+            workstation_id: workstation_id.to_string(),
+            state,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        }
+    }
+}
+
 pub struct WorkstationRegistry {
     workstations: Arc<RwLock<HashMap<String, WorkstationInfo>>>,
     grace_period: Duration,
+    max_streams_per_connection: usize,
+    max_bytes_per_sec: u64,
+    liveness_timeout: Duration,
+    breaker_policy: BreakerPolicy,
+    allow_standby: bool,
+    events: broadcast::Sender<RegistryEvent>,
+    /// Released whenever a workstation is removed.
+    counter: Arc<dyn WorkstationCounter>,
 }
 
 impl WorkstationRegistry {
     pub fn new(grace_period: Duration) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
         Self {
             workstations: Arc::new(RwLock::new(HashMap::new())),
             grace_period,
+            max_streams_per_connection: Semaphore::MAX_PERMITS,
+            max_bytes_per_sec: 0,
+            liveness_timeout: Duration::ZERO,
+            breaker_policy: BreakerPolicy::DISABLED,
+            allow_standby: false,
+            events,
+            counter: Arc::new(InMemoryWorkstationCounter::new()),
         }
     }
 
-    pub async fn register(&self, id: String, connection: quinn::Connection) -> Result<(), String> {
+    /// Receives workstation register, reconnecting and disconnect events.
+    pub fn subscribe(&self) -> broadcast::Receiver<RegistryEvent> {
+        self.events.subscribe()
+    }
+
+    fn emit(&self, workstation_id: &str, state: RegistryEventKind) {
+        // Sending only fails when nobody is subscribed.
+        let _ = self.events.send(RegistryEvent::new(workstation_id, state));
+    }
+
+    pub fn with_max_streams_per_connection(mut self, max_streams: usize) -> Self {
+        self.max_streams_per_connection = max_streams.min(Semaphore::MAX_PERMITS);
+        self
+    }
+
+    /// Caps each workstation's proxied throughput. 0 leaves it unlimited.
+    pub fn with_max_bytes_per_sec(mut self, max_bytes_per_sec: u64) -> Self {
+        self.max_bytes_per_sec = max_bytes_per_sec;
+        self
+    }
+
+    /// Treats workstations silent for longer than `timeout` as disconnected.
+    /// Zero disables the check.
+    pub fn with_liveness_timeout(mut self, timeout: Duration) -> Self {
+        self.liveness_timeout = timeout;
+        self
+    }
+
+    pub fn with_circuit_breaker(mut self, policy: BreakerPolicy) -> Self {
+        self.breaker_policy = policy;
+        self
+    }
+
+    /// Lets a second client register under an id that is already active. It
+    /// waits as a standby and takes over once the active connection fails.
+    pub fn with_standby(mut self, allow_standby: bool) -> Self {
+        self.allow_standby = allow_standby;
+        self
+    }
+
+    /// Frees a removed workstation's slot in `counter`, which must be the one
+    /// registrations are admitted against.
+    pub fn with_workstation_counter(mut self, counter: Arc<dyn WorkstationCounter>) -> Self {
+        self.counter = counter;
+        self
+    }
+
+    pub async fn register(
+        &self,
+        id: String,
+        connection: quinn::Connection,
+        capabilities: ClientCapabilities,
+        metadata: HashMap<String, String>,
+        mut tags: Vec<String>,
+        api_key: &str,
+    ) -> Result<String, String> {
+        validate_workstation_id(&id)?;
+        tags.sort();
+        tags.dedup();
+
         let mut workstations = self.workstations.write().await;
 
-        if workstations.contains_key(&id) {
-            return Err(format!("workstation {} already registered", id));
+        if let Some(info) = workstations.get_mut(&id) {
+            // A client restarting within the grace period takes over its slot,
+            // so requests waiting on the reconnect are served by the new connection.
+            // An entry past its grace period is simply replaced below.
+            match info.state {
+                WorkstationState::Reconnecting { since }
+                    if since.elapsed() <= self.grace_period =>
+                {
+                    info.replace_connection(connection);
+                    info.apply(capabilities);
+                    info.metadata = metadata.into_iter().collect();
+                    info.tags = tags;
+                    info.api_key = api_key.to_string();
+                    info.reconnect_token = new_reconnect_token();
+                    info.state = WorkstationState::Active;
+                    info.touch();
+                    self.emit(&id, RegistryEventKind::Registered);
+                    return Ok(info.reconnect_token.clone());
+                }
+                WorkstationState::Reconnecting { .. } => {}
+                WorkstationState::Active if self.allow_standby => {
+                    let reconnect_token = new_reconnect_token();
+                    info.standbys.push(Standby {
+                        connection,
+                        capabilities,
+                        metadata: metadata.into_iter().collect(),
+                        tags,
+                        api_key: api_key.to_string(),
+                        reconnect_token: reconnect_token.clone(),
+                        promoted: Arc::default(),
+                    });
+                    return Ok(reconnect_token);
+                }
+                WorkstationState::Active => {
+                    return Err(format!("workstation {} already registered", id));
+                }
+            }
         }
 
+        let reconnect_token = new_reconnect_token();
         workstations.insert(
             id.clone(),
             WorkstationInfo {
-                id,
+                id: id.clone(),
                 connection,
                 registered_at: Instant::now(),
                 state: WorkstationState::Active,
+                stream_permits: Arc::new(Semaphore::new(self.max_streams_per_connection)),
+                queued: Arc::default(),
+                streams: Arc::default(),
+                max_frame_bytes: capabilities.max_frame_bytes,
+                ws_open_ack: capabilities.ws_open_ack,
+                expect_continue: capabilities.expect_continue,
+                ws_fragments: capabilities.ws_fragments,
+                ordered_headers: capabilities.ordered_headers,
+                ws_passthrough: capabilities.ws_passthrough,
+                metadata: metadata.into_iter().collect(),
+                tags,
+                standbys: Vec::new(),
+                draining: false,
+                api_key: api_key.to_string(),
+                reconnect_token: reconnect_token.clone(),
+                traffic: Arc::new(TrafficCounters::default()),
+                bandwidth: Arc::new(BandwidthLimiter::new(self.max_bytes_per_sec)),
+                breaker: Arc::new(CircuitBreaker::new(self.breaker_policy)),
+                backend_health: Arc::default(),
+                last_seen: Arc::new(Mutex::new(Instant::now())),
+                max_streams: self.max_streams_per_connection,
             },
         );
+        self.emit(&id, RegistryEventKind::Registered);
 
-        Ok(())
+        Ok(reconnect_token)
     }
 
     pub async fn get(&self, id: &str) -> Option<WorkstationInfo> {
@@ -58,36 +656,197 @@ impl WorkstationRegistry {
         workstations.get(id).cloned()
     }
 
-    pub async fn mark_reconnecting(&self, id: &str) {
+    /// Marks `id` as reconnecting if `connection` is still the one serving it,
+    /// unless a standby can take over; a connection that was already replaced
+    /// has nothing left to mark, and one already reconnecting keeps its
+    /// original grace period. A standby connection is dropped from the list.
+    pub async fn mark_reconnecting(&self, id: &str, connection: &quinn::Connection) {
         let mut workstations = self.workstations.write().await;
-        if let Some(info) = workstations.get_mut(id) {
+        let Some(info) = workstations.get_mut(id) else {
+            return;
+        };
+        if info.connection.stable_id() != connection.stable_id() {
+            info.standbys
+                .retain(|standby| standby.connection.stable_id() != connection.stable_id());
+            return;
+        }
+        if info.state != WorkstationState::Active {
+            return;
+        }
+        if info.promote_standby() {
+            self.emit(id, RegistryEventKind::Registered);
+        } else {
             info.state = WorkstationState::Reconnecting {
                 since: Instant::now(),
             };
+            self.emit(id, RegistryEventKind::Reconnecting);
+        }
+    }
+
+    /// Returns the signal woken when `connection`, a standby for `id`, becomes
+    /// the active connection, or `None` if it is not a standby.
+    pub async fn standby_promotion(
+        &self,
+        id: &str,
+        connection: &quinn::Connection,
+    ) -> Option<Arc<Notify>> {
+        let workstations = self.workstations.read().await;
+        workstations
+            .get(id)?
+            .standbys
+            .iter()
+            .find(|standby| standby.connection.stable_id() == connection.stable_id())
+            .map(|standby| standby.promoted.clone())
+    }
+
+    /// Returns the workstation once it is active. While it is reconnecting this
+    /// polls for up to the rest of its grace period, capped at `max_wait`, and
+    /// says why if it does not come back in time.
+    pub async fn wait_until_active(
+        &self,
+        id: &str,
+        max_wait: Duration,
+    ) -> Result<WorkstationInfo, Unavailable> {
+        let deadline = Instant::now() + max_wait;
+        loop {
+            let info = self.get(id).await.ok_or(Unavailable::Unknown)?;
+            let since = match info.state {
+                WorkstationState::Active => return Ok(info),
+                WorkstationState::Reconnecting { since } => since,
+            };
+            let grace_ends = since + self.grace_period;
+            let give_up_at = deadline.min(grace_ends);
+            let now = Instant::now();
+            if now >= grace_ends {
+                return Err(Unavailable::Unknown);
+            }
+            if now >= give_up_at {
+                return Err(Unavailable::Reconnecting {
+                    remaining: grace_ends - now,
+                });
+            }
+            tokio::time::sleep(RECONNECT_POLL_INTERVAL.min(give_up_at - now)).await;
         }
     }
 
-    pub async fn reconnect(&self, id: &str, connection: quinn::Connection) -> Result<(), String> {
+    /// Checks whether `reconnect` would take `id` over with `reconnect_token`,
+    /// without changing anything.
+    pub async fn check_reconnect(
+        &self,
+        id: &str,
+        reconnect_token: Option<&str>,
+    ) -> Result<(), ReconnectRejection> {
+        let workstations = self.workstations.read().await;
+        match workstations.get(id) {
+            Some(info) => self.reconnect_allowed(info, reconnect_token),
+            None => Err(ReconnectRejection::NotFound(id.to_string())),
+        }
+    }
+
+    fn reconnect_allowed(
+        &self,
+        info: &WorkstationInfo,
+        reconnect_token: Option<&str>,
+    ) -> Result<(), ReconnectRejection> {
+        if !reconnect_token.is_some_and(|token| tokens_match(token, &info.reconnect_token)) {
+            return Err(ReconnectRejection::InvalidToken);
+        }
+        if let WorkstationState::Reconnecting { since } = info.state {
+            if since.elapsed() > self.grace_period {
+                return Err(ReconnectRejection::GraceExpired);
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves `id` onto `connection` and makes `next_token` the one its next
+    /// reconnect must present. If the previous connection is still open it is
+    /// returned so the caller can let its streams finish before closing it;
+    /// new streams use `connection` from now on.
+    pub async fn reconnect(
+        &self,
+        id: &str,
+        connection: quinn::Connection,
+        capabilities: ClientCapabilities,
+        api_key: &str,
+        reconnect_token: Option<&str>,
+        next_token: String,
+    ) -> Result<Reconnected, ReconnectRejection> {
         let mut workstations = self.workstations.write().await;
 
         match workstations.get_mut(id) {
             Some(info) => {
-                if let WorkstationState::Reconnecting { since } = info.state {
-                    if since.elapsed() > self.grace_period {
-                        return Err("grace period expired".to_string());
-                    }
-                }
-                info.connection = connection;
+                self.reconnect_allowed(info, reconnect_token)?;
+                let replaced = info.replace_connection(connection);
+                info.apply(capabilities);
+                info.api_key = api_key.to_string();
+                info.reconnect_token = next_token;
                 info.state = WorkstationState::Active;
-                Ok(())
+                info.touch();
+                self.emit(id, RegistryEventKind::Registered);
+                Ok(Reconnected {
+                    reconnect_token: info.reconnect_token.clone(),
+                    replaced,
+                })
             }
-            None => Err(format!("workstation {} not found", id)),
+            None => Err(ReconnectRejection::NotFound(id.to_string())),
         }
     }
 
     pub async fn unregister(&self, id: &str) {
+        let removed = self.workstations.write().await.remove(id).is_some();
+        if removed {
+            self.counter.release(id).await;
+            self.emit(id, RegistryEventKind::Disconnected);
+        }
+    }
+
+    /// Refreshes the counter slots of every workstation this server holds.
+    pub async fn refresh_slots(&self) {
+        let ids: Vec<String> = self.workstations.read().await.keys().cloned().collect();
+        self.counter.refresh(&ids).await;
+    }
+
+    /// Starts or ends draining `id`. Returns `false` if it is not registered.
+    pub async fn set_draining(&self, id: &str, draining: bool) -> bool {
         let mut workstations = self.workstations.write().await;
-        workstations.remove(id);
+        match workstations.get_mut(id) {
+            Some(info) => {
+                info.draining = draining;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Clears the byte counters for `id`, lifting any quota block.
+    pub async fn reset_traffic(&self, id: &str) -> bool {
+        let workstations = self.workstations.read().await;
+        match workstations.get(id) {
+            Some(info) => {
+                info.traffic.reset();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub async fn list(&self) -> Vec<WorkstationInfo> {
+        let workstations = self.workstations.read().await;
+        workstations.values().cloned().collect()
+    }
+
+    /// Active workstations that declared every tag in `tags`, ordered by id,
+    /// for picking one by capability.
+    pub async fn find_by_tags(&self, tags: &[String]) -> Vec<WorkstationInfo> {
+        let workstations = self.workstations.read().await;
+        let mut found: Vec<WorkstationInfo> = workstations
+            .values()
+            .filter(|info| info.state == WorkstationState::Active && info.has_tags(tags))
+            .cloned()
+            .collect();
+        found.sort_by(|a, b| a.id.cmp(&b.id));
+        found
     }
 
     pub async fn count(&self) -> usize {
@@ -95,16 +854,218 @@ impl WorkstationRegistry {
         workstations.len()
     }
 
-    pub async fn cleanup_expired(&self) {
+    /// Checks the credential each workstation and standby last presented
+    /// against `authenticator` again, e.g. after the API key was rotated, and
+    /// closes and removes those it now refuses. Returns the evicted ids.
+    pub async fn revalidate(&self, authenticator: &dyn Authenticator) -> Vec<String> {
+        // Authenticators may be slow, so they run without the registry locked.
+        let presented: Vec<(String, usize, String)> = {
+            let workstations = self.workstations.read().await;
+            workstations
+                .values()
+                .flat_map(|info| {
+                    std::iter::once((
+                        info.id.clone(),
+                        info.connection.stable_id(),
+                        info.api_key.clone(),
+                    ))
+                    .chain(info.standbys.iter().map(|standby| {
+                        (
+                            info.id.clone(),
+                            standby.connection.stable_id(),
+                            standby.api_key.clone(),
+                        )
+                    }))
+                })
+                .collect()
+        };
+        let mut refused = HashSet::new();
+        for (id, connection, api_key) in presented {
+            if authenticator.authenticate(&id, &api_key).await.is_err() {
+                refused.insert(connection);
+            }
+        }
+
         let mut workstations = self.workstations.write().await;
-        let now = Instant::now();
+        for info in workstations.values_mut() {
+            info.standbys.retain(|standby| {
+                let refused = refused.contains(&standby.connection.stable_id());
+                if refused {
+                    standby
+                        .connection
+                        .close(API_KEY_REVOKED_CODE.into(), b"api key revoked");
+                }
+                !refused
+            });
+        }
+        let stale: Vec<String> = workstations
+            .values()
+            .filter(|info| refused.contains(&info.connection.stable_id()))
+            .map(|info| info.id.clone())
+            .collect();
+
+        for id in &stale {
+            if let Some(info) = workstations.remove(id) {
+                info.connection
+                    .close(API_KEY_REVOKED_CODE.into(), b"api key revoked");
+            }
+        }
+        drop(workstations);
+        for id in &stale {
+            self.counter.release(id).await;
+            self.emit(id, RegistryEventKind::Disconnected);
+        }
+        stale
+    }
+
+    pub async fn reap_idle(&self) -> Vec<String> {
+        self.reap_idle_at(Instant::now()).await
+    }
 
-        workstations.retain(|_id, info| {
-            if let WorkstationState::Reconnecting { since } = info.state {
-                now.duration_since(since) <= self.grace_period
+    /// Marks active workstations that have sent nothing for longer than the
+    /// liveness timeout as of `now` as reconnecting and closes their
+    /// connections, so a half-open connection stops counting as capacity and
+    /// is evicted once its grace period runs out. A standby, if any, takes
+    /// over instead. Returns the reaped ids.
+    pub async fn reap_idle_at(&self, now: Instant) -> Vec<String> {
+        if self.liveness_timeout.is_zero() {
+            return Vec::new();
+        }
+        let mut workstations = self.workstations.write().await;
+        let mut reaped = Vec::new();
+        for (id, info) in workstations.iter_mut() {
+            if info.state != WorkstationState::Active
+                || now.saturating_duration_since(info.last_seen()) <= self.liveness_timeout
+            {
+                continue;
+            }
+            info.connection
+                .close(LIVENESS_TIMEOUT_CODE.into(), b"liveness timeout");
+            if info.promote_standby() {
+                self.emit(id, RegistryEventKind::Registered);
             } else {
-                true
+                info.state = WorkstationState::Reconnecting { since: now };
+                self.emit(id, RegistryEventKind::Reconnecting);
+            }
+            reaped.push(id.clone());
+        }
+        reaped
+    }
+
+    pub async fn cleanup_expired(&self) {
+        self.cleanup_expired_at(Instant::now()).await;
+    }
+
+    /// Evicts workstations that have been reconnecting for longer than the
+    /// grace period as of `now`, closing their stale connections.
+    pub async fn cleanup_expired_at(&self, now: Instant) {
+        let mut workstations = self.workstations.write().await;
+
+        let mut expired = Vec::new();
+        workstations.retain(|id, info| {
+            let is_expired = matches!(info.state, WorkstationState::Reconnecting { since }
+                if now.saturating_duration_since(since) > self.grace_period);
+            if is_expired {
+                info.connection
+                    .close(GRACE_EXPIRED_CODE.into(), b"grace period expired");
+                expired.push(id.clone());
             }
+            !is_expired
         });
+        drop(workstations);
+        for id in &expired {
+            self.counter.release(id).await;
+            self.emit(id, RegistryEventKind::Disconnected);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_workstation_ids() {
+        assert!(validate_workstation_id("my-workstation").is_ok());
+        assert!(validate_workstation_id("ws1").is_ok());
+        assert!(validate_workstation_id(&"a".repeat(MAX_WORKSTATION_ID_LEN)).is_ok());
+    }
+
+    #[test]
+    fn test_reserved_workstation_ids() {
+        for id in ["health", "metrics", "admin"] {
+            let err = validate_workstation_id(id).unwrap_err();
+            assert!(err.contains("reserved"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_invalid_workstation_id_characters() {
+        for id in [
+            "",
+            "has space",
+            "a/b",
+            "Upper",
+            "under_score",
+            "-lead",
+            "trail-",
+        ] {
+            assert!(validate_workstation_id(id).is_err(), "{:?} accepted", id);
+        }
+        assert!(validate_workstation_id(&"a".repeat(MAX_WORKSTATION_ID_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn test_reconnect_tokens() {
+        let token = new_reconnect_token();
+        assert_eq!(token.len(), 32);
+        assert_ne!(token, new_reconnect_token());
+        assert!(tokens_match(&token, &token.clone()));
+        assert!(!tokens_match(&token, &token[..31]));
+        assert!(!tokens_match(&"0".repeat(32), &token));
+    }
+
+    #[test]
+    fn test_traffic_counters() {
+        let traffic = TrafficCounters::default();
+        traffic.add_in(100);
+        traffic.add_out(250);
+        assert_eq!(traffic.bytes_in(), 100);
+        assert_eq!(traffic.bytes_out(), 250);
+        assert_eq!(traffic.total(), 350);
+
+        traffic.reset();
+        assert_eq!(traffic.total(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_connection_streams_wait_idle() {
+        let semaphore = Arc::new(Semaphore::new(4));
+        let streams = Arc::new(ConnectionStreams::default());
+        let permit = |streams: &Arc<ConnectionStreams>| {
+            streams.active.fetch_add(1, Ordering::AcqRel);
+            StreamPermit {
+                _permit: semaphore.clone().try_acquire_owned().unwrap(),
+                streams: streams.clone(),
+            }
+        };
+        let first = permit(&streams);
+        let second = permit(&streams);
+        assert_eq!(streams.active(), 2);
+
+        let waiter = tokio::spawn({
+            let streams = streams.clone();
+            async move { streams.wait_idle().await }
+        });
+        drop(first);
+        tokio::task::yield_now().await;
+        assert!(!waiter.is_finished());
+
+        drop(second);
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("wait_idle should resolve once the last stream ends")
+            .unwrap();
+        assert_eq!(semaphore.available_permits(), 4);
     }
 }
@@ -1,70 +1,333 @@
 // Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
 // Licensed under the FSL-1.1-NC.
 
+use crate::access_log::{access_log, AccessLog, AccessLogWriter};
+use crate::admin::{self, AdminState};
+use crate::auth::{Authenticator, StaticKeyAuthenticator};
+use crate::bind;
+use crate::cache::ResponseCache;
+use crate::cert_store::{CertStore, CertificateBundle, FileCertStore};
+use crate::circuit_breaker::BreakerPolicy;
 use crate::config::Config;
+use crate::cors::{CorsPolicy, OriginAllowlist};
+use crate::error::json_errors;
+use crate::interim;
 use crate::pending::PendingRequests;
-use crate::proxy::{handle_http_proxy, handle_websocket_proxy, ProxyState};
-use crate::registry::WorkstationRegistry;
+use crate::proxy::{
+    handle_connect_proxy, handle_http_proxy, handle_websocket_proxy, ProxyLimits, ProxyState,
+};
+use crate::proxy_protocol;
+use crate::rate_limit::{rate_limit, IpRateLimiter, TokenBucket};
+use crate::registry::{
+    self, validate_workstation_id, BackendHealth, ClientCapabilities, ReconnectRejection,
+    ReplacedConnection, WorkstationRegistry, MIGRATED_CODE, RECONNECT_FAILED_CODE,
+};
+use crate::spool::Spool;
+use crate::stream_id::{RandomStreamIds, StreamIdGenerator};
+use crate::url_rewrite::UrlRewriter;
+use crate::workstation_counter::{InMemoryWorkstationCounter, WorkstationCounter};
 use axum::{
     extract::{Path, State},
-    http::StatusCode,
+    http::{HeaderName, HeaderValue, Method, StatusCode},
     response::IntoResponse,
     routing::{any, get},
-    Router,
+    Json, Router,
 };
+use socket2::SockRef;
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::RwLock;
-use tokio::task::JoinHandle;
-use tracing::{error, info, warn};
+use tokio::sync::{broadcast, watch, RwLock, Semaphore};
+use tokio::task::{AbortHandle, JoinHandle};
+use tracing::{debug, error, info, warn};
+use tunnel_core::quic::ConnectionQuality;
 use tunnel_core::{quic, ErrorMessage, Message, RegisteredMessage};
 
 type AcmeChallenges = Arc<RwLock<HashMap<String, String>>>;
 
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Commit the server was built from, or `unknown` outside a git checkout.
+pub const GIT_SHA: &str = env!("TUNNEL_GIT_SHA");
+
 pub struct TunnelServer {
     config: Config,
     registry: Arc<WorkstationRegistry>,
     pending: Arc<PendingRequests>,
+    /// Taken by `shutdown` so the UDP port is released.
+    quic_endpoint: Arc<std::sync::Mutex<Option<quinn::Endpoint>>>,
     acme_challenges: AcmeChallenges,
+    /// Per-IP request limit; lets everything through at 0 requests per second.
+    rate_limiter: Arc<IpRateLimiter>,
+    /// Per-request limits shared with every proxy router.
+    limits: watch::Sender<ProxyLimits>,
+    /// Configuration last applied by `reload`.
+    reloaded: std::sync::Mutex<Config>,
+    /// Shared by the HTTP and HTTPS routers.
+    response_cache: Option<Arc<ResponseCache>>,
+    authenticator: Arc<dyn Authenticator>,
+    /// Bearer token for `/admin`, following `auth.api_key` across reloads.
+    admin_key: watch::Sender<String>,
+    cert_store: Arc<dyn CertStore>,
+    stream_ids: Arc<dyn StreamIdGenerator>,
+    /// Admits registrations against `limits.max_workstations`.
+    workstation_counter: Arc<dyn WorkstationCounter>,
+    access_log: Option<AccessLog>,
+    /// Taken and spawned by `run`.
+    access_log_writer: std::sync::Mutex<Option<AccessLogWriter>>,
+    /// Filled in as each listener binds.
+    listen_addrs: watch::Sender<ListenAddrs>,
+    /// Listener and background tasks stopped by `shutdown`.
+    tasks: std::sync::Mutex<Vec<AbortHandle>>,
+    /// DER certificate and PKCS#8 key from `self_signed_cert`.
+    self_signed: std::sync::OnceLock<(Vec<u8>, Vec<u8>)>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ListenAddrs {
+    http: Option<SocketAddr>,
+    quic: Option<SocketAddr>,
 }
 
 impl TunnelServer {
     pub fn new(config: Config) -> Self {
-        let registry = Arc::new(WorkstationRegistry::new(Duration::from_secs(
-            config.reliability.grace_period,
-        )));
-        let pending = Arc::new(PendingRequests::new());
+        // Frame counters for `/admin/debug`.
+        tunnel_core::codec::enable_metrics();
+        let workstation_counter: Arc<dyn WorkstationCounter> =
+            Arc::new(InMemoryWorkstationCounter::new());
+        let registry = Arc::new(registry_for(&config, workstation_counter.clone()));
+        let pending = Arc::new(PendingRequests::with_limit(
+            config.limits.max_pending_requests,
+        ));
+        let rate_limiter = Arc::new(IpRateLimiter::new(
+            config.limits.requests_per_second,
+            config.limits.burst,
+        ));
+        let limits = watch::Sender::new(ProxyLimits::from_config(&config));
+
+        let response_cache = ResponseCache::from_config(&config.server.cache).map(Arc::new);
+
+        let authenticator = Arc::new(StaticKeyAuthenticator::new(config.auth.api_key.clone()));
+        let admin_key = watch::Sender::new(config.auth.api_key.clone());
+        let cert_store = Arc::new(FileCertStore::new(config.tls.certs_dir.clone()));
+        let (access_log, access_log_writer) = AccessLog::from_config(&config.server.access_log)
+            .map_or((None, None), |(log, writer)| {
+                (
+                    Some(log.with_base_path(config.server.base_path())),
+                    Some(writer),
+                )
+            });
 
         Self {
+            reloaded: std::sync::Mutex::new(config.clone()),
             config,
             registry,
             pending,
+            quic_endpoint: Arc::new(std::sync::Mutex::new(None)),
             acme_challenges: Arc::new(RwLock::new(HashMap::new())),
+            rate_limiter,
+            limits,
+            response_cache,
+            authenticator,
+            admin_key,
+            cert_store,
+            stream_ids: Arc::new(RandomStreamIds),
+            workstation_counter,
+            access_log,
+            access_log_writer: std::sync::Mutex::new(access_log_writer),
+            listen_addrs: watch::Sender::new(ListenAddrs::default()),
+            tasks: std::sync::Mutex::new(Vec::new()),
+            self_signed: std::sync::OnceLock::new(),
         }
     }
 
+    /// Replaces the shared API key check used at registration and reconnect.
+    pub fn with_authenticator(mut self, authenticator: impl Authenticator + 'static) -> Self {
+        self.authenticator = Arc::new(authenticator);
+        self
+    }
+
+    /// Replaces the `tls.certs_dir` files as the place certificates are kept.
+    pub fn with_cert_store(mut self, store: impl CertStore + 'static) -> Self {
+        self.cert_store = Arc::new(store);
+        self
+    }
+
+    /// Replaces the random ids given to proxied requests and streams, e.g. with
+    /// [`SequentialStreamIds`](crate::stream_id::SequentialStreamIds) for
+    /// reproducible tests.
+    pub fn with_stream_ids(mut self, ids: impl StreamIdGenerator + 'static) -> Self {
+        self.stream_ids = Arc::new(ids);
+        self
+    }
+
+    /// Replaces the per-process count checked against
+    /// `limits.max_workstations`, e.g. with one shared by several servers.
+    pub fn with_workstation_counter(mut self, counter: impl WorkstationCounter + 'static) -> Self {
+        let counter: Arc<dyn WorkstationCounter> = Arc::new(counter);
+        self.registry = Arc::new(registry_for(&self.config, counter.clone()));
+        self.workstation_counter = counter;
+        self
+    }
+
     /// Initialize and return Arc<Self> with ACME configured if TLS is enabled
     pub async fn init(config: Config) -> anyhow::Result<Arc<Self>> {
-        let registry = Arc::new(WorkstationRegistry::new(Duration::from_secs(
-            config.reliability.grace_period,
-        )));
-        let pending = Arc::new(PendingRequests::new());
-        let acme_challenges = Arc::new(RwLock::new(HashMap::new()));
+        Ok(Self::new(config).with_acme())
+    }
 
-        let server = Arc::new(Self {
-            config,
-            registry,
-            pending,
-            acme_challenges,
-        });
+    /// Like [`init`](Self::init) for a server that is already built, e.g. with
+    /// a custom certificate store.
+    pub fn with_acme(self) -> Arc<Self> {
+        let server = Arc::new(self);
 
         if server.config.tls.enabled {
             server.clone().start_acme_manager();
         }
 
-        Ok(server)
+        server
+    }
+
+    /// Binds the HTTP, HTTPS and QUIC ports the server would use and releases
+    /// them again, failing if any of them is unavailable or the access log
+    /// cannot be opened.
+    pub async fn check(&self) -> anyhow::Result<()> {
+        let http_addr = self.config.server.http_bind()?;
+        let https_addr = self.config.server.https_bind()?;
+        let quic_addr = self.config.server.quic_bind()?;
+
+        let _http = bind::tcp_listener(http_addr)
+            .map_err(|e| anyhow::anyhow!("cannot bind HTTP port {}: {}", http_addr, e))?;
+        let _https = bind::tcp_listener(https_addr)
+            .map_err(|e| anyhow::anyhow!("cannot bind HTTPS port {}: {}", https_addr, e))?;
+        let _quic = bind::udp_socket(quic_addr)
+            .map_err(|e| anyhow::anyhow!("cannot bind QUIC port {}: {}", quic_addr, e))?;
+
+        if let Some(path) = &self.config.server.access_log.path {
+            tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .await
+                .map_err(|e| anyhow::anyhow!("cannot open access log {}: {}", path.display(), e))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn registry(&self) -> Arc<WorkstationRegistry> {
+        self.registry.clone()
+    }
+
+    pub fn pending(&self) -> Arc<PendingRequests> {
+        self.pending.clone()
+    }
+
+    fn proxy_state(&self) -> Arc<ProxyState> {
+        Arc::new(ProxyState {
+            registry: self.registry.clone(),
+            pending: self.pending.clone(),
+            limits: self.limits.subscribe(),
+            path_normalization: self.config.server.path_normalization,
+            h2_path_prefixes: self.config.h2.path_prefixes.clone(),
+            add_response_headers: self
+                .config
+                .server
+                .response_headers
+                .add
+                .iter()
+                .filter_map(|(name, value)| {
+                    Some((
+                        HeaderName::from_bytes(name.as_bytes()).ok()?,
+                        HeaderValue::from_str(value).ok()?,
+                    ))
+                })
+                .collect(),
+            remove_response_headers: self
+                .config
+                .server
+                .response_headers
+                .remove
+                .iter()
+                .map(|name| name.to_ascii_lowercase())
+                .collect(),
+            cors: CorsPolicy::from_config(&self.config.server.cors),
+            ws_allowed_origins: OriginAllowlist::from_config(
+                &self.config.server.ws_allowed_origins,
+            ),
+            decompress_responses: self.config.server.decompress_responses,
+            unknown_workstation_status: StatusCode::from_u16(
+                self.config.server.unknown_workstation_status,
+            )
+            .unwrap_or(StatusCode::NOT_FOUND),
+            allowed_methods: self
+                .config
+                .server
+                .allowed_methods
+                .iter()
+                .filter_map(|m| Method::from_bytes(m.as_bytes()).ok())
+                .collect(),
+            cache: self.response_cache.clone(),
+            spool: (self.config.server.spool_threshold > 0).then(|| {
+                Spool::new(
+                    self.config
+                        .server
+                        .spool_dir
+                        .clone()
+                        .unwrap_or_else(std::env::temp_dir),
+                    self.config.server.spool_threshold,
+                )
+            }),
+            url_rewrite: UrlRewriter::from_config(&self.config.server.url_rewrite),
+            stream_ids: self.stream_ids.clone(),
+            public_scheme: self.config.public_scheme(),
+            public_host: self.config.public_host(),
+            base_path: self.config.server.base_path().to_string(),
+        })
+    }
+
+    fn proxy_router(&self) -> Router {
+        let mut router = Router::new()
+            .route("/t/:workstation_id/*path", any(handle_http_proxy))
+            .route("/ws/:workstation_id/*path", get(handle_websocket_proxy));
+
+        router = router.route_layer(axum::middleware::from_fn_with_state(
+            self.rate_limiter.clone(),
+            rate_limit,
+        ));
+
+        let admin = admin::router(Arc::new(AdminState {
+            registry: self.registry.clone(),
+            pending: self.pending.clone(),
+            quic_endpoint: self.quic_endpoint.clone(),
+            api_key: self.admin_key.subscribe(),
+            authenticator: self.authenticator.clone(),
+        }));
+
+        let router = router
+            .route("/health", get(health_check))
+            .route("/version", get(version));
+        // `CONNECT` targets carry no path, so its fallback stays at the root.
+        let base_path = self.config.server.base_path();
+        let (router, admin) = if base_path.is_empty() {
+            (router, admin)
+        } else {
+            (
+                Router::new().nest(base_path, router),
+                Router::new().nest(base_path, admin),
+            )
+        };
+
+        let mut router = router
+            .fallback(handle_connect_proxy)
+            .with_state(self.proxy_state())
+            .layer(axum::middleware::from_fn(json_errors));
+        if let Some(log) = &self.access_log {
+            router = router.layer(axum::middleware::from_fn_with_state(
+                log.clone(),
+                access_log,
+            ));
+        }
+        router.merge(admin)
     }
 
     pub async fn run(self: Arc<Self>) -> anyhow::Result<()> {
@@ -72,6 +335,19 @@ impl TunnelServer {
         let https_handle = self.clone().start_https_server();
         let quic_handle = self.clone().start_quic_server().await?;
         let cleanup_handle = self.clone().start_cleanup_task();
+        if let Some(writer) = self.access_log_writer.lock().unwrap().take() {
+            tokio::spawn(writer.run());
+        }
+        let mut tasks = vec![
+            http_handle.abort_handle(),
+            https_handle.abort_handle(),
+            quic_handle.abort_handle(),
+            cleanup_handle.abort_handle(),
+        ];
+        if !self.config.server.event_webhook.is_empty() {
+            tasks.push(self.clone().start_event_webhook_task().abort_handle());
+        }
+        self.tasks.lock().unwrap().extend(tasks);
 
         tokio::select! {
             result = http_handle => {
@@ -91,6 +367,151 @@ impl TunnelServer {
         Ok(())
     }
 
+    /// Address the HTTP listener is bound to, waiting until `run` binds it.
+    /// With `server.http_port = 0` this is where the OS-assigned port shows up.
+    pub async fn http_addr(&self) -> SocketAddr {
+        self.wait_for_addr(|addrs| addrs.http).await
+    }
+
+    /// Like [`http_addr`](Self::http_addr) for the QUIC endpoint.
+    pub async fn quic_addr(&self) -> SocketAddr {
+        self.wait_for_addr(|addrs| addrs.quic).await
+    }
+
+    async fn wait_for_addr(&self, addr: impl Fn(&ListenAddrs) -> Option<SocketAddr>) -> SocketAddr {
+        let mut rx = self.listen_addrs.subscribe();
+        let addrs = rx
+            .wait_for(|addrs| addr(addrs).is_some())
+            .await
+            .expect("sender is owned by the server");
+        addr(&addrs).unwrap()
+    }
+
+    /// Stops the listeners and background tasks started by `run` and closes
+    /// every tunnel connection, after which `run` returns and the ports are
+    /// free to bind again.
+    pub async fn shutdown(&self) {
+        for task in self.tasks.lock().unwrap().drain(..) {
+            task.abort();
+        }
+        let endpoint = self.quic_endpoint.lock().unwrap().take();
+        if let Some(endpoint) = endpoint {
+            endpoint.close(0u32.into(), b"server shutting down");
+            endpoint.wait_idle().await;
+        }
+        // Closed connections still hold the UDP socket, so drop the ones kept
+        // for the reconnect grace period too.
+        for info in self.registry.list().await {
+            self.registry.unregister(&info.id).await;
+        }
+    }
+
+    /// Applies the settings of `config` that can change while connections
+    /// stay up: the per-request limits and the per-IP rate limit. Returns the
+    /// other settings that differ from the running ones and need a restart.
+    pub fn reload(&self, config: &Config) -> Vec<String> {
+        let mut reloaded = self.reloaded.lock().unwrap();
+        for name in changed_settings(&reloaded, config) {
+            if RELOADABLE_SETTINGS.contains(&name.as_str()) {
+                info!("Reloaded {}", name);
+            }
+        }
+        self.limits.send_replace(ProxyLimits::from_config(config));
+        self.authenticator.reload(&config.auth);
+        self.admin_key.send_replace(config.auth.api_key.clone());
+        if (config.limits.requests_per_second, config.limits.burst)
+            != (reloaded.limits.requests_per_second, reloaded.limits.burst)
+        {
+            self.rate_limiter
+                .set_rate(config.limits.requests_per_second, config.limits.burst);
+        }
+        *reloaded = config.clone();
+
+        let restart: Vec<String> = changed_settings(&self.config, config)
+            .into_iter()
+            .filter(|name| !RELOADABLE_SETTINGS.contains(&name.as_str()))
+            .collect();
+        for name in &restart {
+            warn!("{} changed but takes a restart to apply", name);
+        }
+        restart
+    }
+
+    /// Disconnects every workstation whose credential the authenticator no
+    /// longer accepts, e.g. after `auth.api_key` was rotated. Returns the
+    /// evicted ids.
+    pub async fn revalidate(&self) -> Vec<String> {
+        let evicted = self.registry.revalidate(self.authenticator.as_ref()).await;
+        for id in &evicted {
+            warn!("Workstation {} evicted: credential no longer accepted", id);
+        }
+        evicted
+    }
+
+    /// Reloads `config_path` (or the environment alone) on every SIGHUP,
+    /// applies it with [`reload`](Self::reload) and evicts workstations with
+    /// [`revalidate`](Self::revalidate). A file that fails to load or
+    /// validate is reported and the running settings are kept.
+    #[cfg(unix)]
+    pub fn reload_on_sighup(
+        self: Arc<Self>,
+        config_path: Option<std::path::PathBuf>,
+    ) -> std::io::Result<JoinHandle<()>> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        // Registered before returning, so a SIGHUP right after cannot hit the
+        // default handler and end the process.
+        let mut hangups = signal(SignalKind::hangup())?;
+        Ok(tokio::spawn(async move {
+            while hangups.recv().await.is_some() {
+                info!("SIGHUP received, reloading configuration");
+                match Config::load(config_path.clone()) {
+                    Ok(config) => {
+                        self.reload(&config);
+                        self.revalidate().await;
+                    }
+                    Err(e) => error!("Keeping the running configuration: {}", e),
+                }
+            }
+        }))
+    }
+
+    /// Posts each registry event as JSON to every `server.event_webhook` URL.
+    /// Deliveries are best effort and never hold up the registry.
+    fn start_event_webhook_task(self: Arc<Self>) -> JoinHandle<()> {
+        let mut events = self.registry.subscribe();
+        let client = reqwest::Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+
+        tokio::spawn(async move {
+            loop {
+                let event = match events.recv().await {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Event webhook fell behind, skipped {} events", skipped);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                for url in &self.config.server.event_webhook {
+                    let request = client.post(url).json(&event);
+                    let url = url.clone();
+                    tokio::spawn(async move {
+                        match request.send().await {
+                            Ok(response) if !response.status().is_success() => {
+                                warn!("Event webhook {} returned {}", url, response.status());
+                            }
+                            Ok(_) => {}
+                            Err(e) => warn!("Event webhook {} failed: {}", url, e),
+                        }
+                    });
+                }
+            }
+        })
+    }
+
     fn start_acme_manager(self: Arc<Self>) {
         tokio::spawn(async move {
             loop {
@@ -110,18 +531,13 @@ impl TunnelServer {
             NewOrder, OrderStatus, RetryPolicy,
         };
 
-        let cert_path = self.config.tls.certs_dir.join("cert.pem");
-        let key_path = self.config.tls.certs_dir.join("key.pem");
-
-        if cert_path.exists() && key_path.exists() {
-            if let Ok(cert_pem) = std::fs::read_to_string(&cert_path) {
-                if let Some(days) = Self::days_until_expiry(&cert_pem) {
-                    if days > 30 {
-                        info!("Certificate valid for {} more days, skipping renewal", days);
-                        return Ok(());
-                    }
-                    info!("Certificate expires in {} days, renewing...", days);
+        if let Some(bundle) = self.cert_store.load().await? {
+            if let Some(days) = Self::days_until_expiry(&bundle.cert_pem) {
+                if days > 30 {
+                    info!("Certificate valid for {} more days, skipping renewal", days);
+                    return Ok(());
                 }
+                info!("Certificate expires in {} days, renewing...", days);
             }
         }
 
@@ -132,8 +548,6 @@ impl TunnelServer {
             .as_ref()
             .ok_or_else(|| anyhow::anyhow!("TLS_ACME_EMAIL required"))?;
 
-        std::fs::create_dir_all(&self.config.tls.certs_dir)?;
-
         info!(
             "Requesting certificate for {} via Let's Encrypt",
             self.config.server.domain
@@ -193,10 +607,14 @@ impl TunnelServer {
         let private_key_pem = order.finalize().await?;
         let cert_chain_pem = order.poll_certificate(&RetryPolicy::default()).await?;
 
-        std::fs::write(&key_path, &private_key_pem)?;
-        std::fs::write(&cert_path, &cert_chain_pem)?;
+        self.cert_store
+            .store(&CertificateBundle {
+                cert_pem: cert_chain_pem,
+                key_pem: private_key_pem,
+            })
+            .await?;
 
-        info!("Certificate saved to {}", cert_path.display());
+        info!("Certificate saved");
         Ok(())
     }
 
@@ -215,26 +633,42 @@ impl TunnelServer {
     }
 
     fn start_http_server(self: Arc<Self>) -> JoinHandle<()> {
-        let port = self.config.server.http_port;
+        use tower::ServiceExt;
+
+        let addr = self.config.server.http_bind();
         let acme_challenges = self.acme_challenges.clone();
         let domain = self.config.server.domain.clone();
         let tls_enabled = self.config.tls.enabled;
-        let proxy_state = Arc::new(ProxyState {
-            registry: self.registry.clone(),
-            pending: self.pending.clone(),
-            request_timeout: Duration::from_secs(self.config.reliability.request_timeout),
-        });
+        let permanent = self.config.server.https_redirect_permanent;
+        let exclude = Arc::new(self.config.server.https_redirect_exclude.clone());
+        let proxy_router = self.proxy_router();
+        let listen_addrs = self.listen_addrs.clone();
 
         tokio::spawn(async move {
             let app = if tls_enabled {
                 let redirect_handler = move |req: axum::http::Request<axum::body::Body>| {
                     let domain = domain.clone();
+                    let exclude = exclude.clone();
+                    let proxy_router = proxy_router.clone();
                     async move {
+                        if exclude
+                            .iter()
+                            .any(|p| req.uri().path().starts_with(p.as_str()))
+                        {
+                            return match proxy_router.oneshot(req).await {
+                                Ok(response) => response,
+                                Err(never) => match never {},
+                            };
+                        }
                         let uri = req.uri();
                         let path_and_query =
                             uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
                         let https_url = format!("https://{}{}", domain, path_and_query);
-                        axum::response::Redirect::permanent(&https_url).into_response()
+                        if permanent {
+                            axum::response::Redirect::permanent(&https_url).into_response()
+                        } else {
+                            axum::response::Redirect::temporary(&https_url).into_response()
+                        }
                     }
                 };
 
@@ -245,104 +679,128 @@ impl TunnelServer {
                     )
                     .fallback(redirect_handler)
             } else {
-                Router::new()
-                    .route("/health", get(health_check))
-                    .route("/t/:workstation_id/*path", any(handle_http_proxy))
-                    .route("/ws/:workstation_id/*path", get(handle_websocket_proxy))
-                    .with_state(proxy_state)
+                proxy_router
             };
 
-            let addr = SocketAddr::from(([0, 0, 0, 0], port));
-            let listener = match tokio::net::TcpListener::bind(addr).await {
+            let addr = match addr {
+                Ok(addr) => addr,
+                Err(e) => {
+                    error!("HTTP server not started: {}", e);
+                    return;
+                }
+            };
+            let listener = match bind::tcp_listener(addr) {
                 Ok(l) => l,
                 Err(e) => {
-                    error!("Failed to bind HTTP server: {}", e);
+                    error!("Failed to bind HTTP server to {}: {}", addr, e);
                     return;
                 }
             };
+            let addr = listener.local_addr().unwrap_or(addr);
+            listen_addrs.send_modify(|addrs| addrs.http = Some(addr));
 
-            info!("HTTP server listening on {}", addr);
+            info!(
+                "HTTP server listening on {} ({})",
+                addr,
+                bind::families(SockRef::from(&listener))
+            );
 
-            if let Err(e) = axum::serve(listener, app).await {
-                error!("HTTP server error: {}", e);
+            loop {
+                let (stream, peer_addr) = match listener.accept().await {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        error!("HTTP accept error: {}", e);
+                        continue;
+                    }
+                };
+                let app = app.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = serve_connection(stream, peer_addr, app).await {
+                        debug!("HTTP connection error: {}", e);
+                    }
+                });
             }
         })
     }
 
     fn start_https_server(self: Arc<Self>) -> JoinHandle<()> {
-        let port = self.config.server.https_port;
-        let proxy_state = Arc::new(ProxyState {
-            registry: self.registry.clone(),
-            pending: self.pending.clone(),
-            request_timeout: Duration::from_secs(self.config.reliability.request_timeout),
-        });
+        let addr = self.config.server.https_bind();
+        let app = self.proxy_router();
         let tls_enabled = self.config.tls.enabled;
-        let certs_dir = self.config.tls.certs_dir.clone();
-        let domain = self.config.server.domain.clone();
+        let cert_store = self.cert_store.clone();
+        let tls_policy = self.config.tls.policy();
+        let proxy_protocol = self.config.server.proxy_protocol;
+        let server = self.clone();
 
         tokio::spawn(async move {
-            let app = Router::new()
-                .route("/health", get(health_check))
-                .route("/t/:workstation_id/*path", any(handle_http_proxy))
-                .route("/ws/:workstation_id/*path", get(handle_websocket_proxy))
-                .with_state(proxy_state);
-
-            let addr = SocketAddr::from(([0, 0, 0, 0], port));
-            let listener = match tokio::net::TcpListener::bind(addr).await {
+            let addr = match addr {
+                Ok(addr) => addr,
+                Err(e) => {
+                    error!("HTTPS server not started: {}", e);
+                    return;
+                }
+            };
+            let listener = match bind::tcp_listener(addr) {
                 Ok(l) => l,
                 Err(e) => {
-                    error!("Failed to bind HTTPS server: {}", e);
+                    error!("Failed to bind HTTPS server to {}: {}", addr, e);
                     return;
                 }
             };
 
             if tls_enabled {
-                let cert_path = certs_dir.join("cert.pem");
-                let key_path = certs_dir.join("key.pem");
-
-                let mut attempts = 0;
-                while (!cert_path.exists() || !key_path.exists()) && attempts < 30 {
-                    tokio::time::sleep(Duration::from_secs(10)).await;
-                    attempts += 1;
-                }
-
-                if !cert_path.exists() || !key_path.exists() {
+                let tls_policy = match tls_policy {
+                    Ok(policy) => policy,
+                    Err(e) => {
+                        error!("HTTPS server not started: {}", e);
+                        return;
+                    }
+                };
+                let Some(bundle) = wait_for_certificate(cert_store.as_ref()).await else {
                     warn!("HTTPS: Certificates not available, using self-signed");
-                    let cert = rcgen::generate_simple_self_signed(vec![domain]).unwrap();
-                    let key = rustls::pki_types::PrivateKeyDer::Pkcs8(
-                        rustls::pki_types::PrivatePkcs8KeyDer::from(cert.key_pair.serialize_der()),
-                    );
-                    let cert_der = rustls::pki_types::CertificateDer::from(cert.cert);
+                    let (cert_der, key) = match server.self_signed_cert() {
+                        Ok(cert) => cert,
+                        Err(e) => {
+                            error!("HTTPS server not started: {}", e);
+                            return;
+                        }
+                    };
 
-                    let config = rustls::ServerConfig::builder()
+                    let mut config = tls_policy
+                        .server_builder()
                         .with_no_client_auth()
                         .with_single_cert(vec![cert_der], key)
                         .unwrap();
+                    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
 
                     let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
                     info!("HTTPS server (self-signed) listening on {}", addr);
-                    Self::serve_https(listener, acceptor, app).await;
-                } else {
-                    use rustls::pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
-
-                    let cert_pem = std::fs::read_to_string(&cert_path).unwrap();
-                    let key_pem = std::fs::read_to_string(&key_path).unwrap();
+                    Self::serve_https(listener, acceptor, app, proxy_protocol).await;
+                    return;
+                };
 
-                    let certs: Vec<CertificateDer> =
-                        CertificateDer::pem_slice_iter(cert_pem.as_bytes())
-                            .collect::<Result<Vec<_>, _>>()
-                            .unwrap();
-                    let key = PrivateKeyDer::from_pem_slice(key_pem.as_bytes()).unwrap();
+                use rustls::pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
 
-                    let config = rustls::ServerConfig::builder()
-                        .with_no_client_auth()
-                        .with_single_cert(certs, key)
+                let certs: Vec<CertificateDer> =
+                    CertificateDer::pem_slice_iter(bundle.cert_pem.as_bytes())
+                        .collect::<Result<Vec<_>, _>>()
                         .unwrap();
-
-                    let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
-                    info!("HTTPS server listening on {}", addr);
-                    Self::serve_https(listener, acceptor, app).await;
-                }
+                let key = PrivateKeyDer::from_pem_slice(bundle.key_pem.as_bytes()).unwrap();
+
+                let mut config = tls_policy
+                    .server_builder()
+                    .with_no_client_auth()
+                    .with_single_cert(certs, key)
+                    .unwrap();
+                config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+                let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+                info!(
+                    "HTTPS server listening on {} ({})",
+                    listener.local_addr().unwrap_or(addr),
+                    bind::families(SockRef::from(&listener))
+                );
+                Self::serve_https(listener, acceptor, app, proxy_protocol).await;
             } else {
                 warn!("TLS disabled, HTTPS server not started");
             }
@@ -353,14 +811,10 @@ impl TunnelServer {
         listener: tokio::net::TcpListener,
         acceptor: tokio_rustls::TlsAcceptor,
         app: Router,
+        proxy_protocol: bool,
     ) {
-        use hyper::service::service_fn;
-        use hyper_util::rt::{TokioExecutor, TokioIo};
-        use hyper_util::server::conn::auto::Builder;
-        use tower::ServiceExt;
-
         loop {
-            let (stream, _) = match listener.accept().await {
+            let (mut stream, mut peer_addr) = match listener.accept().await {
                 Ok(conn) => conn,
                 Err(e) => {
                     error!("HTTPS accept error: {}", e);
@@ -372,6 +826,29 @@ impl TunnelServer {
             let app = app.clone();
 
             tokio::spawn(async move {
+                if proxy_protocol {
+                    let header = tokio::time::timeout(
+                        PROXY_HEADER_TIMEOUT,
+                        proxy_protocol::read_header(&mut stream),
+                    )
+                    .await;
+                    match header {
+                        Ok(Ok(Some(client_addr))) => peer_addr = client_addr,
+                        Ok(Ok(None)) => {}
+                        Ok(Err(e)) => {
+                            warn!("Rejecting HTTPS connection from {}: {}", peer_addr, e);
+                            return;
+                        }
+                        Err(_) => {
+                            warn!(
+                                "Rejecting HTTPS connection from {}: no PROXY header",
+                                peer_addr
+                            );
+                            return;
+                        }
+                    }
+                }
+
                 let tls_stream = match acceptor.accept(stream).await {
                     Ok(s) => s,
                     Err(e) => {
@@ -380,15 +857,7 @@ impl TunnelServer {
                     }
                 };
 
-                let service = service_fn(move |req| {
-                    let app = app.clone();
-                    async move { app.oneshot(req).await }
-                });
-
-                if let Err(e) = Builder::new(TokioExecutor::new())
-                    .serve_connection(TokioIo::new(tls_stream), service)
-                    .await
-                {
+                if let Err(e) = serve_connection(tls_stream, peer_addr, app).await {
                     error!("HTTPS connection error: {}", e);
                 }
             });
@@ -396,26 +865,60 @@ impl TunnelServer {
     }
 
     async fn start_quic_server(self: Arc<Self>) -> anyhow::Result<JoinHandle<()>> {
-        let crypto = if self.config.tls.enabled {
-            self.setup_tls_from_files().await?
+        let mut crypto = if self.config.tls.enabled {
+            self.setup_tls_from_store().await?
         } else {
             self.setup_no_tls()?
         };
-
-        let quinn_crypto = quinn::crypto::rustls::QuicServerConfig::try_from(crypto)
-            .map_err(|e| anyhow::anyhow!("Failed to create QUIC config: {}", e))?;
+        // Lets resuming clients send their reconnect as 0-RTT data. QUIC
+        // allows no other non-zero value. `handle_connection` holds back
+        // anything a replay could abuse until the handshake is done.
+        crypto.max_early_data_size = u32::MAX;
+
+        let quinn_crypto = quinn::crypto::rustls::QuicServerConfig::with_initial(
+            Arc::new(crypto),
+            tunnel_core::tls::quic_initial_suite(),
+        )
+        .map_err(|e| anyhow::anyhow!("Failed to create QUIC config: {}", e))?;
         let mut server_config = quinn::ServerConfig::with_crypto(Arc::new(quinn_crypto));
-        let transport_config = Arc::get_mut(&mut server_config.transport).unwrap();
-        transport_config.max_concurrent_bidi_streams(1000u32.into());
+        let mut transport_config = self.config.quic.transport_config();
         transport_config.max_concurrent_uni_streams(0u32.into());
+        server_config.transport_config(Arc::new(transport_config));
+
+        let addr = self.config.server.quic_bind()?;
+        let socket = bind::udp_socket(addr)
+            .map_err(|e| anyhow::anyhow!("Failed to bind QUIC server to {}: {}", addr, e))?;
+        let families = bind::families(SockRef::from(&socket));
+        let endpoint = quinn::Endpoint::new(
+            quinn::EndpointConfig::default(),
+            Some(server_config),
+            socket,
+            Arc::new(quinn::TokioRuntime),
+        )?;
+        *self.quic_endpoint.lock().unwrap() = Some(endpoint.clone());
+        let addr = endpoint.local_addr().unwrap_or(addr);
+        self.listen_addrs
+            .send_modify(|addrs| addrs.quic = Some(addr));
 
-        let addr = SocketAddr::from(([0, 0, 0, 0], self.config.server.https_port));
-        let endpoint = quinn::Endpoint::server(server_config, addr)?;
+        info!(
+            "QUIC server listening on {} ({}, {} congestion control)",
+            addr, families, self.config.quic.congestion_controller
+        );
 
-        info!("QUIC server listening on {}", addr);
+        let mut new_connections = match self.config.limits.max_new_connections_per_sec {
+            0 => None,
+            rate => Some(TokenBucket::new(rate as f64, rate as f64)),
+        };
 
         let handle = tokio::spawn(async move {
             while let Some(conn) = endpoint.accept().await {
+                if let Some(bucket) = &mut new_connections {
+                    if bucket.try_take(1.0).is_err() {
+                        debug!("Refusing QUIC connection from {}", conn.remote_address());
+                        conn.refuse();
+                        continue;
+                    }
+                }
                 let server = self.clone();
                 tokio::spawn(async move {
                     if let Err(e) = server.handle_connection(conn).await {
@@ -430,115 +933,214 @@ impl TunnelServer {
 
     fn setup_no_tls(&self) -> anyhow::Result<rustls::ServerConfig> {
         warn!("TLS disabled, using self-signed certificate");
-        let cert = rcgen::generate_simple_self_signed(vec![self.config.server.domain.clone()])?;
-        let key = rustls::pki_types::PrivateKeyDer::Pkcs8(
-            rustls::pki_types::PrivatePkcs8KeyDer::from(cert.key_pair.serialize_der()),
-        );
-        let cert_der = rustls::pki_types::CertificateDer::from(cert.cert);
+        let (cert_der, key) = self.self_signed_cert()?;
 
-        let mut crypto = rustls::ServerConfig::builder()
+        let mut crypto = self
+            .config
+            .tls
+            .policy()?
+            .server_builder()
             .with_no_client_auth()
             .with_single_cert(vec![cert_der], key)?;
 
-        crypto.alpn_protocols = vec![b"tiflis-tunnel".to_vec()];
+        crypto.alpn_protocols = vec![self.config.tls.alpn.as_bytes().to_vec()];
         Ok(crypto)
     }
 
-    async fn setup_tls_from_files(&self) -> anyhow::Result<rustls::ServerConfig> {
-        use rustls::pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
-
-        let cert_path = self.config.tls.certs_dir.join("cert.pem");
-        let key_path = self.config.tls.certs_dir.join("key.pem");
-
-        const MAX_CERT_WAIT_ATTEMPTS: u32 = 30;
-        let mut attempts = 0;
-        while (!cert_path.exists() || !key_path.exists()) && attempts < MAX_CERT_WAIT_ATTEMPTS {
-            info!(
-                "Waiting for certificates ({}/30)... cert={}, key={}",
-                attempts + 1,
-                cert_path.exists(),
-                key_path.exists()
-            );
-            tokio::time::sleep(Duration::from_secs(10)).await;
-            attempts += 1;
+    /// Certificate for `server.domain` and `tls.self_signed_sans`, generated on
+    /// first use so the HTTPS and QUIC listeners present the same one.
+    fn self_signed_cert(
+        &self,
+    ) -> anyhow::Result<(
+        rustls::pki_types::CertificateDer<'static>,
+        rustls::pki_types::PrivateKeyDer<'static>,
+    )> {
+        if self.self_signed.get().is_none() {
+            let mut sans = vec![self.config.server.domain.clone()];
+            for san in &self.config.tls.self_signed_sans {
+                if !sans.contains(san) {
+                    sans.push(san.clone());
+                }
+            }
+            let cert = rcgen::generate_simple_self_signed(sans)?;
+            let _ = self
+                .self_signed
+                .set((cert.cert.der().to_vec(), cert.key_pair.serialize_der()));
         }
+        let (cert, key) = self.self_signed.get().expect("set above");
+        Ok((
+            rustls::pki_types::CertificateDer::from(cert.clone()),
+            rustls::pki_types::PrivateKeyDer::Pkcs8(rustls::pki_types::PrivatePkcs8KeyDer::from(
+                key.clone(),
+            )),
+        ))
+    }
+
+    async fn setup_tls_from_store(&self) -> anyhow::Result<rustls::ServerConfig> {
+        use rustls::pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
 
-        if !cert_path.exists() || !key_path.exists() {
+        let Some(bundle) = wait_for_certificate(self.cert_store.as_ref()).await else {
             warn!("Certificates not available after timeout, falling back to self-signed");
             return self.setup_no_tls();
-        }
-
-        info!("Loading certificates from {}", cert_path.display());
-
-        let cert_pem = std::fs::read_to_string(&cert_path)?;
-        let key_pem = std::fs::read_to_string(&key_path)?;
+        };
 
-        let certs: Vec<CertificateDer> =
-            CertificateDer::pem_slice_iter(cert_pem.as_bytes()).collect::<Result<Vec<_>, _>>()?;
+        let certs: Vec<CertificateDer> = CertificateDer::pem_slice_iter(bundle.cert_pem.as_bytes())
+            .collect::<Result<Vec<_>, _>>()?;
 
-        let key = PrivateKeyDer::from_pem_slice(key_pem.as_bytes())?;
+        let key = PrivateKeyDer::from_pem_slice(bundle.key_pem.as_bytes())?;
 
-        let mut crypto = rustls::ServerConfig::builder()
+        let mut crypto = self
+            .config
+            .tls
+            .policy()?
+            .server_builder()
             .with_no_client_auth()
             .with_single_cert(certs, key)?;
 
-        crypto.alpn_protocols = vec![b"tiflis-tunnel".to_vec()];
+        crypto.alpn_protocols = vec![self.config.tls.alpn.as_bytes().to_vec()];
 
         info!("TLS configured with Let's Encrypt certificate");
         Ok(crypto)
     }
 
     async fn handle_connection(&self, conn: quinn::Incoming) -> anyhow::Result<()> {
-        let connection = conn.await?;
+        // The first message may arrive as 0-RTT data, before the handshake
+        // shows that the client is live rather than replaying a capture.
+        let (connection, handshake) = match conn.accept()?.into_0rtt() {
+            Ok((connection, handshake)) => (connection, Some(handshake)),
+            Err(connecting) => (connecting.await?, None),
+        };
         let (mut send, mut recv) = connection.accept_bi().await?;
 
-        let msg = quic::recv_message(&mut recv).await?;
+        let msg = match quic::recv_message(&mut recv).await {
+            Err(
+                e @ (tunnel_core::Error::MissingField(_)
+                | tunnel_core::Error::InvalidMessageType(_)),
+            ) => {
+                let error_msg = Message::Error(ErrorMessage {
+                    code: "INVALID_MESSAGE".to_string(),
+                    message: e.to_string(),
+                });
+                return reject(&connection, &mut send, &error_msg).await;
+            }
+            msg => msg?,
+        };
 
         match msg {
             Message::Register(reg) => {
-                if reg.api_key != self.config.auth.api_key {
+                // A replayed register would take the slot, so it waits for the
+                // handshake.
+                if let Some(handshake) = handshake {
+                    handshake.await;
+                }
+                if let Some(reason) = connection.close_reason() {
+                    return Err(reason.into());
+                }
+                if let Err(e) = self
+                    .authenticator
+                    .authenticate(&reg.workstation_id, &reg.api_key)
+                    .await
+                {
                     let error_msg = Message::Error(ErrorMessage {
                         code: "AUTH_FAILED".to_string(),
-                        message: "Invalid API key".to_string(),
+                        message: e.to_string(),
                     });
-                    quic::send_message(&mut send, &error_msg).await?;
-                    return Ok(());
+                    return reject(&connection, &mut send, &error_msg).await;
                 }
 
-                if self.registry.count().await >= self.config.limits.max_workstations {
+                if let Err(e) = validate_workstation_id(&reg.workstation_id) {
                     let error_msg = Message::Error(ErrorMessage {
-                        code: "LIMIT_REACHED".to_string(),
-                        message: "Maximum workstations reached".to_string(),
+                        code: "INVALID_WORKSTATION_ID".to_string(),
+                        message: e,
                     });
-                    quic::send_message(&mut send, &error_msg).await?;
-                    return Ok(());
+                    return reject(&connection, &mut send, &error_msg).await;
                 }
 
-                if let Err(e) = self
-                    .registry
-                    .register(reg.workstation_id.clone(), connection.clone())
-                    .await
-                {
+                if let Err(e) = tunnel_core::validate_metadata(&reg.metadata) {
                     let error_msg = Message::Error(ErrorMessage {
-                        code: "REGISTRATION_FAILED".to_string(),
+                        code: "INVALID_METADATA".to_string(),
                         message: e,
                     });
-                    quic::send_message(&mut send, &error_msg).await?;
-                    return Ok(());
+                    return reject(&connection, &mut send, &error_msg).await;
                 }
 
-                let url = format!(
-                    "{}://{}/t/{}",
-                    if self.config.tls.enabled {
-                        "https"
-                    } else {
-                        "http"
-                    },
-                    self.config.server.domain,
-                    reg.workstation_id
+                if let Err(e) = tunnel_core::validate_tags(&reg.tags) {
+                    let error_msg = Message::Error(ErrorMessage {
+                        code: "INVALID_TAGS".to_string(),
+                        message: e,
+                    });
+                    return reject(&connection, &mut send, &error_msg).await;
+                }
+
+                // A workstation re-registering within its grace period, or
+                // joining as a standby, already holds a slot.
+                let error_msg = match self
+                    .workstation_counter
+                    .try_acquire(&reg.workstation_id, self.config.limits.max_workstations)
+                    .await
+                {
+                    Ok(true) => None,
+                    Ok(false) => Some(ErrorMessage {
+                        code: "LIMIT_REACHED".to_string(),
+                        message: "Maximum workstations reached".to_string(),
+                    }),
+                    Err(e) => {
+                        warn!("Workstation limit check failed: {}", e);
+                        Some(ErrorMessage {
+                            code: "LIMIT_UNAVAILABLE".to_string(),
+                            message: e.to_string(),
+                        })
+                    }
+                };
+                if let Some(error_msg) = error_msg {
+                    return reject(&connection, &mut send, &Message::Error(error_msg)).await;
+                }
+
+                let max_frame_bytes = quic::negotiate_max_frame_bytes(
+                    self.config.limits.max_frame_bytes,
+                    reg.max_frame_bytes,
                 );
 
-                let response = Message::Registered(RegisteredMessage { url });
+                let reconnect_token = match self
+                    .registry
+                    .register(
+                        reg.workstation_id.clone(),
+                        connection.clone(),
+                        ClientCapabilities {
+                            max_frame_bytes,
+                            ws_open_ack: reg.ws_open_ack,
+                            expect_continue: reg.expect_continue,
+                            ws_fragments: reg.ws_fragments,
+                            ordered_headers: reg.ordered_headers,
+                            ws_passthrough: reg.ws_passthrough,
+                        },
+                        reg.metadata,
+                        reg.tags,
+                        &reg.api_key,
+                    )
+                    .await
+                {
+                    Ok(token) => token,
+                    Err(e) => {
+                        if self.registry.get(&reg.workstation_id).await.is_none() {
+                            self.workstation_counter.release(&reg.workstation_id).await;
+                        }
+                        let error_msg = Message::Error(ErrorMessage {
+                            code: "REGISTRATION_FAILED".to_string(),
+                            message: e,
+                        });
+                        return reject(&connection, &mut send, &error_msg).await;
+                    }
+                };
+
+                let url = self.config.workstation_url(&reg.workstation_id);
+
+                let response = Message::Registered(RegisteredMessage {
+                    url,
+                    max_frame_bytes: Some(max_frame_bytes),
+                    server_version: Some(version_string()),
+                    reconnect_token: Some(reconnect_token),
+                });
                 quic::send_message(&mut send, &response).await?;
 
                 info!("Workstation {} registered", reg.workstation_id);
@@ -547,45 +1149,116 @@ impl TunnelServer {
                 self.handle_workstation_messages(connection, &workstation_id)
                     .await;
 
-                self.registry.unregister(&workstation_id).await;
-                info!("Workstation {} disconnected", workstation_id);
+                info!(
+                    "Workstation {} disconnected, holding its slot for {}s",
+                    workstation_id, self.config.reliability.grace_period
+                );
             }
             Message::Reconnect(reconnect) => {
-                if reconnect.api_key != self.config.auth.api_key {
+                if let Err(e) = self
+                    .authenticator
+                    .authenticate(&reconnect.workstation_id, &reconnect.api_key)
+                    .await
+                {
                     let error_msg = Message::Error(ErrorMessage {
                         code: "AUTH_FAILED".to_string(),
-                        message: "Invalid API key".to_string(),
+                        message: e.to_string(),
                     });
-                    quic::send_message(&mut send, &error_msg).await?;
-                    return Ok(());
+                    return reject(&connection, &mut send, &error_msg).await;
                 }
 
-                if let Err(e) = self
-                    .registry
-                    .reconnect(&reconnect.workstation_id, connection.clone())
-                    .await
-                {
+                if let Err(e) = validate_workstation_id(&reconnect.workstation_id) {
                     let error_msg = Message::Error(ErrorMessage {
-                        code: "RECONNECT_FAILED".to_string(),
+                        code: "INVALID_WORKSTATION_ID".to_string(),
                         message: e,
                     });
-                    quic::send_message(&mut send, &error_msg).await?;
-                    return Ok(());
+                    return reject(&connection, &mut send, &error_msg).await;
                 }
 
-                let url = format!(
-                    "{}://{}/t/{}",
-                    if self.config.tls.enabled {
-                        "https"
-                    } else {
-                        "http"
-                    },
-                    self.config.server.domain,
-                    reconnect.workstation_id
+                let max_frame_bytes = quic::negotiate_max_frame_bytes(
+                    self.config.limits.max_frame_bytes,
+                    reconnect.max_frame_bytes,
                 );
 
-                let response = Message::Registered(RegisteredMessage { url });
-                quic::send_message(&mut send, &response).await?;
+                let url = self.config.workstation_url(&reconnect.workstation_id);
+                let registered = |reconnect_token| {
+                    Message::Registered(RegisteredMessage {
+                        url: url.clone(),
+                        max_frame_bytes: Some(max_frame_bytes),
+                        server_version: Some(version_string()),
+                        reconnect_token: Some(reconnect_token),
+                    })
+                };
+                let next_token = registry::new_reconnect_token();
+
+                // A replayed reconnect would take the workstation over and
+                // spend the client's token, so the swap waits for the
+                // handshake. The reply need not: only a client completing the
+                // handshake can read it, so it goes out once the token checks
+                // out, saving the client a round trip.
+                let answered = match handshake {
+                    Some(handshake) => {
+                        if let Err(e) = self
+                            .registry
+                            .check_reconnect(
+                                &reconnect.workstation_id,
+                                reconnect.reconnect_token.as_deref(),
+                            )
+                            .await
+                        {
+                            return reject(&connection, &mut send, &reconnect_refused(e)).await;
+                        }
+                        quic::send_message(&mut send, &registered(next_token.clone())).await?;
+                        handshake.await;
+                        if let Some(reason) = connection.close_reason() {
+                            return Err(reason.into());
+                        }
+                        true
+                    }
+                    None => false,
+                };
+
+                let reconnected = match self
+                    .registry
+                    .reconnect(
+                        &reconnect.workstation_id,
+                        connection.clone(),
+                        ClientCapabilities {
+                            max_frame_bytes,
+                            ws_open_ack: reconnect.ws_open_ack,
+                            expect_continue: reconnect.expect_continue,
+                            ws_fragments: reconnect.ws_fragments,
+                            ordered_headers: reconnect.ordered_headers,
+                            ws_passthrough: reconnect.ws_passthrough,
+                        },
+                        &reconnect.api_key,
+                        reconnect.reconnect_token.as_deref(),
+                        next_token,
+                    )
+                    .await
+                {
+                    Ok(reconnected) => reconnected,
+                    // The token was spent by another connection meanwhile.
+                    Err(e) if answered => {
+                        warn!(
+                            "Workstation {} reconnect refused after its reply: {}",
+                            reconnect.workstation_id, e
+                        );
+                        connection.close(RECONNECT_FAILED_CODE.into(), b"reconnect failed");
+                        return Ok(());
+                    }
+                    Err(e) => return reject(&connection, &mut send, &reconnect_refused(e)).await,
+                };
+                if let Some(replaced) = reconnected.replaced {
+                    tokio::spawn(drain_replaced_connection(
+                        reconnect.workstation_id.clone(),
+                        replaced,
+                    ));
+                }
+
+                if !answered {
+                    quic::send_message(&mut send, &registered(reconnected.reconnect_token)).await?;
+                }
 
                 info!("Workstation {} reconnected", reconnect.workstation_id);
 
@@ -597,7 +1270,7 @@ impl TunnelServer {
                     code: "INVALID_MESSAGE".to_string(),
                     message: "Expected Register or Reconnect message".to_string(),
                 });
-                quic::send_message(&mut send, &error_msg).await?;
+                return reject(&connection, &mut send, &error_msg).await;
             }
         }
 
@@ -609,11 +1282,61 @@ impl TunnelServer {
         connection: quinn::Connection,
         workstation_id: &str,
     ) {
-        while let Ok((mut send, mut recv)) = connection.accept_bi().await {
+        if let Some(promoted) = self
+            .registry
+            .standby_promotion(workstation_id, &connection)
+            .await
+        {
+            info!("Workstation {} is on standby", workstation_id);
+            tokio::select! {
+                _ = promoted.notified() => {
+                    info!("Workstation {} standby took over", workstation_id);
+                }
+                _ = answer_standby_pings(&connection) => {
+                    self.registry
+                        .mark_reconnecting(workstation_id, &connection)
+                        .await;
+                    return;
+                }
+            }
+        }
+
+        let workstation = self.registry.get(workstation_id).await;
+        let (stream_permits, max_frame_bytes) = match &workstation {
+            Some(info) => (info.stream_permits.clone(), info.max_frame_bytes),
+            None => (
+                Arc::new(Semaphore::new(
+                    self.config.limits.max_streams_per_connection,
+                )),
+                quic::LEGACY_MAX_FRAME_BYTES,
+            ),
+        };
+
+        loop {
+            // Acquire a permit before accepting so a saturated connection applies
+            // QUIC flow-control backpressure instead of spawning more tasks.
+            let permit = tokio::select! {
+                permit = stream_permits.clone().acquire_owned() => match permit {
+                    Ok(permit) => permit,
+                    Err(_) => break,
+                },
+                _ = connection.closed() => break,
+            };
+
+            let Ok((mut send, mut recv)) = connection.accept_bi().await else {
+                break;
+            };
+            if let Some(info) = &workstation {
+                info.touch();
+            }
+
             let pending = self.pending.clone();
+            let workstation_id = workstation_id.to_string();
+            let workstation = workstation.clone();
             tokio::spawn(async move {
-                if let Ok(msg) = quic::recv_message(&mut recv).await {
-                    match msg {
+                let _permit = permit;
+                match quic::recv_message_limited(&mut recv, max_frame_bytes).await {
+                    Ok(msg) => match msg {
                         Message::HttpResponse(resp) => {
                             pending
                                 .complete(resp.stream_id, Message::HttpResponse(resp))
@@ -629,36 +1352,294 @@ impl TunnelServer {
                                 .complete(close.stream_id, Message::WsClose(close))
                                 .await;
                         }
+                        Message::StatusUpdate(update) => {
+                            let Some(info) = &workstation else {
+                                return;
+                            };
+                            let health = BackendHealth {
+                                healthy: update.healthy,
+                                detail: update.detail,
+                            };
+                            if !info.set_backend_health(health.clone()) {
+                                return;
+                            }
+                            if health.healthy {
+                                info!("Workstation {} backend is healthy", workstation_id);
+                            } else {
+                                warn!(
+                                    "Workstation {} backend is unhealthy: {}",
+                                    workstation_id,
+                                    health.detail.as_deref().unwrap_or("no detail")
+                                );
+                            }
+                        }
                         Message::Ping(ping) => {
                             let pong = Message::Pong(tunnel_core::PongMessage {
                                 timestamp: ping.timestamp,
+                                server_timestamp: Some(tunnel_core::unix_millis()),
                             });
                             let _ = quic::send_message(&mut send, &pong).await;
+                            let _ = send.finish();
+                        }
+                        other => {
+                            let err = tunnel_core::Error::InvalidMessageType(
+                                other.message_type().to_string(),
+                            );
+                            warn!("Workstation {}: {}", workstation_id, err);
+                            let error_msg = Message::Error(ErrorMessage {
+                                code: "UNEXPECTED_MESSAGE".to_string(),
+                                message: err.to_string(),
+                            });
+                            let _ = quic::send_message(&mut send, &error_msg).await;
+                            let _ = send.finish();
                         }
-                        _ => {}
+                    },
+                    Err(e @ tunnel_core::Error::Decode { .. }) => {
+                        error!("Workstation {}: {}", workstation_id, e);
                     }
+                    Err(_) => {}
                 }
             });
         }
 
-        self.registry.mark_reconnecting(workstation_id).await;
+        if let Some(e) = connection.close_reason() {
+            info!(
+                "Workstation {} connection closed: {}",
+                workstation_id,
+                quic::CloseReason::from(&e)
+            );
+        }
+        self.registry
+            .mark_reconnecting(workstation_id, &connection)
+            .await;
     }
 
     fn start_cleanup_task(self: Arc<Self>) -> JoinHandle<()> {
         tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(10));
+            let mut interval = tokio::time::interval(Duration::from_secs(
+                self.config.reliability.cleanup_interval,
+            ));
             loop {
                 interval.tick().await;
+                for id in self.registry.reap_idle().await {
+                    warn!(
+                        "Workstation {} silent for over {}s, marking reconnecting",
+                        id, self.config.reliability.liveness_timeout
+                    );
+                }
                 self.registry.cleanup_expired().await;
+                self.registry.refresh_slots().await;
+                for info in self.registry.list().await {
+                    let quality = ConnectionQuality::from_connection(&info.connection);
+                    debug!("Workstation {} connection: {}", info.id, quality);
+                }
+                let request_timeout = self.limits.borrow().request_timeout;
+                let stale = self.pending.cleanup_expired(request_timeout).await;
+                if stale > 0 {
+                    warn!("Dropped {} abandoned pending requests", stale);
+                }
+                self.rate_limiter.prune();
             }
         })
     }
 }
 
+/// Settings `TunnelServer::reload` applies to the running server.
+const RELOADABLE_SETTINGS: &[&str] = &[
+    "reliability.request_timeout",
+    "reliability.sse_heartbeat_interval",
+    "reliability.max_sse_duration",
+    "reliability.max_request_duration",
+    "reliability.slow_request_threshold",
+    "reliability.maintenance",
+    "limits.max_ws_message_bytes",
+    "limits.max_header_bytes",
+    "limits.max_queued_requests",
+    "limits.queue_timeout",
+    "limits.max_bytes_per_workstation",
+    "limits.requests_per_second",
+    "limits.burst",
+    "auth.api_key",
+];
+
+/// Serves HTTP/1.1 and HTTP/2 requests on an accepted connection. HTTP/1.1
+/// requests carry [`InterimResponses`](interim::InterimResponses) for relaying
+/// `1xx` responses.
+async fn serve_connection<T>(
+    io: T,
+    peer_addr: SocketAddr,
+    app: Router,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+where
+    T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + Unpin + 'static,
+{
+    use hyper::service::service_fn;
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+    use hyper_util::server::conn::auto::Builder;
+    use tower::ServiceExt;
+
+    let (io, interim) = interim::wrap(io);
+    let service = service_fn(move |mut req: hyper::Request<_>| {
+        req.extensions_mut()
+            .insert(axum::extract::ConnectInfo(peer_addr));
+        if req.version() == hyper::Version::HTTP_11 {
+            req.extensions_mut().insert(interim.clone());
+        }
+        let app = app.clone();
+        async move { app.oneshot(req).await }
+    });
+
+    Builder::new(TokioExecutor::new())
+        .serve_connection_with_upgrades(TokioIo::new(io), service)
+        .await
+}
+
+fn registry_for(config: &Config, counter: Arc<dyn WorkstationCounter>) -> WorkstationRegistry {
+    WorkstationRegistry::new(Duration::from_secs(config.reliability.grace_period))
+        .with_max_streams_per_connection(config.limits.max_streams_per_connection)
+        .with_max_bytes_per_sec(config.limits.max_bytes_per_sec_per_workstation)
+        .with_liveness_timeout(Duration::from_secs(config.reliability.liveness_timeout))
+        .with_circuit_breaker(BreakerPolicy {
+            failures: config.reliability.circuit_breaker_failures,
+            window: Duration::from_secs(config.reliability.circuit_breaker_window),
+            cooldown: Duration::from_secs(config.reliability.circuit_breaker_cooldown),
+        })
+        .with_standby(config.reliability.allow_standby)
+        .with_workstation_counter(counter)
+}
+
+/// Names, as `section.key`, of the settings that differ between `old` and
+/// `new`. Nested tables such as `server.cors` are compared whole.
+fn changed_settings(old: &Config, new: &Config) -> Vec<String> {
+    let (Ok(serde_json::Value::Object(old)), Ok(serde_json::Value::Object(new))) =
+        (serde_json::to_value(old), serde_json::to_value(new))
+    else {
+        return Vec::new();
+    };
+    let empty = serde_json::Map::new();
+    let mut changed = Vec::new();
+    for (section, new_section) in &new {
+        let old_section = old
+            .get(section)
+            .and_then(|v| v.as_object())
+            .unwrap_or(&empty);
+        let new_section = new_section.as_object().unwrap_or(&empty);
+        let keys: std::collections::BTreeSet<&String> =
+            old_section.keys().chain(new_section.keys()).collect();
+        for key in keys {
+            if old_section.get(key) != new_section.get(key) {
+                changed.push(format!("{}.{}", section, key));
+            }
+        }
+    }
+    changed
+}
+
+/// Upper bound on a single event webhook delivery.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Grace given to a client to read a handshake error before the connection is
+/// dropped; closing straight away can discard the reply in flight.
+const REJECT_LINGER: Duration = Duration::from_secs(1);
+
+/// How long a connection may take to send its PROXY header when
+/// `server.proxy_protocol` is on.
+const PROXY_HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The error answering a refused reconnect.
+fn reconnect_refused(rejection: ReconnectRejection) -> Message {
+    let code = match rejection {
+        ReconnectRejection::GraceExpired => tunnel_core::GRACE_EXPIRED_ERROR,
+        _ => "RECONNECT_FAILED",
+    };
+    Message::Error(ErrorMessage {
+        code: code.to_string(),
+        message: rejection.to_string(),
+    })
+}
+
+async fn reject(
+    connection: &quinn::Connection,
+    send: &mut quinn::SendStream,
+    error_msg: &Message,
+) -> anyhow::Result<()> {
+    quic::send_message(send, error_msg).await?;
+    let _ = send.finish();
+    let _ = tokio::time::timeout(REJECT_LINGER, connection.closed()).await;
+    Ok(())
+}
+
+/// Answers pings on a standby connection, which carries no traffic until it
+/// takes over. Returns once the connection closes.
+async fn answer_standby_pings(connection: &quinn::Connection) {
+    while let Ok((mut send, mut recv)) = connection.accept_bi().await {
+        tokio::spawn(async move {
+            if let Ok(Message::Ping(ping)) = quic::recv_message(&mut recv).await {
+                let pong = Message::Pong(tunnel_core::PongMessage {
+                    timestamp: ping.timestamp,
+                    server_timestamp: Some(tunnel_core::unix_millis()),
+                });
+                let _ = quic::send_message(&mut send, &pong).await;
+                let _ = send.finish();
+            }
+        });
+    }
+}
+
+/// Keeps a connection taken over by a reconnect open until the streams still
+/// running on it finish, then closes it. New streams already go to the new
+/// connection, so in-flight responses, WebSockets and SSE streams survive.
+async fn drain_replaced_connection(workstation_id: String, replaced: ReplacedConnection) {
+    let in_flight = replaced.streams.active();
+    if in_flight > 0 {
+        info!(
+            "Workstation {} moved to a new connection, draining {} streams on the old one",
+            workstation_id, in_flight
+        );
+    }
+    tokio::select! {
+        _ = replaced.streams.wait_idle() => {
+            replaced.connection.close(MIGRATED_CODE.into(), b"migrated");
+        }
+        _ = replaced.connection.closed() => {}
+    }
+}
+
 async fn health_check() -> impl IntoResponse {
     (StatusCode::OK, "OK")
 }
 
+async fn version() -> impl IntoResponse {
+    Json(serde_json::json!({ "version": VERSION, "git_sha": GIT_SHA }))
+}
+
+fn version_string() -> String {
+    format!("{} ({})", VERSION, GIT_SHA)
+}
+
+/// Polls the certificate store until the ACME task has stored a certificate,
+/// giving up after five minutes.
+async fn wait_for_certificate(store: &dyn CertStore) -> Option<CertificateBundle> {
+    const MAX_CERT_WAIT_ATTEMPTS: u32 = 30;
+    let mut attempts = 0;
+    loop {
+        match store.load().await {
+            Ok(Some(bundle)) => return Some(bundle),
+            Ok(None) => {}
+            Err(e) => warn!("Failed to load certificate: {}", e),
+        }
+        if attempts == MAX_CERT_WAIT_ATTEMPTS {
+            return None;
+        }
+        attempts += 1;
+        info!(
+            "Waiting for certificates ({}/{})...",
+            attempts, MAX_CERT_WAIT_ATTEMPTS
+        );
+        tokio::time::sleep(Duration::from_secs(10)).await;
+    }
+}
+
 async fn handle_acme_challenge(
     State(challenges): State<AcmeChallenges>,
     Path(token): Path<String>,
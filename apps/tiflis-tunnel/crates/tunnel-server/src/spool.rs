@@ -0,0 +1,424 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+//! Spooling of large HTTP bodies to temp files (`server.spool_threshold`).
+//! An upload over the threshold is written to disk as it arrives and streamed
+//! from there into its tunnel frame. A response frame over it has its body
+//! decoded to disk as it arrives and is served from the file. Neither body is
+//! held in memory whole. Files are removed once the transfer ends or fails.
+
+use crate::rate_limit::BandwidthLimiter;
+use axum::body::{Body, Bytes};
+use axum::http::StatusCode;
+use base64::Engine;
+use futures::StreamExt;
+use std::io;
+use std::path::PathBuf;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::warn;
+use tunnel_core::{codec, Error, Message};
+use uuid::Uuid;
+
+/// Read size when streaming a spooled body back out.
+const READ_CHUNK: usize = 64 * 1024;
+
+/// Body bytes encoded per write when streaming a spooled body into a frame.
+/// A multiple of 3, so the pieces concatenate to one base64 string.
+const ENCODE_CHUNK: usize = 48 * 1024;
+
+/// Base64 characters collected before decoding them. A multiple of 4.
+const DECODE_CHUNK: usize = 64 * 1024;
+
+/// A message's `body` field once serialized with an empty value.
+const EMPTY_BODY: &[u8] = b"\"body\":\"\"";
+
+pub struct Spool {
+    dir: PathBuf,
+    /// Bodies larger than this many bytes go to disk.
+    threshold: usize,
+}
+
+/// A spool file, removed when dropped.
+struct SpoolFile {
+    path: PathBuf,
+}
+
+impl Drop for SpoolFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            if e.kind() != io::ErrorKind::NotFound {
+                warn!("Failed to remove spool file {}: {}", self.path.display(), e);
+            }
+        }
+    }
+}
+
+/// A body kept in a spool file, removed once this is dropped.
+pub struct SpooledBody {
+    file: SpoolFile,
+    len: u64,
+}
+
+/// A request body read by [`Spool::collect`].
+pub enum CollectedBody {
+    Memory(Vec<u8>),
+    Spooled(SpooledBody),
+}
+
+impl Spool {
+    pub fn new(dir: PathBuf, threshold: usize) -> Self {
+        Self { dir, threshold }
+    }
+
+    async fn create(&self) -> io::Result<(SpoolFile, tokio::fs::File)> {
+        let spool = SpoolFile {
+            path: self
+                .dir
+                .join(format!("tunnel-spool-{}", Uuid::new_v4().simple())),
+        };
+        let file = tokio::fs::File::create(&spool.path).await?;
+        Ok((spool, file))
+    }
+
+    /// Reads a request body. Once it grows past the threshold the rest is
+    /// written to disk as it arrives.
+    pub async fn collect(&self, body: Body) -> Result<CollectedBody, StatusCode> {
+        let mut stream = body.into_data_stream();
+        let mut buf = Vec::new();
+        let mut spooled: Option<(SpoolFile, tokio::fs::File, u64)> = None;
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|_| StatusCode::BAD_REQUEST)?;
+            if let Some((_, file, len)) = &mut spooled {
+                file.write_all(&chunk).await.map_err(spool_failed)?;
+                *len += chunk.len() as u64;
+                continue;
+            }
+            buf.extend_from_slice(&chunk);
+            if buf.len() > self.threshold {
+                let (spool, mut file) = self.create().await.map_err(spool_failed)?;
+                file.write_all(&buf).await.map_err(spool_failed)?;
+                spooled = Some((spool, file, buf.len() as u64));
+                buf = Vec::new();
+            }
+        }
+        let Some((file, mut written, len)) = spooled else {
+            return Ok(CollectedBody::Memory(buf));
+        };
+        written.flush().await.map_err(spool_failed)?;
+        Ok(CollectedBody::Spooled(SpooledBody { file, len }))
+    }
+
+    /// Receives the next message on `recv`. A frame over the threshold has the
+    /// base64 value of its `body` field decoded into a spool file as it
+    /// arrives; the message then comes back with an empty body, along with
+    /// the file.
+    pub async fn recv_message(
+        &self,
+        recv: &mut quinn::RecvStream,
+        max_frame_bytes: usize,
+    ) -> tunnel_core::Result<(Message, Option<SpooledBody>)> {
+        let mut len_buf = [0u8; 4];
+        read_exact(recv, &mut len_buf).await?;
+        let len = u32::from_be_bytes(len_buf) as usize;
+        if len > max_frame_bytes {
+            return Err(Error::MessageTooLarge {
+                size: len,
+                limit: max_frame_bytes,
+            });
+        }
+        let context = format!("stream {}", recv.id());
+        if len <= self.threshold {
+            let mut data = vec![0u8; len];
+            read_exact(recv, &mut data).await?;
+            let msg = codec::decode_payload(&data, &context)?;
+            msg.validate()?;
+            return Ok((msg, None));
+        }
+
+        let mut splitter = BodySplitter::default();
+        let mut spooled: Option<(SpoolFile, tokio::fs::File, u64)> = None;
+        let mut buf = vec![0u8; READ_CHUNK];
+        let mut decoded = Vec::new();
+        let mut remaining = len;
+        while remaining > 0 {
+            let n = remaining.min(READ_CHUNK);
+            read_exact(recv, &mut buf[..n]).await?;
+            remaining -= n;
+            splitter.feed(&buf[..n], &mut decoded)?;
+            if decoded.is_empty() {
+                continue;
+            }
+            if spooled.is_none() {
+                let (spool, file) = self.create().await?;
+                spooled = Some((spool, file, 0));
+            }
+            if let Some((_, file, len)) = &mut spooled {
+                file.write_all(&decoded).await?;
+                *len += decoded.len() as u64;
+            }
+            decoded.clear();
+        }
+
+        let msg = codec::decode_payload(&splitter.finish()?, &context)?;
+        msg.validate()?;
+        let spooled = match spooled {
+            Some((file, mut written, len)) => {
+                written.flush().await?;
+                Some(SpooledBody { file, len })
+            }
+            None => None,
+        };
+        Ok((msg, spooled))
+    }
+}
+
+impl SpooledBody {
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Sends `msg`, whose `body` must be `Some("")`, with this body streamed
+    /// in as the base64 value of that field, each piece waiting on
+    /// `bandwidth`. Nothing is written when the frame would exceed
+    /// `max_frame_bytes`.
+    pub async fn send_in(
+        &self,
+        send: &mut quinn::SendStream,
+        msg: &Message,
+        max_frame_bytes: usize,
+        bandwidth: &BandwidthLimiter,
+    ) -> tunnel_core::Result<()> {
+        let json = serde_json::to_vec(msg)?;
+        // The field comes after the headers, so the last match is the field
+        // even when a header is named `body`. The split is before the closing
+        // quote.
+        let split = json
+            .windows(EMPTY_BODY.len())
+            .rposition(|window| window == EMPTY_BODY)
+            .ok_or_else(|| Error::MissingField("body".to_string()))?
+            + EMPTY_BODY.len()
+            - 1;
+        let size = json.len() as u64 + self.len.div_ceil(3) * 4;
+        if size > max_frame_bytes as u64 || size > u32::MAX as u64 {
+            return Err(Error::MessageTooLarge {
+                size: size as usize,
+                limit: max_frame_bytes,
+            });
+        }
+
+        let mut file = tokio::fs::File::open(&self.file.path).await?;
+        write_all(send, &(size as u32).to_be_bytes()).await?;
+        write_all(send, &json[..split]).await?;
+        let mut buf = vec![0u8; ENCODE_CHUNK];
+        let mut remaining = self.len;
+        while remaining > 0 {
+            let n = remaining.min(ENCODE_CHUNK as u64) as usize;
+            file.read_exact(&mut buf[..n]).await?;
+            remaining -= n as u64;
+            bandwidth.acquire(n).await;
+            let encoded = base64::engine::general_purpose::STANDARD.encode(&buf[..n]);
+            write_all(send, encoded.as_bytes()).await?;
+        }
+        write_all(send, &json[split..]).await
+    }
+
+    /// Streams the file as a response body. It is removed when the body ends
+    /// or the caller goes away.
+    pub async fn into_body(self) -> io::Result<Body> {
+        let file = tokio::fs::File::open(&self.file.path).await?;
+        let stream = futures::stream::unfold(Some((file, self.file)), |state| async move {
+            let (mut file, spool) = state?;
+            let mut buf = vec![0u8; READ_CHUNK];
+            match file.read(&mut buf).await {
+                Ok(0) => None,
+                Ok(n) => {
+                    buf.truncate(n);
+                    Some((Ok(Bytes::from(buf)), Some((file, spool))))
+                }
+                Err(e) => Some((Err(e), None)),
+            }
+        });
+        Ok(Body::from_stream(stream))
+    }
+}
+
+async fn read_exact(recv: &mut quinn::RecvStream, buf: &mut [u8]) -> tunnel_core::Result<()> {
+    recv.read_exact(buf).await.map_err(|e| match e {
+        quinn::ReadExactError::FinishedEarly(_) => Error::Connection("stream closed".to_string()),
+        quinn::ReadExactError::ReadError(e) => Error::Connection(e.to_string()),
+    })
+}
+
+async fn write_all(send: &mut quinn::SendStream, buf: &[u8]) -> tunnel_core::Result<()> {
+    send.write_all(buf)
+        .await
+        .map_err(|e| Error::Connection(e.to_string()))
+}
+
+/// Where [`BodySplitter`] is within the frame.
+#[derive(Debug, Default, PartialEq, Eq)]
+enum SplitState {
+    #[default]
+    Outside,
+    /// Just read the `:` after the top-level key `body`.
+    BodyValueNext,
+    /// Inside the `body` string.
+    InBody,
+    /// Past the `body` string.
+    Done,
+}
+
+/// Takes a JSON frame in pieces, keeping it with the value of its top-level
+/// `body` string emptied while decoding that base64 value as it goes.
+#[derive(Default)]
+struct BodySplitter {
+    skeleton: Vec<u8>,
+    state: SplitState,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    /// Contents of the top-level string being read, while short enough to be
+    /// the `body` key.
+    string: Option<Vec<u8>>,
+    /// Whether the last token was a top-level `"body"` string.
+    body_key: bool,
+    /// Base64 characters not yet decoded.
+    pending: Vec<u8>,
+}
+
+impl BodySplitter {
+    fn feed(&mut self, data: &[u8], decoded: &mut Vec<u8>) -> tunnel_core::Result<()> {
+        for &b in data {
+            if self.state == SplitState::InBody {
+                match b {
+                    b'"' => {
+                        self.decode(decoded, self.pending.len())?;
+                        self.skeleton.push(b);
+                        self.state = SplitState::Done;
+                    }
+                    b'\\' => {
+                        return Err(Error::Decode {
+                            detail: "escape in base64 body".to_string(),
+                        })
+                    }
+                    _ => {
+                        self.pending.push(b);
+                        if self.pending.len() >= DECODE_CHUNK {
+                            self.decode(decoded, DECODE_CHUNK)?;
+                        }
+                    }
+                }
+                continue;
+            }
+            self.skeleton.push(b);
+            if self.in_string {
+                if self.escaped {
+                    self.escaped = false;
+                } else if b == b'\\' {
+                    self.escaped = true;
+                } else if b == b'"' {
+                    self.in_string = false;
+                    self.body_key = self.string.take().is_some_and(|s| s == b"body");
+                    continue;
+                }
+                if let Some(string) = &mut self.string {
+                    string.push(b);
+                    if string.len() > 4 {
+                        self.string = None;
+                    }
+                }
+                continue;
+            }
+            if b.is_ascii_whitespace() {
+                continue;
+            }
+            if self.state == SplitState::BodyValueNext {
+                if b == b'"' {
+                    self.state = SplitState::InBody;
+                    continue;
+                }
+                self.state = SplitState::Outside;
+            }
+            let body_key = std::mem::take(&mut self.body_key);
+            match b {
+                b'"' => {
+                    self.in_string = true;
+                    self.string = (self.depth == 1).then(Vec::new);
+                }
+                b'{' | b'[' => self.depth += 1,
+                b'}' | b']' => self.depth = self.depth.saturating_sub(1),
+                b':' if body_key && self.state == SplitState::Outside => {
+                    self.state = SplitState::BodyValueNext;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Decodes the first `n` pending characters into `decoded`.
+    fn decode(&mut self, decoded: &mut Vec<u8>, n: usize) -> tunnel_core::Result<()> {
+        base64::engine::general_purpose::STANDARD.decode_vec(&self.pending[..n], decoded)?;
+        self.pending.drain(..n);
+        Ok(())
+    }
+
+    /// The frame with an empty `body`.
+    fn finish(self) -> tunnel_core::Result<Vec<u8>> {
+        if self.state == SplitState::InBody {
+            return Err(Error::Decode {
+                detail: "frame ended inside its body".to_string(),
+            });
+        }
+        Ok(self.skeleton)
+    }
+}
+
+fn spool_failed(e: io::Error) -> StatusCode {
+    warn!("Failed to spool request body: {}", e);
+    StatusCode::INSUFFICIENT_STORAGE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tunnel_core::HttpResponseMessage;
+
+    #[test]
+    fn test_splitter_decodes_body_and_keeps_the_rest() {
+        let body: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        // A header named `body` must not be mistaken for the field.
+        let headers = HashMap::from([
+            ("body".to_string(), "\"body\":\"x".to_string()),
+            (
+                "content-type".to_string(),
+                "application/octet-stream".to_string(),
+            ),
+        ]);
+        let msg = Message::HttpResponse(HttpResponseMessage {
+            stream_id: Uuid::new_v4(),
+            status: 200,
+            headers: headers.clone(),
+            body: Some(codec::encode_body(&body)),
+        });
+        let frame = serde_json::to_vec(&msg).unwrap();
+
+        let mut splitter = BodySplitter::default();
+        let mut decoded = Vec::new();
+        for piece in frame.chunks(1000) {
+            splitter.feed(piece, &mut decoded).unwrap();
+        }
+        assert_eq!(decoded, body);
+        let skeleton = splitter.finish().unwrap();
+        let Message::HttpResponse(resp) = codec::decode_payload(&skeleton, "test").unwrap() else {
+            panic!("not an http_response");
+        };
+        assert_eq!(resp.body.as_deref(), Some(""));
+        assert_eq!(resp.headers, headers);
+    }
+}
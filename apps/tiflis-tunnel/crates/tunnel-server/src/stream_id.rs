@@ -0,0 +1,44 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+//! Ids that tie a proxied request to its tunnel stream and pending entry.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use uuid::Uuid;
+
+/// Hands out stream ids. The server uses random ones; embedders and tests
+/// can supply their own through `TunnelServer::with_stream_ids`.
+pub trait StreamIdGenerator: Send + Sync {
+    fn next_id(&self) -> Uuid;
+}
+
+/// Random v4 ids, the default.
+#[derive(Debug, Default)]
+pub struct RandomStreamIds;
+
+impl StreamIdGenerator for RandomStreamIds {
+    fn next_id(&self) -> Uuid {
+        Uuid::new_v4()
+    }
+}
+
+/// Consecutive ids starting at `seed`, e.g. `00000000-0000-0000-0000-000000000001`
+/// for a seed of 1, so runs are reproducible.
+#[derive(Debug)]
+pub struct SequentialStreamIds {
+    next: AtomicU64,
+}
+
+impl SequentialStreamIds {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            next: AtomicU64::new(seed),
+        }
+    }
+}
+
+impl StreamIdGenerator for SequentialStreamIds {
+    fn next_id(&self) -> Uuid {
+        Uuid::from_u64_pair(0, self.next.fetch_add(1, Ordering::Relaxed))
+    }
+}
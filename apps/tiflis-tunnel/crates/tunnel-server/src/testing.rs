@@ -0,0 +1,110 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+//! In-process server for integration tests, enabled by the `testing` feature.
+
+use crate::config::Config;
+use crate::server::TunnelServer;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::error;
+
+/// API key the test server accepts unless the config says otherwise.
+pub const TEST_API_KEY: &str = "test-api-key-minimum-32-characters-long";
+
+/// How long [`TestServer::start`] waits for the listeners to bind.
+const READY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A [`TunnelServer`] running on OS-assigned loopback ports with TLS off.
+pub struct TestServer {
+    pub server: Arc<TunnelServer>,
+    pub http_addr: SocketAddr,
+    pub quic_addr: SocketAddr,
+    pub api_key: String,
+    run: JoinHandle<()>,
+}
+
+impl TestServer {
+    pub async fn start() -> anyhow::Result<Self> {
+        Self::start_with(|_| {}).await
+    }
+
+    /// Starts a server after `configure` adjusts the test config, and returns
+    /// once the HTTP and QUIC listeners are bound.
+    pub async fn start_with(configure: impl FnOnce(&mut Config)) -> anyhow::Result<Self> {
+        Self::start_custom(configure, |server| server).await
+    }
+
+    /// Like [`start_with`](Self::start_with), with `build` adjusting the
+    /// server before it runs, e.g. to install a custom authenticator or
+    /// stream id generator.
+    pub async fn start_custom(
+        configure: impl FnOnce(&mut Config),
+        build: impl FnOnce(TunnelServer) -> TunnelServer,
+    ) -> anyhow::Result<Self> {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+
+        let mut config = Config::default();
+        config.server.domain = "localhost".to_string();
+        config.server.http_port = 0;
+        config.server.https_port = 0;
+        config.server.quic_port = Some(0);
+        config.tls.enabled = false;
+        config.auth.api_key = TEST_API_KEY.to_string();
+        configure(&mut config);
+
+        let api_key = config.auth.api_key.clone();
+        let server = Arc::new(build(TunnelServer::new(config)));
+        let run = tokio::spawn({
+            let server = server.clone();
+            async move {
+                if let Err(e) = server.run().await {
+                    error!("Test server failed: {}", e);
+                }
+            }
+        });
+
+        let ready = async { (server.http_addr().await, server.quic_addr().await) };
+        let Ok((http_addr, quic_addr)) = tokio::time::timeout(READY_TIMEOUT, ready).await else {
+            server.shutdown().await;
+            run.abort();
+            anyhow::bail!("test server did not bind within {:?}", READY_TIMEOUT);
+        };
+
+        Ok(Self {
+            server,
+            http_addr: loopback(http_addr),
+            quic_addr: loopback(quic_addr),
+            api_key,
+            run,
+        })
+    }
+
+    /// `http://` URL for `path` on the server itself, e.g. `/health`.
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}/{}", self.http_addr, path.trim_start_matches('/'))
+    }
+
+    /// URL that reaches `path` on the workstation through the tunnel.
+    pub fn proxy_url(&self, workstation_id: &str, path: &str) -> String {
+        self.url(&format!(
+            "t/{}/{}",
+            workstation_id,
+            path.trim_start_matches('/')
+        ))
+    }
+
+    /// Stops the listeners, drops every tunnel connection and waits for the
+    /// run loop to end.
+    pub async fn shutdown(self) {
+        self.server.shutdown().await;
+        let _ = self.run.await;
+    }
+}
+
+/// Listeners bind to all interfaces; tests reach them over loopback.
+fn loopback(addr: SocketAddr) -> SocketAddr {
+    SocketAddr::from((Ipv4Addr::LOCALHOST, addr.port()))
+}
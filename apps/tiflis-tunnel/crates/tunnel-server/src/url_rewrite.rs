@@ -0,0 +1,151 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+//! Opt-in rewriting of local URLs in HTML and CSS responses, so pages that
+//! link to e.g. `http://localhost:3000` work through the tunnel.
+
+use crate::config::UrlRewriteRule;
+use axum::http::{header, HeaderMap};
+
+/// Largest body rewritten; bigger ones are relayed as they are.
+pub const MAX_REWRITE_BYTES: usize = 4 << 20;
+
+/// Rules from `server.url_rewrite`.
+#[derive(Debug, Clone)]
+pub struct UrlRewriter {
+    rules: Vec<UrlRewriteRule>,
+}
+
+impl UrlRewriter {
+    /// Returns `None` when no rules are configured.
+    pub fn from_config(rules: &[UrlRewriteRule]) -> Option<Self> {
+        (!rules.is_empty()).then(|| Self {
+            rules: rules.to_vec(),
+        })
+    }
+
+    /// Applies the rules to an uncompressed `text/html` or `text/css` body,
+    /// replacing rules without a `to` with `tunnel_url`. Other bodies are
+    /// returned as is. `Content-Length` is dropped when the body changes.
+    pub fn apply(&self, tunnel_url: &str, headers: &mut HeaderMap, body: Vec<u8>) -> Vec<u8> {
+        if body.len() > MAX_REWRITE_BYTES || !is_rewritable(headers) {
+            return body;
+        }
+        // Pages in legacy encodings are left alone rather than mangled.
+        let mut text = match String::from_utf8(body) {
+            Ok(text) => text,
+            Err(e) => return e.into_bytes(),
+        };
+        let original_len = text.len();
+        let mut changed = false;
+        for rule in &self.rules {
+            if text.contains(&rule.from) {
+                text = text.replace(&rule.from, rule.to.as_deref().unwrap_or(tunnel_url));
+                changed = true;
+            }
+        }
+        if changed && text.len() != original_len {
+            headers.remove(header::CONTENT_LENGTH);
+        }
+        text.into_bytes()
+    }
+}
+
+fn is_rewritable(headers: &HeaderMap) -> bool {
+    let encoded = headers
+        .get(header::CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| !v.trim().eq_ignore_ascii_case("identity"));
+    if encoded {
+        return false;
+    }
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.split(';').next().unwrap_or("").trim())
+        .is_some_and(|mime| {
+            mime.eq_ignore_ascii_case("text/html") || mime.eq_ignore_ascii_case("text/css")
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewriter() -> UrlRewriter {
+        UrlRewriter::from_config(&[
+            UrlRewriteRule {
+                from: "http://localhost:3000".to_string(),
+                to: None,
+            },
+            UrlRewriteRule {
+                from: "http://127.0.0.1:9000".to_string(),
+                to: Some("https://cdn.example.com".to_string()),
+            },
+        ])
+        .unwrap()
+    }
+
+    fn headers(content_type: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, content_type.parse().unwrap());
+        headers.insert(header::CONTENT_LENGTH, "10".parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_rewrites_html_and_css() {
+        let tunnel = "https://tunnel.example.com/t/ws";
+        let mut html = headers("text/html; charset=utf-8");
+        let body = rewriter().apply(
+            tunnel,
+            &mut html,
+            b"<a href=\"http://localhost:3000/x\"><img src=\"http://127.0.0.1:9000/a.png\">"
+                .to_vec(),
+        );
+        assert_eq!(
+            body,
+            b"<a href=\"https://tunnel.example.com/t/ws/x\"><img src=\"https://cdn.example.com/a.png\">"
+        );
+        assert!(!html.contains_key(header::CONTENT_LENGTH));
+
+        let mut css = headers("TEXT/CSS");
+        let body = rewriter().apply(
+            tunnel,
+            &mut css,
+            b"a{background:url(http://localhost:3000/bg.png)}".to_vec(),
+        );
+        assert_eq!(
+            body,
+            b"a{background:url(https://tunnel.example.com/t/ws/bg.png)}"
+        );
+    }
+
+    #[test]
+    fn test_leaves_other_bodies_untouched() {
+        let tunnel = "https://tunnel.example.com/t/ws";
+        let body = b"http://localhost:3000".to_vec();
+        for content_type in ["application/octet-stream", "image/png", "application/json"] {
+            let mut binary = headers(content_type);
+            assert_eq!(rewriter().apply(tunnel, &mut binary, body.clone()), body);
+            assert!(binary.contains_key(header::CONTENT_LENGTH));
+        }
+
+        let mut gzipped = headers("text/html");
+        gzipped.insert(header::CONTENT_ENCODING, "gzip".parse().unwrap());
+        assert_eq!(rewriter().apply(tunnel, &mut gzipped, body.clone()), body);
+
+        let mut html = headers("text/html");
+        let latin1 = b"\xe9 http://localhost:3000".to_vec();
+        assert_eq!(rewriter().apply(tunnel, &mut html, latin1.clone()), latin1);
+
+        let mut large = body.clone();
+        large.resize(MAX_REWRITE_BYTES + 1, b' ');
+        assert_eq!(rewriter().apply(tunnel, &mut html, large.clone()), large);
+    }
+
+    #[test]
+    fn test_no_rules_disables_rewriting() {
+        assert!(UrlRewriter::from_config(&[]).is_none());
+    }
+}
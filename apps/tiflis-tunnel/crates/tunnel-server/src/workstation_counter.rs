@@ -0,0 +1,199 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+//! Counts registered workstations against `limits.max_workstations`, either
+//! per process or across every server sharing a backend.
+
+use async_trait::async_trait;
+use std::collections::HashSet;
+use std::sync::Mutex;
+#[cfg(feature = "redis")]
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CounterError {
+    #[error("workstation counter unavailable: {0}")]
+    Unavailable(String),
+}
+
+/// Holds one slot per registered workstation id. Slots are keyed by id so a
+/// workstation re-registering within its grace period, or a standby, reuses
+/// the slot it already has. Embedders install one through
+/// `TunnelServer::with_workstation_counter`.
+#[async_trait]
+pub trait WorkstationCounter: Send + Sync {
+    /// Takes a slot for `workstation_id` unless `max` are already taken by
+    /// other ids. Returns `false` when the limit is reached.
+    async fn try_acquire(&self, workstation_id: &str, max: usize) -> Result<bool, CounterError>;
+
+    /// Frees the slot held by `workstation_id`, if any.
+    async fn release(&self, workstation_id: &str);
+
+    /// Confirms that this server still holds the slots of `workstation_ids`.
+    /// Called on every cleanup tick, so a counter shared between servers can
+    /// expire the slots of one that died without releasing them.
+    async fn refresh(&self, _workstation_ids: &[String]) {}
+}
+
+/// Slots kept in this process, the default.
+#[derive(Debug, Default)]
+pub struct InMemoryWorkstationCounter {
+    held: Mutex<HashSet<String>>,
+}
+
+impl InMemoryWorkstationCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn count(&self) -> usize {
+        self.held.lock().unwrap().len()
+    }
+}
+
+#[async_trait]
+impl WorkstationCounter for InMemoryWorkstationCounter {
+    async fn try_acquire(&self, workstation_id: &str, max: usize) -> Result<bool, CounterError> {
+        let mut held = self.held.lock().unwrap();
+        if held.contains(workstation_id) {
+            return Ok(true);
+        }
+        if held.len() >= max {
+            return Ok(false);
+        }
+        held.insert(workstation_id.to_string());
+        Ok(true)
+    }
+
+    async fn release(&self, workstation_id: &str) {
+        self.held.lock().unwrap().remove(workstation_id);
+    }
+}
+
+/// Slots kept in a Redis sorted set, so every server pointed at the same key
+/// shares one `max_workstations` cap. Each id is scored with the time its slot
+/// expires, pushed back on every refresh; the slots of a server that died
+/// without releasing them lapse after the TTL.
+#[cfg(feature = "redis")]
+pub struct RedisWorkstationCounter {
+    connection: redis::aio::MultiplexedConnection,
+    key: String,
+    ttl: Duration,
+}
+
+/// TTL of a slot that is not refreshed. Must exceed the cleanup interval.
+#[cfg(feature = "redis")]
+pub const DEFAULT_SLOT_TTL: Duration = Duration::from_secs(60);
+
+/// Drops lapsed slots, then adds the id unless the set is full, atomically.
+/// Expiry is scored in Redis' own clock so servers need not agree on time.
+#[cfg(feature = "redis")]
+const ACQUIRE_SCRIPT: &str = r"
+local time = redis.call('TIME')
+local now = time[1] * 1000 + math.floor(time[2] / 1000)
+redis.call('ZREMRANGEBYSCORE', KEYS[1], '-inf', now)
+if not redis.call('ZSCORE', KEYS[1], ARGV[1])
+    and redis.call('ZCARD', KEYS[1]) >= tonumber(ARGV[2]) then
+    return 0
+end
+redis.call('ZADD', KEYS[1], now + tonumber(ARGV[3]), ARGV[1])
+return 1
+";
+
+/// Pushes back the expiry of every id in `ARGV[2..]` by `ARGV[1]` ms.
+#[cfg(feature = "redis")]
+const REFRESH_SCRIPT: &str = r"
+local time = redis.call('TIME')
+local expires = time[1] * 1000 + math.floor(time[2] / 1000) + tonumber(ARGV[1])
+for i = 2, #ARGV do
+    redis.call('ZADD', KEYS[1], expires, ARGV[i])
+end
+return #ARGV - 1
+";
+
+#[cfg(feature = "redis")]
+impl RedisWorkstationCounter {
+    /// Connects to `url`, e.g. `redis://127.0.0.1/`, keeping slots under `key`.
+    pub async fn connect(url: &str, key: impl Into<String>) -> Result<Self, CounterError> {
+        let client =
+            redis::Client::open(url).map_err(|e| CounterError::Unavailable(e.to_string()))?;
+        let connection = client
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(|e| CounterError::Unavailable(e.to_string()))?;
+        Ok(Self {
+            connection,
+            key: key.into(),
+            ttl: DEFAULT_SLOT_TTL,
+        })
+    }
+
+    /// Sets how long a slot outlives its last refresh.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+}
+
+#[cfg(feature = "redis")]
+#[async_trait]
+impl WorkstationCounter for RedisWorkstationCounter {
+    async fn try_acquire(&self, workstation_id: &str, max: usize) -> Result<bool, CounterError> {
+        let mut connection = self.connection.clone();
+        let acquired: i64 = redis::Script::new(ACQUIRE_SCRIPT)
+            .key(&self.key)
+            .arg(workstation_id)
+            .arg(max)
+            .arg(self.ttl.as_millis() as u64)
+            .invoke_async(&mut connection)
+            .await
+            .map_err(|e| CounterError::Unavailable(e.to_string()))?;
+        Ok(acquired == 1)
+    }
+
+    async fn release(&self, workstation_id: &str) {
+        let mut connection = self.connection.clone();
+        let removed: redis::RedisResult<i64> = redis::cmd("ZREM")
+            .arg(&self.key)
+            .arg(workstation_id)
+            .query_async(&mut connection)
+            .await;
+        if let Err(e) = removed {
+            tracing::warn!("Failed to release slot for {}: {}", workstation_id, e);
+        }
+    }
+
+    async fn refresh(&self, workstation_ids: &[String]) {
+        if workstation_ids.is_empty() {
+            return;
+        }
+        let mut connection = self.connection.clone();
+        let refreshed: redis::RedisResult<i64> = redis::Script::new(REFRESH_SCRIPT)
+            .key(&self.key)
+            .arg(self.ttl.as_millis() as u64)
+            .arg(workstation_ids)
+            .invoke_async(&mut connection)
+            .await;
+        if let Err(e) = refreshed {
+            tracing::warn!("Failed to refresh workstation slots: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_in_memory_counter_keys_slots_by_id() {
+        let counter = InMemoryWorkstationCounter::new();
+        assert!(counter.try_acquire("a", 1).await.unwrap());
+        assert!(counter.try_acquire("a", 1).await.unwrap());
+        assert!(!counter.try_acquire("b", 1).await.unwrap());
+
+        counter.release("a").await;
+        assert!(counter.try_acquire("b", 1).await.unwrap());
+        assert_eq!(counter.count(), 1);
+    }
+}
@@ -12,6 +12,14 @@ use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tokio::task::JoinHandle;
+use tunnel_server::config::Config as ServerConfig;
+use tunnel_server::registry::RegistryEventKind;
+use tunnel_server::server::TunnelServer;
+
+/// Upper bound on waiting for a listener to bind or a client to register.
+const READY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+type ServerConfigFn = Arc<dyn Fn(&mut ServerConfig) + Send + Sync>;
 
 pub struct TestEnvironment {
     pub server_http_port: u16,
@@ -21,12 +29,16 @@ pub struct TestEnvironment {
     pub workstation_id: String,
     #[allow(dead_code)]
     pub tunnel_url: String,
+    #[allow(dead_code)]
+    pub server: Arc<TunnelServer>,
+    server_config_fn: ServerConfigFn,
     _server_handle: JoinHandle<()>,
     _mock_handle: JoinHandle<()>,
     _client_handle: Option<JoinHandle<()>>,
 }
 
 impl TestEnvironment {
+    #[allow(dead_code)]
     pub async fn new() -> Self {
         let workstation_id = format!("test-ws-{}", rand::random::<u16>());
         Self::new_with_config(&workstation_id, None, None).await
@@ -49,10 +61,27 @@ impl TestEnvironment {
         Self::new_with_config(&workstation_id, None, Some(max_workstations)).await
     }
 
+    #[allow(dead_code)]
     pub async fn new_with_config(
         workstation_id: &str,
         grace_period: Option<u64>,
         max_workstations: Option<usize>,
+    ) -> Self {
+        Self::new_with_server_config(workstation_id, move |config| {
+            if let Some(grace) = grace_period {
+                config.reliability.grace_period = grace;
+            }
+            if let Some(max) = max_workstations {
+                config.limits.max_workstations = max;
+            }
+        })
+        .await
+    }
+
+    #[allow(dead_code)]
+    pub async fn new_with_server_config(
+        workstation_id: &str,
+        configure: impl Fn(&mut ServerConfig) + Send + Sync + 'static,
     ) -> Self {
         let _ = rustls::crypto::ring::default_provider().install_default();
 
@@ -63,17 +92,16 @@ impl TestEnvironment {
         let server_quic_port = get_free_port().await;
         let mock_server_port = get_free_port().await;
 
-        let server_handle = spawn_tunnel_server(
+        let server_config_fn: ServerConfigFn = Arc::new(configure);
+        let (server, server_handle) = spawn_tunnel_server(
             server_http_port,
             server_quic_port,
             api_key.clone(),
-            grace_period,
-            max_workstations,
+            server_config_fn.clone(),
         );
 
-        let mock_handle = spawn_mock_server(mock_server_port);
-
-        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        let mock_handle = spawn_mock_server(mock_server_port).await;
+        wait_until_listening(&server).await;
 
         let tunnel_url = format!("http://localhost:{}", server_http_port);
 
@@ -84,26 +112,59 @@ impl TestEnvironment {
             api_key,
             workstation_id: workstation_id.clone(),
             tunnel_url,
+            server,
+            server_config_fn,
             _server_handle: server_handle,
             _mock_handle: mock_handle,
             _client_handle: None,
         }
     }
 
+    #[allow(dead_code)]
     pub async fn start_client(&mut self) {
         let server_address = format!("127.0.0.1:{}", self.server_quic_port);
         let api_key = self.api_key.clone();
         let workstation_id = self.workstation_id.clone();
         let local_address = format!("http://localhost:{}", self.mock_server_port);
 
-        let client_handle =
-            spawn_tunnel_client(server_address, api_key, workstation_id, local_address);
+        let mut events = self.server.registry().subscribe();
+        let client_handle = spawn_tunnel_client(
+            server_address,
+            api_key,
+            workstation_id.clone(),
+            local_address,
+        );
 
-        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+        // Tests that expect registration to fail carry on after the timeout.
+        let _ = tokio::time::timeout(READY_TIMEOUT, async {
+            while let Ok(event) = events.recv().await {
+                if event.workstation_id == workstation_id
+                    && matches!(event.state, RegistryEventKind::Registered)
+                {
+                    break;
+                }
+            }
+        })
+        .await;
 
         self._client_handle = Some(client_handle);
     }
 
+    /// Client config pointing at this environment, for tests that drive
+    /// `tunnel_client::connection::Connection` directly.
+    #[allow(dead_code)]
+    pub fn client_config(&self) -> tunnel_client::config::Config {
+        let mut config = tunnel_client::config::Config::default();
+        config.server.address = format!("127.0.0.1:{}", self.server_quic_port);
+        config.auth.api_key = self.api_key.clone();
+        config.workstation.id = self.workstation_id.clone();
+        config.workstation.local_address = format!("http://localhost:{}", self.mock_server_port);
+        config.session.ticket_path =
+            std::env::temp_dir().join(format!("tunnel-test-{}.ticket", rand::random::<u32>()));
+        config
+    }
+
+    #[allow(dead_code)]
     pub fn proxy_url(&self, path: &str) -> String {
         format!(
             "http://localhost:{}/t/{}/{}",
@@ -121,7 +182,8 @@ impl TestEnvironment {
     }
 
     #[allow(dead_code)]
-    pub fn stop_server(&mut self) {
+    pub async fn stop_server(&mut self) {
+        self.server.shutdown().await;
         self._server_handle.abort();
     }
 
@@ -132,18 +194,27 @@ impl TestEnvironment {
 
     #[allow(dead_code)]
     pub async fn restart_server_with_grace_period(&mut self, grace_period: Option<u64>) {
+        self.server.shutdown().await;
         self._server_handle.abort();
         tokio::time::sleep(std::time::Duration::from_secs(2)).await;
 
-        self._server_handle = spawn_tunnel_server(
+        let base_config_fn = self.server_config_fn.clone();
+        let config_fn: ServerConfigFn = Arc::new(move |config: &mut ServerConfig| {
+            base_config_fn(config);
+            if let Some(grace) = grace_period {
+                config.reliability.grace_period = grace;
+            }
+        });
+
+        let (server, server_handle) = spawn_tunnel_server(
             self.server_http_port,
             self.server_quic_port,
             self.api_key.clone(),
-            grace_period,
-            None,
+            config_fn,
         );
-
-        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        wait_until_listening(&server).await;
+        self.server = server;
+        self._server_handle = server_handle;
     }
 
     #[allow(dead_code)]
@@ -159,49 +230,73 @@ async fn get_free_port() -> u16 {
     listener.local_addr().unwrap().port()
 }
 
-fn spawn_tunnel_server(
-    http_port: u16,
-    quic_port: u16,
-    api_key: String,
-    grace_period: Option<u64>,
-    max_workstations: Option<usize>,
-) -> JoinHandle<()> {
-    tokio::spawn(async move {
-        use tunnel_server::config::Config;
-        use tunnel_server::server::TunnelServer;
-
-        std::env::set_var("RUST_LOG", "tunnel_server=info,tunnel_client=info");
-        let _ = tracing_subscriber::fmt()
-            .with_max_level(tracing::Level::INFO)
-            .try_init();
+async fn wait_until_listening(server: &TunnelServer) {
+    tokio::time::timeout(READY_TIMEOUT, async {
+        server.http_addr().await;
+        server.quic_addr().await;
+    })
+    .await
+    .expect("tunnel server did not start listening");
+}
 
-        println!(
-            "Starting tunnel server on HTTP:{} QUIC:{}",
-            http_port, quic_port
-        );
+/// Collects formatted log output for the test thread.
+#[allow(dead_code)]
+#[derive(Clone, Default)]
+pub struct LogBuffer(Arc<std::sync::Mutex<Vec<u8>>>);
 
-        let mut config = Config::default();
-        config.server.domain = "localhost".to_string();
-        config.server.http_port = http_port;
-        config.server.https_port = quic_port;
-        config.tls.enabled = false;
-        config.auth.api_key = api_key;
+impl std::io::Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
 
-        if let Some(grace) = grace_period {
-            config.reliability.grace_period = grace;
-        }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
 
-        if let Some(max) = max_workstations {
-            config.limits.max_workstations = max;
-        }
+impl LogBuffer {
+    #[allow(dead_code)]
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+}
 
-        let server = Arc::new(TunnelServer::new(config));
+fn spawn_tunnel_server(
+    http_port: u16,
+    quic_port: u16,
+    api_key: String,
+    configure: ServerConfigFn,
+) -> (Arc<TunnelServer>, JoinHandle<()>) {
+    std::env::set_var("RUST_LOG", "tunnel_server=info,tunnel_client=info");
+    let _ = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .try_init();
+
+    println!(
+        "Starting tunnel server on HTTP:{} QUIC:{}",
+        http_port, quic_port
+    );
+
+    let mut config = ServerConfig::default();
+    config.server.domain = "localhost".to_string();
+    config.server.http_port = http_port;
+    config.server.https_port = quic_port;
+    config.tls.enabled = false;
+    config.auth.api_key = api_key;
+    configure(&mut config);
+
+    let server = Arc::new(TunnelServer::new(config));
+    let run_server = server.clone();
+    let handle = tokio::spawn(async move {
         println!("Tunnel server created, starting run loop...");
-        match server.run().await {
+        match run_server.run().await {
             Ok(_) => println!("Server exited normally"),
             Err(e) => eprintln!("Server error: {}", e),
         }
-    })
+    });
+
+    (server, handle)
 }
 
 fn spawn_tunnel_client(
@@ -235,7 +330,10 @@ fn spawn_tunnel_client(
     })
 }
 
-fn spawn_mock_server(port: u16) -> JoinHandle<()> {
+async fn spawn_mock_server(port: u16) -> JoinHandle<()> {
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+
     tokio::spawn(async move {
         let ws_connections = Arc::new(Mutex::new(Vec::new()));
         let ws_connections_clone = ws_connections.clone();
@@ -243,10 +341,36 @@ fn spawn_mock_server(port: u16) -> JoinHandle<()> {
         let app = Router::new()
             .route("/", get(|| async { "Hello from mock server" }))
             .route("/health", get(|| async { "OK" }))
+            .route(
+                "/file",
+                get(|headers| async move { ranged_file(MOCK_FILE_SIZE, headers) }),
+            )
+            .route(
+                "/large-file",
+                get(|headers| async move { ranged_file(MOCK_LARGE_FILE_SIZE, headers) }),
+            )
+            .route(
+                "/large-file/unranged",
+                get(|| async { Body::from(mock_file(MOCK_LARGE_FILE_SIZE)) }),
+            )
+            .route(
+                "/headers",
+                get(|| async {
+                    (
+                        [("x-powered-by", "mock"), ("x-frame-options", "SAMEORIGIN")],
+                        "OK",
+                    )
+                }),
+            )
             .route(
                 "/echo",
                 any(|body: String| async move { format!("Echo: {}", body) }),
             )
+            .route(
+                "/upload/refuse",
+                // Answers without reading the body, so no `100 Continue` is sent.
+                any(|| async { (axum::http::StatusCode::EXPECTATION_FAILED, "upload refused") }),
+            )
             .route(
                 "/error",
                 get(|| async {
@@ -263,6 +387,37 @@ fn spawn_mock_server(port: u16) -> JoinHandle<()> {
                     "Slow response"
                 }),
             )
+            .route(
+                "/redirect",
+                get(|| async {
+                    (
+                        axum::http::StatusCode::FOUND,
+                        [("location", "health")],
+                        "Moved",
+                    )
+                }),
+            )
+            .route(
+                "/forwarded",
+                get(|headers: axum::http::HeaderMap| async move {
+                    ["x-forwarded-proto", "x-forwarded-host", "forwarded"]
+                        .iter()
+                        .map(|name| {
+                            let value = headers
+                                .get(*name)
+                                .and_then(|v| v.to_str().ok())
+                                .unwrap_or_default();
+                            format!("{}: {}", name, value)
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                }),
+            )
+            .route("/echo.Echo/Say", axum::routing::post(grpc_echo_handler))
+            .route(
+                "/large/:bytes",
+                get(|Path(bytes): Path<usize>| async move { "x".repeat(bytes) }),
+            )
             .route(
                 "/api/*path",
                 any(
@@ -304,14 +459,37 @@ fn spawn_mock_server(port: u16) -> JoinHandle<()> {
             .route("/sse/events/:count", get(sse_events_with_count_handler))
             .route("/sse/slow", get(sse_slow_handler))
             .route("/sse/error", get(sse_error_handler))
-            .route("/sse/large", get(sse_large_handler));
+            .route("/sse/large", get(sse_large_handler))
+            .route("/sse/quiet", get(sse_quiet_handler))
+            .route("/sse/endless", get(sse_endless_handler));
 
-        let addr = SocketAddr::from(([127, 0, 0, 1], port));
-        let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
         axum::serve(listener, app).await.unwrap();
     })
 }
 
+/// Minimal unary gRPC echo: returns the length-prefixed request message as-is
+/// with `grpc-status: 0` in the trailers.
+async fn grpc_echo_handler(body: Body) -> Response {
+    use http_body_util::{BodyExt, StreamBody};
+    use hyper::body::Frame;
+
+    let message = body.collect().await.unwrap().to_bytes();
+
+    let mut trailers = axum::http::HeaderMap::new();
+    trailers.insert("grpc-status", "0".parse().unwrap());
+
+    let frames = futures::stream::iter(vec![
+        Ok::<_, std::io::Error>(Frame::data(message)),
+        Ok(Frame::trailers(trailers)),
+    ]);
+
+    Response::builder()
+        .status(200)
+        .header("content-type", "application/grpc")
+        .body(Body::new(StreamBody::new(frames)))
+        .unwrap()
+}
+
 async fn sse_events_handler() -> Response {
     sse_events_with_count_handler(Path(3)).await
 }
@@ -410,3 +588,90 @@ async fn sse_large_handler() -> Response {
         .body(Body::from_stream(stream))
         .unwrap()
 }
+
+async fn sse_quiet_handler() -> Response {
+    let (mut tx, rx) = futures::channel::mpsc::channel::<Result<String, std::io::Error>>(16);
+
+    tokio::spawn(async move {
+        use futures::SinkExt;
+        let _ = tx.send(Ok("data: first\n\n".to_string())).await;
+        tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+        let _ = tx.send(Ok("data: second\n\n".to_string())).await;
+    });
+
+    Response::builder()
+        .status(200)
+        .header("content-type", "text/event-stream")
+        .header("cache-control", "no-cache")
+        .body(Body::from_stream(rx))
+        .unwrap()
+}
+
+async fn sse_endless_handler() -> Response {
+    let (mut tx, rx) = futures::channel::mpsc::channel::<Result<String, std::io::Error>>(16);
+
+    tokio::spawn(async move {
+        use futures::SinkExt;
+        let mut i = 0u64;
+        loop {
+            i += 1;
+            if tx.send(Ok(format!("data: tick{}\n\n", i))).await.is_err() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        }
+    });
+
+    Response::builder()
+        .status(200)
+        .header("content-type", "text/event-stream")
+        .header("cache-control", "no-cache")
+        .body(Body::from_stream(rx))
+        .unwrap()
+}
+
+/// Size of the body served by `/file`.
+pub const MOCK_FILE_SIZE: usize = 1000;
+
+/// Size of the body served by `/large-file`, and in full by
+/// `/large-file/unranged`, which ignores `Range`.
+pub const MOCK_LARGE_FILE_SIZE: usize = 256 * 1024;
+
+/// A mock file of `size` bytes where byte `i` has the value `i % 256`.
+pub fn mock_file(size: usize) -> Vec<u8> {
+    (0..size).map(|i| (i % 256) as u8).collect()
+}
+
+fn ranged_file(size: usize, headers: axum::http::HeaderMap) -> Response {
+    let file = mock_file(size);
+
+    let range = headers
+        .get("range")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("bytes="))
+        .and_then(|v| v.split_once('-'));
+    let Some((start, end)) = range else {
+        return Response::builder()
+            .status(200)
+            .header("accept-ranges", "bytes")
+            .body(Body::from(file))
+            .unwrap();
+    };
+
+    let start: usize = start.parse().unwrap_or(0);
+    let end: usize = end.parse().unwrap_or(size - 1).min(size - 1);
+    if start > end {
+        return Response::builder()
+            .status(416)
+            .header("content-range", format!("bytes */{}", size))
+            .body(Body::empty())
+            .unwrap();
+    }
+
+    Response::builder()
+        .status(206)
+        .header("accept-ranges", "bytes")
+        .header("content-range", format!("bytes {}-{}/{}", start, end, size))
+        .body(Body::from(file[start..=end].to_vec()))
+        .unwrap()
+}
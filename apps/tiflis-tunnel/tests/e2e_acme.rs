@@ -17,6 +17,8 @@ use axum::{routing::get, Router};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
+use tunnel_server::cert_store::{CertStore, CertificateBundle, FileCertStore};
+use tunnel_server::server::TunnelServer;
 
 type AcmeChallenges = Arc<RwLock<HashMap<String, String>>>;
 
@@ -349,6 +351,417 @@ async fn test_acme_challenge_cleanup_after_validation() {
     assert_eq!(resp_after.status(), 404);
 }
 
+/// Certificate store kept in memory, standing in for S3 or a KV store.
+#[derive(Default)]
+struct MemoryCertStore {
+    bundle: std::sync::Mutex<Option<CertificateBundle>>,
+}
+
+#[async_trait::async_trait]
+impl CertStore for MemoryCertStore {
+    async fn load(&self) -> anyhow::Result<Option<CertificateBundle>> {
+        Ok(self.bundle.lock().unwrap().clone())
+    }
+
+    async fn store(&self, bundle: &CertificateBundle) -> anyhow::Result<()> {
+        *self.bundle.lock().unwrap() = Some(bundle.clone());
+        Ok(())
+    }
+}
+
+fn self_signed_bundle() -> CertificateBundle {
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    CertificateBundle {
+        cert_pem: cert.cert.pem(),
+        key_pem: cert.key_pair.serialize_pem(),
+    }
+}
+
+#[tokio::test]
+async fn test_memory_cert_store_round_trips() {
+    let store = MemoryCertStore::default();
+    assert!(store.load().await.unwrap().is_none());
+
+    let bundle = self_signed_bundle();
+    store.store(&bundle).await.unwrap();
+    assert_eq!(store.load().await.unwrap(), Some(bundle.clone()));
+
+    let renewed = self_signed_bundle();
+    store.store(&renewed).await.unwrap();
+    assert_eq!(store.load().await.unwrap(), Some(renewed));
+}
+
+#[tokio::test]
+async fn test_file_cert_store_round_trips() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let store = FileCertStore::new(temp_dir.path().join("certs"));
+    assert!(store.load().await.unwrap().is_none());
+
+    let bundle = self_signed_bundle();
+    store.store(&bundle).await.unwrap();
+    assert_eq!(store.load().await.unwrap(), Some(bundle.clone()));
+    assert_eq!(
+        std::fs::read_to_string(temp_dir.path().join("certs/cert.pem")).unwrap(),
+        bundle.cert_pem
+    );
+}
+
+#[tokio::test]
+async fn test_quic_listener_loads_certificate_from_custom_store() {
+    let free_tcp = || {
+        std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    };
+    let quic_port = std::net::UdpSocket::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let mut config = tunnel_server::config::Config::default();
+    config.server.domain = "localhost".to_string();
+    config.server.http_port = free_tcp();
+    config.server.https_port = free_tcp();
+    config.server.quic_port = Some(quic_port);
+    config.tls.enabled = true;
+    config.tls.certs_dir = std::env::temp_dir().join(format!("no-certs-{}", rand::random::<u32>()));
+    config.auth.api_key = "cert-store-test-key-cert-store-test-key".to_string();
+
+    let store = MemoryCertStore::default();
+    store.store(&self_signed_bundle()).await.unwrap();
+    let server = Arc::new(TunnelServer::new(config.clone()).with_cert_store(store));
+    let run = server.clone();
+    let server_handle = tokio::spawn(async move {
+        let _ = run.run().await;
+    });
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    let mut client_config = tunnel_client::config::Config::default();
+    client_config.server.address = format!("127.0.0.1:{}", quic_port);
+    client_config.auth.api_key = config.auth.api_key.clone();
+    client_config.workstation.id = format!("test-ws-{}", rand::random::<u16>());
+    client_config.session.ticket_path =
+        std::env::temp_dir().join(format!("tunnel-test-{}.ticket", rand::random::<u32>()));
+    tunnel_client::connection::Connection::new(client_config)
+        .connect()
+        .await
+        .expect("QUIC should be up with the stored certificate");
+    assert!(!config.tls.certs_dir.exists());
+
+    server_handle.abort();
+}
+
+/// Runs a TLS-enabled server and returns its plain HTTP port.
+async fn spawn_tls_server(
+    configure: impl FnOnce(&mut tunnel_server::config::Config),
+) -> (u16, tokio::task::JoinHandle<()>) {
+    spawn_tls_server_with_store(MemoryCertStore::default(), configure).await
+}
+
+async fn spawn_tls_server_with_store(
+    store: MemoryCertStore,
+    configure: impl FnOnce(&mut tunnel_server::config::Config),
+) -> (u16, tokio::task::JoinHandle<()>) {
+    let free_tcp = || {
+        std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    };
+    let mut config = tunnel_server::config::Config::default();
+    config.server.domain = "tunnel.example.com".to_string();
+    config.server.http_port = free_tcp();
+    config.server.https_port = free_tcp();
+    config.tls.enabled = true;
+    config.auth.api_key = "redirect-test-key-redirect-test-key".to_string();
+    configure(&mut config);
+
+    let http_port = config.server.http_port;
+    let server = Arc::new(TunnelServer::new(config).with_cert_store(store));
+    let handle = tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    (http_port, handle)
+}
+
+fn no_redirect_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_https_redirect_is_temporary_by_default() {
+    let (port, handle) = spawn_tls_server(|_| {}).await;
+
+    let response = no_redirect_client()
+        .get(format!("http://127.0.0.1:{}/t/ws/page?x=1", port))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 307);
+    assert_eq!(
+        response.headers()["location"],
+        "https://tunnel.example.com/t/ws/page?x=1"
+    );
+
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_https_redirect_can_be_permanent() {
+    let (port, handle) = spawn_tls_server(|config| {
+        config.server.https_redirect_permanent = true;
+    })
+    .await;
+
+    let response = no_redirect_client()
+        .get(format!("http://127.0.0.1:{}/t/ws/page", port))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 308);
+
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_https_redirect_skips_excluded_prefixes() {
+    let (port, handle) = spawn_tls_server(|config| {
+        config.server.https_redirect_exclude = vec!["/health".to_string()];
+    })
+    .await;
+    let client = no_redirect_client();
+
+    let response = client
+        .get(format!("http://127.0.0.1:{}/health", port))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let response = client
+        .get(format!("http://127.0.0.1:{}/version", port))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 307);
+
+    handle.abort();
+}
+
+/// Completes a TLS handshake with the HTTPS listener on `port`, offering only
+/// `versions` and trusting the certificate in `bundle`.
+async fn tls_handshake(
+    port: u16,
+    bundle: &CertificateBundle,
+    versions: &[&'static rustls::SupportedProtocolVersion],
+) -> std::io::Result<()> {
+    use rustls::pki_types::{pem::PemObject, CertificateDer, ServerName};
+
+    let mut roots = rustls::RootCertStore::empty();
+    roots
+        .add(CertificateDer::from_pem_slice(bundle.cert_pem.as_bytes()).unwrap())
+        .unwrap();
+    let config = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_protocol_versions(versions)
+    .unwrap()
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+
+    let stream = tokio::net::TcpStream::connect(("127.0.0.1", port)).await?;
+    tokio_rustls::TlsConnector::from(Arc::new(config))
+        .connect(ServerName::try_from("localhost").unwrap(), stream)
+        .await
+        .map(|_| ())
+}
+
+#[tokio::test]
+async fn test_tls13_only_rejects_tls12_handshake() {
+    let bundle = self_signed_bundle();
+    let store = MemoryCertStore::default();
+    store.store(&bundle).await.unwrap();
+    let https_port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let (_, handle) = spawn_tls_server_with_store(store, |config| {
+        config.server.https_port = https_port;
+        config.tls.min_version = "1.3".to_string();
+    })
+    .await;
+
+    let tls12 = tls_handshake(https_port, &bundle, &[&rustls::version::TLS12]).await;
+    assert!(tls12.is_err(), "TLS 1.2 handshake should be rejected");
+    tls_handshake(https_port, &bundle, &[&rustls::version::TLS13])
+        .await
+        .expect("TLS 1.3 handshake should succeed");
+
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_tls12_accepted_by_default() {
+    let bundle = self_signed_bundle();
+    let store = MemoryCertStore::default();
+    store.store(&bundle).await.unwrap();
+    let https_port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let (_, handle) = spawn_tls_server_with_store(store, |config| {
+        config.server.https_port = https_port;
+    })
+    .await;
+
+    tls_handshake(https_port, &bundle, &[&rustls::version::TLS12])
+        .await
+        .expect("TLS 1.2 handshake should succeed");
+
+    handle.abort();
+}
+
+/// Sends `proxy_header` and then a GET for `path` over TLS to the HTTPS
+/// listener on `port`, returning the raw response.
+async fn https_get_behind_proxy(
+    port: u16,
+    bundle: &CertificateBundle,
+    proxy_header: &[u8],
+    path: &str,
+) -> std::io::Result<String> {
+    use rustls::pki_types::{pem::PemObject, CertificateDer, ServerName};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut roots = rustls::RootCertStore::empty();
+    roots
+        .add(CertificateDer::from_pem_slice(bundle.cert_pem.as_bytes()).unwrap())
+        .unwrap();
+    let config = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .unwrap()
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+
+    let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", port)).await?;
+    stream.write_all(proxy_header).await?;
+    let mut tls = tokio_rustls::TlsConnector::from(Arc::new(config))
+        .connect(ServerName::try_from("localhost").unwrap(), stream)
+        .await?;
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        path
+    );
+    tls.write_all(request.as_bytes()).await?;
+    let mut response = Vec::new();
+    tls.read_to_end(&mut response).await?;
+    Ok(String::from_utf8_lossy(&response).into_owned())
+}
+
+#[tokio::test]
+async fn test_proxy_protocol_header_sets_client_address() {
+    let bundle = self_signed_bundle();
+    let store = MemoryCertStore::default();
+    store.store(&bundle).await.unwrap();
+    let https_port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let dir = tempfile::tempdir().unwrap();
+    let log_path = dir.path().join("access.log");
+    let configured_path = log_path.clone();
+
+    let (_, handle) = spawn_tls_server_with_store(store, |config| {
+        config.server.https_port = https_port;
+        config.server.proxy_protocol = true;
+        config.server.access_log.path = Some(configured_path);
+    })
+    .await;
+
+    // v2: PROXY command, TCP over IPv4, 203.0.113.7:4242 -> 10.0.0.1:443.
+    let mut v2 = b"\r\n\r\n\0\r\nQUIT\n\x21\x11\x00\x0c".to_vec();
+    v2.extend_from_slice(&[203, 0, 113, 7, 10, 0, 0, 1]);
+    v2.extend_from_slice(&4242u16.to_be_bytes());
+    v2.extend_from_slice(&443u16.to_be_bytes());
+    let response = https_get_behind_proxy(https_port, &bundle, &v2, "/t/lb-ws/a")
+        .await
+        .expect("request with a v2 header should be served");
+    assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+
+    let v1 = b"PROXY TCP4 198.51.100.9 10.0.0.1 5151 443\r\n";
+    let response = https_get_behind_proxy(https_port, &bundle, v1, "/t/lb-ws/b")
+        .await
+        .expect("request with a v1 header should be served");
+    assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+
+    // Without a header the connection is refused rather than trusted.
+    let direct = https_get_behind_proxy(https_port, &bundle, b"", "/t/lb-ws/c").await;
+    assert!(direct.map_or(true, |response| response.is_empty()));
+
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+    let remotes = loop {
+        let content = std::fs::read_to_string(&log_path).unwrap_or_default();
+        let remotes: Vec<String> = content
+            .lines()
+            .map(|line| {
+                let entry: serde_json::Value = serde_json::from_str(line).unwrap();
+                entry["remote"].as_str().unwrap().to_string()
+            })
+            .collect();
+        if remotes.len() >= 2 || tokio::time::Instant::now() > deadline {
+            break remotes;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    };
+    assert_eq!(remotes, ["203.0.113.7:4242", "198.51.100.9:5151"]);
+
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_quic_requires_a_shared_cipher_suite() {
+    use tunnel_client::testing::{client_config, TestServer};
+
+    let server = TestServer::start_with(|config| {
+        config.tls.cipher_suites = vec!["TLS13_AES_256_GCM_SHA384".to_string()];
+    })
+    .await
+    .unwrap();
+
+    let mut config = client_config(&server, "http://127.0.0.1:1");
+    config.tls.cipher_suites = vec!["TLS13_CHACHA20_POLY1305_SHA256".to_string()];
+    assert!(tunnel_client::connection::Connection::new(config)
+        .connect()
+        .await
+        .is_err());
+
+    let mut config = client_config(&server, "http://127.0.0.1:1");
+    config.tls.cipher_suites = vec![
+        "TLS13_CHACHA20_POLY1305_SHA256".to_string(),
+        "TLS13_AES_256_GCM_SHA384".to_string(),
+    ];
+    tunnel_client::connection::Connection::new(config)
+        .connect()
+        .await
+        .expect("client sharing a suite should connect");
+
+    server.shutdown().await;
+}
+
 async fn handle_acme_challenge(
     axum::extract::State(challenges): axum::extract::State<AcmeChallenges>,
     axum::extract::Path(token): axum::extract::Path<String>,
@@ -375,3 +788,46 @@ fn days_until_expiry(cert_pem: &str) -> Option<i64> {
 }
 
 use axum::response::IntoResponse;
+
+#[tokio::test]
+async fn test_self_signed_certificate_includes_configured_sans() {
+    use tunnel_client::testing::{client_config, TestServer};
+    use x509_parser::extensions::GeneralName;
+
+    let server = TestServer::start_with(|config| {
+        config.tls.self_signed_sans = vec!["127.0.0.1".to_string(), "dev.example.test".to_string()];
+    })
+    .await
+    .unwrap();
+
+    let config = client_config(&server, "http://127.0.0.1:1");
+    let (conn, _url) = tunnel_client::connection::Connection::new(config)
+        .connect()
+        .await
+        .expect("client should connect");
+    let certs = conn
+        .peer_identity()
+        .unwrap()
+        .downcast::<Vec<rustls::pki_types::CertificateDer<'static>>>()
+        .unwrap();
+    let parsed = x509_parser::parse_x509_certificate(&certs[0]).unwrap().1;
+    let sans: Vec<String> = parsed
+        .subject_alternative_name()
+        .unwrap()
+        .unwrap()
+        .value
+        .general_names
+        .iter()
+        .map(|name| match name {
+            GeneralName::DNSName(dns) => dns.to_string(),
+            GeneralName::IPAddress(ip) => {
+                let octets: [u8; 4] = (*ip).try_into().unwrap();
+                std::net::Ipv4Addr::from(octets).to_string()
+            }
+            other => panic!("unexpected SAN {:?}", other),
+        })
+        .collect();
+    assert_eq!(sans, ["localhost", "127.0.0.1", "dev.example.test"]);
+
+    server.shutdown().await;
+}
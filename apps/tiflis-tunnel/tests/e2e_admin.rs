@@ -0,0 +1,379 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+mod common;
+
+use common::TestEnvironment;
+use tunnel_client::connection::Connection;
+
+#[tokio::test]
+async fn test_admin_requires_api_key() {
+    let env = TestEnvironment::new().await;
+    let url = format!(
+        "http://localhost:{}/admin/workstations",
+        env.server_http_port
+    );
+
+    let client = reqwest::Client::new();
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.status(), 401);
+
+    let response = client
+        .get(&url)
+        .bearer_auth("wrong-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+}
+
+#[tokio::test]
+async fn test_admin_reports_connection_quality() {
+    let mut env = TestEnvironment::new().await;
+    env.start_client().await;
+
+    for _ in 0..5 {
+        let response = reqwest::get(env.proxy_url("health")).await.unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    let workstations: serde_json::Value = reqwest::Client::new()
+        .get(format!(
+            "http://localhost:{}/admin/workstations",
+            env.server_http_port
+        ))
+        .bearer_auth(&env.api_key)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    let entry = workstations
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|w| w["id"] == env.workstation_id.as_str())
+        .expect("Workstation should be listed");
+    assert_eq!(entry["state"], "active");
+    let quality = &entry["quality"];
+    assert!(quality["sent_packets"].as_u64().unwrap() > 0);
+    assert!(quality["cwnd"].as_u64().unwrap() > 0);
+    assert!(quality["rtt_ms"].is_u64());
+}
+
+#[tokio::test]
+async fn test_admin_lists_registration_metadata() {
+    let env = TestEnvironment::new().await;
+
+    let mut config = env.client_config();
+    config
+        .workstation
+        .metadata
+        .insert("region".to_string(), "eu-west".to_string());
+    config
+        .workstation
+        .metadata
+        .insert("hostname".to_string(), "build-box".to_string());
+    let mut connection = Connection::new(config);
+    let (_conn, _url) = connection.connect().await.expect("Failed to register");
+
+    let workstations: serde_json::Value = reqwest::Client::new()
+        .get(format!(
+            "http://localhost:{}/admin/workstations",
+            env.server_http_port
+        ))
+        .bearer_auth(&env.api_key)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    let entry = workstations
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|w| w["id"] == env.workstation_id.as_str())
+        .expect("Workstation should be listed");
+    assert_eq!(entry["metadata"]["region"], "eu-west");
+    assert_eq!(entry["metadata"]["hostname"], "build-box");
+}
+
+#[tokio::test]
+async fn test_admin_lists_and_filters_by_tags() {
+    let env = TestEnvironment::new().await;
+
+    let mut staging = env.client_config();
+    staging.workstation.tags = vec!["gpu=true".to_string(), "env=staging".to_string()];
+    let (_staging, _url) = Connection::new(staging)
+        .connect()
+        .await
+        .expect("Failed to register");
+    let mut prod = env.client_config();
+    prod.workstation.id = format!("{}-prod", env.workstation_id);
+    prod.workstation.tags = vec!["env=prod".to_string(), "gpu=true".to_string()];
+    let (_prod, _url) = Connection::new(prod)
+        .connect()
+        .await
+        .expect("Failed to register");
+
+    let list = |query: &'static str| {
+        let url = format!(
+            "http://localhost:{}/admin/workstations{}",
+            env.server_http_port, query
+        );
+        let api_key = env.api_key.clone();
+        async move {
+            let workstations: serde_json::Value = reqwest::Client::new()
+                .get(url)
+                .bearer_auth(api_key)
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            workstations
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|w| (w["id"].as_str().unwrap().to_string(), w["tags"].clone()))
+                .collect::<Vec<_>>()
+        }
+    };
+
+    let all = list("").await;
+    assert_eq!(all.len(), 2);
+    let (_, tags) = all
+        .iter()
+        .find(|(id, _)| *id == env.workstation_id)
+        .unwrap();
+    assert_eq!(*tags, serde_json::json!(["env=staging", "gpu=true"]));
+
+    let gpu = list("?tags=gpu=true").await;
+    assert_eq!(gpu.len(), 2);
+
+    let staging_gpu = list("?tags=env=staging,gpu=true").await;
+    assert_eq!(staging_gpu.len(), 1);
+    assert_eq!(staging_gpu[0].0, env.workstation_id);
+
+    assert!(list("?tags=env=dev").await.is_empty());
+
+    let selected = env
+        .server
+        .registry()
+        .find_by_tags(&["env=prod".to_string()])
+        .await;
+    assert_eq!(selected.len(), 1);
+    assert_eq!(selected[0].id, format!("{}-prod", env.workstation_id));
+}
+
+#[tokio::test]
+async fn test_debug_snapshot_shows_workstations_and_pending_requests() {
+    let mut env = TestEnvironment::new().await;
+    env.start_client().await;
+
+    let url = format!("http://localhost:{}/admin/debug", env.server_http_port);
+    let client = reqwest::Client::new();
+    assert_eq!(client.get(&url).send().await.unwrap().status(), 401);
+
+    let slow_url = env.proxy_url("slow");
+    let request = tokio::spawn(async move { reqwest::get(slow_url).await });
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+
+    let snapshot: serde_json::Value = client
+        .get(&url)
+        .bearer_auth(&env.api_key)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    let entry = snapshot["workstations"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|w| w["id"] == env.workstation_id.as_str())
+        .expect("Workstation should be listed");
+    assert_eq!(entry["state"], "active");
+    assert!(entry["last_seen_ms"].is_u64());
+
+    let pending = snapshot["pending"].as_array().unwrap();
+    assert_eq!(pending.len(), 1);
+    assert!(pending[0]["age_ms"].as_u64().unwrap() >= 200);
+    assert!(snapshot["quic_connections"].as_u64().unwrap() >= 1);
+    assert!(snapshot["runtime"]["workers"].as_u64().unwrap() >= 1);
+
+    request.abort();
+}
+
+#[tokio::test]
+async fn test_oversized_metadata_is_rejected() {
+    let env = TestEnvironment::new().await;
+
+    let mut config = env.client_config();
+    config.workstation.metadata.insert(
+        "notes".to_string(),
+        "x".repeat(tunnel_core::MAX_METADATA_BYTES),
+    );
+    let mut connection = Connection::new(config);
+    let err = connection
+        .connect()
+        .await
+        .expect_err("Registration should be rejected");
+    assert!(err.to_string().contains("metadata"), "{}", err);
+    assert!(env
+        .server
+        .registry()
+        .get(&env.workstation_id)
+        .await
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_draining_refuses_new_requests_but_finishes_streams() {
+    use futures::StreamExt;
+
+    let mut env = TestEnvironment::new().await;
+    env.start_client().await;
+    let client = reqwest::Client::new();
+    let drain_url = format!(
+        "http://localhost:{}/admin/workstations/{}/drain",
+        env.server_http_port, env.workstation_id
+    );
+
+    let response = client
+        .get(env.proxy_url("sse/slow"))
+        .header("Accept", "text/event-stream")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let mut stream = response.bytes_stream();
+    let first = stream.next().await.unwrap().unwrap();
+    assert!(String::from_utf8_lossy(&first).contains("slow_event1"));
+
+    let response = client.post(&drain_url).send().await.unwrap();
+    assert_eq!(response.status(), 401);
+    let response = client
+        .post(&drain_url)
+        .bearer_auth(&env.api_key)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 204);
+
+    let response = client
+        .get(env.proxy_url("health"))
+        .header("Accept", "application/json")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 503);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "WORKSTATION_DRAINING");
+
+    let mut received = String::from_utf8_lossy(&first).to_string();
+    while let Some(chunk) = tokio::time::timeout(std::time::Duration::from_secs(5), stream.next())
+        .await
+        .expect("SSE stream stalled while draining")
+    {
+        received.push_str(&String::from_utf8_lossy(&chunk.unwrap()));
+    }
+    assert!(received.contains("slow_event5"));
+
+    let workstations: serde_json::Value = client
+        .get(format!(
+            "http://localhost:{}/admin/workstations",
+            env.server_http_port
+        ))
+        .bearer_auth(&env.api_key)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(workstations[0]["draining"], true);
+
+    let response = client
+        .delete(&drain_url)
+        .bearer_auth(&env.api_key)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 204);
+    let response = reqwest::get(env.proxy_url("health")).await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    let response = client
+        .post(format!(
+            "http://localhost:{}/admin/workstations/unknown-ws/drain",
+            env.server_http_port
+        ))
+        .bearer_auth(&env.api_key)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn test_revalidate_after_key_rotation_evicts_workstations() {
+    let env = TestEnvironment::new().await;
+    let mut connection = Connection::new(env.client_config());
+    let (conn, _url) = connection.connect().await.expect("Failed to register");
+
+    let client = reqwest::Client::new();
+    let url = format!("http://localhost:{}/admin/revalidate", env.server_http_port);
+    let evicted: Vec<String> = client
+        .post(&url)
+        .bearer_auth(&env.api_key)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(evicted.is_empty());
+
+    let rotated = "rotated-api-key-minimum-32-characters";
+    let mut config = tunnel_server::config::Config::default();
+    config.auth.api_key = rotated.to_string();
+    env.server.reload(&config);
+
+    let response = client
+        .post(&url)
+        .bearer_auth(&env.api_key)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+    let evicted: Vec<String> = client
+        .post(&url)
+        .bearer_auth(rotated)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(evicted, vec![env.workstation_id.clone()]);
+
+    let err = tokio::time::timeout(std::time::Duration::from_secs(5), conn.closed())
+        .await
+        .expect("Evicted connection should be closed");
+    assert!(err.to_string().contains("api key revoked"), "{}", err);
+
+    let mut config = env.client_config();
+    config.auth.api_key = rotated.to_string();
+    Connection::new(config)
+        .connect()
+        .await
+        .expect("Rotated key should register");
+}
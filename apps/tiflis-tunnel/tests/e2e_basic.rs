@@ -4,6 +4,9 @@
 mod common;
 
 use common::TestEnvironment;
+use tunnel_client::connection::Connection;
+use tunnel_server::config::Config as ServerConfig;
+use tunnel_server::server::TunnelServer;
 
 #[tokio::test]
 async fn test_server_starts() {
@@ -15,6 +18,34 @@ async fn test_server_starts() {
     assert_eq!(response.status(), 200);
 }
 
+#[tokio::test]
+async fn test_version_endpoint_reports_crate_version() {
+    let env = TestEnvironment::new().await;
+
+    let version_url = format!("http://localhost:{}/version", env.server_http_port);
+    let response = reqwest::get(&version_url).await.expect("Failed to connect");
+    assert_eq!(response.status(), 200);
+
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+    assert!(!body["git_sha"].as_str().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_register_response_includes_server_version() {
+    let env = TestEnvironment::new().await;
+
+    let mut connection = Connection::new(env.client_config());
+    connection.connect().await.expect("Failed to register");
+
+    let version = connection.server_version().expect("No server version");
+    assert!(
+        version.starts_with(env!("CARGO_PKG_VERSION")),
+        "{}",
+        version
+    );
+}
+
 #[tokio::test]
 async fn test_mock_server_works() {
     let env = TestEnvironment::new().await;
@@ -115,3 +146,247 @@ async fn test_long_running_session() {
         tokio::time::sleep(std::time::Duration::from_millis(50)).await;
     }
 }
+
+async fn free_port() -> u16 {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    listener.local_addr().unwrap().port()
+}
+
+fn check_config(http_port: u16, https_port: u16) -> ServerConfig {
+    let mut config = ServerConfig::default();
+    config.server.domain = "localhost".to_string();
+    config.server.http_port = http_port;
+    config.server.https_port = https_port;
+    config.tls.enabled = false;
+    config
+}
+
+#[tokio::test]
+async fn test_server_check_binds_free_ports() {
+    let config = check_config(free_port().await, free_port().await);
+    TunnelServer::new(config)
+        .check()
+        .await
+        .expect("Check should pass on free ports");
+}
+
+#[tokio::test]
+async fn test_server_check_fails_when_port_in_use() {
+    let occupied = tokio::net::TcpListener::bind("0.0.0.0:0").await.unwrap();
+    let port = occupied.local_addr().unwrap().port();
+
+    let config = check_config(port, free_port().await);
+    let err = TunnelServer::new(config)
+        .check()
+        .await
+        .expect_err("Check should fail while the HTTP port is taken");
+    assert!(err.to_string().contains("HTTP port"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_server_check_fails_on_unparsable_bind_address() {
+    let mut config = check_config(free_port().await, free_port().await);
+    config.server.quic_bind_address = Some("localhost".to_string());
+    let err = TunnelServer::new(config)
+        .check()
+        .await
+        .expect_err("Check should not fall back to another address");
+    assert!(err.to_string().contains("'localhost'"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_quic_listens_on_its_own_port() {
+    let https_port = free_port().await;
+    let quic_port = std::net::UdpSocket::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    assert_ne!(https_port, quic_port);
+
+    let mut config = check_config(free_port().await, https_port);
+    config.server.quic_port = Some(quic_port);
+    config.auth.api_key = "separate-quic-port-key-separate-quic-port".to_string();
+    let server = std::sync::Arc::new(TunnelServer::new(config));
+    let run = server.clone();
+    let server_handle = tokio::spawn(async move {
+        let _ = run.run().await;
+    });
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    let mut client_config = tunnel_client::config::Config::default();
+    client_config.server.address = format!("127.0.0.1:{}", quic_port);
+    client_config.auth.api_key = "separate-quic-port-key-separate-quic-port".to_string();
+    client_config.workstation.id = format!("test-ws-{}", rand::random::<u16>());
+    client_config.session.ticket_path =
+        std::env::temp_dir().join(format!("tunnel-test-{}.ticket", rand::random::<u32>()));
+    Connection::new(client_config.clone())
+        .connect()
+        .await
+        .expect("Client should register over the QUIC port");
+    assert!(server
+        .registry()
+        .get(&client_config.workstation.id)
+        .await
+        .is_some());
+
+    // QUIC left the HTTPS port number alone.
+    std::net::UdpSocket::bind(("0.0.0.0", https_port))
+        .expect("QUIC should not be bound to the HTTPS port");
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_dual_stack_bind_accepts_ipv4_and_ipv6() {
+    let mut config = check_config(0, 0);
+    config.server.bind_address = "::".to_string();
+    config.server.quic_port = Some(0);
+    config.auth.api_key = "dual-stack-bind-key-dual-stack-bind-key".to_string();
+    let server = std::sync::Arc::new(TunnelServer::new(config));
+    let run = server.clone();
+    let server_handle = tokio::spawn(async move {
+        let _ = run.run().await;
+    });
+    let http_port = server.http_addr().await.port();
+    let quic_port = server.quic_addr().await.port();
+
+    for host in ["127.0.0.1", "[::1]"] {
+        let response = reqwest::get(format!("http://{}:{}/health", host, http_port))
+            .await
+            .unwrap_or_else(|e| panic!("HTTP over {} failed: {}", host, e));
+        assert_eq!(response.status(), 200, "{}", host);
+
+        let mut client_config = tunnel_client::config::Config::default();
+        client_config.server.address = format!("{}:{}", host, quic_port);
+        client_config.auth.api_key = "dual-stack-bind-key-dual-stack-bind-key".to_string();
+        client_config.workstation.id = format!("test-ws-{}", rand::random::<u16>());
+        client_config.session.ticket_path =
+            std::env::temp_dir().join(format!("tunnel-test-{}.ticket", rand::random::<u32>()));
+        Connection::new(client_config.clone())
+            .connect()
+            .await
+            .unwrap_or_else(|e| panic!("QUIC over {} failed: {}", host, e));
+        assert!(server
+            .registry()
+            .get(&client_config.workstation.id)
+            .await
+            .is_some());
+    }
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_client_check_completes_handshake_without_registering() {
+    let env = TestEnvironment::new().await;
+
+    Connection::new(env.client_config())
+        .check()
+        .await
+        .expect("Check should reach the server");
+
+    assert!(env
+        .server
+        .registry()
+        .get(&env.workstation_id)
+        .await
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_client_connects_to_ipv6_only_server() {
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let key = rustls::pki_types::PrivateKeyDer::Pkcs8(rustls::pki_types::PrivatePkcs8KeyDer::from(
+        cert.key_pair.serialize_der(),
+    ));
+    let mut crypto = rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(vec![cert.cert.der().clone()], key)
+        .unwrap();
+    crypto.alpn_protocols = vec![b"tiflis-tunnel".to_vec()];
+    let server_config = quinn::ServerConfig::with_crypto(std::sync::Arc::new(
+        quinn::crypto::rustls::QuicServerConfig::try_from(crypto).unwrap(),
+    ));
+
+    let endpoint = quinn::Endpoint::server(server_config, "[::1]:0".parse().unwrap())
+        .expect("Failed to bind [::1]");
+    let addr = endpoint.local_addr().unwrap();
+    let accept = tokio::spawn(async move {
+        let connection = endpoint.accept().await.unwrap().await.unwrap();
+        connection.closed().await;
+    });
+
+    let mut config = TestEnvironment::new().await.client_config();
+    config.server.address = addr.to_string();
+    config.server.prefer_ipv6 = true;
+
+    Connection::new(config)
+        .check()
+        .await
+        .expect("Client should reach a server listening on ::1");
+
+    tokio::time::timeout(std::time::Duration::from_secs(5), accept)
+        .await
+        .expect("Server should see the connection")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_quic_congestion_controller_is_configurable() {
+    use quinn::congestion::{Bbr, NewReno};
+    use tunnel_client::testing::{client_config, TestServer};
+    use tunnel_core::quic::CongestionController;
+
+    let server = TestServer::start_with(|config| {
+        config.quic.congestion_controller = CongestionController::Bbr;
+    })
+    .await
+    .unwrap();
+    let mut config = client_config(&server, "http://127.0.0.1:1");
+    config.quic.congestion_controller = CongestionController::NewReno;
+    let workstation_id = config.workstation.id.clone();
+
+    let (conn, _url) = Connection::new(config)
+        .connect()
+        .await
+        .expect("client should connect");
+    assert!(conn.congestion_state().into_any().is::<NewReno>());
+
+    let info = server.server.registry().get(&workstation_id).await.unwrap();
+    assert!(info.connection.congestion_state().into_any().is::<Bbr>());
+
+    server.shutdown().await;
+}
+
+#[test]
+fn test_unknown_congestion_controller_rejected_at_load() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    std::fs::write(&path, "[quic]\ncongestion_controller = \"vegas\"\n").unwrap();
+
+    let err = ServerConfig::load(Some(path.clone())).unwrap_err();
+    assert!(err.to_string().contains("vegas"), "{}", err);
+    let err = tunnel_client::config::Config::load(Some(path)).unwrap_err();
+    assert!(err.to_string().contains("vegas"), "{}", err);
+
+    // The only test here that loads the environment, so the variable cannot
+    // leak into another.
+    std::env::set_var("QUIC_CONGESTION_CONTROLLER", "vegas");
+    let server_err = ServerConfig::load(None).unwrap_err();
+    let client_err = tunnel_client::config::Config::load(None).unwrap_err();
+    std::env::remove_var("QUIC_CONGESTION_CONTROLLER");
+    for err in [server_err, client_err] {
+        let err = err.to_string();
+        assert!(err.contains("QUIC_CONGESTION_CONTROLLER"), "{}", err);
+        assert!(err.contains("expected cubic, bbr or newreno"), "{}", err);
+    }
+
+    assert_eq!(
+        "bbr".parse::<tunnel_core::quic::CongestionController>(),
+        Ok(tunnel_core::quic::CongestionController::Bbr)
+    );
+    assert!("vegas"
+        .parse::<tunnel_core::quic::CongestionController>()
+        .is_err());
+}
@@ -0,0 +1,417 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+mod common;
+
+use common::TestEnvironment;
+use tunnel_client::handle::TunnelClientHandle;
+
+async fn get_json(url: &str) -> (u16, serde_json::Value) {
+    let response = reqwest::Client::new()
+        .get(url)
+        .header("accept", "application/json")
+        .send()
+        .await
+        .expect("Failed to make request");
+    let status = response.status().as_u16();
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "application/json"
+    );
+    (status, response.json().await.expect("Expected a JSON body"))
+}
+
+fn assert_error(body: &serde_json::Value, code: &str) {
+    assert_eq!(body["error"]["code"], code, "{}", body);
+    assert!(body["error"]["message"]
+        .as_str()
+        .is_some_and(|m| !m.is_empty()));
+}
+
+#[tokio::test]
+async fn test_unknown_workstation_returns_json_error() {
+    let env = TestEnvironment::new().await;
+
+    let (status, body) = get_json(&env.proxy_url("health")).await;
+    assert_eq!(status, 404);
+    assert_error(&body, "WORKSTATION_NOT_FOUND");
+}
+
+#[tokio::test]
+async fn test_unknown_route_returns_json_error() {
+    let env = TestEnvironment::new().await;
+
+    let url = format!("http://localhost:{}/nope", env.server_http_port);
+    let (status, body) = get_json(&url).await;
+    assert_eq!(status, 404);
+    assert_error(&body, "ROUTE_NOT_FOUND");
+}
+
+#[tokio::test]
+async fn test_workstation_timeout_returns_json_error() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let mut env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.reliability.request_timeout = 1;
+    })
+    .await;
+    env.start_client().await;
+
+    let (status, body) = get_json(&env.proxy_url("slow")).await;
+    assert_eq!(status, 504);
+    assert_error(&body, "WORKSTATION_TIMEOUT");
+}
+
+#[tokio::test]
+async fn test_unreachable_backend_returns_json_error() {
+    let env = TestEnvironment::new().await;
+
+    let mut config = env.client_config();
+    config.workstation.local_address = "http://127.0.0.1:1".to_string();
+    let (_handle, _url) = TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let (status, body) = get_json(&env.proxy_url("health")).await;
+    assert_eq!(status, 502);
+    assert_error(&body, "WORKSTATION_UNREACHABLE");
+}
+
+#[tokio::test]
+async fn test_quota_exceeded_returns_json_error() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let mut env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.limits.max_bytes_per_workstation = 1_000;
+    })
+    .await;
+    env.start_client().await;
+
+    reqwest::get(env.proxy_url("large/2000"))
+        .await
+        .expect("Failed to make request");
+
+    let (status, body) = get_json(&env.proxy_url("health")).await;
+    assert_eq!(status, 429);
+    assert_error(&body, "QUOTA_EXCEEDED");
+}
+
+#[tokio::test]
+async fn test_rate_limited_returns_json_error() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let mut env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.limits.requests_per_second = 1;
+        config.limits.burst = 1;
+    })
+    .await;
+    env.start_client().await;
+
+    reqwest::get(env.proxy_url("health"))
+        .await
+        .expect("Failed to make request");
+
+    let response = reqwest::Client::new()
+        .get(env.proxy_url("health"))
+        .header("accept", "application/json")
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(response.status(), 429);
+    assert!(response.headers().get("retry-after").is_some());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_error(&body, "RATE_LIMITED");
+}
+
+#[tokio::test]
+async fn test_errors_stay_bare_without_json_accept() {
+    let env = TestEnvironment::new().await;
+
+    let response = reqwest::get(env.proxy_url("health"))
+        .await
+        .expect("Failed to make request");
+    assert_eq!(response.status(), 404);
+    assert!(response.text().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_workstation_errors_are_relayed_unchanged() {
+    let mut env = TestEnvironment::new().await;
+    env.start_client().await;
+
+    let response = reqwest::Client::new()
+        .get(env.proxy_url("error"))
+        .header("accept", "application/json")
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(response.status(), 500);
+    let body = response.text().await.unwrap();
+    assert!(!body.contains("\"error\":{\"code\""), "{}", body);
+}
+
+#[tokio::test]
+async fn test_failing_backend_trips_circuit_breaker() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.reliability.circuit_breaker_failures = 3;
+        config.reliability.circuit_breaker_window = 30;
+        config.reliability.circuit_breaker_cooldown = 2;
+    })
+    .await;
+
+    // Nothing listens on the backend port until the breaker has tripped.
+    let backend_port = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let mut config = env.client_config();
+    config.workstation.local_address = format!("http://127.0.0.1:{}", backend_port);
+    let (_handle, _url) = TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    for _ in 0..3 {
+        let (status, body) = get_json(&env.proxy_url("health")).await;
+        assert_eq!(status, 502);
+        assert_error(&body, "WORKSTATION_UNREACHABLE");
+    }
+
+    let response = reqwest::Client::new()
+        .get(env.proxy_url("health"))
+        .header("accept", "application/json")
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(response.status(), 503);
+    assert!(response.headers().get("retry-after").is_some());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_error(&body, "CIRCUIT_OPEN");
+
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", backend_port))
+        .await
+        .unwrap();
+    let app = axum::Router::new().route("/health", axum::routing::get(|| async { "OK" }));
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    // Still open: the backend is not asked again until the cooldown is over.
+    let response = reqwest::get(env.proxy_url("health")).await.unwrap();
+    assert_eq!(response.status(), 503);
+
+    tokio::time::sleep(std::time::Duration::from_millis(2100)).await;
+    let response = reqwest::get(env.proxy_url("health")).await.unwrap();
+    assert_eq!(
+        response.status(),
+        200,
+        "half-open probe should reach the backend"
+    );
+    let response = reqwest::get(env.proxy_url("health")).await.unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn test_unhealthy_backend_returns_json_error() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    let env = TestEnvironment::new().await;
+
+    let healthy = Arc::new(AtomicBool::new(true));
+    let probe = healthy.clone();
+    let app = axum::Router::new()
+        .route(
+            "/healthz",
+            axum::routing::get(move || {
+                let probe = probe.clone();
+                async move {
+                    if probe.load(Ordering::SeqCst) {
+                        axum::http::StatusCode::OK
+                    } else {
+                        axum::http::StatusCode::INTERNAL_SERVER_ERROR
+                    }
+                }
+            }),
+        )
+        .route("/hello", axum::routing::get(|| async { "hello" }));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let mut config = env.client_config();
+    config.workstation.local_address = format!("http://127.0.0.1:{}", backend_port);
+    config.workstation.health_check_path = Some("/healthz".to_string());
+    config.workstation.health_check_interval = 1;
+    let client = tokio::spawn(async move {
+        let _ = tunnel_client::client::TunnelClient::new(config).run().await;
+    });
+    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+
+    let response = reqwest::get(env.proxy_url("hello")).await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    healthy.store(false, Ordering::SeqCst);
+    tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+    let (status, body) = get_json(&env.proxy_url("hello")).await;
+    assert_eq!(status, 503);
+    assert_error(&body, "BACKEND_UNHEALTHY");
+
+    healthy.store(true, Ordering::SeqCst);
+    tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+    let response = reqwest::get(env.proxy_url("hello")).await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    client.abort();
+}
+
+#[tokio::test]
+async fn test_oversized_request_headers_return_json_error() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let mut env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.limits.max_header_bytes = 4 * 1024;
+    })
+    .await;
+    env.start_client().await;
+
+    let response = reqwest::Client::new()
+        .get(env.proxy_url("health"))
+        .header("accept", "application/json")
+        .header("x-big", "a".repeat(8 * 1024))
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(response.status(), 431);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_error(&body, "HEADERS_TOO_LARGE");
+
+    let response = reqwest::get(env.proxy_url("health")).await.unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn test_oversized_response_headers_return_bad_gateway() {
+    let env = TestEnvironment::new().await;
+
+    let app = axum::Router::new()
+        .route(
+            "/big",
+            axum::routing::get(|| async { ([("x-big", "a".repeat(8 * 1024))], "hello") }),
+        )
+        .route("/small", axum::routing::get(|| async { "hello" }));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let mut config = env.client_config();
+    config.workstation.local_address = format!("http://127.0.0.1:{}", backend_port);
+    config.limits.max_header_bytes = 4 * 1024;
+    let (_handle, _url) = TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let response = reqwest::get(env.proxy_url("big")).await.unwrap();
+    assert_eq!(response.status(), 502);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "local backend response headers too large"
+    );
+
+    let response = reqwest::get(env.proxy_url("small")).await.unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn test_disallowed_method_returns_405_with_allow() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let mut env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.server.allowed_methods = vec!["GET".to_string(), "HEAD".to_string()];
+    })
+    .await;
+
+    // Refused before the workstation is looked up, so no client is needed.
+    let response = reqwest::Client::new()
+        .post(env.proxy_url("health"))
+        .header("accept", "application/json")
+        .body("payload")
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(response.status(), 405);
+    assert_eq!(response.headers().get("allow").unwrap(), "GET, HEAD");
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_error(&body, "METHOD_NOT_ALLOWED");
+
+    env.start_client().await;
+    let response = reqwest::get(env.proxy_url("health"))
+        .await
+        .expect("Failed to make request");
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "OK");
+
+    let response = reqwest::Client::new()
+        .delete(env.proxy_url("health"))
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(response.status(), 405);
+}
+
+async fn get_retry_after(url: &str) -> (u16, Option<u64>, serde_json::Value) {
+    let response = reqwest::Client::new()
+        .get(url)
+        .header("accept", "application/json")
+        .send()
+        .await
+        .expect("Failed to make request");
+    let status = response.status().as_u16();
+    let retry_after = response
+        .headers()
+        .get("retry-after")
+        .map(|v| v.to_str().unwrap().parse().unwrap());
+    (status, retry_after, response.json().await.unwrap())
+}
+
+#[tokio::test]
+async fn test_unknown_workstation_can_return_503() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.server.unknown_workstation_status = 503;
+    })
+    .await;
+
+    let (status, retry_after, body) = get_retry_after(&env.proxy_url("health")).await;
+    assert_eq!(status, 503);
+    assert_eq!(retry_after, Some(5));
+    assert_error(&body, "WORKSTATION_UNAVAILABLE");
+}
+
+#[tokio::test]
+async fn test_workstation_in_grace_period_returns_503() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.reliability.request_timeout = 1;
+        config.reliability.grace_period = 30;
+    })
+    .await;
+    let mut events = env.server.registry().subscribe();
+    let mut connection = tunnel_client::connection::Connection::new(env.client_config());
+    let (conn, _url) = connection.connect().await.expect("Failed to register");
+    conn.close(0u32.into(), b"bye");
+    tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while let Ok(event) = events.recv().await {
+            if event.state == tunnel_server::registry::RegistryEventKind::Reconnecting {
+                break;
+            }
+        }
+    })
+    .await
+    .expect("Workstation never started reconnecting");
+
+    // Held for the request timeout, then refused with the grace time left.
+    let (status, retry_after, body) = get_retry_after(&env.proxy_url("health")).await;
+    assert_eq!(status, 503);
+    assert!(
+        retry_after.is_some_and(|secs| (1..=30).contains(&secs)),
+        "{:?}",
+        retry_after
+    );
+    assert_error(&body, "WORKSTATION_RECONNECTING");
+}
@@ -0,0 +1,51 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+mod common;
+
+use bytes::Bytes;
+use common::TestEnvironment;
+use http_body_util::{BodyExt, Full};
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+
+fn grpc_frame(message: &[u8]) -> Bytes {
+    let mut frame = Vec::with_capacity(5 + message.len());
+    frame.push(0);
+    frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    frame.extend_from_slice(message);
+    Bytes::from(frame)
+}
+
+#[tokio::test]
+async fn test_grpc_unary_echo_passthrough() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let mut env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.h2.path_prefixes = vec!["/echo.Echo/".to_string()];
+    })
+    .await;
+    env.start_client().await;
+
+    let client: Client<_, Full<Bytes>> = Client::builder(TokioExecutor::new())
+        .http2_only(true)
+        .build_http();
+
+    let request_body = grpc_frame(b"hello over h2");
+    let request = hyper::Request::post(env.proxy_url("echo.Echo/Say"))
+        .header("content-type", "application/grpc")
+        .header("te", "trailers")
+        .body(Full::new(request_body.clone()))
+        .unwrap();
+
+    let response = client.request(request).await.expect("Request failed");
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "application/grpc"
+    );
+
+    let collected = response.into_body().collect().await.unwrap();
+    let trailers = collected.trailers().cloned().expect("Expected trailers");
+    assert_eq!(trailers.get("grpc-status").unwrap(), "0");
+    assert_eq!(collected.to_bytes(), request_body);
+}
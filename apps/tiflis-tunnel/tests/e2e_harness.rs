@@ -0,0 +1,135 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+use axum::{routing::get, Router};
+use std::net::{TcpListener, UdpSocket};
+use tunnel_client::testing::{TestHarness, TestServer};
+
+/// Serves `GET /hello` on a loopback port and returns its base URL.
+async fn spawn_backend() -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = Router::new().route("/hello", get(|| async { "hello from backend" }));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+#[tokio::test]
+async fn test_harness_proxies_to_local_service() {
+    let harness = TestHarness::start(&spawn_backend().await)
+        .await
+        .expect("Harness failed to start");
+    assert!(harness
+        .tunnel_url
+        .ends_with(&format!("/t/{}", harness.workstation_id)));
+
+    // No sleep: `start` returns once the workstation is registered.
+    let response = reqwest::get(harness.proxy_url("hello")).await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "hello from backend");
+
+    harness.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_servers_get_their_own_ports() {
+    let first = TestServer::start().await.expect("First server failed");
+    let second = TestServer::start().await.expect("Second server failed");
+    assert_ne!(first.http_addr, second.http_addr);
+    assert_ne!(first.quic_addr, second.quic_addr);
+
+    for server in [&first, &second] {
+        let response = reqwest::get(server.url("health")).await.unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    first.shutdown().await;
+    second.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_harness_shutdown_releases_ports() {
+    let harness = TestHarness::start(&spawn_backend().await)
+        .await
+        .expect("Harness failed to start");
+    let http_addr = harness.server.http_addr;
+    let quic_addr = harness.server.quic_addr;
+    let proxy_url = harness.proxy_url("hello");
+
+    harness.shutdown().await;
+
+    let fresh_client = reqwest::Client::new();
+    assert!(fresh_client.get(&proxy_url).send().await.is_err());
+    TcpListener::bind(("0.0.0.0", http_addr.port())).expect("HTTP port still bound");
+    UdpSocket::bind(("0.0.0.0", quic_addr.port())).expect("QUIC port still bound");
+}
+
+#[tokio::test]
+async fn test_harness_configures_server_and_client() {
+    let harness = TestHarness::start_with(
+        &spawn_backend().await,
+        |server| server.auth.api_key = "another-api-key-minimum-32-characters".to_string(),
+        |client| client.workstation.id = "harness-ws".to_string(),
+    )
+    .await
+    .expect("Harness failed to start");
+    assert_eq!(harness.workstation_id, "harness-ws");
+    assert_eq!(
+        harness.server.api_key,
+        "another-api-key-minimum-32-characters"
+    );
+
+    let response = reqwest::get(harness.proxy_url("hello")).await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    harness.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_sequential_stream_ids_reach_the_workstation() {
+    use tunnel_core::{codec, quic, HttpResponseMessage, Message};
+    use tunnel_server::stream_id::SequentialStreamIds;
+
+    let server = TestServer::start_custom(
+        |_| {},
+        |server| server.with_stream_ids(SequentialStreamIds::new(1)),
+    )
+    .await
+    .expect("Server failed to start");
+    let config = tunnel_client::testing::client_config(&server, "http://127.0.0.1:1");
+    let workstation_id = config.workstation.id.clone();
+    let (conn, _url) = tunnel_client::connection::Connection::new(config)
+        .connect()
+        .await
+        .expect("Failed to register");
+
+    // Stands in for the client: answers each request with the id it carried.
+    tokio::spawn(async move {
+        while let Ok((mut send, mut recv)) = conn.accept_bi().await {
+            let Ok(Message::HttpRequest(request)) = quic::recv_message(&mut recv).await else {
+                continue;
+            };
+            let response = Message::HttpResponse(HttpResponseMessage {
+                stream_id: request.stream_id,
+                status: 200,
+                headers: Default::default(),
+                body: Some(codec::encode_body(request.stream_id.to_string().as_bytes())),
+            });
+            quic::send_message(&mut send, &response).await.unwrap();
+            let _ = send.finish();
+        }
+    });
+
+    for expected in [
+        "00000000-0000-0000-0000-000000000001",
+        "00000000-0000-0000-0000-000000000002",
+    ] {
+        let response = reqwest::get(server.proxy_url(&workstation_id, "id"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.text().await.unwrap(), expected);
+    }
+
+    server.shutdown().await;
+}
@@ -3,7 +3,7 @@
 
 mod common;
 
-use common::TestEnvironment;
+use common::{LogBuffer, TestEnvironment};
 
 #[tokio::test]
 async fn test_http_get_request() {
@@ -86,6 +86,21 @@ async fn test_http_large_payload() {
     assert!(body.contains(&large_payload[..100]));
 }
 
+#[tokio::test]
+async fn test_http_response_over_legacy_frame_limit() {
+    let mut env = TestEnvironment::new().await;
+    env.start_client().await;
+
+    let size = 12 * 1024 * 1024;
+    let response = reqwest::get(env.proxy_url(&format!("large/{}", size)))
+        .await
+        .expect("Failed to make request");
+
+    assert_eq!(response.status(), 200);
+    let body = response.bytes().await.unwrap();
+    assert_eq!(body.len(), size);
+}
+
 #[tokio::test]
 async fn test_http_concurrent_requests() {
     let mut env = TestEnvironment::new().await;
@@ -143,3 +158,1577 @@ async fn test_http_request_timeout_with_client() {
 
     assert!(result.is_err() || result.unwrap().status() == 504);
 }
+
+#[tokio::test]
+async fn test_response_headers_added_and_removed() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let mut env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        let headers = &mut config.server.response_headers;
+        headers
+            .add
+            .insert("X-Frame-Options".to_string(), "DENY".to_string());
+        headers.add.insert(
+            "Strict-Transport-Security".to_string(),
+            "max-age=31536000".to_string(),
+        );
+        headers.remove.push("X-Powered-By".to_string());
+    })
+    .await;
+    env.start_client().await;
+
+    let response = reqwest::get(env.proxy_url("headers"))
+        .await
+        .expect("Failed to make request");
+
+    assert_eq!(response.status(), 200);
+    let headers = response.headers();
+    assert_eq!(headers.get_all("x-frame-options").iter().count(), 1);
+    assert_eq!(headers["x-frame-options"], "DENY");
+    assert_eq!(headers["strict-transport-security"], "max-age=31536000");
+    assert!(headers.get("x-powered-by").is_none());
+}
+
+fn cors_config(config: &mut tunnel_server::config::Config) {
+    let cors = &mut config.server.cors;
+    cors.enabled = true;
+    cors.allowed_origins = vec!["https://app.example.com".to_string()];
+    cors.allow_credentials = true;
+}
+
+#[tokio::test]
+async fn test_cors_preflight_answered_at_edge() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let mut env = TestEnvironment::new_with_server_config(&workstation_id, cors_config).await;
+    env.start_client().await;
+
+    let client = reqwest::Client::new();
+    let response = client
+        .request(reqwest::Method::OPTIONS, env.proxy_url("api/items"))
+        .header("origin", "https://app.example.com")
+        .header("access-control-request-method", "PUT")
+        .header("access-control-request-headers", "content-type, x-token")
+        .send()
+        .await
+        .expect("Failed to make request");
+
+    assert_eq!(response.status(), 204);
+    let headers = response.headers();
+    assert_eq!(
+        headers["access-control-allow-origin"],
+        "https://app.example.com"
+    );
+    assert!(headers["access-control-allow-methods"]
+        .to_str()
+        .unwrap()
+        .contains("PUT"));
+    assert_eq!(
+        headers["access-control-allow-headers"],
+        "content-type, x-token"
+    );
+    assert_eq!(headers["access-control-allow-credentials"], "true");
+
+    let info = env
+        .server
+        .registry()
+        .get(&env.workstation_id)
+        .await
+        .unwrap();
+    assert_eq!(
+        info.traffic.bytes_out(),
+        0,
+        "preflight reached the workstation"
+    );
+
+    let response = client
+        .request(reqwest::Method::OPTIONS, env.proxy_url("api/items"))
+        .header("origin", "https://evil.example.com")
+        .header("access-control-request-method", "PUT")
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(response.status(), 204);
+    assert!(response
+        .headers()
+        .get("access-control-allow-origin")
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_cors_headers_added_to_proxied_response() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let mut env = TestEnvironment::new_with_server_config(&workstation_id, cors_config).await;
+    env.start_client().await;
+
+    let client = reqwest::Client::new();
+    let response = client
+        .get(env.proxy_url("api/items"))
+        .header("origin", "https://app.example.com")
+        .send()
+        .await
+        .expect("Failed to make request");
+
+    assert_eq!(response.status(), 200);
+    let headers = response.headers();
+    assert_eq!(
+        headers["access-control-allow-origin"],
+        "https://app.example.com"
+    );
+    assert_eq!(headers["access-control-allow-credentials"], "true");
+    assert_eq!(response.text().await.unwrap(), "API response for: items");
+
+    let response = client
+        .get(env.proxy_url("api/items"))
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert!(response
+        .headers()
+        .get("access-control-allow-origin")
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_host_header_selects_local_backend() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let api_port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let app =
+            axum::Router::new().route("/health", axum::routing::get(|| async { "api backend" }));
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let env = TestEnvironment::new().await;
+    let mut config = env.client_config();
+    config
+        .workstation
+        .host_backends
+        .insert("api".to_string(), format!("http://127.0.0.1:{}", api_port));
+    let (_handle, _url) = tunnel_client::handle::TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let client = reqwest::Client::new();
+    let api = client
+        .get(env.proxy_url("health"))
+        .header("host", "api.ws.example.com")
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(api.text().await.unwrap(), "api backend");
+
+    let fallback = client
+        .get(env.proxy_url("health"))
+        .header("host", "app.ws.example.com")
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(fallback.text().await.unwrap(), "OK");
+}
+
+#[tokio::test]
+async fn test_fallback_backend_serves_when_primary_is_down() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let down_port = listener.local_addr().unwrap().port();
+    drop(listener);
+
+    let env = TestEnvironment::new().await;
+    let mut config = env.client_config();
+    let standby = config.workstation.local_address.clone();
+    config.workstation.local_address = format!("http://127.0.0.1:{}", down_port);
+    config.workstation.fallback_addresses = vec![standby];
+    let (_handle, _url) = tunnel_client::handle::TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let response = reqwest::Client::new()
+        .post(env.proxy_url("echo"))
+        .body("test payload")
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(response.status(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("Echo: test payload"));
+}
+
+#[tokio::test]
+async fn test_route_rules_select_local_backend() {
+    use tunnel_client::config::RouteRule;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let static_port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let app = axum::Router::new().fallback(|| async { "static backend" });
+        axum::serve(listener, app).await.unwrap();
+    });
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let api_port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let app = axum::Router::new().fallback(|| async { "api backend" });
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let env = TestEnvironment::new().await;
+    let mut config = env.client_config();
+    config.workstation.routes = vec![
+        RouteRule {
+            accept: Some("text/html".to_string()),
+            backend: format!("http://127.0.0.1:{}", static_port),
+            ..Default::default()
+        },
+        RouteRule {
+            path: Some("*.css".to_string()),
+            backend: format!("http://127.0.0.1:{}", static_port),
+            ..Default::default()
+        },
+        RouteRule {
+            method: Some("post".to_string()),
+            content_type: Some("application/json".to_string()),
+            backend: format!("http://127.0.0.1:{}", api_port),
+            ..Default::default()
+        },
+        RouteRule {
+            accept: Some("application/json".to_string()),
+            backend: format!("http://127.0.0.1:{}", api_port),
+            ..Default::default()
+        },
+    ];
+    let (_handle, _url) = tunnel_client::handle::TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let client = reqwest::Client::new();
+    let page = client
+        .get(env.proxy_url("dashboard"))
+        .header("accept", "text/html,application/xhtml+xml;q=0.9,*/*;q=0.8")
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(page.text().await.unwrap(), "static backend");
+
+    let stylesheet = client
+        .get(env.proxy_url("assets/app.css?v=3"))
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(stylesheet.text().await.unwrap(), "static backend");
+
+    let api = client
+        .get(env.proxy_url("dashboard"))
+        .header("accept", "application/json")
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(api.text().await.unwrap(), "api backend");
+
+    let upload = client
+        .post(env.proxy_url("items"))
+        .header("content-type", "application/json; charset=utf-8")
+        .body("{}")
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(upload.text().await.unwrap(), "api backend");
+
+    let fallback = client
+        .get(env.proxy_url("health"))
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(fallback.text().await.unwrap(), "OK");
+}
+
+#[tokio::test]
+async fn test_path_rewrite_adds_prefix() {
+    let env = TestEnvironment::new().await;
+    let mut config = env.client_config();
+    config.workstation.path_rewrite.add_prefix = Some("/api".to_string());
+    let (_handle, _url) = tunnel_client::handle::TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let response = reqwest::get(env.proxy_url("users/42?full=1"))
+        .await
+        .expect("Failed to make request");
+    assert_eq!(response.text().await.unwrap(), "API response for: users/42");
+}
+
+#[tokio::test]
+async fn test_path_rewrite_strips_prefix() {
+    let env = TestEnvironment::new().await;
+    let mut config = env.client_config();
+    config.workstation.path_rewrite.strip_prefix = Some("/app".to_string());
+    let (_handle, _url) = tunnel_client::handle::TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let client = reqwest::Client::new();
+    let stripped = client
+        .get(env.proxy_url("app/health"))
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(stripped.text().await.unwrap(), "OK");
+
+    // Only whole segments are stripped.
+    let untouched = client
+        .get(env.proxy_url("apple/health"))
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(untouched.status(), 404);
+}
+
+#[tokio::test]
+async fn test_path_rewrite_defaults_to_no_op() {
+    let env = TestEnvironment::new().await;
+    let config = env.client_config();
+    assert!(config.workstation.path_rewrite.strip_prefix.is_none());
+    assert!(config.workstation.path_rewrite.add_prefix.is_none());
+    let (_handle, _url) = tunnel_client::handle::TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let response = reqwest::get(env.proxy_url("api/items"))
+        .await
+        .expect("Failed to make request");
+    assert_eq!(response.text().await.unwrap(), "API response for: items");
+}
+
+/// Backend answering `/cached` with a cacheable response and `/private` with a
+/// `no-store` one, counting the requests that reach it.
+async fn spawn_caching_backend() -> (u16, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let hits = std::sync::Arc::new(AtomicUsize::new(0));
+    let (cached_hits, private_hits) = (hits.clone(), hits.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let app = axum::Router::new()
+            .route(
+                "/cached",
+                axum::routing::get(move || async move {
+                    let n = cached_hits.fetch_add(1, Ordering::SeqCst);
+                    (
+                        [("cache-control", "public, max-age=60"), ("etag", "\"v1\"")],
+                        format!("cached {}", n),
+                    )
+                }),
+            )
+            .route(
+                "/private",
+                axum::routing::get(move || async move {
+                    let n = private_hits.fetch_add(1, Ordering::SeqCst);
+                    ([("cache-control", "no-store")], format!("private {}", n))
+                }),
+            );
+        axum::serve(listener, app).await.unwrap();
+    });
+    (port, hits)
+}
+
+#[tokio::test]
+async fn test_cacheable_response_served_from_edge_cache() {
+    let (backend_port, hits) = spawn_caching_backend().await;
+    let env = TestEnvironment::new_with_server_config("test-ws", |config| {
+        config.server.cache.enabled = true;
+    })
+    .await;
+    let mut config = env.client_config();
+    config.workstation.local_address = format!("http://127.0.0.1:{}", backend_port);
+    let (_handle, _url) = tunnel_client::handle::TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let client = reqwest::Client::new();
+    let first = client.get(env.proxy_url("cached")).send().await.unwrap();
+    assert!(first.headers().get("age").is_none());
+    assert_eq!(first.text().await.unwrap(), "cached 0");
+
+    let second = client.get(env.proxy_url("cached")).send().await.unwrap();
+    assert!(second.headers().get("age").is_some());
+    assert_eq!(second.text().await.unwrap(), "cached 0");
+
+    let revalidated = client
+        .get(env.proxy_url("cached"))
+        .header("if-none-match", "\"v1\"")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(revalidated.status(), 304);
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    // Another query string is another entry.
+    let other = client
+        .get(env.proxy_url("cached?page=2"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(other.text().await.unwrap(), "cached 1");
+}
+
+#[tokio::test]
+async fn test_no_store_response_bypasses_edge_cache() {
+    let (backend_port, hits) = spawn_caching_backend().await;
+    let env = TestEnvironment::new_with_server_config("test-ws", |config| {
+        config.server.cache.enabled = true;
+    })
+    .await;
+    let mut config = env.client_config();
+    config.workstation.local_address = format!("http://127.0.0.1:{}", backend_port);
+    let (_handle, _url) = tunnel_client::handle::TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let client = reqwest::Client::new();
+    for expected in ["private 0", "private 1"] {
+        let response = client.get(env.proxy_url("private")).send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), expected);
+    }
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_signature_headers_keep_their_order() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        // Echoes the signature-relevant headers in the order they arrived.
+        let app = axum::Router::new().fallback(|headers: axum::http::HeaderMap| async move {
+            headers
+                .iter()
+                .filter(|(name, _)| name.as_str().starts_with("x-amz-") || *name == "authorization")
+                .map(|(name, value)| format!("{}={}", name, value.to_str().unwrap()))
+                .collect::<Vec<_>>()
+                .join("\n")
+        });
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let env = TestEnvironment::new().await;
+    let mut config = env.client_config();
+    config.workstation.local_address = format!("http://127.0.0.1:{}", backend_port);
+    let (_handle, _url) = tunnel_client::handle::TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let sent = [
+        ("x-amz-date", "20260101T000000Z"),
+        ("x-amz-security-token", "token"),
+        ("authorization", "AWS4-HMAC-SHA256 Credential=AKID/20260101/us-east-1/s3/aws4_request, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature=abc"),
+        ("x-amz-content-sha256", "UNSIGNED-PAYLOAD"),
+        ("x-amz-meta-tag", "first"),
+        ("x-amz-meta-tag", "second"),
+    ];
+    let mut request = reqwest::Client::new().get(env.proxy_url("bucket/key"));
+    for (name, value) in sent {
+        request = request.header(name, value);
+    }
+    let echoed = request.send().await.unwrap().text().await.unwrap();
+
+    let expected: Vec<String> = sent
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+    assert_eq!(echoed, expected.join("\n"));
+}
+
+#[tokio::test]
+async fn test_interleaved_repeated_headers_are_grouped_and_lowercased() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // Answers with the `x-amz-*` lines of the request head as they came in.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            let mut byte = [0u8; 1];
+            stream.read_exact(&mut byte).await.unwrap();
+            head.push(byte[0]);
+        }
+        let lines: Vec<&str> = std::str::from_utf8(&head)
+            .unwrap()
+            .lines()
+            .filter(|line| line.to_ascii_lowercase().starts_with("x-amz-"))
+            .collect();
+        let body = lines.join("\n");
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await.unwrap();
+    });
+
+    let env = TestEnvironment::new().await;
+    let mut config = env.client_config();
+    config.workstation.local_address = format!("http://127.0.0.1:{}", backend_port);
+    let (_handle, _url) = tunnel_client::handle::TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let mut caller = tokio::net::TcpStream::connect(("127.0.0.1", env.server_http_port))
+        .await
+        .unwrap();
+    let request = format!(
+        "GET /t/{}/bucket/key HTTP/1.1\r\nHost: localhost\r\nX-Amz-Meta-Tag: first\r\n\
+         X-Amz-Date: 20260101T000000Z\r\nx-amz-meta-tag: second\r\n\
+         X-AMZ-Security-Token: token\r\nConnection: close\r\n\r\n",
+        env.workstation_id
+    );
+    caller.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    caller.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    let (_, echoed) = response.split_once("\r\n\r\n").unwrap();
+
+    // The values of a repeated name follow its first occurrence, and names
+    // lose their case.
+    assert_eq!(
+        echoed,
+        "x-amz-meta-tag: first\nx-amz-meta-tag: second\n\
+         x-amz-date: 20260101T000000Z\nx-amz-security-token: token"
+    );
+}
+
+#[tokio::test]
+async fn test_gzip_response_inflated_for_identity_client() {
+    // "hello, tunnel" gzip-compressed.
+    const GZIPPED: &[u8] = &[
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xcb, 0x48, 0xcd, 0xc9, 0xc9,
+        0xd7, 0x51, 0x28, 0x29, 0xcd, 0xcb, 0x4b, 0xcd, 0x01, 0x00, 0xbb, 0x40, 0x61, 0xfc, 0x0d,
+        0x00, 0x00, 0x00,
+    ];
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let app = axum::Router::new().fallback(|| async {
+            (
+                [("content-encoding", "gzip"), ("content-type", "text/plain")],
+                GZIPPED,
+            )
+        });
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.server.decompress_responses = true;
+    })
+    .await;
+    let mut config = env.client_config();
+    config.workstation.local_address = format!("http://127.0.0.1:{}", backend_port);
+    let (_handle, _url) = tunnel_client::handle::TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let client = reqwest::Client::new();
+    let identity = client
+        .get(env.proxy_url("page"))
+        .header("accept-encoding", "identity")
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(identity.status(), 200);
+    assert!(identity.headers().get("content-encoding").is_none());
+    assert_eq!(identity.text().await.unwrap(), "hello, tunnel");
+
+    // Callers that accept gzip get the backend's bytes unchanged.
+    let gzip = client
+        .get(env.proxy_url("page"))
+        .header("accept-encoding", "gzip, deflate")
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(gzip.headers().get("content-encoding").unwrap(), "gzip");
+    assert_eq!(gzip.bytes().await.unwrap().as_ref(), GZIPPED);
+}
+
+#[tokio::test]
+async fn test_url_rewrite_points_html_links_at_tunnel() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_port = listener.local_addr().unwrap().port();
+    let local_base = format!("http://127.0.0.1:{}", backend_port);
+    let html = format!("<a href=\"{}/docs\">docs</a>", local_base);
+    let binary = local_base.clone().into_bytes();
+    tokio::spawn(async move {
+        let app = axum::Router::new()
+            .route(
+                "/page",
+                axum::routing::get(move || async move {
+                    ([("content-type", "text/html; charset=utf-8")], html)
+                }),
+            )
+            .route(
+                "/blob",
+                axum::routing::get(move || async move {
+                    ([("content-type", "application/octet-stream")], binary)
+                }),
+            );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let from = local_base.clone();
+    let env = TestEnvironment::new_with_server_config(&workstation_id, move |config| {
+        config.server.url_rewrite = vec![tunnel_server::config::UrlRewriteRule {
+            from: from.clone(),
+            to: None,
+        }];
+    })
+    .await;
+    let mut config = env.client_config();
+    config.workstation.local_address = local_base.clone();
+    let (_handle, _url) = tunnel_client::handle::TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let client = reqwest::Client::new();
+    let page = client
+        .get(env.proxy_url("page"))
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(page.status(), 200);
+    assert_eq!(
+        page.text().await.unwrap(),
+        format!(
+            "<a href=\"http://localhost:{}/t/{}/docs\">docs</a>",
+            env.server_http_port, workstation_id
+        )
+    );
+
+    let blob = client
+        .get(env.proxy_url("blob"))
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(blob.bytes().await.unwrap().as_ref(), local_base.as_bytes());
+}
+
+#[tokio::test]
+async fn test_base_path_prefixes_routes_and_registered_url() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.server.base_path = "/tunnel/".to_string();
+    })
+    .await;
+    let (_handle, url) = tunnel_client::handle::TunnelClientHandle::connect(env.client_config())
+        .await
+        .expect("Failed to connect");
+    assert_eq!(
+        url,
+        format!(
+            "http://localhost:{}/tunnel/t/{}",
+            env.server_http_port, workstation_id
+        )
+    );
+
+    let base = format!("http://localhost:{}/tunnel", env.server_http_port);
+    let proxied = reqwest::get(format!("{}/t/{}/health", base, workstation_id))
+        .await
+        .expect("Failed to make request");
+    assert_eq!(proxied.status(), 200);
+    assert_eq!(proxied.text().await.unwrap(), "OK");
+
+    let health = reqwest::get(format!("{}/health", base)).await.unwrap();
+    assert_eq!(health.status(), 200);
+
+    let unprefixed = reqwest::get(env.proxy_url("health")).await.unwrap();
+    assert_eq!(unprefixed.status(), 404);
+}
+
+async fn send_connect(env: &TestEnvironment, target: &str) -> (tokio::net::TcpStream, String) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", env.server_http_port))
+        .await
+        .expect("Failed to reach the server");
+    let credentials =
+        tunnel_core::codec::encode_body(format!("{}:", env.workstation_id).as_bytes());
+    let request = format!(
+        "CONNECT {target} HTTP/1.1\r\nHost: {target}\r\nProxy-Authorization: Basic {credentials}\r\n\r\n"
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut head = Vec::new();
+    let mut byte = [0u8; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        if stream.read(&mut byte).await.unwrap() == 0 {
+            break;
+        }
+        head.push(byte[0]);
+    }
+    (stream, String::from_utf8_lossy(&head).to_string())
+}
+
+#[tokio::test]
+async fn test_connect_relays_bytes_to_allowed_target() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let echo = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let echo_port = echo.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = echo.accept().await {
+            tokio::spawn(async move {
+                let (mut read, mut write) = socket.split();
+                let _ = tokio::io::copy(&mut read, &mut write).await;
+            });
+        }
+    });
+
+    let env = TestEnvironment::new().await;
+    let mut config = env.client_config();
+    config.workstation.connect_allow = vec![format!("127.0.0.1:{}", echo_port)];
+    let (_handle, _url) = tunnel_client::handle::TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let (mut stream, head) = send_connect(&env, &format!("127.0.0.1:{}", echo_port)).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+
+    stream.write_all(b"hello through connect").await.unwrap();
+    let mut buf = [0u8; 21];
+    tokio::time::timeout(
+        std::time::Duration::from_secs(5),
+        stream.read_exact(&mut buf),
+    )
+    .await
+    .expect("Timed out waiting for echo")
+    .unwrap();
+    assert_eq!(&buf, b"hello through connect");
+
+    let (_stream, head) = send_connect(&env, "127.0.0.1:1").await;
+    assert!(head.starts_with("HTTP/1.1 403"), "{}", head);
+}
+
+#[tokio::test]
+async fn test_range_request_returns_partial_content() {
+    let mut env = TestEnvironment::new().await;
+    env.start_client().await;
+
+    let client = reqwest::Client::new();
+    let response = client
+        .get(env.proxy_url("file"))
+        .header("range", "bytes=100-199")
+        .send()
+        .await
+        .expect("Failed to make request");
+
+    assert_eq!(response.status(), 206);
+    assert_eq!(
+        response.headers().get("content-range").unwrap(),
+        &format!("bytes 100-199/{}", common::MOCK_FILE_SIZE)
+    );
+    assert_eq!(response.headers().get("accept-ranges").unwrap(), "bytes");
+    assert_eq!(response.headers().get("content-length").unwrap(), "100");
+    let body = response.bytes().await.unwrap();
+    let expected: Vec<u8> = (100..200).map(|i| i as u8).collect();
+    assert_eq!(body.as_ref(), expected.as_slice());
+
+    let response = client
+        .get(env.proxy_url("file"))
+        .header("range", "bytes=5000-6000")
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(response.status(), 416);
+    assert_eq!(
+        response.headers().get("content-range").unwrap(),
+        &format!("bytes */{}", common::MOCK_FILE_SIZE)
+    );
+}
+
+#[tokio::test]
+async fn test_range_of_file_larger_than_frame_limit() {
+    const FRAME_LIMIT: usize = 64 * 1024;
+    let env = TestEnvironment::new().await;
+    let mut config = env.client_config();
+    config.limits.max_frame_bytes = FRAME_LIMIT;
+    let (_handle, _url) = tunnel_client::handle::TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+    let file = common::mock_file(common::MOCK_LARGE_FILE_SIZE);
+    let client = reqwest::Client::new();
+
+    // A range that fits a frame arrives as requested.
+    let response = client
+        .get(env.proxy_url("large-file"))
+        .header("range", "bytes=200000-200999")
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(response.status(), 206);
+    assert_eq!(
+        response.headers().get("content-range").unwrap(),
+        &format!("bytes 200000-200999/{}", common::MOCK_LARGE_FILE_SIZE)
+    );
+    let body = response.bytes().await.unwrap();
+    assert_eq!(body.as_ref(), &file[200_000..201_000]);
+
+    // Following each short 206 with the next range assembles the file, as a
+    // resumable download or media player would.
+    for path in ["large-file", "large-file/unranged"] {
+        let mut assembled = Vec::new();
+        while assembled.len() < file.len() {
+            let response = client
+                .get(env.proxy_url(path))
+                .header("range", format!("bytes={}-", assembled.len()))
+                .send()
+                .await
+                .expect("Failed to make request");
+            assert_eq!(response.status(), 206, "{}", path);
+            let content_range = response.headers()["content-range"]
+                .to_str()
+                .unwrap()
+                .to_string();
+            let body = response.bytes().await.unwrap();
+            assert!(!body.is_empty() && body.len() < FRAME_LIMIT);
+            assert_eq!(
+                content_range,
+                format!(
+                    "bytes {}-{}/{}",
+                    assembled.len(),
+                    assembled.len() + body.len() - 1,
+                    common::MOCK_LARGE_FILE_SIZE
+                )
+            );
+            assembled.extend_from_slice(&body);
+        }
+        assert_eq!(assembled, file, "{}", path);
+    }
+
+    let response = client
+        .get(env.proxy_url("large-file/unranged"))
+        .header("range", format!("bytes={}-", common::MOCK_LARGE_FILE_SIZE))
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(response.status(), 416);
+    assert_eq!(
+        response.headers().get("content-range").unwrap(),
+        &format!("bytes */{}", common::MOCK_LARGE_FILE_SIZE)
+    );
+}
+
+/// Sends a request head with `Expect: 100-continue` and no body yet.
+async fn send_expect_continue_head(
+    env: &TestEnvironment,
+    path: &str,
+    content_length: usize,
+) -> tokio::net::TcpStream {
+    use tokio::io::AsyncWriteExt;
+
+    let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", env.server_http_port))
+        .await
+        .unwrap();
+    let head = format!(
+        "POST /t/{}/{} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\nExpect: 100-continue\r\n\r\n",
+        env.workstation_id, path, content_length
+    );
+    stream.write_all(head.as_bytes()).await.unwrap();
+    stream
+}
+
+/// Reads one response head, up to and including the blank line.
+async fn read_response_head(stream: &mut tokio::net::TcpStream) -> String {
+    use tokio::io::AsyncReadExt;
+
+    let mut head = Vec::new();
+    let mut byte = [0u8; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        stream.read_exact(&mut byte).await.unwrap();
+        head.push(byte[0]);
+    }
+    String::from_utf8(head).unwrap()
+}
+
+#[tokio::test]
+async fn test_expect_continue_relays_backend_interim_response() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut env = TestEnvironment::new().await;
+    env.start_client().await;
+
+    let body = "x".repeat(64 * 1024);
+    let mut stream = send_expect_continue_head(&env, "echo", body.len()).await;
+
+    let interim = tokio::time::timeout(
+        std::time::Duration::from_secs(5),
+        read_response_head(&mut stream),
+    )
+    .await
+    .expect("No interim response");
+    assert!(interim.starts_with("HTTP/1.1 100"), "{}", interim);
+
+    stream.write_all(body.as_bytes()).await.unwrap();
+
+    let head = read_response_head(&mut stream).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    let length: usize = head
+        .lines()
+        .find_map(|line| {
+            line.to_ascii_lowercase()
+                .strip_prefix("content-length:")
+                .map(|v| v.trim().parse().unwrap())
+        })
+        .expect("No content-length");
+    let mut response_body = vec![0u8; length];
+    stream.read_exact(&mut response_body).await.unwrap();
+    assert_eq!(
+        String::from_utf8(response_body).unwrap(),
+        format!("Echo: {}", body)
+    );
+}
+
+#[tokio::test]
+async fn test_expect_continue_rejection_skips_upload() {
+    let mut env = TestEnvironment::new().await;
+    env.start_client().await;
+
+    let mut stream = send_expect_continue_head(&env, "upload/refuse", 1024 * 1024).await;
+
+    // The final status arrives without the body ever being sent.
+    let head = tokio::time::timeout(
+        std::time::Duration::from_secs(5),
+        read_response_head(&mut stream),
+    )
+    .await
+    .expect("No response before upload");
+    assert!(head.starts_with("HTTP/1.1 417"), "{}", head);
+
+    let info = env
+        .server
+        .registry()
+        .get(&env.workstation_id)
+        .await
+        .unwrap();
+    assert_eq!(info.traffic.bytes_in(), 0);
+}
+
+#[tokio::test]
+async fn test_access_log_writes_lines_and_rotates() {
+    let dir = tempfile::tempdir().unwrap();
+    let log_path = dir.path().join("access.log");
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let configured_path = log_path.clone();
+    let mut env = TestEnvironment::new_with_server_config(&workstation_id, move |config| {
+        config.server.access_log.path = Some(configured_path.clone());
+        config.server.access_log.max_bytes = 600;
+        config.server.access_log.max_files = 2;
+    })
+    .await;
+    env.start_client().await;
+
+    for i in 0..10 {
+        let response = reqwest::get(env.proxy_url(&format!("api/item-{}", i)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+    reqwest::get(format!("http://localhost:{}/health", env.server_http_port))
+        .await
+        .unwrap();
+
+    let rotated = log_path.with_extension("log.1");
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+    let mut lines = Vec::new();
+    loop {
+        lines.clear();
+        for path in [&rotated, &log_path] {
+            if let Ok(content) = std::fs::read_to_string(path) {
+                lines.extend(content.lines().map(str::to_string));
+            }
+        }
+        let done = lines.last().is_some_and(|line| line.contains("item-9"));
+        if done || tokio::time::Instant::now() > deadline {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+
+    assert!(rotated.exists(), "log should have rotated past 600 bytes");
+    assert!(std::fs::metadata(&log_path).unwrap().len() <= 600);
+    // Two rotated files at most, so the oldest entries may be gone.
+    assert!(!log_path.with_extension("log.3").exists());
+    assert!(lines.len() >= 2);
+
+    let last: serde_json::Value = serde_json::from_str(lines.last().unwrap()).unwrap();
+    assert_eq!(last["workstation"], env.workstation_id.as_str());
+    assert_eq!(last["method"], "GET");
+    assert_eq!(
+        last["path"],
+        format!("/t/{}/api/item-9", env.workstation_id)
+    );
+    assert_eq!(last["status"], 200);
+    assert!(last["duration_ms"].is_u64());
+    assert!(last["remote"].as_str().unwrap().starts_with("127.0.0.1:"));
+    assert!(
+        lines.iter().all(|line| !line.contains("/health\"")),
+        "non-tunnel requests are not logged"
+    );
+}
+
+/// Sends a GET for `raw_path` as written, since URL parsers in HTTP clients
+/// would already resolve `..` and empty segments. Returns the whole response.
+async fn send_raw_get(env: &TestEnvironment, raw_path: &str) -> String {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", env.server_http_port))
+        .await
+        .unwrap();
+    let request = format!(
+        "GET /t/{}/{} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        env.workstation_id, raw_path
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+#[tokio::test]
+async fn test_request_paths_are_normalized() {
+    let mut env = TestEnvironment::new().await;
+    env.start_client().await;
+
+    let response = send_raw_get(&env, "api/users").await;
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(
+        response.ends_with("API response for: users"),
+        "{}",
+        response
+    );
+
+    let response = send_raw_get(&env, "/api//a//b").await;
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(response.ends_with("API response for: a/b"), "{}", response);
+
+    let response = send_raw_get(&env, "api/a/../b").await;
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(response.ends_with("API response for: b"), "{}", response);
+
+    let response = send_raw_get(&env, "../secret").await;
+    assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+    let response = send_raw_get(&env, "api/../../secret").await;
+    assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+}
+
+/// Backend whose `/work` takes 10s, reporting on the channel when a request
+/// starts and whether it finished or was abandoned by the caller.
+async fn spawn_observed_backend() -> (u16, tokio::sync::mpsc::UnboundedReceiver<&'static str>) {
+    struct Abandoned(Option<tokio::sync::mpsc::UnboundedSender<&'static str>>);
+    impl Drop for Abandoned {
+        fn drop(&mut self) {
+            if let Some(events) = self.0.take() {
+                let _ = events.send("cancelled");
+            }
+        }
+    }
+
+    let (events, observed) = tokio::sync::mpsc::unbounded_channel();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let app = axum::Router::new().route(
+        "/work",
+        axum::routing::get(move || {
+            let events = events.clone();
+            async move {
+                let _ = events.send("started");
+                let mut abandoned = Abandoned(Some(events));
+                tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+                let _ = abandoned.0.take().unwrap().send("finished");
+                "done"
+            }
+        }),
+    );
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (port, observed)
+}
+
+#[tokio::test]
+async fn test_caller_disconnect_cancels_backend_request() {
+    let (backend_port, mut observed) = spawn_observed_backend().await;
+    let env = TestEnvironment::new().await;
+    let mut config = env.client_config();
+    config.workstation.local_address = format!("http://127.0.0.1:{}", backend_port);
+    let (_handle, _url) = tunnel_client::handle::TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let caller = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_millis(500))
+        .build()
+        .unwrap();
+    let result = caller.get(env.proxy_url("work")).send().await;
+    assert!(result.unwrap_err().is_timeout());
+    assert_eq!(observed.recv().await, Some("started"));
+
+    // The caller gave up, so the backend must not keep working until the
+    // server's request timeout.
+    let event = tokio::time::timeout(std::time::Duration::from_secs(3), observed.recv())
+        .await
+        .expect("Backend request was not cancelled");
+    assert_eq!(event, Some("cancelled"));
+    assert_eq!(env.server.pending().count().await, 0);
+}
+
+#[tokio::test]
+async fn test_slow_request_logs_warning() {
+    // The runtime is single-threaded, so the server and client tasks log
+    // through this thread's subscriber.
+    let logs = LogBuffer::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let mut env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.reliability.slow_request_threshold = 1000;
+    })
+    .await;
+    env.start_client().await;
+
+    let response = reqwest::get(env.proxy_url("health")).await.unwrap();
+    assert_eq!(response.status(), 200);
+    let response = reqwest::get(env.proxy_url("slow")).await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    let logs = logs.contents();
+    let slow = logs
+        .lines()
+        .find(|line| line.contains("Slow request"))
+        .expect("no slow-request warning logged");
+    assert!(slow.contains("WARN"), "{}", slow);
+    assert!(
+        slow.contains(&format!("GET /slow on {} took", workstation_id)),
+        "{}",
+        slow
+    );
+    let health = logs
+        .lines()
+        .find(|line| line.contains("GET /health on"))
+        .expect("no round trip logged for /health");
+    assert!(health.contains("DEBUG"), "{}", health);
+}
+
+#[tokio::test]
+async fn test_relative_redirect_is_relayed_to_caller() {
+    let mut env = TestEnvironment::new().await;
+    env.start_client().await;
+
+    let no_redirect = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+    let response = no_redirect
+        .get(env.proxy_url("redirect"))
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(response.status(), 302);
+    assert_eq!(response.headers().get("location").unwrap(), "health");
+
+    // Resolved against the tunnel URL, the redirect stays inside the tunnel.
+    let response = reqwest::get(env.proxy_url("redirect")).await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.url().as_str(), env.proxy_url("health"));
+    assert_eq!(response.text().await.unwrap(), "OK");
+}
+
+#[tokio::test]
+async fn test_backend_sees_public_scheme_and_host() {
+    let mut env = TestEnvironment::new().await;
+    env.start_client().await;
+
+    let response = reqwest::Client::new()
+        .get(env.proxy_url("forwarded"))
+        .header("x-forwarded-host", "spoofed.example.com")
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.text().await.unwrap(),
+        format!(
+            "x-forwarded-proto: http\nx-forwarded-host: localhost:{0}\nforwarded: proto=http;host=\"localhost:{0}\"",
+            env.server_http_port
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_injected_headers_reach_backend_only() {
+    let secret_dir = tempfile::tempdir().unwrap();
+    let secret_path = secret_dir.path().join("internal_token");
+    std::fs::write(&secret_path, "Bearer internal-secret\n").unwrap();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend = format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+    let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorded = seen.clone();
+    tokio::spawn(async move {
+        let app = axum::Router::new().route(
+            "/page",
+            axum::routing::get(move |headers: axum::http::HeaderMap| async move {
+                recorded.lock().unwrap().push(
+                    ["authorization", "x-service"]
+                        .iter()
+                        .flat_map(|name| headers.get_all(*name))
+                        .map(|v| v.to_str().unwrap().to_string())
+                        .collect::<Vec<_>>(),
+                );
+                "OK"
+            }),
+        );
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let env = TestEnvironment::new().await;
+    let mut config = env.client_config();
+    config.workstation.local_address = backend;
+    config
+        .workstation
+        .inject_headers
+        .insert("x-service".to_string(), "tunnel".to_string());
+    config
+        .workstation
+        .inject_header_files
+        .insert("authorization".to_string(), secret_path);
+    config.load_inject_header_files().unwrap();
+    let (_handle, _url) = tunnel_client::handle::TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let response = reqwest::Client::new()
+        .get(env.proxy_url("page"))
+        .header("authorization", "Bearer caller-supplied")
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(response.status(), 200);
+    assert!(response.headers().get("authorization").is_none());
+    assert!(response.headers().get("x-service").is_none());
+    assert_eq!(response.text().await.unwrap(), "OK");
+
+    assert_eq!(
+        seen.lock().unwrap().as_slice(),
+        [vec![
+            "Bearer internal-secret".to_string(),
+            "tunnel".to_string()
+        ]]
+    );
+}
+
+/// Reads an HTTP message head up to the blank line, lowercased.
+async fn read_head(stream: &mut tokio::net::TcpStream) -> String {
+    use tokio::io::AsyncReadExt;
+
+    let mut head = Vec::new();
+    let mut byte = [0u8; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        if stream.read(&mut byte).await.unwrap() == 0 {
+            break;
+        }
+        head.push(byte[0]);
+    }
+    String::from_utf8_lossy(&head).to_ascii_lowercase()
+}
+
+#[tokio::test]
+async fn test_hop_by_hop_headers_stay_on_their_hop() {
+    use tokio::io::AsyncWriteExt;
+
+    // Raw sockets on both ends, so no HTTP library adds or drops headers.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend = format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+    let (seen_tx, seen_rx) = tokio::sync::oneshot::channel();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let head = read_head(&mut stream).await;
+        stream
+            .write_all(
+                b"HTTP/1.1 200 OK\r\n\
+                  Content-Length: 2\r\n\
+                  Connection: X-Backend-Hop\r\n\
+                  X-Backend-Hop: 1\r\n\
+                  Keep-Alive: timeout=5\r\n\
+                  Proxy-Authenticate: Basic\r\n\
+                  X-Kept: backend\r\n\
+                  \r\nOK",
+            )
+            .await
+            .unwrap();
+        let _ = seen_tx.send(head);
+    });
+
+    let env = TestEnvironment::new().await;
+    let mut config = env.client_config();
+    config.workstation.local_address = backend;
+    let (_handle, _url) = tunnel_client::handle::TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let mut caller = tokio::net::TcpStream::connect(("127.0.0.1", env.server_http_port))
+        .await
+        .unwrap();
+    let request = format!(
+        "GET /t/{}/page HTTP/1.1\r\n\
+         Host: localhost\r\n\
+         Connection: keep-alive, X-Session\r\n\
+         X-Session: abc\r\n\
+         Keep-Alive: timeout=5\r\n\
+         Proxy-Authorization: Basic Zm9vOg==\r\n\
+         X-Kept: caller\r\n\
+         \r\n",
+        env.workstation_id
+    );
+    caller.write_all(request.as_bytes()).await.unwrap();
+    let response = read_head(&mut caller).await;
+
+    let forwarded = seen_rx.await.unwrap();
+    assert!(
+        forwarded.contains("\r\nx-kept: caller\r\n"),
+        "{}",
+        forwarded
+    );
+    for name in ["x-session", "keep-alive", "proxy-authorization"] {
+        assert!(
+            !forwarded.contains(&format!("\r\n{}:", name)),
+            "{} reached the backend:\n{}",
+            name,
+            forwarded
+        );
+    }
+
+    assert!(response.starts_with("http/1.1 200"), "{}", response);
+    assert!(response.contains("\r\nx-kept: backend\r\n"), "{}", response);
+    for name in ["x-backend-hop", "keep-alive", "proxy-authenticate"] {
+        assert!(
+            !response.contains(&format!("\r\n{}:", name)),
+            "{} reached the caller:\n{}",
+            name,
+            response
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_early_hints_reach_caller_before_final_response() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend = format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+    let (release_tx, release_rx) = tokio::sync::oneshot::channel::<()>();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        read_head(&mut stream).await;
+        stream
+            .write_all(
+                b"HTTP/1.1 103 Early Hints\r\n\
+                  Link: </style.css>; rel=preload; as=style\r\n\
+                  Link: </app.js>; rel=preload; as=script\r\n\
+                  \r\n",
+            )
+            .await
+            .unwrap();
+        // The final response waits until the caller has seen the hints.
+        let _ = release_rx.await;
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\npage")
+            .await
+            .unwrap();
+    });
+
+    let env = TestEnvironment::new().await;
+    let mut config = env.client_config();
+    config.workstation.local_address = backend;
+    config.workstation.forward_informational = true;
+    let (_handle, _url) = tunnel_client::handle::TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let mut caller = tokio::net::TcpStream::connect(("127.0.0.1", env.server_http_port))
+        .await
+        .unwrap();
+    let request = format!(
+        "GET /t/{}/page HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        env.workstation_id
+    );
+    caller.write_all(request.as_bytes()).await.unwrap();
+
+    let hints = tokio::time::timeout(std::time::Duration::from_secs(5), read_head(&mut caller))
+        .await
+        .expect("Early hints did not arrive ahead of the response");
+    assert!(hints.starts_with("http/1.1 103"), "{}", hints);
+    assert!(
+        hints.contains("</style.css>; rel=preload; as=style")
+            && hints.contains("</app.js>; rel=preload; as=script"),
+        "{}",
+        hints
+    );
+
+    release_tx.send(()).unwrap();
+    let response = read_head(&mut caller).await;
+    assert!(response.starts_with("http/1.1 200"), "{}", response);
+    let mut body = [0u8; 4];
+    caller.read_exact(&mut body).await.unwrap();
+    assert_eq!(&body, b"page");
+}
+
+/// Relays TCP connections to `upstream`, counting the ones accepted.
+async fn spawn_counting_relay(
+    upstream: u16,
+) -> (u16, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let accepted = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = accepted.clone();
+    tokio::spawn(async move {
+        while let Ok((mut inbound, _)) = listener.accept().await {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            tokio::spawn(async move {
+                let mut outbound = tokio::net::TcpStream::connect(("127.0.0.1", upstream))
+                    .await
+                    .unwrap();
+                let _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await;
+            });
+        }
+    });
+    (port, accepted)
+}
+
+/// Sends `rounds` waves of `concurrency` parallel requests through a client
+/// configured by `configure`, returning the connections its backend accepted.
+async fn backend_connections_for(
+    rounds: usize,
+    concurrency: usize,
+    configure: impl FnOnce(&mut tunnel_client::config::WorkstationConfig),
+) -> usize {
+    let env = TestEnvironment::new().await;
+    let (relay_port, accepted) = spawn_counting_relay(env.mock_server_port).await;
+    let mut config = env.client_config();
+    config.workstation.local_address = format!("http://127.0.0.1:{}", relay_port);
+    configure(&mut config.workstation);
+    let (_handle, _url) = tunnel_client::handle::TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let client = reqwest::Client::new();
+    for _ in 0..rounds {
+        let requests = (0..concurrency).map(|_| async {
+            let response = client.get(env.proxy_url("health")).send().await.unwrap();
+            assert_eq!(response.status(), 200);
+            assert_eq!(response.text().await.unwrap(), "OK");
+        });
+        futures::future::join_all(requests).await;
+    }
+    accepted.load(std::sync::atomic::Ordering::SeqCst)
+}
+
+#[tokio::test]
+async fn test_backend_connections_are_reused() {
+    let (rounds, concurrency) = (5, 8);
+
+    let pooled = backend_connections_for(rounds, concurrency, |_| {}).await;
+    assert!(
+        pooled <= concurrency,
+        "{} connections for {} parallel requests",
+        pooled,
+        concurrency
+    );
+
+    let unpooled = backend_connections_for(rounds, concurrency, |workstation| {
+        workstation.pool_max_idle_per_host = 0;
+    })
+    .await;
+    assert_eq!(unpooled, rounds * concurrency);
+
+    let multiplexed = backend_connections_for(rounds, concurrency, |workstation| {
+        workstation.http2_prior_knowledge = true;
+    })
+    .await;
+    assert_eq!(multiplexed, 1);
+}
+
+fn spool_files(dir: &std::path::Path) -> usize {
+    std::fs::read_dir(dir).unwrap().count()
+}
+
+async fn wait_for_spool_files(dir: &std::path::Path, expected: usize) {
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+    while spool_files(dir) != expected {
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "expected {} spool files, found {}",
+            expected,
+            spool_files(dir)
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_large_bodies_spooled_to_disk() {
+    let dir = tempfile::tempdir().unwrap();
+    let spool_dir = dir.path().to_path_buf();
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let mut env = TestEnvironment::new_with_server_config(&workstation_id, move |config| {
+        config.server.spool_dir = Some(spool_dir.clone());
+        config.server.spool_threshold = 64 * 1024;
+    })
+    .await;
+    env.start_client().await;
+
+    // Small bodies stay in memory.
+    let response = reqwest::get(env.proxy_url("health")).await.unwrap();
+    assert_eq!(response.text().await.unwrap(), "OK");
+    assert_eq!(spool_files(dir.path()), 0);
+
+    // A large response is streamed from a spool file, which stays while the
+    // caller has not read it (the body is larger than the socket buffers)
+    // and is gone once it has.
+    let size = 16 * 1024 * 1024;
+    let response = reqwest::get(env.proxy_url(&format!("large/{}", size)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.content_length(), Some(size as u64));
+    assert_eq!(spool_files(dir.path()), 1);
+    let body = response.bytes().await.unwrap();
+    assert_eq!(body.len(), size);
+    assert!(body.iter().all(|&b| b == b'x'));
+    wait_for_spool_files(dir.path(), 0).await;
+
+    let size = 1024 * 1024;
+
+    // A large upload goes to disk while it is still arriving.
+    let (tx, rx) = futures::channel::mpsc::unbounded::<Result<Vec<u8>, std::io::Error>>();
+    let upload = tokio::spawn(
+        reqwest::Client::new()
+            .post(env.proxy_url("echo"))
+            .body(reqwest::Body::wrap_stream(rx))
+            .send(),
+    );
+    tx.unbounded_send(Ok(vec![b'y'; size / 2])).unwrap();
+    wait_for_spool_files(dir.path(), 1).await;
+    tx.unbounded_send(Ok(vec![b'y'; size / 2])).unwrap();
+    drop(tx);
+
+    let response = upload.await.unwrap().unwrap();
+    assert_eq!(response.status(), 200);
+    let body = response.text().await.unwrap();
+    assert_eq!(body, format!("Echo: {}", "y".repeat(size)));
+    wait_for_spool_files(dir.path(), 0).await;
+}
@@ -0,0 +1,345 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+mod common;
+
+use common::TestEnvironment;
+use std::time::Duration;
+use tunnel_client::connection::Connection;
+
+#[tokio::test]
+async fn test_stream_flood_is_bounded() {
+    let max_streams = 4;
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let env = TestEnvironment::new_with_server_config(&workstation_id, move |config| {
+        config.limits.max_streams_per_connection = max_streams;
+    })
+    .await;
+
+    let mut connection = Connection::new(env.client_config());
+    let (conn, _url) = connection.connect().await.expect("Failed to register");
+
+    // Each stream sends a partial length prefix so the server-side handler
+    // blocks waiting for the rest of the frame and keeps its permit.
+    let mut streams = Vec::new();
+    for _ in 0..20 {
+        let (mut send, recv) = conn.open_bi().await.expect("Failed to open stream");
+        send.write_all(&[0]).await.expect("Failed to write");
+        streams.push((send, recv));
+    }
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let info = env
+        .server
+        .registry()
+        .get(&env.workstation_id)
+        .await
+        .expect("Workstation not registered");
+    assert_eq!(info.active_streams(), max_streams);
+
+    drop(streams);
+    conn.close(0u32.into(), b"done");
+}
+
+#[tokio::test]
+async fn test_requests_per_ip_are_rate_limited() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let mut env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.limits.requests_per_second = 1;
+        config.limits.burst = 3;
+    })
+    .await;
+    env.start_client().await;
+
+    let client = reqwest::Client::new();
+    let mut ok = 0;
+    let mut limited = 0;
+
+    for _ in 0..10 {
+        let response = client
+            .get(env.proxy_url("health"))
+            .send()
+            .await
+            .expect("Request failed");
+        match response.status().as_u16() {
+            200 => ok += 1,
+            429 => {
+                assert!(response.headers().contains_key("retry-after"));
+                limited += 1;
+            }
+            status => panic!("Unexpected status {}", status),
+        }
+    }
+
+    assert!(ok >= 3);
+    assert!(limited > 0);
+
+    let health_url = format!("http://localhost:{}/health", env.server_http_port);
+    let response = reqwest::get(&health_url).await.expect("Request failed");
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn test_traffic_quota_blocks_workstation() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let mut env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.limits.max_bytes_per_workstation = 10_000;
+    })
+    .await;
+    env.start_client().await;
+
+    let client = reqwest::Client::new();
+    for _ in 0..2 {
+        let response = client
+            .get(env.proxy_url("large/6000"))
+            .send()
+            .await
+            .expect("Request failed");
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.bytes().await.unwrap().len(), 6000);
+    }
+
+    let response = client
+        .get(env.proxy_url("health"))
+        .send()
+        .await
+        .expect("Request failed");
+    assert_eq!(response.status(), 429);
+
+    let registry = env.server.registry();
+    let info = registry
+        .get(&env.workstation_id)
+        .await
+        .expect("Workstation not registered");
+    assert_eq!(info.traffic.bytes_out(), 12_000);
+
+    let reset_url = format!(
+        "http://localhost:{}/admin/workstations/{}/traffic/reset",
+        env.server_http_port, env.workstation_id
+    );
+    let response = client.post(&reset_url).send().await.unwrap();
+    assert_eq!(response.status(), 401);
+    let response = client
+        .post(&reset_url)
+        .bearer_auth(&env.api_key)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 204);
+    assert_eq!(info.traffic.bytes_out(), 0);
+
+    let response = client
+        .post(format!(
+            "http://localhost:{}/admin/workstations/unknown-ws/traffic/reset",
+            env.server_http_port
+        ))
+        .bearer_auth(&env.api_key)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    let response = client
+        .get(env.proxy_url("health"))
+        .send()
+        .await
+        .expect("Request failed");
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn test_bandwidth_throttle_paces_large_transfer() {
+    let rate = 500_000;
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let mut env = TestEnvironment::new_with_server_config(&workstation_id, move |config| {
+        config.limits.max_bytes_per_sec_per_workstation = rate;
+    })
+    .await;
+    env.start_client().await;
+
+    // The first second's worth passes as a burst; the rest is paced at `rate`.
+    let size = 2_000_000;
+    let start = std::time::Instant::now();
+    let response = reqwest::get(env.proxy_url(&format!("large/{}", size)))
+        .await
+        .expect("Request failed");
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.bytes().await.unwrap().len(), size);
+    let elapsed = start.elapsed();
+
+    let expected = Duration::from_secs_f64((size as u64 - rate) as f64 / rate as f64);
+    assert!(
+        elapsed >= expected.mul_f64(0.9),
+        "transfer finished in {:?}, faster than the cap allows",
+        elapsed
+    );
+    assert!(
+        elapsed <= expected + Duration::from_secs(2),
+        "transfer took {:?}, well beyond the cap",
+        elapsed
+    );
+}
+
+#[tokio::test]
+async fn test_bandwidth_throttle_streams_while_pacing() {
+    let rate = 500_000;
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let mut env = TestEnvironment::new_with_server_config(&workstation_id, move |config| {
+        config.limits.max_bytes_per_sec_per_workstation = rate;
+    })
+    .await;
+    env.start_client().await;
+
+    // Paced per chunk, the body starts flowing long before the cap is met.
+    let size = 2_000_000;
+    let start = std::time::Instant::now();
+    let mut response = reqwest::get(env.proxy_url(&format!("large/{}", size)))
+        .await
+        .expect("Request failed");
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.content_length(), Some(size as u64));
+    let first = response.chunk().await.unwrap().expect("empty body");
+    assert!(!first.is_empty());
+    let first_byte = start.elapsed();
+    let mut received = first.len();
+    while let Some(chunk) = response.chunk().await.unwrap() {
+        received += chunk.len();
+    }
+    assert_eq!(received, size);
+    assert!(
+        first_byte < Duration::from_secs(1),
+        "first byte took {:?}",
+        first_byte
+    );
+    assert!(
+        start.elapsed() >= Duration::from_secs(2),
+        "transfer finished in {:?}, faster than the cap allows",
+        start.elapsed()
+    );
+}
+
+#[tokio::test]
+async fn test_new_connections_are_rate_limited() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.limits.max_new_connections_per_sec = 2;
+    })
+    .await;
+
+    let attempts = (0..8).map(|_| async {
+        let mut config = env.client_config();
+        config.reconnect.handshake_retries = 0;
+        Connection::new(config).check().await
+    });
+    let results = futures::future::join_all(attempts).await;
+    let accepted = results.iter().filter(|r| r.is_ok()).count();
+    assert!(
+        (1..=3).contains(&accepted),
+        "{} of {} connections accepted",
+        accepted,
+        results.len()
+    );
+
+    // The bucket refills, so a later connection gets through.
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    Connection::new(env.client_config())
+        .check()
+        .await
+        .expect("Connection after the refill should be accepted");
+}
+
+#[tokio::test]
+async fn test_request_queue_bounds_burst_to_slow_workstation() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let mut env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        // One slot is held for the next stream the workstation opens.
+        config.limits.max_streams_per_connection = 3;
+        config.limits.max_queued_requests = 2;
+        config.limits.queue_timeout = 1000;
+    })
+    .await;
+    env.start_client().await;
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    // `/slow` takes 5s: two requests hold the slots, two wait in the queue
+    // until it times them out and the rest find it full.
+    let client = reqwest::Client::new();
+    let requests = (0..8).map(|_| {
+        let request = client.get(env.proxy_url("slow")).send();
+        async move {
+            let started = std::time::Instant::now();
+            let status = request.await.expect("Failed to make request").status();
+            (status.as_u16(), started.elapsed())
+        }
+    });
+    let results = futures::future::join_all(requests).await;
+
+    let count = |code| results.iter().filter(|(status, _)| *status == code).count();
+    assert_eq!(count(200), 2, "{:?}", results);
+    assert_eq!(count(504), 2, "{:?}", results);
+    assert_eq!(count(503), 4, "{:?}", results);
+    for (status, elapsed) in &results {
+        match status {
+            503 => assert!(*elapsed < Duration::from_millis(900), "{:?}", elapsed),
+            504 => assert!(
+                *elapsed >= Duration::from_secs(1) && *elapsed < Duration::from_secs(4),
+                "{:?}",
+                elapsed
+            ),
+            _ => {}
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_pending_request_cap_refuses_flood_to_stalled_workstation() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let mut env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.limits.max_pending_requests = 3;
+    })
+    .await;
+    env.start_client().await;
+
+    // `/slow` stalls for 5s, so the first three requests stay pending and
+    // the rest are turned away at once.
+    let client = reqwest::Client::new();
+    let requests = (0..10).map(|_| {
+        let request = client
+            .get(env.proxy_url("slow"))
+            .header("accept", "application/json")
+            .send();
+        async move {
+            let started = std::time::Instant::now();
+            let response = request.await.expect("Failed to make request");
+            let status = response.status().as_u16();
+            let body = response.text().await.unwrap();
+            (status, body, started.elapsed())
+        }
+    });
+    let pending = env.server.pending();
+    let (results, peak) = tokio::join!(futures::future::join_all(requests), async {
+        let mut peak = 0;
+        for _ in 0..20 {
+            peak = peak.max(pending.count().await);
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        peak
+    });
+
+    assert_eq!(peak, 3);
+    let refused: Vec<_> = results
+        .iter()
+        .filter(|(status, ..)| *status == 503)
+        .collect();
+    assert_eq!(refused.len(), 7, "{:?}", results);
+    for (_, body, elapsed) in refused {
+        assert!(body.contains("TOO_MANY_PENDING"), "{}", body);
+        assert!(*elapsed < Duration::from_secs(1), "{:?}", elapsed);
+    }
+    assert_eq!(
+        results.iter().filter(|(status, ..)| *status == 200).count(),
+        3
+    );
+    assert_eq!(pending.count().await, 0);
+}
@@ -3,7 +3,14 @@
 
 mod common;
 
-use common::TestEnvironment;
+use common::{LogBuffer, TestEnvironment};
+use std::sync::Arc;
+use tunnel_client::connection::Connection;
+use tunnel_core::{quic, Message, PongMessage};
+use tunnel_server::auth::{AuthError, Authenticator, StaticKeyAuthenticator};
+use tunnel_server::registry::{RegistryEventKind, WorkstationState};
+use tunnel_server::server::TunnelServer;
+use tunnel_server::workstation_counter::{CounterError, WorkstationCounter};
 
 #[tokio::test]
 async fn test_successful_registration() {
@@ -102,3 +109,1038 @@ async fn test_max_workstations_limit() {
 
     assert_eq!(response2.status(), 200);
 }
+
+#[tokio::test]
+async fn test_unexpected_message_is_reported() {
+    let env = TestEnvironment::new().await;
+
+    let mut connection = Connection::new(env.client_config());
+    let (conn, _url) = connection.connect().await.expect("Failed to register");
+
+    let out_of_sequence = Message::Pong(PongMessage {
+        timestamp: 1,
+        server_timestamp: None,
+    });
+    let response = quic::send_and_receive(&conn, &out_of_sequence)
+        .await
+        .expect("Expected an error reply");
+
+    match response {
+        Message::Error(err) => {
+            assert_eq!(err.code, "UNEXPECTED_MESSAGE");
+            assert!(err.message.contains("pong"));
+        }
+        other => panic!("Expected Error message, got {}", other.message_type()),
+    }
+}
+
+#[tokio::test]
+async fn test_reserved_workstation_id_is_rejected() {
+    let env = TestEnvironment::new().await;
+
+    let mut config = env.client_config();
+    config.workstation.id = "admin".to_string();
+
+    let err = Connection::new(config)
+        .connect()
+        .await
+        .expect_err("Reserved id should be rejected");
+    assert!(err.to_string().contains("reserved"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_workstation_id_with_illegal_characters_is_rejected() {
+    let env = TestEnvironment::new().await;
+
+    let mut config = env.client_config();
+    config.workstation.id = "bad/id with spaces".to_string();
+
+    let err = Connection::new(config)
+        .connect()
+        .await
+        .expect_err("Invalid id should be rejected");
+    assert!(err.to_string().contains("may only contain"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_mismatched_alpn_cannot_connect() {
+    let env = TestEnvironment::new().await;
+
+    let mut config = env.client_config();
+    config.tls.alpn = "other-fleet".to_string();
+
+    let err = Connection::new(config)
+        .connect()
+        .await
+        .expect_err("Connection with a foreign ALPN should fail");
+    assert!(err.to_string().contains("ALPN"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_api_key_from_file_authenticates() {
+    let key_dir = tempfile::tempdir().unwrap();
+    let key_path = key_dir.path().join("api_key");
+    std::fs::write(&key_path, "file-based-api-key-minimum-32-characters\n").unwrap();
+
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let server_key_path = key_path.clone();
+    let env = TestEnvironment::new_with_server_config(&workstation_id, move |config| {
+        config.auth.api_key_file = Some(server_key_path.clone());
+        config.load_api_key_file().unwrap();
+    })
+    .await;
+
+    let mut config = env.client_config();
+    config.auth.api_key = String::new();
+    config.auth.api_key_file = Some(key_path);
+    config.load_api_key_file().unwrap();
+    assert_eq!(
+        config.auth.api_key,
+        "file-based-api-key-minimum-32-characters"
+    );
+
+    Connection::new(config)
+        .connect()
+        .await
+        .expect("File-based key should authenticate");
+}
+
+#[tokio::test]
+async fn test_revalidate_evicts_clients_with_rotated_key() {
+    let env = TestEnvironment::new().await;
+
+    let mut connection = Connection::new(env.client_config());
+    let (conn, _url) = connection.connect().await.expect("Failed to register");
+
+    let registry = env.server.registry();
+    assert!(registry
+        .revalidate(&StaticKeyAuthenticator::new(&env.api_key))
+        .await
+        .is_empty());
+
+    let evicted = registry
+        .revalidate(&StaticKeyAuthenticator::new(
+            "rotated-api-key-minimum-32-characters",
+        ))
+        .await;
+    assert_eq!(evicted, vec![env.workstation_id.clone()]);
+    assert!(registry.get(&env.workstation_id).await.is_none());
+
+    let err = tokio::time::timeout(std::time::Duration::from_secs(5), conn.closed())
+        .await
+        .expect("Evicted connection should be closed");
+    assert!(err.to_string().contains("api key revoked"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_application_close_code_is_logged() {
+    // The runtime is single-threaded, so the server and client tasks log
+    // through this thread's subscriber.
+    let logs = LogBuffer::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let mut env = TestEnvironment::new().await;
+    env.start_client().await;
+
+    env.server
+        .registry()
+        .revalidate(&StaticKeyAuthenticator::new(
+            "rotated-api-key-minimum-32-characters",
+        ))
+        .await;
+
+    let expected = "Connection closed: closed by peer with code 0x4001: api key revoked";
+    tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while !logs.contents().contains(expected) {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("close reason not logged:\n{}", logs.contents()));
+    assert!(
+        logs.contents().contains(&format!(
+            "Workstation {} connection closed: closed locally",
+            env.workstation_id
+        )),
+        "{}",
+        logs.contents()
+    );
+}
+
+#[tokio::test]
+async fn test_registry_events_on_register_and_disconnect() {
+    let env = TestEnvironment::new().await;
+    let registry = env.server.registry();
+    let mut events = registry.subscribe();
+
+    let mut connection = Connection::new(env.client_config());
+    let (conn, _url) = connection.connect().await.expect("Failed to register");
+
+    let event = tokio::time::timeout(std::time::Duration::from_secs(5), events.recv())
+        .await
+        .expect("No registered event")
+        .unwrap();
+    assert_eq!(event.workstation_id, env.workstation_id);
+    assert_eq!(event.state, RegistryEventKind::Registered);
+    assert!(event.timestamp > 0);
+
+    conn.close(0u32.into(), b"bye");
+    let event = tokio::time::timeout(std::time::Duration::from_secs(5), events.recv())
+        .await
+        .expect("No reconnecting event")
+        .unwrap();
+    assert_eq!(event.state, RegistryEventKind::Reconnecting);
+
+    registry.unregister(&env.workstation_id).await;
+    let event = tokio::time::timeout(std::time::Duration::from_secs(5), events.recv())
+        .await
+        .expect("No disconnected event")
+        .unwrap();
+    assert_eq!(event.workstation_id, env.workstation_id);
+    assert_eq!(event.state, RegistryEventKind::Disconnected);
+}
+
+#[tokio::test]
+async fn test_registry_events_are_posted_to_webhook() {
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<serde_json::Value>(8);
+    let receiver = axum::Router::new().route(
+        "/events",
+        axum::routing::post(move |axum::Json(body): axum::Json<serde_json::Value>| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send(body).await;
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let webhook = format!("http://{}/events", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, receiver).await.unwrap() });
+
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let env = TestEnvironment::new_with_server_config(&workstation_id, move |config| {
+        config.server.event_webhook = vec![webhook.clone()];
+    })
+    .await;
+
+    let mut connection = Connection::new(env.client_config());
+    let (_conn, _url) = connection.connect().await.expect("Failed to register");
+
+    let payload = tokio::time::timeout(std::time::Duration::from_secs(5), rx.recv())
+        .await
+        .expect("Webhook was not called")
+        .unwrap();
+    assert_eq!(payload["workstation_id"], workstation_id.as_str());
+    assert_eq!(payload["state"], "registered");
+    assert!(payload["timestamp"].as_u64().unwrap() > 0);
+}
+
+struct SignedTokenAuthenticator {
+    secret: &'static str,
+}
+
+fn sign_token(secret: &str, workstation_id: &str) -> String {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    secret.hash(&mut hasher);
+    workstation_id.hash(&mut hasher);
+    format!("{}.{:016x}", workstation_id, hasher.finish())
+}
+
+#[async_trait::async_trait]
+impl Authenticator for SignedTokenAuthenticator {
+    async fn authenticate(&self, workstation_id: &str, credential: &str) -> Result<(), AuthError> {
+        if credential == sign_token(self.secret, workstation_id) {
+            Ok(())
+        } else {
+            Err(AuthError::Rejected("token signature mismatch".to_string()))
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_custom_authenticator_validates_signed_token() {
+    let http_port = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let quic_port = std::net::UdpSocket::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let mut config = tunnel_server::config::Config::default();
+    config.server.domain = "localhost".to_string();
+    config.server.http_port = http_port;
+    config.server.https_port = quic_port;
+    config.tls.enabled = false;
+    config.auth.api_key = "unused-shared-key-unused-shared-key".to_string();
+
+    let server = Arc::new(
+        TunnelServer::new(config).with_authenticator(SignedTokenAuthenticator { secret: "s3cret" }),
+    );
+    let server_handle = tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let mut client_config = tunnel_client::config::Config::default();
+    client_config.server.address = format!("127.0.0.1:{}", quic_port);
+    client_config.workstation.id = workstation_id.clone();
+    client_config.session.ticket_path =
+        std::env::temp_dir().join(format!("tunnel-test-{}.ticket", rand::random::<u32>()));
+
+    client_config.auth.api_key = sign_token("s3cret", "someone-else");
+    let err = Connection::new(client_config.clone())
+        .connect()
+        .await
+        .expect_err("Token for another workstation should be rejected");
+    assert!(err.to_string().contains("signature mismatch"), "{}", err);
+
+    client_config.auth.api_key = sign_token("s3cret", &workstation_id);
+    Connection::new(client_config)
+        .connect()
+        .await
+        .expect("Signed token should be accepted");
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_handshake_retries_until_server_comes_up() {
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    let http_port = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let quic_port = std::net::UdpSocket::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let api_key = "test-api-key-minimum-32-characters-long".to_string();
+
+    let mut client_config = tunnel_client::config::Config::default();
+    client_config.server.address = format!("127.0.0.1:{}", quic_port);
+    client_config.auth.api_key = api_key.clone();
+    client_config.workstation.id = format!("test-ws-{}", rand::random::<u16>());
+    client_config.session.ticket_path =
+        std::env::temp_dir().join(format!("tunnel-test-{}.ticket", rand::random::<u32>()));
+    // Each attempt against the missing server gives up after a second.
+    client_config.quic.max_idle_timeout = 1;
+    client_config.reconnect.handshake_retries = 10;
+    client_config.reconnect.handshake_retry_delay = 100;
+
+    let connect = tokio::spawn(async move {
+        let mut connection = Connection::new(client_config);
+        connection
+            .connect()
+            .await
+            .map(|(conn, url)| (connection, conn, url))
+    });
+
+    tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+    assert!(!connect.is_finished(), "Connect should still be retrying");
+
+    let mut config = tunnel_server::config::Config::default();
+    config.server.domain = "localhost".to_string();
+    config.server.http_port = http_port;
+    config.server.https_port = quic_port;
+    config.tls.enabled = false;
+    config.auth.api_key = api_key;
+    let server = Arc::new(TunnelServer::new(config));
+    let server_handle = tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+
+    let (_connection, _conn, url) =
+        tokio::time::timeout(std::time::Duration::from_secs(10), connect)
+            .await
+            .expect("Connect should finish within the retries")
+            .unwrap()
+            .expect("Client should connect once the server is up");
+    assert!(url.contains("/t/test-ws-"), "{}", url);
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_connect_timeout_against_unresponsive_server() {
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    // Bound but never read, so the handshake gets no answer at all.
+    let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+
+    let mut config = tunnel_client::config::Config::default();
+    config.server.address = silent.local_addr().unwrap().to_string();
+    config.auth.api_key = "test-api-key-minimum-32-characters-long".to_string();
+    config.workstation.id = format!("test-ws-{}", rand::random::<u16>());
+    config.session.ticket_path =
+        std::env::temp_dir().join(format!("tunnel-test-{}.ticket", rand::random::<u32>()));
+    config.reconnect.connect_timeout = 1;
+
+    let started = std::time::Instant::now();
+    let err = Connection::new(config)
+        .connect()
+        .await
+        .expect_err("Connect to a silent server should time out");
+    let elapsed = started.elapsed();
+    assert!(
+        matches!(err, tunnel_core::Error::ConnectTimeout(limit) if limit.as_secs() == 1),
+        "{}",
+        err
+    );
+    assert!(
+        elapsed >= std::time::Duration::from_secs(1) && elapsed < std::time::Duration::from_secs(3),
+        "timed out after {:?}",
+        elapsed
+    );
+}
+
+#[tokio::test]
+async fn test_rejected_api_key_is_not_retried() {
+    let env = TestEnvironment::new().await;
+
+    let mut config = env.client_config();
+    config.auth.api_key = "wrong-api-key-wrong-api-key-wrong-key".to_string();
+    config.reconnect.handshake_retries = 5;
+    config.reconnect.handshake_retry_delay = 2000;
+
+    let started = std::time::Instant::now();
+    let err = Connection::new(config)
+        .connect()
+        .await
+        .expect_err("Wrong API key should be rejected");
+    assert!(err.to_string().contains("server error"), "{}", err);
+    assert!(
+        started.elapsed() < std::time::Duration::from_secs(2),
+        "rejected after {:?}",
+        started.elapsed()
+    );
+}
+
+#[tokio::test]
+async fn test_reconnect_with_valid_token() {
+    let env = TestEnvironment::new().await;
+
+    let mut connection = Connection::new(env.client_config());
+    connection.connect().await.expect("Failed to register");
+    assert!(connection.reconnect_token().is_some());
+
+    connection
+        .migrate()
+        .await
+        .expect("Reconnect with the issued token should succeed");
+}
+
+#[tokio::test]
+async fn test_reconnect_with_wrong_token_is_rejected() {
+    let env = TestEnvironment::new().await;
+
+    let mut owner = Connection::new(env.client_config());
+    owner.connect().await.expect("Failed to register");
+    let registry = env.server.registry();
+    let owner_addr = registry
+        .get(&env.workstation_id)
+        .await
+        .unwrap()
+        .connection
+        .remote_address();
+
+    // The API key alone is not enough to take over the workstation.
+    let mut intruder = Connection::new(env.client_config());
+    let err = intruder
+        .migrate()
+        .await
+        .expect_err("Reconnect without a token should be rejected");
+    assert!(
+        err.to_string().contains("invalid reconnect token"),
+        "{}",
+        err
+    );
+
+    intruder.set_reconnect_token("0".repeat(32));
+    let err = intruder
+        .migrate()
+        .await
+        .expect_err("Reconnect with a wrong token should be rejected");
+    assert!(
+        err.to_string().contains("invalid reconnect token"),
+        "{}",
+        err
+    );
+
+    let info = registry.get(&env.workstation_id).await.unwrap();
+    assert_eq!(info.connection.remote_address(), owner_addr);
+}
+
+#[tokio::test]
+async fn test_reconnect_token_rotates_on_each_reconnect() {
+    let env = TestEnvironment::new().await;
+
+    let mut connection = Connection::new(env.client_config());
+    connection.connect().await.expect("Failed to register");
+    let first = connection.reconnect_token().unwrap().to_string();
+
+    connection.migrate().await.expect("First reconnect failed");
+    let second = connection.reconnect_token().unwrap().to_string();
+    assert_ne!(first, second);
+
+    connection.migrate().await.expect("Second reconnect failed");
+    let third = connection.reconnect_token().unwrap().to_string();
+    assert_ne!(second, third);
+
+    // Tokens already used are no longer accepted.
+    for stale in [first, second] {
+        let mut replay = Connection::new(env.client_config());
+        replay.set_reconnect_token(stale);
+        let err = replay
+            .migrate()
+            .await
+            .expect_err("A used token should be rejected");
+        assert!(
+            err.to_string().contains("invalid reconnect token"),
+            "{}",
+            err
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_standby_takes_over_when_primary_fails() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.reliability.allow_standby = true;
+    })
+    .await;
+    let registry = env.server.registry();
+
+    let (primary, _url) = tunnel_client::handle::TunnelClientHandle::connect(env.client_config())
+        .await
+        .expect("Primary failed to register");
+    let (_standby, _url) = tunnel_client::handle::TunnelClientHandle::connect(env.client_config())
+        .await
+        .expect("Standby failed to register");
+
+    let info = registry.get(&workstation_id).await.unwrap();
+    assert_eq!(info.standbys.len(), 1);
+    let standby_addr = info.standbys[0].connection.remote_address();
+    assert_ne!(info.connection.remote_address(), standby_addr);
+
+    let response = reqwest::get(env.proxy_url("health")).await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    primary.connection().close(0u32.into(), b"machine died");
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+    let info = registry.get(&workstation_id).await.unwrap();
+    assert_eq!(info.state, WorkstationState::Active);
+    assert_eq!(info.connection.remote_address(), standby_addr);
+    assert!(info.standbys.is_empty());
+
+    for _ in 0..3 {
+        let response = reqwest::get(env.proxy_url("health")).await.unwrap();
+        assert_eq!(response.status(), 200);
+    }
+}
+
+/// Polls the client's control API over TCP until it reports `connected`.
+async fn wait_for_connected_status(addr: &str) -> serde_json::Value {
+    let url = format!("http://{}/status", addr);
+    for _ in 0..100 {
+        if let Ok(response) = reqwest::get(&url).await {
+            let status: serde_json::Value = response.json().await.unwrap();
+            if status["state"] == "connected" {
+                return status;
+            }
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    panic!("Client never reported a connection on {}", url);
+}
+
+#[tokio::test]
+async fn test_control_socket_reports_tunnel_url() {
+    let env = TestEnvironment::new().await;
+    let control_addr = {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().to_string()
+    };
+    let mut config = env.client_config();
+    config.workstation.control_socket = Some(control_addr.clone());
+    let client = tokio::spawn(async move {
+        let _ = tunnel_client::client::TunnelClient::new(config).run().await;
+    });
+
+    let status = wait_for_connected_status(&control_addr).await;
+    assert_eq!(status["workstation_id"], env.workstation_id.as_str());
+    assert_eq!(status["reconnect_attempts"], 0);
+    let url = status["url"].as_str().unwrap();
+    assert!(
+        url.ends_with(&format!("/t/{}", env.workstation_id)),
+        "{}",
+        url
+    );
+
+    let response = reqwest::get(format!("http://{}/other", control_addr))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    client.abort();
+}
+
+/// Reads `name` from the Prometheus text served on `addr`.
+async fn scrape_metric(addr: &str, name: &str) -> Option<f64> {
+    let text = reqwest::get(format!("http://{}/metrics", addr))
+        .await
+        .ok()?
+        .text()
+        .await
+        .ok()?;
+    text.lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(' ')?.parse().ok())
+}
+
+#[tokio::test]
+async fn test_client_metrics_count_proxied_requests() {
+    let env = TestEnvironment::new().await;
+    let metrics_addr = {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().to_string()
+    };
+    let mut config = env.client_config();
+    config.workstation.metrics_address = Some(metrics_addr.clone());
+    config.workstation.health_check_path = Some("/health".to_string());
+    let client = tokio::spawn(async move {
+        let _ = tunnel_client::client::TunnelClient::new(config).run().await;
+    });
+
+    for _ in 0..100 {
+        if scrape_metric(&metrics_addr, "tunnel_client_connected").await == Some(1.0) {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(
+        scrape_metric(&metrics_addr, "tunnel_client_http_requests_total").await,
+        Some(0.0)
+    );
+
+    for _ in 0..3 {
+        let response = reqwest::get(env.proxy_url("health")).await.unwrap();
+        assert_eq!(response.status(), 200);
+    }
+    let response = reqwest::Client::new()
+        .post(env.proxy_url("echo"))
+        .body("hello")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.text().await.unwrap(), "Echo: hello");
+
+    let metric = |name| scrape_metric(&metrics_addr, name);
+    assert_eq!(metric("tunnel_client_http_requests_total").await, Some(4.0));
+    assert_eq!(metric("tunnel_client_http_failures_total").await, Some(0.0));
+    assert_eq!(
+        metric("tunnel_client_http_request_bytes_total").await,
+        Some(5.0)
+    );
+    // "OK" three times and "Echo: hello".
+    assert_eq!(
+        metric("tunnel_client_http_response_bytes_total").await,
+        Some(17.0)
+    );
+    assert_eq!(metric("tunnel_client_reconnects_total").await, Some(0.0));
+    assert_eq!(metric("tunnel_client_backend_healthy").await, Some(1.0));
+
+    let response = reqwest::get(format!("http://{}/other", metrics_addr))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    client.abort();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_control_socket_on_unix_path() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let env = TestEnvironment::new().await;
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("client.sock");
+    let mut config = env.client_config();
+    config.workstation.control_socket = Some(path.to_string_lossy().into_owned());
+    let client = tokio::spawn(async move {
+        let _ = tunnel_client::client::TunnelClient::new(config).run().await;
+    });
+
+    let mut body = String::new();
+    for _ in 0..100 {
+        if let Ok(mut stream) = tokio::net::UnixStream::connect(&path).await {
+            stream
+                .write_all(b"GET /status HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                .await
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+            body = response.split("\r\n\r\n").nth(1).unwrap().to_string();
+            if body.contains("\"connected\"") {
+                break;
+            }
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    let status: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(status["state"], "connected");
+    assert!(status["url"]
+        .as_str()
+        .unwrap()
+        .ends_with(&format!("/t/{}", env.workstation_id)));
+
+    client.abort();
+    let _ = client.await;
+    assert!(!path.exists(), "socket file left behind");
+}
+
+/// Relays UDP datagrams to `upstream`, holding each one back for `delay` in
+/// both directions, as if the server were far away.
+async fn spawn_delay_relay(
+    upstream: std::net::SocketAddr,
+    delay: std::time::Duration,
+) -> std::net::SocketAddr {
+    use tokio::net::UdpSocket;
+
+    let front = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+    let addr = front.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut backs = std::collections::HashMap::new();
+        let mut buf = vec![0u8; 65536];
+        while let Ok((n, client)) = front.recv_from(&mut buf).await {
+            if let std::collections::hash_map::Entry::Vacant(entry) = backs.entry(client) {
+                let back = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+                back.connect(upstream).await.unwrap();
+                let (front, reader) = (front.clone(), back.clone());
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 65536];
+                    while let Ok(n) = reader.recv(&mut buf).await {
+                        let (front, packet) = (front.clone(), buf[..n].to_vec());
+                        tokio::spawn(async move {
+                            tokio::time::sleep(delay).await;
+                            let _ = front.send_to(&packet, client).await;
+                        });
+                    }
+                });
+                entry.insert(back);
+            }
+            let (back, packet) = (backs[&client].clone(), buf[..n].to_vec());
+            tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                let _ = back.send(&packet).await;
+            });
+        }
+    });
+    addr
+}
+
+#[tokio::test]
+async fn test_zero_rtt_reconnect_saves_a_round_trip() {
+    let env = TestEnvironment::new().await;
+    let relay = spawn_delay_relay(
+        ([127, 0, 0, 1], env.server_quic_port).into(),
+        std::time::Duration::from_millis(50),
+    )
+    .await;
+    let mut config = env.client_config();
+    config.server.address = relay.to_string();
+
+    let mut connection = Connection::new(config.clone());
+    let (first, _url) = connection.connect().await.expect("Failed to register");
+    assert!(!connection.resumed_early());
+    first.close(0u32.into(), b"network change");
+
+    let started = std::time::Instant::now();
+    let (resumed, url) = connection.connect().await.expect("Reconnect failed");
+    let early = started.elapsed();
+    assert!(connection.resumed_early(), "reconnect did not use 0-RTT");
+    assert!(url.ends_with(&format!("/t/{}", env.workstation_id)));
+    tunnel_client::ping::ping(&resumed)
+        .await
+        .expect("Resumed connection is not served");
+    let info = env
+        .server
+        .registry()
+        .get(&env.workstation_id)
+        .await
+        .unwrap();
+    assert_eq!(info.state, WorkstationState::Active);
+
+    // The same reconnect without a TLS session to resume.
+    let mut fresh = Connection::new(config);
+    fresh.set_reconnect_token(connection.reconnect_token().unwrap().to_string());
+    let started = std::time::Instant::now();
+    fresh
+        .migrate()
+        .await
+        .expect("Full handshake reconnect failed");
+    let full = started.elapsed();
+    assert!(!fresh.resumed_early());
+
+    // Each round trip through the relay takes 100ms; 0-RTT saves one.
+    assert!(
+        early + std::time::Duration::from_millis(50) < full,
+        "0-RTT took {:?}, full handshake {:?}",
+        early,
+        full
+    );
+}
+
+/// Relays UDP datagrams to `upstream`. While the returned buffer is `Some`,
+/// datagrams from clients the relay has not seen yet are kept there instead
+/// of being forwarded, as an attacker capturing a first flight would.
+async fn spawn_capture_relay(
+    upstream: std::net::SocketAddr,
+) -> (
+    std::net::SocketAddr,
+    Arc<std::sync::Mutex<Option<Vec<Vec<u8>>>>>,
+) {
+    use tokio::net::UdpSocket;
+
+    let front = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+    let addr = front.local_addr().unwrap();
+    let captured = Arc::new(std::sync::Mutex::new(None::<Vec<Vec<u8>>>));
+    let capture = captured.clone();
+    tokio::spawn(async move {
+        let mut backs = std::collections::HashMap::new();
+        let mut buf = vec![0u8; 65536];
+        while let Ok((n, client)) = front.recv_from(&mut buf).await {
+            if let std::collections::hash_map::Entry::Vacant(entry) = backs.entry(client) {
+                if let Some(held) = capture.lock().unwrap().as_mut() {
+                    held.push(buf[..n].to_vec());
+                    continue;
+                }
+                let back = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+                back.connect(upstream).await.unwrap();
+                let (front, reader) = (front.clone(), back.clone());
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 65536];
+                    while let Ok(n) = reader.recv(&mut buf).await {
+                        let _ = front.send_to(&buf[..n], client).await;
+                    }
+                });
+                entry.insert(back);
+            }
+            let _ = backs[&client].send(&buf[..n]).await;
+        }
+    });
+    (addr, captured)
+}
+
+#[tokio::test]
+async fn test_replayed_early_reconnect_leaves_live_connection_alone() {
+    let env = TestEnvironment::new().await;
+    let server: std::net::SocketAddr = ([127, 0, 0, 1], env.server_quic_port).into();
+    let (relay, captured) = spawn_capture_relay(server).await;
+    let mut config = env.client_config();
+    config.server.address = relay.to_string();
+
+    let mut connection = Connection::new(config);
+    let (live, _url) = connection.connect().await.expect("Failed to register");
+    let registry = env.server.registry();
+    let live_id = registry
+        .get(&env.workstation_id)
+        .await
+        .unwrap()
+        .connection
+        .stable_id();
+
+    // Capture the first flight of a 0-RTT reconnect without delivering it.
+    *captured.lock().unwrap() = Some(Vec::new());
+    let attempt =
+        tokio::time::timeout(std::time::Duration::from_millis(500), connection.migrate()).await;
+    assert!(attempt.is_err(), "reconnect should not reach the server");
+    let flight = captured.lock().unwrap().take().unwrap();
+    assert!(!flight.is_empty());
+
+    // Replay it from another address, never answering the server.
+    let replayer = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    for datagram in &flight {
+        replayer.send_to(datagram, server).await.unwrap();
+    }
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    let info = registry.get(&env.workstation_id).await.unwrap();
+    assert_eq!(info.state, WorkstationState::Active);
+    assert_eq!(info.connection.stable_id(), live_id);
+    assert!(live.close_reason().is_none());
+    tunnel_client::ping::ping(&live)
+        .await
+        .expect("Live connection is no longer served");
+}
+
+#[tokio::test]
+async fn test_reconnect_falls_back_to_register_after_restart() {
+    let mut env = TestEnvironment::new().await;
+
+    let mut connection = Connection::new(env.client_config());
+    let (first, _url) = connection.connect().await.expect("Failed to register");
+    first.close(0u32.into(), b"network change");
+    env.restart_server().await;
+
+    // The new server knows neither the TLS session nor the workstation.
+    let (_connection, url) = connection
+        .connect()
+        .await
+        .expect("Client should register again");
+    assert!(!connection.resumed_early());
+    assert!(url.ends_with(&format!("/t/{}", env.workstation_id)));
+    let info = env
+        .server
+        .registry()
+        .get(&env.workstation_id)
+        .await
+        .unwrap();
+    assert_eq!(info.state, WorkstationState::Active);
+}
+
+#[tokio::test]
+async fn test_in_memory_counter_enforces_workstation_cap() {
+    use tunnel_client::testing::{client_config, TestServer};
+
+    let server = TestServer::start_with(|config| config.limits.max_workstations = 1)
+        .await
+        .expect("Server failed to start");
+    let first = client_config(&server, "http://127.0.0.1:1");
+    let first_id = first.workstation.id.clone();
+    let (_first_conn, _url) = Connection::new(first)
+        .connect()
+        .await
+        .expect("First registration failed");
+
+    let second = client_config(&server, "http://127.0.0.1:1");
+    let err = Connection::new(second.clone())
+        .connect()
+        .await
+        .expect_err("Registration over the cap should be rejected");
+    assert!(err.to_string().contains("Maximum workstations"), "{}", err);
+
+    server.server.registry().unregister(&first_id).await;
+    let (_second_conn, _url) = Connection::new(second)
+        .connect()
+        .await
+        .expect("Unregistering should free the slot");
+
+    server.shutdown().await;
+}
+
+/// Stands in for a shared backend such as Redis: every server built with a
+/// clone sees the same slots.
+#[derive(Clone, Default)]
+struct SharedCounter {
+    held: Arc<std::sync::Mutex<std::collections::HashSet<String>>>,
+    refreshed: Arc<std::sync::Mutex<Vec<String>>>,
+}
+
+#[async_trait::async_trait]
+impl WorkstationCounter for SharedCounter {
+    async fn try_acquire(&self, workstation_id: &str, max: usize) -> Result<bool, CounterError> {
+        let mut held = self.held.lock().unwrap();
+        Ok(held.contains(workstation_id) || held.len() < max && held.insert(workstation_id.into()))
+    }
+
+    async fn release(&self, workstation_id: &str) {
+        self.held.lock().unwrap().remove(workstation_id);
+    }
+
+    async fn refresh(&self, workstation_ids: &[String]) {
+        self.refreshed
+            .lock()
+            .unwrap()
+            .extend_from_slice(workstation_ids);
+    }
+}
+
+#[tokio::test]
+async fn test_server_refreshes_counter_slots() {
+    use tunnel_client::testing::{client_config, TestServer};
+
+    let counter = SharedCounter::default();
+    let server = TestServer::start_custom(|config| config.reliability.cleanup_interval = 1, {
+        let counter = counter.clone();
+        |server| server.with_workstation_counter(counter)
+    })
+    .await
+    .expect("Server failed to start");
+
+    let config = client_config(&server, "http://127.0.0.1:1");
+    let id = config.workstation.id.clone();
+    let (_conn, _url) = Connection::new(config)
+        .connect()
+        .await
+        .expect("Registration failed");
+
+    tokio::time::sleep(std::time::Duration::from_millis(2500)).await;
+    assert!(
+        counter.refreshed.lock().unwrap().contains(&id),
+        "the cleanup tick should refresh the workstation's slot"
+    );
+
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_shared_counter_caps_workstations_across_servers() {
+    use tunnel_client::testing::{client_config, TestServer};
+
+    let counter = SharedCounter::default();
+    let mut servers = Vec::new();
+    for _ in 0..2 {
+        let counter = counter.clone();
+        let server = TestServer::start_custom(
+            |config| config.limits.max_workstations = 1,
+            |server| server.with_workstation_counter(counter),
+        )
+        .await
+        .expect("Server failed to start");
+        servers.push(server);
+    }
+
+    let first = client_config(&servers[0], "http://127.0.0.1:1");
+    let first_id = first.workstation.id.clone();
+    let (_first_conn, _url) = Connection::new(first)
+        .connect()
+        .await
+        .expect("First registration failed");
+
+    // The second server has no workstations of its own, but the shared cap is
+    // already taken.
+    let second = client_config(&servers[1], "http://127.0.0.1:1");
+    let err = Connection::new(second.clone())
+        .connect()
+        .await
+        .expect_err("Shared cap should reject the registration");
+    assert!(err.to_string().contains("Maximum workstations"), "{}", err);
+
+    servers[0].server.registry().unregister(&first_id).await;
+    assert!(counter.held.lock().unwrap().is_empty());
+    let (_second_conn, _url) = Connection::new(second)
+        .connect()
+        .await
+        .expect("Releasing on one server should free the shared slot");
+
+    for server in servers {
+        server.shutdown().await;
+    }
+}
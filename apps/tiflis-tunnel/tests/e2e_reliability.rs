@@ -4,6 +4,7 @@
 mod common;
 
 use common::TestEnvironment;
+use tunnel_client::connection::Connection;
 
 #[tokio::test]
 async fn test_request_timeout() {
@@ -112,6 +113,144 @@ async fn test_grace_period_expiration() {
     assert_eq!(response2.status(), 404);
 }
 
+#[tokio::test]
+async fn test_client_registers_again_after_grace_period_expires() {
+    use tunnel_server::registry::WorkstationState;
+
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.reliability.grace_period = 1;
+        // Keeps the expired entry around, so the reconnect is refused for
+        // the grace period rather than for an unknown workstation.
+        config.reliability.cleanup_interval = 3600;
+    })
+    .await;
+    let registry = env.server.registry();
+
+    let mut connection = Connection::new(env.client_config());
+    let (first, _url) = connection.connect().await.expect("Failed to register");
+    let stale_token = connection.reconnect_token().unwrap().to_string();
+    first.close(0u32.into(), b"going away");
+
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+    while registry.get(&workstation_id).await.unwrap().state == WorkstationState::Active {
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "never marked reconnecting"
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+
+    // Another process holding the old token is told the grace period is over.
+    let mut late = Connection::new(env.client_config());
+    late.set_reconnect_token(stale_token.clone());
+    let err = late
+        .migrate()
+        .await
+        .expect_err("Expired reconnect should fail");
+    assert!(err.to_string().contains("grace period expired"), "{}", err);
+
+    let (_second, url) = connection
+        .connect()
+        .await
+        .expect("Client should register again");
+    assert!(url.ends_with(&workstation_id), "{}", url);
+    assert_ne!(connection.reconnect_token(), Some(stale_token.as_str()));
+
+    let info = registry.get(&workstation_id).await.unwrap();
+    assert_eq!(info.state, WorkstationState::Active);
+    assert!(info.connection.close_reason().is_none());
+}
+
+#[tokio::test]
+async fn test_cleanup_evicts_only_expired_workstations() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.reliability.grace_period = 2;
+        // Keep the background sweep out of the way; the test drives it.
+        config.reliability.cleanup_interval = 3600;
+    })
+    .await;
+    let registry = env.server.registry();
+
+    let mut active = Connection::new(env.client_config());
+    let (_active_conn, _url) = active.connect().await.expect("Failed to register");
+
+    let stale_id = format!("{}-stale", env.workstation_id);
+    let mut stale_config = env.client_config();
+    stale_config.workstation.id = stale_id.clone();
+    let mut stale = Connection::new(stale_config);
+    let (stale_conn, _url) = stale.connect().await.expect("Failed to register");
+
+    let info = registry.get(&stale_id).await.unwrap();
+    registry
+        .mark_reconnecting(&stale_id, &info.connection)
+        .await;
+
+    registry.cleanup_expired_at(std::time::Instant::now()).await;
+    assert!(
+        registry.get(&stale_id).await.is_some(),
+        "evicted within the grace period"
+    );
+
+    registry
+        .cleanup_expired_at(std::time::Instant::now() + std::time::Duration::from_secs(3))
+        .await;
+    assert!(registry.get(&stale_id).await.is_none());
+    assert!(registry.get(&env.workstation_id).await.is_some());
+
+    let err = tokio::time::timeout(std::time::Duration::from_secs(5), stale_conn.closed())
+        .await
+        .expect("Evicted connection should be closed");
+    assert!(err.to_string().contains("grace period expired"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_silent_workstation_is_reaped() {
+    use tunnel_server::registry::WorkstationState;
+
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.reliability.liveness_timeout = 1;
+        config.reliability.cleanup_interval = 1;
+        config.reliability.grace_period = 1;
+    })
+    .await;
+    let registry = env.server.registry();
+
+    let mut chatty = Connection::new(env.client_config());
+    let (chatty_conn, _url) = chatty.connect().await.expect("Failed to register");
+
+    // Registers and then never sends anything, like a half-open connection.
+    let silent_id = format!("{}-silent", env.workstation_id);
+    let mut silent_config = env.client_config();
+    silent_config.workstation.id = silent_id.clone();
+    let mut silent = Connection::new(silent_config);
+    let (silent_conn, _url) = silent.connect().await.expect("Failed to register");
+
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(10);
+    while registry.get(&silent_id).await.is_some() {
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "silent workstation was not evicted"
+        );
+        tunnel_client::ping::ping(&chatty_conn)
+            .await
+            .expect("Ping failed");
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    }
+
+    let chatty_info = registry.get(&env.workstation_id).await.unwrap();
+    assert_eq!(chatty_info.state, WorkstationState::Active);
+    assert_eq!(registry.count().await, 1);
+
+    let err = tokio::time::timeout(std::time::Duration::from_secs(5), silent_conn.closed())
+        .await
+        .expect("Reaped connection should be closed");
+    assert!(err.to_string().contains("liveness timeout"), "{}", err);
+}
+
 #[tokio::test]
 async fn test_in_flight_requests_during_disconnect() {
     let mut env = TestEnvironment::new_with_grace_period(10).await;
@@ -158,3 +297,267 @@ async fn test_0rtt_reconnection_with_session_tickets() {
     let reconnect_duration = start.elapsed();
     println!("Reconnection took: {:?}", reconnect_duration);
 }
+
+#[tokio::test]
+async fn test_request_waits_for_quick_reconnect() {
+    let mut env = TestEnvironment::new_with_grace_period(10).await;
+    env.start_client().await;
+
+    let response = reqwest::get(&env.proxy_url("health"))
+        .await
+        .expect("Failed to make request");
+    assert_eq!(response.status(), 200);
+
+    env.stop_client();
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+    let url = env.proxy_url("health");
+    let request_handle = tokio::spawn(async move { reqwest::get(&url).await });
+
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert!(!request_handle.is_finished());
+
+    env.start_client().await;
+
+    let response = request_handle
+        .await
+        .unwrap()
+        .expect("Request should survive the reconnect");
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "OK");
+}
+
+#[tokio::test]
+async fn test_custom_quic_idle_timeout_closes_idle_connection() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.quic.max_idle_timeout = 2;
+    })
+    .await;
+
+    let mut connection = tunnel_client::connection::Connection::new(env.client_config());
+    let (conn, _url) = connection.connect().await.expect("Failed to register");
+
+    let idle_since = std::time::Instant::now();
+    let err = tokio::time::timeout(std::time::Duration::from_secs(10), conn.closed())
+        .await
+        .expect("Idle connection should be closed by the idle timeout");
+    let idle_for = idle_since.elapsed();
+
+    assert!(err.to_string().contains("timed out"), "{}", err);
+    assert!(
+        idle_for >= std::time::Duration::from_millis(1500)
+            && idle_for < std::time::Duration::from_secs(5),
+        "closed after {:?}",
+        idle_for
+    );
+}
+
+#[tokio::test]
+async fn test_timed_out_request_leaves_no_pending_entry() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let mut env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.reliability.request_timeout = 1;
+    })
+    .await;
+    env.start_client().await;
+
+    let pending = env.server.pending();
+    let url = env.proxy_url("slow");
+    let request = tokio::spawn(async move { reqwest::get(url).await });
+
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    assert_eq!(pending.count().await, 1);
+
+    let response = request.await.unwrap().expect("Failed to make request");
+    assert_eq!(response.status(), 504);
+    assert_eq!(pending.count().await, 0);
+}
+
+#[tokio::test]
+async fn test_local_request_timeout_answers_before_server_timeout() {
+    let env = TestEnvironment::new().await;
+
+    let mut config = env.client_config();
+    config.workstation.local_request_timeout = 1;
+    let (_handle, _url) = tunnel_client::handle::TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let started = std::time::Instant::now();
+    let response = reqwest::get(env.proxy_url("slow"))
+        .await
+        .expect("Failed to make request");
+
+    assert_eq!(response.status(), 504);
+    assert!(
+        started.elapsed() < std::time::Duration::from_secs(4),
+        "answered after {:?}",
+        started.elapsed()
+    );
+    assert_eq!(response.text().await.unwrap(), "local backend timed out");
+}
+
+#[tokio::test]
+async fn test_max_request_duration_cuts_off_dribbling_body() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.reliability.max_request_duration = 1;
+    })
+    .await;
+
+    // Headers go out at once; the body trickles in over several seconds.
+    let app = axum::Router::new()
+        .route(
+            "/dribble",
+            axum::routing::get(|| async {
+                let stream = futures::stream::unfold(0, |sent| async move {
+                    if sent == 20 {
+                        return None;
+                    }
+                    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+                    Some((
+                        Ok::<_, std::io::Error>(bytes::Bytes::from_static(b"x")),
+                        sent + 1,
+                    ))
+                });
+                axum::body::Body::from_stream(stream)
+            }),
+        )
+        .route("/quick", axum::routing::get(|| async { "ok" }));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let mut config = env.client_config();
+    config.workstation.local_address = format!("http://127.0.0.1:{}", backend_port);
+    let (_handle, _url) = tunnel_client::handle::TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let started = std::time::Instant::now();
+    let response = reqwest::get(env.proxy_url("dribble"))
+        .await
+        .expect("Failed to make request");
+
+    assert_eq!(response.status(), 504);
+    assert!(
+        started.elapsed() < std::time::Duration::from_secs(4),
+        "answered after {:?}",
+        started.elapsed()
+    );
+
+    let response = reqwest::get(env.proxy_url("quick")).await.unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_sighup_reloads_request_timeout_without_reconnecting() {
+    use std::sync::Arc;
+    use tunnel_server::server::TunnelServer;
+
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let app = axum::Router::new().fallback(|| async {
+            tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+            "slow"
+        });
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let api_key = "test-api-key-minimum-32-characters-long";
+    let config_path =
+        std::env::temp_dir().join(format!("tunnel-reload-{}.toml", rand::random::<u32>()));
+    let write_config = |request_timeout: u64| {
+        let toml = format!(
+            "[server]\ndomain = \"localhost\"\nhttp_port = 0\nhttps_port = 0\nquic_port = 0\n\
+             [tls]\nenabled = false\n[auth]\napi_key = \"{}\"\n\
+             [reliability]\nrequest_timeout = {}\n[limits]\n",
+            api_key, request_timeout
+        );
+        std::fs::write(&config_path, toml).unwrap();
+    };
+    write_config(1);
+
+    let config = tunnel_server::config::Config::load(Some(config_path.clone())).unwrap();
+    let server = Arc::new(TunnelServer::new(config));
+    server
+        .clone()
+        .reload_on_sighup(Some(config_path.clone()))
+        .expect("Failed to watch SIGHUP");
+    tokio::spawn({
+        let server = server.clone();
+        async move { server.run().await }
+    });
+    let http_port = server.http_addr().await.port();
+    let quic_port = server.quic_addr().await.port();
+
+    let mut client_config = tunnel_client::config::Config::default();
+    client_config.server.address = format!("127.0.0.1:{}", quic_port);
+    client_config.auth.api_key = api_key.to_string();
+    client_config.workstation.id = format!("test-ws-{}", rand::random::<u16>());
+    client_config.workstation.local_address = format!("http://127.0.0.1:{}", backend_port);
+    client_config.session.ticket_path =
+        std::env::temp_dir().join(format!("tunnel-test-{}.ticket", rand::random::<u32>()));
+    let workstation_id = client_config.workstation.id.clone();
+    let (_handle, _url) = tunnel_client::handle::TunnelClientHandle::connect(client_config)
+        .await
+        .expect("Failed to connect");
+    let connection_id = server
+        .registry()
+        .get(&workstation_id)
+        .await
+        .expect("Workstation not registered")
+        .connection
+        .stable_id();
+
+    let url = format!("http://127.0.0.1:{}/t/{}/slow", http_port, workstation_id);
+    let response = reqwest::get(&url).await.unwrap();
+    assert_eq!(response.status(), 504, "2s backend within a 1s timeout");
+
+    write_config(5);
+    let status = std::process::Command::new("kill")
+        .args(["-HUP", &std::process::id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+    let response = reqwest::get(&url).await.unwrap();
+    assert_eq!(response.status(), 200, "2s backend within a 5s timeout");
+    assert_eq!(response.text().await.unwrap(), "slow");
+
+    let info = server.registry().get(&workstation_id).await.unwrap();
+    assert_eq!(info.connection.stable_id(), connection_id);
+    let _ = std::fs::remove_file(&config_path);
+}
+
+#[tokio::test]
+async fn test_reloaded_maintenance_mode_refuses_new_requests() {
+    let mut env = TestEnvironment::new().await;
+    env.start_client().await;
+    let client = reqwest::Client::new();
+
+    let mut config = tunnel_server::config::Config::default();
+    config.auth.api_key = env.api_key.clone();
+    config.reliability.maintenance = true;
+    env.server.reload(&config);
+
+    let response = client
+        .get(env.proxy_url("health"))
+        .header("accept", "application/json")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 503);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "MAINTENANCE");
+
+    config.reliability.maintenance = false;
+    env.server.reload(&config);
+    let response = client.get(env.proxy_url("health")).send().await.unwrap();
+    assert_eq!(response.status(), 200, "workstation should stay connected");
+}
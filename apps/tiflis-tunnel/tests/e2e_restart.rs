@@ -4,6 +4,7 @@
 mod common;
 
 use common::TestEnvironment;
+use tunnel_client::connection::Connection;
 
 #[tokio::test]
 async fn test_client_reconnects_after_sudden_restart() {
@@ -278,3 +279,24 @@ async fn test_server_restart_preserves_different_workstation() {
         .expect("env2 should still work after env1 server restart");
     assert_eq!(response2_after.status(), 200);
 }
+
+#[tokio::test]
+async fn test_reconnect_with_ticket_registers_after_server_restart() {
+    let mut env = TestEnvironment::new().await;
+    let config = env.client_config();
+    // A ticket left on disk by the client's previous run.
+    std::fs::write(&config.session.ticket_path, b"stale-ticket").unwrap();
+
+    // The new server has never seen the workstation and refuses the reconnect.
+    env.restart_server().await;
+    let mut connection = Connection::new(config.clone());
+    let (_conn, url) = connection
+        .connect()
+        .await
+        .expect("Refused reconnect should fall back to registering");
+    assert!(url.contains(&env.workstation_id), "{}", url);
+    assert!(
+        !config.session.ticket_path.exists(),
+        "Refused ticket should be removed"
+    );
+}
@@ -0,0 +1,101 @@
+// Copyright (c) 2026 Roman Barinov <rbarinov@gmail.com>
+// Licensed under the FSL-1.1-NC.
+
+mod common;
+
+use common::TestEnvironment;
+use std::collections::HashMap;
+use tunnel_client::handle::{TunnelClientHandle, TunnelRequest, WsFrame};
+
+#[tokio::test]
+async fn test_embedded_client_serves_public_requests() {
+    let env = TestEnvironment::new().await;
+
+    let (_handle, url) = TunnelClientHandle::connect(env.client_config())
+        .await
+        .expect("Failed to connect");
+    assert!(url.ends_with(&format!("/t/{}", env.workstation_id)));
+
+    let response = reqwest::get(env.proxy_url("health"))
+        .await
+        .expect("Request failed");
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "OK");
+}
+
+#[tokio::test]
+async fn test_handle_request_round_trip() {
+    let env = TestEnvironment::new().await;
+    let (handle, _url) = TunnelClientHandle::connect(env.client_config())
+        .await
+        .expect("Failed to connect");
+
+    let response = handle
+        .request(TunnelRequest {
+            method: "POST".to_string(),
+            path: "/echo".to_string(),
+            body: b"typed".to_vec(),
+            ..Default::default()
+        })
+        .await
+        .expect("Request failed");
+
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body, b"Echo: typed");
+
+    // The request went out through the tunnel, not straight to the backend.
+    let info = env
+        .server
+        .registry()
+        .get(&env.workstation_id)
+        .await
+        .expect("Workstation not registered");
+    assert!(info.traffic.bytes_in() >= b"typed".len() as u64);
+}
+
+#[tokio::test]
+async fn test_handle_websocket_and_sse() {
+    let env = TestEnvironment::new().await;
+    let (handle, _url) = TunnelClientHandle::connect(env.client_config())
+        .await
+        .expect("Failed to connect");
+
+    let mut ws = handle
+        .open_ws("/ws", HashMap::new())
+        .await
+        .expect("Failed to open WebSocket");
+    ws.send(WsFrame::Text("hello".to_string())).await.unwrap();
+    assert_eq!(
+        ws.recv().await.unwrap(),
+        WsFrame::Text("Echo: hello".to_string())
+    );
+
+    let mut sse = handle
+        .open_sse("GET", "/sse/events/2", HashMap::new())
+        .await
+        .expect("Failed to open SSE stream");
+    assert_eq!(sse.status, 200);
+
+    let mut received = Vec::new();
+    while let Some(chunk) = sse.next_chunk().await.expect("SSE stream failed") {
+        received.extend(chunk);
+    }
+    let text = String::from_utf8(received).unwrap();
+    assert!(text.contains("data: event1"));
+    assert!(text.contains("data: event2"));
+}
+
+#[tokio::test]
+async fn test_ping_measures_round_trip() {
+    let env = TestEnvironment::new().await;
+    let (handle, _url) = TunnelClientHandle::connect(env.client_config())
+        .await
+        .expect("Failed to connect");
+
+    let sample = handle.ping().await.expect("Ping failed");
+    assert!(sample.rtt < std::time::Duration::from_secs(5));
+    let offset = sample
+        .clock_offset_ms
+        .expect("Server should report its clock");
+    assert!(offset.abs() < tunnel_client::ping::CLOCK_SKEW_WARN_MS);
+}
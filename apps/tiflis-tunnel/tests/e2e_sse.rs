@@ -7,6 +7,8 @@ use common::TestEnvironment;
 use futures::StreamExt;
 use std::time::Duration;
 use tokio::time::timeout;
+use tunnel_client::handle::TunnelClientHandle;
+use tunnel_server::registry::MIGRATED_CODE;
 
 #[tokio::test]
 async fn test_sse_basic_streaming() {
@@ -274,3 +276,313 @@ async fn test_sse_after_client_reconnect() {
 
     assert_eq!(events.len(), 2);
 }
+
+#[tokio::test]
+async fn test_sse_heartbeat_during_silence() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let mut env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.reliability.sse_heartbeat_interval = 1;
+    })
+    .await;
+    env.start_client().await;
+
+    let client = reqwest::Client::new();
+    let response = client
+        .get(env.proxy_url("sse/quiet"))
+        .header("Accept", "text/event-stream")
+        .send()
+        .await
+        .expect("Failed to connect");
+    assert_eq!(response.status(), 200);
+
+    let mut stream = response.bytes_stream();
+    let mut received = String::new();
+
+    while let Ok(Some(chunk)) = timeout(Duration::from_secs(5), stream.next()).await {
+        if let Ok(data) = chunk {
+            received.push_str(&String::from_utf8_lossy(&data));
+        }
+        if received.contains("second") {
+            break;
+        }
+    }
+
+    assert!(received.contains("data: first"));
+    assert!(received.contains(":\n\n"), "no heartbeat in {:?}", received);
+    assert!(received.contains("data: second"));
+}
+
+#[tokio::test]
+async fn test_sse_closed_after_max_duration() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let mut env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.reliability.max_sse_duration = 1;
+    })
+    .await;
+    env.start_client().await;
+
+    let client = reqwest::Client::new();
+    let started = std::time::Instant::now();
+    let response = client
+        .get(env.proxy_url("sse/endless"))
+        .header("Accept", "text/event-stream")
+        .send()
+        .await
+        .expect("Failed to connect");
+    assert_eq!(response.status(), 200);
+
+    let mut stream = response.bytes_stream();
+    let mut received = String::new();
+
+    let ended = timeout(Duration::from_secs(10), async {
+        while let Some(chunk) = stream.next().await {
+            match chunk {
+                Ok(data) => received.push_str(&String::from_utf8_lossy(&data)),
+                Err(_) => break,
+            }
+        }
+    })
+    .await;
+
+    assert!(ended.is_ok(), "SSE stream was not closed");
+    let elapsed = started.elapsed();
+    assert!(
+        elapsed >= Duration::from_millis(900),
+        "closed after {:?}",
+        elapsed
+    );
+    assert!(
+        elapsed < Duration::from_secs(5),
+        "closed after {:?}",
+        elapsed
+    );
+    assert!(received.contains("data: tick1"));
+}
+
+/// SSE backend that sends one event and then stays silent, reporting when
+/// the tunnel client closes its connection.
+async fn spawn_silent_sse_backend() -> (u16, tokio::sync::oneshot::Receiver<()>) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (closed_tx, closed_rx) = tokio::sync::oneshot::channel();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut head = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+            let n = stream.read(&mut buf).await.unwrap();
+            head.extend_from_slice(&buf[..n]);
+        }
+        stream
+            .write_all(
+                b"HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\ntransfer-encoding: chunked\r\n\r\n\
+                  d\r\ndata: hello\n\n\r\n",
+            )
+            .await
+            .unwrap();
+        while let Ok(n) = stream.read(&mut buf).await {
+            if n == 0 {
+                break;
+            }
+        }
+        let _ = closed_tx.send(());
+    });
+    (port, closed_rx)
+}
+
+#[tokio::test]
+async fn test_sse_max_duration_closes_backend_connection() {
+    let (backend_port, backend_closed) = spawn_silent_sse_backend().await;
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.reliability.max_sse_duration = 1;
+    })
+    .await;
+    let mut config = env.client_config();
+    config.workstation.local_address = format!("http://127.0.0.1:{}", backend_port);
+    let (_handle, _url) = TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let response = reqwest::Client::new()
+        .get(env.proxy_url("events"))
+        .header("Accept", "text/event-stream")
+        .send()
+        .await
+        .expect("Failed to open SSE");
+    assert_eq!(response.status(), 200);
+    let mut stream = response.bytes_stream();
+    let first = timeout(Duration::from_secs(2), stream.next())
+        .await
+        .expect("Timeout waiting for the event")
+        .unwrap()
+        .unwrap();
+    assert_eq!(first.as_ref(), b"data: hello\n\n");
+
+    // The backend sends nothing more, so only the server's `SseClose` can
+    // make the client let go of it.
+    timeout(Duration::from_secs(5), backend_closed)
+        .await
+        .expect("Backend connection still open after the duration limit")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_sse_survives_client_reconnect() {
+    let env = TestEnvironment::new().await;
+    let config = env.client_config();
+    let (old_handle, _url) = TunnelClientHandle::connect(config.clone())
+        .await
+        .expect("Failed to connect");
+
+    let response = reqwest::Client::new()
+        .get(env.proxy_url("sse/slow"))
+        .header("Accept", "text/event-stream")
+        .send()
+        .await
+        .expect("Failed to open SSE");
+    assert_eq!(response.status(), 200);
+    let mut stream = response.bytes_stream();
+    let first = timeout(Duration::from_secs(5), stream.next())
+        .await
+        .expect("Timed out waiting for the first event")
+        .unwrap()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&first).contains("slow_event1"));
+
+    let (_new_handle, _url) = old_handle
+        .migrate(config)
+        .await
+        .expect("Failed to reconnect");
+
+    // New requests go to the new connection while the stream keeps going.
+    let response = reqwest::get(env.proxy_url("health")).await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    let mut body = String::new();
+    while let Ok(Some(chunk)) = timeout(Duration::from_secs(5), stream.next()).await {
+        body.push_str(&String::from_utf8_lossy(&chunk.expect("SSE stream broke")));
+    }
+    for i in 2..=5 {
+        assert!(body.contains(&format!("slow_event{}", i)), "{}", body);
+    }
+
+    // With nothing left in flight the server retires the old connection.
+    let reason = timeout(Duration::from_secs(5), old_handle.connection().closed())
+        .await
+        .expect("Old connection should be closed after draining");
+    match reason {
+        quinn::ConnectionError::ApplicationClosed(close) => {
+            assert_eq!(close.error_code, MIGRATED_CODE.into());
+        }
+        other => panic!("Unexpected close: {}", other),
+    }
+}
+
+/// Serves one SSE response whose body is written in `pieces`, each sent as its
+/// own chunk after a pause so the client reads them separately.
+async fn spawn_split_sse_backend(pieces: &'static [&'static str]) -> u16 {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut head = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+            let n = stream.read(&mut buf).await.unwrap();
+            head.extend_from_slice(&buf[..n]);
+        }
+        stream
+            .write_all(
+                b"HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\ntransfer-encoding: chunked\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        for piece in pieces {
+            let chunk = format!("{:x}\r\n{}\r\n", piece.len(), piece);
+            stream.write_all(chunk.as_bytes()).await.unwrap();
+            stream.flush().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        stream.write_all(b"0\r\n\r\n").await.unwrap();
+    });
+    port
+}
+
+#[tokio::test]
+async fn test_sse_events_split_by_backend_arrive_whole() {
+    let backend_port = spawn_split_sse_backend(&[
+        "data: fir",
+        "st\n\nid: 2\ndata: sec",
+        "ond\n",
+        "\n",
+        "event: last\r\ndata: third\r\n\r\n",
+    ])
+    .await;
+    let env = TestEnvironment::new().await;
+    let mut config = env.client_config();
+    config.workstation.local_address = format!("http://127.0.0.1:{}", backend_port);
+    let (_handle, _url) = TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let response = reqwest::Client::new()
+        .get(env.proxy_url("events"))
+        .header("Accept", "text/event-stream")
+        .send()
+        .await
+        .expect("Failed to open SSE");
+    assert_eq!(response.status(), 200);
+
+    let mut stream = response.bytes_stream();
+    let mut body = String::new();
+    while let Ok(Some(chunk)) = timeout(Duration::from_secs(5), stream.next()).await {
+        let chunk = String::from_utf8(chunk.unwrap().to_vec()).unwrap();
+        assert!(
+            chunk.ends_with("\n\n") || chunk.ends_with("\r\n\r\n"),
+            "chunk splits an event: {:?}",
+            chunk
+        );
+        body.push_str(&chunk);
+    }
+    assert_eq!(
+        body,
+        "data: first\n\nid: 2\ndata: second\n\nevent: last\r\ndata: third\r\n\r\n"
+    );
+}
+
+#[tokio::test]
+async fn test_sse_uses_fallback_backend_when_primary_is_down() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let down_port = listener.local_addr().unwrap().port();
+    drop(listener);
+
+    let env = TestEnvironment::new().await;
+    let mut config = env.client_config();
+    let standby = config.workstation.local_address.clone();
+    config.workstation.local_address = format!("http://127.0.0.1:{}", down_port);
+    config.workstation.fallback_addresses = vec![standby];
+    let (_handle, _url) = TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let response = reqwest::Client::new()
+        .get(env.proxy_url("sse/events"))
+        .header("Accept", "text/event-stream")
+        .send()
+        .await
+        .expect("Failed to connect");
+    assert_eq!(response.status(), 200);
+
+    let mut stream = response.bytes_stream();
+    let first = timeout(Duration::from_secs(5), stream.next())
+        .await
+        .expect("No event from the standby")
+        .expect("Stream ended")
+        .unwrap();
+    assert!(String::from_utf8_lossy(&first).contains("event1"));
+}
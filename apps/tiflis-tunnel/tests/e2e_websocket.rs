@@ -6,6 +6,7 @@ mod common;
 use common::TestEnvironment;
 use futures::{SinkExt, StreamExt};
 use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tunnel_client::handle::TunnelClientHandle;
 
 #[tokio::test]
 async fn test_websocket_connection() {
@@ -161,6 +162,35 @@ async fn test_websocket_large_message() {
     write.send(Message::Close(None)).await.unwrap();
 }
 
+#[tokio::test]
+async fn test_websocket_message_larger_than_a_frame_is_fragmented() {
+    let mut env = TestEnvironment::new().await;
+    env.start_client().await;
+
+    let ws_url = env.proxy_url("ws").replace("http://", "ws://");
+    let (ws_stream, _) = connect_async(&ws_url).await.expect("Failed to connect");
+    let (mut write, mut read) = ws_stream.split();
+
+    // Base64 makes this ~16 MB on the wire, well past the 10 MB frame cap.
+    let payload: Vec<u8> = (0..12 * 1024 * 1024u32).map(|i| (i % 251) as u8).collect();
+    write.send(Message::Binary(payload.clone())).await.unwrap();
+
+    let echoed = tokio::time::timeout(std::time::Duration::from_secs(30), read.next())
+        .await
+        .expect("Timed out waiting for echo");
+    match echoed {
+        Some(Ok(Message::Binary(data))) => {
+            assert_eq!(data.len(), payload.len());
+            assert!(data == payload, "Echoed payload differs");
+        }
+        Some(Ok(other)) => panic!("Expected binary message, got {} bytes", other.len()),
+        Some(Err(e)) => panic!("WebSocket error: {}", e),
+        None => panic!("WebSocket closed before the echo"),
+    }
+
+    write.send(Message::Close(None)).await.unwrap();
+}
+
 #[tokio::test]
 async fn test_websocket_upgrade_from_http() {
     let mut env = TestEnvironment::new().await;
@@ -177,3 +207,416 @@ async fn test_websocket_upgrade_from_http() {
     let (mut write, _read) = ws_stream.split();
     write.send(Message::Close(None)).await.unwrap();
 }
+
+#[tokio::test]
+async fn test_websocket_oversized_message_closes_with_1009() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let mut env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.limits.max_ws_message_bytes = 1024;
+    })
+    .await;
+    env.start_client().await;
+
+    let ws_url = env.proxy_url("ws").replace("http://", "ws://");
+    let (ws_stream, _) = connect_async(&ws_url).await.expect("Failed to connect");
+    let (mut write, mut read) = ws_stream.split();
+
+    write
+        .send(Message::Text("small".to_string()))
+        .await
+        .unwrap();
+    match read.next().await {
+        Some(Ok(Message::Text(response))) => assert!(response.contains("Echo: small")),
+        other => panic!("Expected text message, got {:?}", other),
+    }
+
+    write.send(Message::Text("x".repeat(2048))).await.unwrap();
+
+    let close = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while let Some(msg) = read.next().await {
+            if let Ok(Message::Close(frame)) = msg {
+                return frame;
+            }
+        }
+        None
+    })
+    .await
+    .expect("Timed out waiting for close");
+
+    let frame = close.expect("Expected a close frame");
+    assert_eq!(u16::from(frame.code), 1009);
+}
+
+#[tokio::test]
+async fn test_websocket_allowed_path_upgrades() {
+    let env = TestEnvironment::new().await;
+    let mut config = env.client_config();
+    config.workstation.ws_allow_paths = vec!["/ws".to_string()];
+    let (_handle, _url) = TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let ws_url = env.proxy_url("ws").replace("http://", "ws://");
+    let (ws_stream, _) = connect_async(&ws_url).await.expect("Failed to connect");
+    let (mut write, mut read) = ws_stream.split();
+
+    write
+        .send(Message::Text("allowed".to_string()))
+        .await
+        .unwrap();
+    match read.next().await {
+        Some(Ok(Message::Text(response))) => assert!(response.contains("Echo: allowed")),
+        other => panic!("Expected text message, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_websocket_denied_path_is_refused() {
+    let env = TestEnvironment::new().await;
+    let mut config = env.client_config();
+    config.workstation.ws_deny_paths = vec!["/w*".to_string()];
+    let (_handle, _url) = TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let ws_url = env.proxy_url("ws").replace("http://", "ws://");
+    let (ws_stream, _) = connect_async(&ws_url).await.expect("Failed to connect");
+    let (_write, mut read) = ws_stream.split();
+
+    let close = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while let Some(msg) = read.next().await {
+            if let Ok(Message::Close(frame)) = msg {
+                return frame;
+            }
+        }
+        None
+    })
+    .await
+    .expect("Timed out waiting for close");
+
+    let frame = close.expect("Expected a close frame");
+    assert_eq!(u16::from(frame.code), 1008);
+}
+
+#[tokio::test]
+async fn test_websocket_local_backend_down_returns_502() {
+    let env = TestEnvironment::new().await;
+    let unused_port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let mut config = env.client_config();
+    config.workstation.local_address = format!("http://127.0.0.1:{}", unused_port);
+    let (_handle, _url) = TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let ws_url = env.proxy_url("ws").replace("http://", "ws://");
+    match connect_async(&ws_url).await {
+        Err(tokio_tungstenite::tungstenite::Error::Http(response)) => {
+            assert_eq!(response.status(), 502);
+        }
+        Err(e) => panic!("Expected an HTTP error response, got {}", e),
+        Ok(_) => panic!("Upgrade should fail when the local WebSocket is down"),
+    }
+}
+
+/// Reads an HTTP head, up to and including the blank line, and returns it
+/// with header names lowercased.
+async fn read_head(stream: &mut tokio::net::TcpStream) -> String {
+    use tokio::io::AsyncReadExt;
+
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        let mut byte = [0u8; 1];
+        stream.read_exact(&mut byte).await.unwrap();
+        head.push(byte[0]);
+    }
+    String::from_utf8(head)
+        .unwrap()
+        .lines()
+        .map(|line| match line.split_once(':') {
+            Some((name, value)) => format!("{}:{}", name.to_ascii_lowercase(), value),
+            None => line.to_ascii_lowercase(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[tokio::test]
+async fn test_websocket_deflate_is_negotiated_end_to_end() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+
+    // "Hello" compressed with permessage-deflate (RFC 7692, section 7.2.3.1).
+    const COMPRESSED: [u8; 7] = [0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00];
+    // FIN, RSV1 (compressed) and the text opcode.
+    const COMPRESSED_TEXT: u8 = 0xc1;
+
+    // A backend that accepts permessage-deflate and answers one compressed
+    // frame with another, neither of which the tunnel could parse.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let local_port = listener.local_addr().unwrap().port();
+    let backend = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let head = read_head(&mut stream).await;
+        assert!(head.contains("sec-websocket-extensions: permessage-deflate"));
+        let key = head
+            .lines()
+            .find_map(|line| line.strip_prefix("sec-websocket-key: "))
+            .unwrap()
+            .trim()
+            .to_string();
+        let response = format!(
+            "HTTP/1.1 101 Switching Protocols\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n\
+             Sec-WebSocket-Accept: {}\r\nSec-WebSocket-Extensions: permessage-deflate\r\n\r\n",
+            derive_accept_key(key.as_bytes())
+        );
+        stream.write_all(response.as_bytes()).await.unwrap();
+
+        let mut frame = [0u8; 2 + 4 + COMPRESSED.len()];
+        stream.read_exact(&mut frame).await.unwrap();
+        assert_eq!(frame[0], COMPRESSED_TEXT);
+        assert_eq!(frame[1], 0x80 | COMPRESSED.len() as u8);
+        let mask = [frame[2], frame[3], frame[4], frame[5]];
+        let payload: Vec<u8> = frame[6..]
+            .iter()
+            .enumerate()
+            .map(|(i, b)| b ^ mask[i % 4])
+            .collect();
+        assert_eq!(payload, COMPRESSED);
+
+        let mut reply = vec![COMPRESSED_TEXT, COMPRESSED.len() as u8];
+        reply.extend_from_slice(&COMPRESSED);
+        stream.write_all(&reply).await.unwrap();
+        let mut rest = Vec::new();
+        let _ = stream.read_to_end(&mut rest).await;
+    });
+
+    let env = TestEnvironment::new().await;
+    let mut config = env.client_config();
+    config.workstation.local_address = format!("http://127.0.0.1:{}", local_port);
+    let (_handle, _url) = TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let key = "dGhlIHNhbXBsZSBub25jZQ==";
+    let mut caller = tokio::net::TcpStream::connect(("127.0.0.1", env.server_http_port))
+        .await
+        .unwrap();
+    let request = format!(
+        "GET /t/{}/ws HTTP/1.1\r\nHost: localhost\r\nConnection: Upgrade\r\n\
+         Upgrade: websocket\r\nSec-WebSocket-Version: 13\r\nSec-WebSocket-Key: {}\r\n\
+         Sec-WebSocket-Extensions: permessage-deflate; client_max_window_bits\r\n\r\n",
+        env.workstation_id, key
+    );
+    caller.write_all(request.as_bytes()).await.unwrap();
+
+    let head = read_head(&mut caller).await;
+    assert!(head.starts_with("http/1.1 101"), "{}", head);
+    assert!(head.contains(&format!(
+        "sec-websocket-accept: {}",
+        derive_accept_key(key.as_bytes())
+    )));
+    assert!(head.contains("sec-websocket-extensions: permessage-deflate"));
+
+    let mask = [0x37, 0xfa, 0x21, 0x3d];
+    let mut frame = vec![COMPRESSED_TEXT, 0x80 | COMPRESSED.len() as u8];
+    frame.extend_from_slice(&mask);
+    frame.extend(COMPRESSED.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+    caller.write_all(&frame).await.unwrap();
+
+    let mut reply = [0u8; 2 + COMPRESSED.len()];
+    tokio::time::timeout(
+        std::time::Duration::from_secs(5),
+        caller.read_exact(&mut reply),
+    )
+    .await
+    .expect("Timed out waiting for the compressed reply")
+    .unwrap();
+    assert_eq!(reply[0], COMPRESSED_TEXT);
+    assert_eq!(&reply[2..], COMPRESSED);
+
+    drop(caller);
+    backend.await.unwrap();
+}
+
+/// WebSocket backend that closes with 1011 when sent "fail" and reports the
+/// close frame it receives from the caller.
+async fn spawn_closing_ws_backend() -> (u16, tokio::sync::mpsc::UnboundedReceiver<(u16, String)>) {
+    use axum::extract::ws::{CloseFrame, Message as AxumMessage, WebSocketUpgrade};
+
+    let (seen_tx, seen_rx) = tokio::sync::mpsc::unbounded_channel();
+    let app = axum::Router::new().route(
+        "/ws",
+        axum::routing::get(move |ws: WebSocketUpgrade| {
+            let seen_tx = seen_tx.clone();
+            async move {
+                ws.on_upgrade(move |mut socket| async move {
+                    while let Some(Ok(msg)) = socket.recv().await {
+                        match msg {
+                            AxumMessage::Text(text) if text == "fail" => {
+                                let frame = CloseFrame {
+                                    code: 1011,
+                                    reason: "backend exploded".into(),
+                                };
+                                let _ = socket.send(AxumMessage::Close(Some(frame))).await;
+                                break;
+                            }
+                            AxumMessage::Close(frame) => {
+                                let frame = frame
+                                    .map(|f| (f.code, f.reason.to_string()))
+                                    .unwrap_or((0, String::new()));
+                                let _ = seen_tx.send(frame);
+                                break;
+                            }
+                            _ => {}
+                        }
+                    }
+                })
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    (port, seen_rx)
+}
+
+#[tokio::test]
+async fn test_websocket_close_code_and_reason_cross_the_tunnel() {
+    use tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame};
+
+    let env = TestEnvironment::new().await;
+    let (port, mut seen) = spawn_closing_ws_backend().await;
+    let mut config = env.client_config();
+    config.workstation.local_address = format!("http://127.0.0.1:{}", port);
+    let (_handle, _url) = TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+    let ws_url = env.proxy_url("ws").replace("http://", "ws://");
+
+    // Backend to browser.
+    let (ws_stream, _) = connect_async(&ws_url).await.expect("Failed to connect");
+    let (mut write, mut read) = ws_stream.split();
+    write.send(Message::Text("fail".to_string())).await.unwrap();
+    let close = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while let Some(msg) = read.next().await {
+            if let Ok(Message::Close(frame)) = msg {
+                return frame;
+            }
+        }
+        None
+    })
+    .await
+    .expect("Timed out waiting for close")
+    .expect("Expected a close frame");
+    assert_eq!(u16::from(close.code), 1011);
+    assert_eq!(close.reason, "backend exploded");
+
+    // Browser to backend.
+    let (mut ws_stream, _) = connect_async(&ws_url).await.expect("Failed to connect");
+    ws_stream
+        .close(Some(CloseFrame {
+            code: CloseCode::from(4001),
+            reason: "user left".into(),
+        }))
+        .await
+        .unwrap();
+    let seen = tokio::time::timeout(std::time::Duration::from_secs(5), seen.recv())
+        .await
+        .expect("Timed out waiting for the backend to see the close")
+        .unwrap();
+    assert_eq!(seen, (4001, "user left".to_string()));
+}
+
+async fn connect_with_origin(
+    env: &TestEnvironment,
+    origin: &str,
+) -> Result<
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>,
+    tokio_tungstenite::tungstenite::Error,
+> {
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+    let ws_url = env.proxy_url("ws").replace("http://", "ws://");
+    let mut request = ws_url.into_client_request().unwrap();
+    request
+        .headers_mut()
+        .insert("origin", origin.parse().unwrap());
+    connect_async(request).await.map(|(stream, _)| stream)
+}
+
+#[tokio::test]
+async fn test_websocket_allowed_origin_upgrades() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let mut env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.server.ws_allowed_origins = vec![
+            "https://app.example.com".to_string(),
+            "https://*.example.org".to_string(),
+        ];
+    })
+    .await;
+    env.start_client().await;
+
+    for origin in ["https://app.example.com", "https://dev.example.org"] {
+        let ws_stream = connect_with_origin(&env, origin)
+            .await
+            .unwrap_or_else(|e| panic!("{} should upgrade: {}", origin, e));
+        let (mut write, mut read) = ws_stream.split();
+        write.send(Message::Text(origin.to_string())).await.unwrap();
+        match read.next().await {
+            Some(Ok(Message::Text(response))) => assert!(response.contains(origin)),
+            other => panic!("Expected text message, got {:?}", other),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_websocket_disallowed_origin_is_forbidden() {
+    let workstation_id = format!("test-ws-{}", rand::random::<u16>());
+    let mut env = TestEnvironment::new_with_server_config(&workstation_id, |config| {
+        config.server.ws_allowed_origins = vec!["https://*.example.org".to_string()];
+    })
+    .await;
+    env.start_client().await;
+
+    for origin in ["https://evil.com", "https://example.org.evil.com", "null"] {
+        match connect_with_origin(&env, origin).await {
+            Err(tokio_tungstenite::tungstenite::Error::Http(response)) => {
+                assert_eq!(response.status(), 403, "{}", origin)
+            }
+            other => panic!("{} should be refused, got {:?}", origin, other.map(|_| ())),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_websocket_uses_fallback_backend_when_primary_is_down() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let down_port = listener.local_addr().unwrap().port();
+    drop(listener);
+
+    let env = TestEnvironment::new().await;
+    let mut config = env.client_config();
+    let standby = config.workstation.local_address.clone();
+    config.workstation.local_address = format!("http://127.0.0.1:{}", down_port);
+    config.workstation.fallback_addresses = vec![standby];
+    let (_handle, _url) = TunnelClientHandle::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let ws_url = env.proxy_url("ws").replace("http://", "ws://");
+    let (mut ws_stream, _) = connect_async(&ws_url).await.expect("Failed to connect");
+    ws_stream
+        .send(Message::Text("via standby".to_string()))
+        .await
+        .unwrap();
+    match ws_stream.next().await {
+        Some(Ok(Message::Text(response))) => assert!(response.contains("Echo: via standby")),
+        other => panic!("Expected text message, got {:?}", other),
+    }
+}